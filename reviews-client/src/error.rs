@@ -0,0 +1,60 @@
+use std::fmt;
+
+/// What a call can fail with.
+#[derive(Debug)]
+pub enum Error {
+    /// The request could not be sent or its response not read (connection, timeout, TLS).
+    Transport(reqwest::Error),
+    /// The service answered with an error status; `message` is its `{"error": ...}` text.
+    Api { status: u16, message: String },
+    /// A response body that does not match the expected type.
+    Decode(serde_json::Error),
+    /// The builder was given a setting that cannot be sent, or a request cannot be retried.
+    Config(String),
+}
+
+impl Error {
+    /// HTTP status of an `Api` error.
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            Error::Api { status, .. } => Some(*status),
+            _ => None,
+        }
+    }
+
+    /// 409: the `version` sent with an upsert or delete is not the current one.
+    pub fn is_conflict(&self) -> bool { self.status() == Some(409) }
+
+    pub fn is_not_found(&self) -> bool { self.status() == Some(404) }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Transport(e) => write!(f, "request failed: {e}"),
+            Error::Api { status, message } => write!(f, "service returned {status}: {message}"),
+            Error::Decode(e) => write!(f, "unexpected response body: {e}"),
+            Error::Config(msg) => f.write_str(msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Transport(e) => Some(e),
+            Error::Decode(e) => Some(e),
+            Error::Api { .. } | Error::Config(_) => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self { Error::Transport(e) }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self { Error::Decode(e) }
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -0,0 +1,673 @@
+//! Typed async client for the review search service (rust-spfresh-services), one method
+//! per v1 endpoint. Left out: the replication log (the protocol between a primary and its
+//! replicas), `/graphql`, `/analytics/query` and `/export/parquet`, which are meant for
+//! tools of their own.
+//!
+//! ```no_run
+//! # async fn run() -> reviews_client::Result<()> {
+//! use reviews_client::{Client, SearchReq};
+//!
+//! let client = Client::builder("http://localhost:8000").principal("checkout").build()?;
+//! let resp = client.search(&SearchReq::new("battery life").top_k(10)).await?;
+//! for hit in resp.hits { println!("{} {:.3} {}", hit.id, hit.score, hit.review.review_title); }
+//! # Ok(()) }
+//! ```
+//!
+//! Requests the service turned away before doing any work (429 over quota, 503 load
+//! shedding) are retried with backoff. Transport failures and 502/504 are retried only for
+//! idempotent calls (reads, searches, alias updates), since a write that timed out may
+//! still have been applied.
+
+mod error;
+mod types;
+
+pub use error::{Error, Result};
+pub use types::*;
+
+use reqwest::{header::HeaderMap, Method, RequestBuilder, Response, StatusCode};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{collections::BTreeMap, marker::PhantomData, time::Duration};
+
+/// First retry delay; doubled on each further attempt unless the service sends Retry-After.
+const BACKOFF_BASE: Duration = Duration::from_millis(200);
+/// Longest wait between two attempts, Retry-After included.
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+pub struct ClientBuilder {
+    base_url: String,
+    principal: Option<String>,
+    bearer_token: Option<String>,
+    timeout: Duration,
+    max_retries: u32,
+}
+
+impl ClientBuilder {
+    /// Sent as `x-principal`: the tenant quotas and the audit log are keyed by it.
+    pub fn principal(mut self, principal: impl Into<String>) -> Self {
+        self.principal = Some(principal.into());
+        self
+    }
+
+    /// Sent as `Authorization: Bearer`, for deployments behind an authenticating proxy.
+    pub fn bearer_token(mut self, token: impl Into<String>) -> Self {
+        self.bearer_token = Some(token.into());
+        self
+    }
+
+    /// Deadline of one attempt (default 30 s).
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Retries after the first attempt (default 3); the waits between them double from
+    /// 200 ms up to 30 s.
+    pub fn max_retries(mut self, n: u32) -> Self {
+        self.max_retries = n;
+        self
+    }
+
+    pub fn build(self) -> Result<Client> {
+        let mut headers = HeaderMap::new();
+        if let Some(p) = &self.principal {
+            headers.insert("x-principal", p.parse().map_err(|_| invalid_header("principal"))?);
+        }
+        if let Some(t) = &self.bearer_token {
+            headers.insert(reqwest::header::AUTHORIZATION, format!("Bearer {t}").parse().map_err(|_| invalid_header("bearer token"))?);
+        }
+        let http = reqwest::Client::builder().default_headers(headers).timeout(self.timeout).build()?;
+        Ok(Client { http, base: format!("{}/v1", self.base_url.trim_end_matches('/')), max_retries: self.max_retries })
+    }
+}
+
+fn invalid_header(what: &str) -> Error {
+    Error::Config(format!("{what} is not a valid header value"))
+}
+
+/// Cheap to clone; clones share one connection pool.
+#[derive(Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    /// Base URL with the `/v1` prefix.
+    base: String,
+    max_retries: u32,
+}
+
+/// Whether a failed attempt may be repeated without risking a second write.
+#[derive(Clone, Copy, PartialEq)]
+enum Retry {
+    Idempotent,
+    /// Only retried when the service refused it outright.
+    Once,
+}
+
+impl Client {
+    /// `base_url` is the service root, e.g. `http://localhost:8000`.
+    pub fn builder(base_url: impl Into<String>) -> ClientBuilder {
+        ClientBuilder {
+            base_url: base_url.into(),
+            principal: None,
+            bearer_token: None,
+            timeout: Duration::from_secs(30),
+            max_retries: 3,
+        }
+    }
+
+    pub fn new(base_url: impl Into<String>) -> Result<Self> {
+        Self::builder(base_url).build()
+    }
+
+    // ---- reviews ----
+
+    /// POST /reviews
+    pub async fn insert(&self, review: &Review) -> Result<ReviewResp> {
+        self.json(Method::POST, "/reviews", Some(&InsertReq { review: review.clone(), return_vector: false }), Retry::Once).await
+    }
+
+    /// `insert`, getting back in `ReviewResp::vector` the vector the service embedded.
+    pub async fn insert_returning_vector(&self, review: &Review) -> Result<ReviewResp> {
+        self.json(Method::POST, "/reviews", Some(&InsertReq { review: review.clone(), return_vector: true }), Retry::Once).await
+    }
+
+    /// POST /reviews/bulk
+    pub async fn insert_bulk(&self, reviews: &[Review]) -> Result<BulkResp> {
+        self.json(Method::POST, "/reviews/bulk", Some(&BulkInsertReq { reviews: reviews.to_vec() }), Retry::Once).await
+    }
+
+    /// POST /reviews/raw — with a vector computed by the caller, of the index's dimension.
+    pub async fn insert_raw(&self, review: &Review, vector: &[f32]) -> Result<ReviewResp> {
+        let req = RawInsertReq { review: review.clone(), vector: vector.to_vec(), embedder: None, return_vector: false };
+        self.json(Method::POST, "/reviews/raw", Some(&req), Retry::Once).await
+    }
+
+    /// `insert_raw` with a vector computed by the registered `embedder`; refused with
+    /// `Error::is_conflict` once the active index was built by another, e.g. mid-migration.
+    pub async fn insert_raw_as(&self, review: &Review, vector: &[f32], embedder: &str) -> Result<ReviewResp> {
+        let req = RawInsertReq { review: review.clone(), vector: vector.to_vec(), embedder: Some(embedder.to_string()), return_vector: false };
+        self.json(Method::POST, "/reviews/raw", Some(&req), Retry::Once).await
+    }
+
+    /// POST /reviews/upsert — `review.external_id` is required; to replace the review
+    /// holding it, set `review.version` to the version read, or get `Error::is_conflict`.
+    pub async fn upsert(&self, review: &Review) -> Result<UpsertResp> {
+        self.json(Method::POST, "/reviews/upsert", Some(&InsertReq { review: review.clone(), return_vector: false }), Retry::Once).await
+    }
+
+    /// DELETE /reviews/:id?version=
+    pub async fn delete(&self, id: usize, version: u64) -> Result<()> {
+        let req = self.http.delete(self.url(&format!("/reviews/{id}"))).query(&[("version", version)]);
+        self.send(req, Retry::Once).await.map(drop)
+    }
+
+    /// PATCH /reviews/:id — rating, product or status changed in place, version-checked.
+    pub async fn patch(&self, id: usize, req: &PatchReq) -> Result<PatchResp> {
+        self.json(Method::PATCH, &format!("/reviews/{id}"), Some(req), Retry::Once).await
+    }
+
+    /// POST /reviews/:id/reembed — the review's vector recomputed by the current embedder.
+    pub async fn reembed(&self, id: usize) -> Result<ReembedResp> {
+        self.json(Method::POST, &format!("/reviews/{id}/reembed"), None::<&()>, Retry::Idempotent).await
+    }
+
+    /// POST /reviews/dry-run — what an insert of `review` would store (status, language,
+    /// terms, vector) and what moderation makes of it, without writing anything.
+    pub async fn dry_run(&self, review: &Review) -> Result<DryRunResp> {
+        self.json(Method::POST, "/reviews/dry-run", Some(&InsertReq { review: review.clone(), return_vector: false }), Retry::Idempotent).await
+    }
+
+    /// POST /reviews/import — one NDJSON request; lines that fail are reported, not fatal.
+    pub async fn import(&self, reviews: &[Review]) -> Result<ImportResp> {
+        let mut body = Vec::new();
+        for r in reviews {
+            serde_json::to_writer(&mut body, r)?;
+            body.push(b'\n');
+        }
+        let req = self.http.post(self.url("/reviews/import")).header("content-type", "application/x-ndjson").body(body);
+        decode(self.send(req, Retry::Once).await?).await
+    }
+
+    /// GET /reviews?cursor=&limit= — one page in id order; pass `next_cursor` back for the next.
+    pub async fn list_reviews(&self, cursor: Option<&str>, limit: Option<usize>) -> Result<ListResp> {
+        let mut req = self.http.get(self.url("/reviews"));
+        if let Some(c) = cursor { req = req.query(&[("cursor", c)]); }
+        if let Some(l) = limit { req = req.query(&[("limit", l)]); }
+        decode(self.send(req, Retry::Idempotent).await?).await
+    }
+
+    /// POST /reviews/batch — up to 1000 reviews by id in one round trip, in the order of
+    /// `ids`, with `found: false` for ids without a live review.
+    pub async fn get_many(&self, ids: &[usize]) -> Result<Vec<BatchItem>> {
+        let resp: BatchGetResp = self.json(Method::POST, "/reviews/batch", Some(&BatchGetReq { ids: ids.to_vec() }), Retry::Idempotent).await?;
+        Ok(resp.reviews)
+    }
+
+    /// GET /export/full?collection= — every live review with its stored vector, read as
+    /// the service streams it.
+    pub async fn export_full(&self, collection: Option<&str>) -> Result<NdjsonStream<FullRecord>> {
+        let mut req = self.http.get(self.url("/export/full"));
+        if let Some(c) = collection { req = req.query(&[("collection", c)]); }
+        Ok(NdjsonStream::new(self.send(req, Retry::Idempotent).await?))
+    }
+
+    /// GET /vectors/:id?collection=&quantize= — review `id`'s stored vector, as floats or,
+    /// with `int8`, scalar-quantized.
+    pub async fn vector(&self, id: usize, collection: Option<&str>, int8: bool) -> Result<VectorResp> {
+        let mut req = self.http.get(self.url(&format!("/vectors/{id}")));
+        if let Some(c) = collection { req = req.query(&[("collection", c)]); }
+        if int8 { req = req.query(&[("quantize", "int8")]); }
+        decode(self.send(req, Retry::Idempotent).await?).await
+    }
+
+    // ---- search ----
+
+    /// POST /search
+    pub async fn search(&self, req: &SearchReq) -> Result<SearchResp> {
+        self.json(Method::POST, "/search", Some(req), Retry::Idempotent).await
+    }
+
+    /// POST /search/stream — hits one at a time, for `top_k` beyond what /search allows.
+    pub async fn search_stream(&self, req: &SearchReq) -> Result<NdjsonStream<SearchHit>> {
+        let req = self.http.post(self.url("/search/stream")).json(req);
+        Ok(NdjsonStream::new(self.send(req, Retry::Idempotent).await?))
+    }
+
+    /// POST /search/range — every review within a distance of the query, nearest first.
+    pub async fn range_search(&self, req: &RangeSearchReq) -> Result<RangeSearchResp> {
+        self.json(Method::POST, "/search/range", Some(req), Retry::Idempotent).await
+    }
+
+    /// POST /compare — two products' reviews on one topic, side by side.
+    pub async fn compare(&self, req: &CompareReq) -> Result<CompareResp> {
+        self.json(Method::POST, "/compare", Some(req), Retry::Idempotent).await
+    }
+
+    /// POST /ask — a question answered by the service's chat model from the best matching
+    /// reviews, with citations; on a service built with its `llm` feature.
+    pub async fn ask(&self, req: &AskReq) -> Result<AskResp> {
+        self.json(Method::POST, "/ask", Some(req), Retry::Idempotent).await
+    }
+
+    /// GET /products?prefix=&limit= — product ids in id order, with review counts.
+    pub async fn products(&self, prefix: Option<&str>, limit: Option<usize>) -> Result<ProductsResp> {
+        let mut req = self.http.get(self.url("/products"));
+        if let Some(p) = prefix { req = req.query(&[("prefix", p)]); }
+        if let Some(l) = limit { req = req.query(&[("limit", l)]); }
+        decode(self.send(req, Retry::Idempotent).await?).await
+    }
+
+    /// POST /analytics/projection — 2D coordinates of a sample of review vectors.
+    pub async fn projection(&self, req: &ProjectionReq) -> Result<ProjectionResp> {
+        self.json(Method::POST, "/analytics/projection", Some(req), Retry::Idempotent).await
+    }
+
+    /// GET /products/:id/ratings-timeline — average rating per day or week.
+    pub async fn ratings_timeline(&self, product_id: &str, params: &TimelineParams) -> Result<TimelineResp> {
+        let req = self.http.get(self.product_url(product_id, "ratings-timeline")?).query(params);
+        decode(self.send(req, Retry::Idempotent).await?).await
+    }
+
+    /// GET /products/:id/pros-cons — what the product's reviews praise and complain about.
+    pub async fn pros_cons(&self, product_id: &str, params: &ProsConsParams) -> Result<ProsConsResp> {
+        let req = self.http.get(self.product_url(product_id, "pros-cons")?).query(params);
+        decode(self.send(req, Retry::Idempotent).await?).await
+    }
+
+    /// GET /products/:id/aspects — the aspects the product's reviews bring up, the ones
+    /// dragging its rating down first.
+    pub async fn aspects(&self, product_id: &str, params: &AspectsParams) -> Result<AspectsResp> {
+        let req = self.http.get(self.product_url(product_id, "aspects")?).query(params);
+        decode(self.send(req, Retry::Idempotent).await?).await
+    }
+
+    /// GET /analytics/trending — terms more common in recent reviews than before.
+    pub async fn trending(&self, params: &TrendingParams) -> Result<TrendingResp> {
+        decode(self.send(self.http.get(self.url("/analytics/trending")).query(params), Retry::Idempotent).await?).await
+    }
+
+    /// GET /analytics/composition — the live reviews by product, language and rating.
+    pub async fn composition(&self, params: &CompositionParams) -> Result<CompositionResp> {
+        decode(self.send(self.http.get(self.url("/analytics/composition")).query(params), Retry::Idempotent).await?).await
+    }
+
+    /// POST /feedback — the user clicked `clicked` among `shown`.
+    pub async fn feedback(&self, req: &FeedbackReq) -> Result<()> {
+        let req = self.http.post(self.url("/feedback")).json(req);
+        self.send(req, Retry::Once).await.map(drop)
+    }
+
+    /// POST /eval
+    pub async fn eval(&self, req: &EvalReq) -> Result<EvalResp> {
+        self.json(Method::POST, "/eval", Some(req), Retry::Idempotent).await
+    }
+
+    // ---- moderation ----
+
+    /// GET /admin/moderation — one page of the reviews pending, flagged or rejected.
+    pub async fn moderation_queue(&self, params: &QueueParams) -> Result<QueueResp> {
+        decode(self.send(self.http.get(self.url("/admin/moderation")).query(params), Retry::Idempotent).await?).await
+    }
+
+    /// POST /admin/moderation/:id/approve — makes the review searchable; `reason` is kept
+    /// in the moderation log.
+    pub async fn approve(&self, id: usize, reason: Option<&str>) -> Result<()> {
+        self.decide(id, "approve", reason).await
+    }
+
+    /// POST /admin/moderation/:id/reject — keeps the review out of search without deleting it.
+    pub async fn reject(&self, id: usize, reason: Option<&str>) -> Result<()> {
+        self.decide(id, "reject", reason).await
+    }
+
+    async fn decide(&self, id: usize, decision: &str, reason: Option<&str>) -> Result<()> {
+        let body = DecisionReq { reason: reason.map(str::to_string) };
+        let req = self.http.post(self.url(&format!("/admin/moderation/{id}/{decision}"))).json(&body);
+        self.send(req, Retry::Once).await.map(drop)
+    }
+
+    // ---- admin ----
+
+    /// POST /admin/generate
+    pub async fn generate(&self, req: &GenerateReq) -> Result<GenerateResp> {
+        self.json(Method::POST, "/admin/generate", Some(req), Retry::Once).await
+    }
+
+    /// POST /admin/reindex — starts a job; follow it with `job`.
+    pub async fn reindex(&self, req: &ReindexReq) -> Result<ReindexResp> {
+        self.json(Method::POST, "/admin/reindex", Some(req), Retry::Once).await
+    }
+
+    /// POST /admin/purge — starts a job erasing every deleted review for good.
+    pub async fn purge(&self) -> Result<PurgeResp> {
+        self.json(Method::POST, "/admin/purge", None::<&()>, Retry::Once).await
+    }
+
+    /// POST /admin/idf — starts a job recomputing the embedder's document frequencies.
+    pub async fn refresh_idf(&self, req: &IdfReq) -> Result<IdfResp> {
+        self.json(Method::POST, "/admin/idf", Some(req), Retry::Once).await
+    }
+
+    /// POST /admin/compact — asks for a compaction on the service's next start.
+    pub async fn request_compaction(&self) -> Result<CompactResp> {
+        // Asking twice leaves one request.
+        self.json(Method::POST, "/admin/compact", None::<&()>, Retry::Idempotent).await
+    }
+
+    /// POST /admin/snapshot — starts a job copying the data dir under `snapshots/`.
+    pub async fn snapshot(&self) -> Result<SnapshotResp> {
+        self.json(Method::POST, "/admin/snapshot", None::<&()>, Retry::Once).await
+    }
+
+    /// GET /admin/verify — checksums of every index directory, and what to repair.
+    pub async fn verify(&self) -> Result<VerifyResp> {
+        self.json(Method::GET, "/admin/verify", None::<&()>, Retry::Idempotent).await
+    }
+
+    /// GET /admin/read-only
+    pub async fn read_only(&self) -> Result<bool> {
+        let resp: ReadOnly = self.json(Method::GET, "/admin/read-only", None::<&()>, Retry::Idempotent).await?;
+        Ok(resp.read_only)
+    }
+
+    /// POST /admin/read-only — turns read-only mode on or off until the service restarts.
+    pub async fn set_read_only(&self, read_only: bool) -> Result<()> {
+        self.json::<_, ReadOnly>(Method::POST, "/admin/read-only", Some(&ReadOnly { read_only }), Retry::Idempotent).await.map(drop)
+    }
+
+    /// GET /jobs
+    pub async fn jobs(&self) -> Result<Vec<Job>> {
+        self.json(Method::GET, "/jobs", None::<&()>, Retry::Idempotent).await
+    }
+
+    /// GET /jobs/:id
+    pub async fn job(&self, id: uuid::Uuid) -> Result<Job> {
+        self.json(Method::GET, &format!("/jobs/{id}"), None::<&()>, Retry::Idempotent).await
+    }
+
+    /// GET /aliases — alias name to collection.
+    pub async fn aliases(&self) -> Result<BTreeMap<String, String>> {
+        self.json(Method::GET, "/aliases", None::<&()>, Retry::Idempotent).await
+    }
+
+    /// POST /aliases — create or re-point an alias.
+    pub async fn put_alias(&self, name: &str, collection: &str) -> Result<AliasReq> {
+        let body = AliasReq { name: name.into(), collection: collection.into() };
+        // Re-pointing to the same collection again changes nothing.
+        self.json(Method::POST, "/aliases", Some(&body), Retry::Idempotent).await
+    }
+
+    /// DELETE /aliases/:name
+    pub async fn delete_alias(&self, name: &str) -> Result<()> {
+        let req = self.http.delete(self.url(&format!("/aliases/{name}")));
+        self.send(req, Retry::Once).await.map(drop)
+    }
+
+    /// GET /collections
+    pub async fn collections(&self) -> Result<Vec<CollectionInfo>> {
+        self.json(Method::GET, "/collections", None::<&()>, Retry::Idempotent).await
+    }
+
+    /// GET /embedders
+    pub async fn embedders(&self) -> Result<Vec<ModelInfo>> {
+        self.json(Method::GET, "/embedders", None::<&()>, Retry::Idempotent).await
+    }
+
+    /// POST /embedders — `embedder` as in the service config, e.g. `{"type": "tfidf", "dim": 8192}`.
+    pub async fn register_embedder(&self, req: &RegisterEmbedderReq) -> Result<()> {
+        let req = self.http.post(self.url("/embedders")).json(req);
+        self.send(req, Retry::Once).await.map(drop)
+    }
+
+    /// GET /stats
+    pub async fn stats(&self) -> Result<StatsResp> {
+        self.json(Method::GET, "/stats", None::<&()>, Retry::Idempotent).await
+    }
+
+    /// GET /metrics — Prometheus text format.
+    pub async fn metrics(&self) -> Result<String> {
+        Ok(self.send(self.http.get(self.url("/metrics")), Retry::Idempotent).await?.text().await?)
+    }
+
+    /// GET /admin/storage
+    pub async fn storage(&self) -> Result<StorageReport> {
+        self.json(Method::GET, "/admin/storage", None::<&()>, Retry::Idempotent).await
+    }
+
+    /// GET /admin/usage — usage and effective quota of every tenant.
+    pub async fn usage(&self) -> Result<Vec<TenantUsage>> {
+        self.json(Method::GET, "/admin/usage", None::<&()>, Retry::Idempotent).await
+    }
+
+    /// GET /admin/drift — how far queries and documents have drifted from their baselines;
+    /// None when the active embedder does not track drift.
+    pub async fn drift(&self) -> Result<Option<DriftResp>> {
+        self.json(Method::GET, "/admin/drift", None::<&()>, Retry::Idempotent).await
+    }
+
+    /// POST /admin/drift/reset — starts new baselines.
+    pub async fn reset_drift(&self) -> Result<()> {
+        self.send(self.http.post(self.url("/admin/drift/reset")), Retry::Once).await.map(drop)
+    }
+
+    /// GET /admin/audit
+    pub async fn audit(&self, q: &AuditQuery) -> Result<AuditResp> {
+        decode(self.send(self.http.get(self.url("/admin/audit")).query(q), Retry::Idempotent).await?).await
+    }
+
+    // ---- plumbing ----
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{path}", self.base)
+    }
+
+    /// `/products/<product_id>/<what>`, the id percent-encoded as one path segment.
+    fn product_url(&self, product_id: &str, what: &str) -> Result<reqwest::Url> {
+        let mut url = reqwest::Url::parse(&self.url("/products")).map_err(|e| Error::Config(format!("base URL: {e}")))?;
+        url.path_segments_mut().map_err(|()| Error::Config("base URL cannot have a path".into()))?.push(product_id).push(what);
+        Ok(url)
+    }
+
+    async fn json<B: Serialize + ?Sized, T: DeserializeOwned>(&self, method: Method, path: &str, body: Option<&B>, retry: Retry) -> Result<T> {
+        let mut req = self.http.request(method, self.url(path));
+        if let Some(b) = body { req = req.json(b); }
+        decode(self.send(req, retry).await?).await
+    }
+
+    /// Sends `req`, retrying as described in the crate docs; error statuses become `Error::Api`.
+    async fn send(&self, req: RequestBuilder, retry: Retry) -> Result<Response> {
+        let mut attempt = 0;
+        loop {
+            // Bodies here are always in memory, so the request can be cloned.
+            let this = req.try_clone().ok_or_else(|| Error::Config("request body is a stream and cannot be retried".into()))?;
+            let (err, retry_after) = match this.send().await {
+                Ok(resp) if resp.status().is_success() => return Ok(resp),
+                Ok(resp) => {
+                    let status = resp.status();
+                    let retry_after = resp.headers().get("retry-after")
+                        .and_then(|v| v.to_str().ok()?.parse().ok())
+                        .map(Duration::from_secs);
+                    let err = api_error(resp).await;
+                    let transient = matches!(status, StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE)
+                        || (retry == Retry::Idempotent && matches!(status, StatusCode::BAD_GATEWAY | StatusCode::GATEWAY_TIMEOUT));
+                    if !transient { return Err(err); }
+                    (err, retry_after)
+                }
+                Err(e) if retry == Retry::Idempotent && (e.is_connect() || e.is_timeout()) => (e.into(), None),
+                Err(e) => return Err(e.into()),
+            };
+            if attempt >= self.max_retries { return Err(err); }
+            tokio::time::sleep(retry_after.unwrap_or_else(|| backoff(attempt)).min(MAX_BACKOFF)).await;
+            attempt += 1;
+        }
+    }
+}
+
+/// Wait before retry `attempt + 1` when the service sends no Retry-After.
+fn backoff(attempt: u32) -> Duration {
+    BACKOFF_BASE.saturating_mul(2u32.saturating_pow(attempt)).min(MAX_BACKOFF)
+}
+
+#[derive(Deserialize)]
+struct ErrorBody { error: String }
+
+async fn api_error(resp: Response) -> Error {
+    let status = resp.status().as_u16();
+    let text = resp.text().await.unwrap_or_default();
+    let message = serde_json::from_str::<ErrorBody>(&text).map(|b| b.error).unwrap_or(text);
+    Error::Api { status, message }
+}
+
+async fn decode<T: DeserializeOwned>(resp: Response) -> Result<T> {
+    Ok(serde_json::from_slice(&resp.bytes().await?)?)
+}
+
+/// NDJSON response read one item at a time as chunks arrive.
+pub struct NdjsonStream<T> {
+    resp: Response,
+    buf: Vec<u8>,
+    done: bool,
+    _item: PhantomData<fn() -> T>,
+}
+
+impl<T: DeserializeOwned> NdjsonStream<T> {
+    fn new(resp: Response) -> Self {
+        Self { resp, buf: Vec::new(), done: false, _item: PhantomData }
+    }
+
+    /// The next item, or None at the end of the response.
+    pub async fn next(&mut self) -> Option<Result<T>> {
+        loop {
+            if let Some(nl) = self.buf.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = self.buf.drain(..=nl).collect();
+                if line.iter().all(u8::is_ascii_whitespace) { continue; }
+                return Some(serde_json::from_slice(&line).map_err(Error::from));
+            }
+            if self.done {
+                if self.buf.iter().all(u8::is_ascii_whitespace) { return None; }
+                let rest = std::mem::take(&mut self.buf);
+                return Some(serde_json::from_slice(&rest).map_err(Error::from));
+            }
+            match self.resp.chunk().await {
+                Ok(Some(chunk)) => self.buf.extend_from_slice(&chunk),
+                Ok(None) => self.done = true,
+                Err(e) => {
+                    self.done = true;
+                    self.buf.clear();
+                    return Some(Err(e.into()));
+                }
+            }
+        }
+    }
+
+    /// Reads the rest of the response.
+    pub async fn collect(mut self) -> Result<Vec<T>> {
+        let mut out = Vec::new();
+        while let Some(item) = self.next().await { out.push(item?); }
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fmt::Debug;
+
+    fn client() -> Client { Client::new("http://localhost:8000").unwrap() }
+
+    /// What the service reads back from the query string `params` is sent as.
+    fn query_round_trip<T: Serialize + DeserializeOwned + PartialEq + Debug>(params: &T) {
+        let c = client();
+        let req = c.http.get(c.url("/x")).query(params).build().unwrap();
+        let sent: T = serde_urlencoded::from_str(req.url().query().unwrap_or("")).unwrap();
+        assert_eq!(&sent, params);
+    }
+
+    fn json_round_trip<T: Serialize + DeserializeOwned + PartialEq + Debug>(body: &T) {
+        let back: T = serde_json::from_slice(&serde_json::to_vec(body).unwrap()).unwrap();
+        assert_eq!(&back, body);
+    }
+
+    #[test]
+    fn query_params_round_trip() {
+        query_round_trip(&TrendingParams::default());
+        query_round_trip(&TrendingParams { window_days: 1, product_id: Some("p 1&2".into()), ..Default::default() });
+        query_round_trip(&TimelineParams { bucket: Granularity::Week, from: Some(1_700_000_000), to: None });
+        query_round_trip(&ProsConsParams { similarity: 0.5, ..Default::default() });
+        query_round_trip(&AspectsParams { min_reviews: 3 });
+        query_round_trip(&CompositionParams::default());
+        query_round_trip(&QueueParams { status: QueueStatus::Flagged, after: Some(41), limit: 10 });
+        query_round_trip(&AuditQuery { action: Some(Action::RefreshIdf), id: Some(7), ..Default::default() });
+    }
+
+    #[test]
+    fn omitted_params_are_the_defaults() {
+        let parse = |q| serde_urlencoded::from_str::<TrendingParams>(q).unwrap();
+        assert_eq!(parse(""), TrendingParams::default());
+        assert_eq!(parse("limit=5").limit, 5);
+        assert_eq!(serde_urlencoded::from_str::<QueueParams>("status=quarantined").unwrap().status, QueueStatus::Pending);
+        assert_eq!(serde_json::from_str::<ReindexReq>("{}").unwrap(), ReindexReq::default());
+        assert!(ReindexReq::default().swap);
+    }
+
+    #[test]
+    fn request_bodies_round_trip() {
+        json_round_trip(&ReindexReq { swap: false, embedder: Some("minilm".into()) });
+        json_round_trip(&IdfReq { half_life_days: Some(30.0) });
+        json_round_trip(&DecisionReq::default());
+        json_round_trip(&AskReq { question: "does it last?".into(), lang: vec!["en".into()], ..Default::default() });
+        json_round_trip(&EvalReq { cases: vec![EvalCase { query: "q".into(), relevant: vec![1, 2] }], k: Some(5), per_case: true, ..Default::default() });
+        json_round_trip(&FeedbackReq { query: "q".into(), shown: vec![3, 1], clicked: 1, collection: None });
+        json_round_trip(&GenerateReq { count: 10, seed: Some(1), products: None });
+        json_round_trip(&RegisterEmbedderReq { name: "t".into(), embedder: serde_json::json!({"type": "tfidf", "dim": 64}) });
+    }
+
+    #[test]
+    fn response_bodies_round_trip() {
+        let metrics = Metrics { recall: 0.5, mrr: 1.0, ndcg: 0.75 };
+        json_round_trip(&EvalResp { k: 10, cases: 2, mean: metrics, per_case: Some(vec![metrics, Metrics::default()]) });
+        json_round_trip(&CompositionResp {
+            reviews: 3,
+            products: 1,
+            per_product: None,
+            top_products: vec![Share { key: ProductKey { product_id: "p".into() }, reviews: 3, share: 1.0 }],
+            langs: vec![Share { key: LangKey { lang: "th".into() }, reviews: 3, share: 1.0 }],
+            ratings: vec![Share { key: RatingKey { rating: 5 }, reviews: 3, share: 1.0 }],
+        });
+        json_round_trip(&QueueResp {
+            items: vec![QueueItem { id: 4, status: ReviewStatus::Pending, decision: Some(Decision::Flagged), reason: Some("spam".into()), ts_ms: Some(1) }],
+            next_after: Some(4),
+        });
+        json_round_trip(&DryRunResp {
+            review: Review::default(),
+            moderation: Some(Verdict { decision: Decision::Pending, reason: "word".into() }),
+            terms: vec!["battery".into()],
+            vector: vec![0.0, 1.0],
+            top_dims: vec![],
+        });
+        json_round_trip(&AuditResp {
+            entries: vec![AuditEntry {
+                ts_ms: 1,
+                principal: "ops".into(),
+                request_id: "r".into(),
+                action: Action::Purge,
+                ids: [1, 2, 3, 9].into_iter().collect(),
+                subject: None,
+            }],
+            next_cursor: Some(1),
+        });
+        json_round_trip(&StatsResp { reviews: 1, vectors: 1, vector_cache: CacheStats::default(), last_flush_at: None });
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        assert_eq!(backoff(0), Duration::from_millis(200));
+        assert_eq!(backoff(3), Duration::from_millis(1600));
+        assert_eq!(backoff(40), MAX_BACKOFF);
+        assert_eq!(backoff(u32::MAX), MAX_BACKOFF);
+    }
+
+    #[test]
+    fn product_ids_are_one_path_segment() {
+        let url = client().product_url("a/b c", "aspects").unwrap();
+        assert_eq!(url.path(), "/v1/products/a%2Fb%20c/aspects");
+    }
+}
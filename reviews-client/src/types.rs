@@ -0,0 +1,13 @@
+//! Request and response bodies of the v1 API, from reviews-types, which the service itself
+//! uses. Optional request fields left as None are not sent, so the service applies its
+//! defaults.
+
+// Every body; `ModelInfo` and `RegisterEmbedderReq` below shadow the generic ones.
+pub use reviews_types::*;
+
+/// GET /embedders: the embedder section as written in the service's config, e.g.
+/// `{"type": "tfidf", "dim": 4096}`, read as JSON.
+pub type ModelInfo = reviews_types::ModelInfo<serde_json::Value>;
+
+/// POST /embedders, `embedder` as in `ModelInfo`.
+pub type RegisterEmbedderReq = reviews_types::RegisterEmbedderReq<serde_json::Value>;
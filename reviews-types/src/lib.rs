@@ -0,0 +1,1468 @@
+//! Request and response bodies of the review, search and admin endpoints, used by the
+//! service to (de)serialize them and by its clients (the Leptos UI, reviews-client) to
+//! build and read them, so the wire format has a single definition. Only serde (and uuid
+//! for job ids) here: the crate must also build for wasm32.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Review {
+    pub review_title: String,
+    pub review_body: String,
+    pub product_id: String,
+    pub review_rating: i32,
+    /// Seconds since the epoch. Stamped on write unless the client supplies it (e.g. when
+    /// importing historical reviews); absent on reviews written before it existed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<u64>,
+    /// The source system's id. A review carrying one supersedes any earlier review with
+    /// the same external_id, which then drops out of search, listing and export.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub external_id: Option<String>,
+    /// Assigned on write: 1 for a new review, one more than the replaced review's for a
+    /// replacement. Send back the version you read to replace or delete a review; a
+    /// different one gets 409. Always present in reads.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<u64>,
+    /// Language code, e.g. `th` or `en`. Detected from the text on write unless the client
+    /// supplies it; absent on reviews written before it existed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lang: Option<String>,
+    /// Moderation status. Set on write (the configured default, or `pending` when the
+    /// moderation checks hold the review), whatever the client sends; reads show the
+    /// current one. Reviews written before it existed are `approved`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<ReviewStatus>,
+    /// Product aspects the review talks about (battery, shipping, quality, ...), each with
+    /// the sentiment of what it says about them. Extracted on write, whatever the client
+    /// sends; absent on reviews written before it existed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aspects: Option<Vec<AspectMention>>,
+}
+
+/// One aspect of a product a review talks about.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct AspectMention {
+    /// Its canonical name, e.g. `shipping` for a review about delivery.
+    pub aspect: String,
+    /// From -1 (all negative) to 1 (all positive).
+    pub sentiment: f32,
+}
+
+impl Review {
+    /// Text fed to the embedder.
+    pub fn embed_text(&self) -> String { format!("{} {}", self.review_title, self.review_body) }
+}
+
+/// POST /reviews and /reviews/upsert.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct InsertReq {
+    pub review: Review,
+    /// Send back the vector stored for the review.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub return_vector: bool,
+}
+
+/// POST /reviews/bulk.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct BulkInsertReq { pub reviews: Vec<Review> }
+
+/// POST /reviews/raw: a review with a vector computed by the caller.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct RawInsertReq {
+    pub review: Review,
+    pub vector: Vec<f32>,
+    /// Registered embedder (see /embedders) that computed `vector`; the insert is refused
+    /// unless the active index was built by it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embedder: Option<String>,
+    /// Send back the vector stored, `vector` normalised.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub return_vector: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ReviewResp {
+    pub id: usize,
+    /// The review's stored vector, with `return_vector`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vector: Option<Vec<f32>>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BulkResp { pub inserted: usize }
+
+/// PATCH /reviews/:id: fields that do not go into the review's vector, changed in place.
+/// Fields left out keep their value.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PatchReq {
+    /// The version last read; a different one gets 409.
+    pub version: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub review_rating: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub product_id: Option<String>,
+    /// Set as a moderation decision would.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<ReviewStatus>,
+}
+
+/// The review as patched, under its unchanged id.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PatchResp {
+    pub id: usize,
+    pub review: Review,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct UpsertResp {
+    pub id: usize,
+    pub external_id: String,
+    /// Id of the review this one replaced; None if the external_id was new.
+    pub replaced: Option<usize>,
+    pub version: u64,
+    /// The review's stored vector, with `return_vector`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vector: Option<Vec<f32>>,
+}
+
+/// POST /reviews/batch: up to 1000 ids.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct BatchGetReq { pub ids: Vec<usize> }
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BatchItem {
+    pub id: usize,
+    /// False for ids never written, deleted or taken over by a later review with the same
+    /// external_id, and for lines failing their checksum.
+    pub found: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub review: Option<Review>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct BatchGetResp {
+    /// One per requested id, in the order requested (repeats included).
+    pub reviews: Vec<BatchItem>,
+}
+
+/// GET /vectors/:id: a stored vector as the mirror file holds it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct VectorResp {
+    pub id: usize,
+    pub dim: usize,
+    /// False once the review was deleted or replaced; its vector stays in the mirror.
+    pub live: bool,
+    /// Where the record (length and checksum, then the vector) starts in the mirror file.
+    pub offset: u64,
+    pub norm: f32,
+    /// As stored, unless quantized.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vector: Option<Vec<f32>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub int8: Option<Int8Vector>,
+    /// What the index file stores next to the vector: the review's external_id, if it has
+    /// one of at most 256 bytes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payload: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Int8Vector {
+    /// `values[i] * scale` approximates dimension i.
+    pub scale: f32,
+    pub values: Vec<i8>,
+}
+
+/// POST /reviews/:id/reembed.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct ReembedResp {
+    pub id: usize,
+    /// Cosine similarity of the new vector with the one it replaced: near 1 when the text
+    /// embeds as before. None if the old one failed its checksum.
+    pub similarity: Option<f32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct SearchReq {
+    /// Leave empty when sending `queries` or `positive_ids`.
+    #[serde(default)]
+    pub query: String,
+    /// Several phrasings of one topic, searched together instead of `query`: each review is
+    /// scored against all of them and the similarities fused per `fusion`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub queries: Vec<String>,
+    /// How `queries` are combined (default `max`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fusion: Option<Fusion>,
+    /// Weight of each of `queries` for `fusion: mean`, in the same order (default all 1).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weights: Option<Vec<f32>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<usize>,
+    /// Also run the query against the shadow index and return both result lists.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub compare: bool,
+    /// Search by example instead of by text: reviews similar to these ids (the mean of
+    /// their vectors, less `negative_weight` times the mean of `negative_ids`). The
+    /// examples themselves are left out of the hits.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub positive_ids: Vec<usize>,
+    /// Reviews to steer away from; needs `positive_ids`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub negative_ids: Vec<usize>,
+    /// Default 0.5.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub negative_weight: Option<f32>,
+    /// Alias or collection to search instead of the active one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collection: Option<String>,
+    /// Registered embedder (see /embedders) to search with: the collection it built, the
+    /// active one first. With `collection`, that collection must have been built by it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embedder: Option<String>,
+    /// How the title and body vectors are scored, on a service storing them (`[fields]`);
+    /// its configured default otherwise.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fields: Option<FieldScoring>,
+    /// Re-rank the top hits by late interaction (per-word max-sim against several vectors
+    /// per review), on a service storing them (`[late_interaction]`). Needs `query`.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub late_interaction: bool,
+    /// Score only the vectors whose binary sketches are closest to the query's, on a service
+    /// keeping them (`[search.sketch]`, where this defaults to true); false scores every one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sketch: Option<bool>,
+    /// Take the hits from the spfresh index's traversal, with the filters applied inside it,
+    /// on a service set up for it (`[search] ann`, where this defaults to true); false scans
+    /// every vector. Plain single-query searches only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ann: Option<bool>,
+    /// Break each hit's score down by term and by ranking stage.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub explain: bool,
+    /// Two-stage search: score only up to this many reviews sharing a query term (keyword
+    /// prefilter) instead of every vector. Reviews with no query term are never returned.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub candidates: Option<usize>,
+    /// Multiply scores by 0.5^(age / half-life) so newer reviews rank higher. Reviews
+    /// without `created_at` are not decayed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub half_life_days: Option<f64>,
+    /// Ranking formula replacing the plain cosine, e.g. `cosine * 0.8 + rating / 5 * 0.2`.
+    /// Recency decay, if requested, applies to its result.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score: Option<String>,
+    /// Return at most `per_group` hits per group; the rest are counted in the group's
+    /// best hit as `collapsed`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group_by: Option<GroupBy>,
+    /// Hits kept per group with `group_by` (default 1).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub per_group: Option<usize>,
+    /// Only reviews in one of these languages (see `Review::lang`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub lang: Vec<String>,
+    /// Also return pending and rejected reviews, for curation; only approved ones otherwise.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub include_unapproved: bool,
+    /// Only reviews of one of these products.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub product_ids: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_rating: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_rating: Option<i32>,
+    /// Only reviews created at or after this time, in seconds since the epoch. With
+    /// either bound, reviews without `created_at` are left out.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_after: Option<u64>,
+    /// Only reviews created before this time, in seconds since the epoch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_before: Option<u64>,
+}
+
+impl SearchReq {
+    pub fn new(query: impl Into<String>) -> Self {
+        Self { query: query.into(), ..Default::default() }
+    }
+
+    /// A search over several phrasings of one topic.
+    pub fn fused(queries: impl IntoIterator<Item = impl Into<String>>, fusion: Fusion) -> Self {
+        Self { queries: queries.into_iter().map(Into::into).collect(), fusion: Some(fusion), ..Default::default() }
+    }
+
+    /// "More like these": a search by example review ids.
+    pub fn like(positive_ids: impl Into<Vec<usize>>, negative_ids: impl Into<Vec<usize>>) -> Self {
+        Self { positive_ids: positive_ids.into(), negative_ids: negative_ids.into(), ..Default::default() }
+    }
+
+    pub fn top_k(mut self, k: usize) -> Self {
+        self.top_k = Some(k);
+        self
+    }
+
+    pub fn collection(mut self, collection: impl Into<String>) -> Self {
+        self.collection = Some(collection.into());
+        self
+    }
+
+    pub fn embedder(mut self, embedder: impl Into<String>) -> Self {
+        self.embedder = Some(embedder.into());
+        self
+    }
+
+    pub fn fields(mut self, fields: FieldScoring) -> Self {
+        self.fields = Some(fields);
+        self
+    }
+}
+
+fn is_false(b: &bool) -> bool { !*b }
+
+/// How the similarities of a review to each of `queries` become its one score.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Fusion {
+    /// The best match among the queries.
+    #[default]
+    Max,
+    /// The weighted average of the similarities.
+    Mean,
+}
+
+/// How a review's similarity to the query is scored when its title and body have vectors
+/// of their own.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum FieldScoring {
+    /// The single vector of title and body together, as without per-field vectors.
+    #[default]
+    Combined,
+    /// The better of the title and body similarities.
+    Max,
+    /// The weighted average of the title and body similarities.
+    Weighted { title: f32, body: f32 },
+}
+
+/// Where a review stands in moderation; only `approved` reviews are searched by default.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ReviewStatus {
+    Pending,
+    #[default]
+    Approved,
+    Rejected,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupBy {
+    ProductId,
+}
+
+/// One dimension's share of a hit's score: query weight times document weight.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TermWeight {
+    pub dim: usize,
+    /// The term behind `dim`; only embedders with an exact vocabulary know it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub term: Option<String>,
+    pub weight: f32,
+}
+
+/// The stages behind a hit's score: the cosine, then the `score` formula and recency
+/// decay when the request asked for them.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct ScoreParts {
+    pub cosine: f32,
+    /// The formula's value, which replaces the cosine.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub formula: Option<f32>,
+    /// Recency factor the score is multiplied by; absent for undated reviews.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decay: Option<f32>,
+}
+
+impl ScoreParts {
+    /// The hit's final score.
+    pub fn total(&self) -> f32 { self.formula.unwrap_or(self.cosine) * self.decay.unwrap_or(1.0) }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SearchHit {
+    pub id: usize,
+    pub score: f32,
+    pub review: Review,
+    /// Per-term contributions to `score`, with `explain: true`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub explain: Option<Vec<TermWeight>>,
+    /// How `score` was computed from the cosine, with `explain: true`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score_parts: Option<ScoreParts>,
+    /// With `group_by`: hits of the same group left out, on the group's best hit only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collapsed: Option<usize>,
+    /// Router mode: the shard that returned this hit (ids are per shard).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shard: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct SearchResp {
+    pub hits: Vec<SearchHit>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shadow_hits: Option<Vec<SearchHit>>,
+    /// Router mode: shards that did not answer, so `hits` cover only part of the corpus.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub failed_shards: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stats: Option<SearchStats>,
+}
+
+/// How much work a search did and where its time went. With `compare`, the shadow index's
+/// share is included; in router mode, counts are summed over the shards that answered and
+/// each stage is the slowest shard's.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct SearchStats {
+    /// Vectors looked at: every stored one, or those sharing a term with the query when
+    /// `candidates` is set.
+    pub candidates: usize,
+    /// Candidates that passed the filters (not deleted or superseded) and were ranked.
+    pub matched: usize,
+    pub embed_ms: f64,
+    /// Reading candidate vectors and computing their cosine.
+    pub retrieve_ms: f64,
+    /// `score` formula, recency decay, sorting and grouping.
+    pub score_ms: f64,
+    /// Reading the returned hits' reviews.
+    pub fetch_ms: f64,
+    pub total_ms: f64,
+}
+
+/// POST /search/range: every review within a distance of the query rather than a top-k,
+/// e.g. to find near-duplicates.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct RangeSearchReq {
+    pub query: String,
+    /// Greatest distance from the query a hit may have, in the index's metric.
+    pub max_distance: f32,
+    /// Most hits returned, nearest first; capped like /search/stream's `top_k`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+    /// Alias or collection to search instead of the active one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collection: Option<String>,
+    /// Also return pending and rejected reviews; only approved ones otherwise.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub include_unapproved: bool,
+    /// Hits carry their `external_id` from the index file instead of the review, which
+    /// skips the metadata reads.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub ids_only: bool,
+}
+
+impl RangeSearchReq {
+    pub fn new(query: impl Into<String>, max_distance: f32) -> Self {
+        Self { query: query.into(), max_distance, ..Default::default() }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RangeHit {
+    pub id: usize,
+    pub distance: f32,
+    /// Left out with `ids_only`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub review: Option<Review>,
+    /// With `ids_only`, the review's external_id as the index stores it (see
+    /// `VectorResp::payload`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub external_id: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct RangeSearchResp {
+    /// Nearest first.
+    pub hits: Vec<RangeHit>,
+    /// The index stopped at `limit`: more reviews are within `max_distance`.
+    pub truncated: bool,
+}
+
+/// POST /compare: the reviews of two products on one topic, side by side.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct CompareReq {
+    pub products: [String; 2],
+    pub query: String,
+    /// Hits per product.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<usize>,
+    /// Alias or collection to search instead of the active one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collection: Option<String>,
+}
+
+impl CompareReq {
+    pub fn new(a: impl Into<String>, b: impl Into<String>, query: impl Into<String>) -> Self {
+        Self { products: [a.into(), b.into()], query: query.into(), ..Default::default() }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CompareResp {
+    /// In the order of the request's `products`.
+    pub products: [ProductComparison; 2],
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ProductComparison {
+    pub product_id: String,
+    /// The product's reviews most relevant to the query, best first.
+    pub hits: Vec<SearchHit>,
+    /// Over `hits`.
+    pub topic: RatingSummary,
+    /// Over all the product's approved reviews.
+    pub overall: RatingSummary,
+}
+
+/// Ratings of a set of reviews, with sentiment read off the stars: 4-5 positive, 3
+/// neutral, 1-2 negative. Reviews rated outside 1-5 (e.g. imported unrated) are left out.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct RatingSummary {
+    pub count: usize,
+    pub avg_rating: Option<f64>,
+    pub positive: usize,
+    pub neutral: usize,
+    pub negative: usize,
+    /// (positive - negative) / count, from -1 to 1.
+    pub sentiment: Option<f64>,
+}
+
+impl RatingSummary {
+    pub fn of(ratings: impl IntoIterator<Item = i32>) -> Self {
+        let mut s = Self::default();
+        let mut sum = 0i64;
+        for r in ratings.into_iter().filter(|r| (1..=5).contains(r)) {
+            s.count += 1;
+            sum += r as i64;
+            match r {
+                4.. => s.positive += 1,
+                3 => s.neutral += 1,
+                _ => s.negative += 1,
+            }
+        }
+        if s.count > 0 {
+            s.avg_rating = Some(sum as f64 / s.count as f64);
+            s.sentiment = Some((s.positive as f64 - s.negative as f64) / s.count as f64);
+        }
+        s
+    }
+}
+
+/// POST /analytics/projection: 2D coordinates of review vectors, for plotting.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ProjectionReq {
+    #[serde(default)]
+    pub method: ProjectionMethod,
+    /// Points returned: a uniform sample of the matching reviews (default 1000, at most
+    /// 10000).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sample: Option<usize>,
+    /// Sampling seed; the same seed over the same reviews gives the same points.
+    #[serde(default)]
+    pub seed: u64,
+    /// Project these reviews instead of a sample (the filters still apply).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ids: Vec<usize>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub product_ids: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub lang: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_rating: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_rating: Option<i32>,
+    /// Also project pending and rejected reviews; only approved ones otherwise.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub include_unapproved: bool,
+    /// What each point's `label` is (default `rating`).
+    #[serde(default)]
+    pub label: PointLabel,
+    /// Also return each point's review title, e.g. for hover text.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub titles: bool,
+    /// Alias or collection to project instead of the active one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collection: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProjectionMethod {
+    /// The two directions of greatest variance.
+    #[default]
+    Pca,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PointLabel {
+    #[default]
+    Rating,
+    ProductId,
+    Lang,
+    Status,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ProjectionResp {
+    /// Reviews matching the filters, of which `points` is a sample.
+    pub matched: usize,
+    /// Share of the sample's variance along x and along y.
+    pub explained_variance: [f64; 2],
+    pub points: Vec<ProjectedPoint>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ProjectedPoint {
+    pub id: usize,
+    pub x: f64,
+    pub y: f64,
+    pub label: String,
+    /// With `titles`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+}
+
+/// GET /products: the products reviews have been written for, by id.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ProductsResp {
+    /// Products matching the prefix, of which `products` may be the first `limit`.
+    pub total: usize,
+    pub products: Vec<ProductInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ProductInfo {
+    pub product_id: String,
+    /// Live, approved reviews.
+    pub reviews: usize,
+    pub avg_rating: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus { Running, Done, Failed }
+
+/// A long-running admin job (reindex, purge, snapshot, ...), as GET /jobs lists it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Job {
+    pub id: uuid::Uuid,
+    pub kind: String,
+    pub status: JobStatus,
+    pub processed: usize,
+    pub total: usize,
+    pub error: Option<String>,
+    pub started_at: u64,
+    pub finished_at: Option<u64>,
+}
+
+/// GET /admin/verify: one index directory's vectors.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct IndexCheck {
+    /// Relative to the data dir; "" for the data dir itself.
+    pub dir: String,
+    pub active: bool,
+    pub vectors: usize,
+    /// Records failing their checksum.
+    pub corrupt: usize,
+    /// Bytes after the last whole record, left by an interrupted append.
+    pub partial_bytes: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct VerifyResp {
+    pub reviews: usize,
+    pub indexes: Vec<IndexCheck>,
+    /// What is wrong and how to repair it; empty when all is well.
+    pub problems: Vec<String>,
+}
+
+/// GET and POST /admin/read-only.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReadOnly { pub read_only: bool }
+
+/// POST /reviews/import: the NDJSON lines that were not inserted, by 1-based line number.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct LineError { pub line: usize, pub error: String }
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ImportResp {
+    pub inserted: usize,
+    pub failed: usize,
+    pub errors: Vec<LineError>,
+}
+
+/// GET /reviews: one page of live reviews in id order.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ListItem { pub id: usize, pub review: Review }
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ListResp {
+    pub items: Vec<ListItem>,
+    /// Present when the page was full; pass it back as `cursor` for the next page.
+    pub next_cursor: Option<String>,
+}
+
+/// One line of GET /export/full.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct FullRecord {
+    pub id: usize,
+    pub review: Review,
+    /// None when the mirror record fails its checksum.
+    pub vector: Option<Vec<f32>>,
+}
+
+/// POST /reviews/dry-run: what an insert of the review would do, without writing it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct DryRunResp {
+    /// As an insert would write it: status, language, aspects, version and creation time
+    /// filled in.
+    pub review: Review,
+    /// What the moderation checks make of it; None when it passes them.
+    pub moderation: Option<Verdict>,
+    /// Tokens of its title and body, in order, as the keyword index and the TF-IDF embedder
+    /// see them.
+    pub terms: Vec<String>,
+    /// What the active index would store.
+    pub vector: Vec<f32>,
+    /// The vector's largest dimensions, with the term behind each for embedders that know it.
+    pub top_dims: Vec<TermWeight>,
+}
+
+/// One moderation log entry's outcome.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Decision {
+    /// Held by the checks (logged as `quarantined` before statuses existed).
+    #[serde(alias = "quarantined")]
+    Pending,
+    /// Caught by the checks, but left with its status.
+    Flagged,
+    Approved,
+    Rejected,
+}
+
+impl Decision {
+    /// The status it sets; a flag sets none.
+    pub fn status(self) -> Option<ReviewStatus> {
+        match self {
+            Self::Pending => Some(ReviewStatus::Pending),
+            Self::Flagged => None,
+            Self::Approved => Some(ReviewStatus::Approved),
+            Self::Rejected => Some(ReviewStatus::Rejected),
+        }
+    }
+}
+
+/// What the moderation checks made of a review that did not pass them.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Verdict {
+    pub decision: Decision,
+    pub reason: String,
+}
+
+/// Which reviews GET /admin/moderation lists.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum QueueStatus {
+    #[default]
+    #[serde(alias = "quarantined")]
+    Pending,
+    Flagged,
+    Rejected,
+}
+
+/// GET /admin/moderation query.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(default)]
+pub struct QueueParams {
+    pub status: QueueStatus,
+    /// Only ids above this one, for the next page.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after: Option<usize>,
+    pub limit: usize,
+}
+
+impl Default for QueueParams {
+    fn default() -> Self { Self { status: QueueStatus::Pending, after: None, limit: 100 } }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct QueueItem {
+    pub id: usize,
+    pub status: ReviewStatus,
+    /// The latest moderation entry: why the review was held or flagged, or the curator's
+    /// note. Absent for reviews pending only because that is the default status.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decision: Option<Decision>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ts_ms: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct QueueResp {
+    pub items: Vec<QueueItem>,
+    /// Present when the page was full; pass it back as `after`.
+    pub next_after: Option<usize>,
+}
+
+/// POST /admin/moderation/:id/approve and /reject; the body is optional.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct DecisionReq {
+    /// Kept in the moderation log and shown in the queue.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// POST /feedback: which of the hits shown for a query the user clicked.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct FeedbackReq {
+    pub query: String,
+    /// Hit ids in the order the user saw them.
+    pub shown: Vec<usize>,
+    pub clicked: usize,
+    /// Alias or collection the results came from; None for the active one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collection: Option<String>,
+}
+
+/// POST /eval: retrieval quality over queries with known answers.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct EvalReq {
+    pub cases: Vec<EvalCase>,
+    /// Cut-off of the metrics (default 10).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub k: Option<usize>,
+    /// Alias or collection to evaluate instead of the active one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collection: Option<String>,
+    /// Also return the metrics of every case.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub per_case: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct EvalCase {
+    pub query: String,
+    /// Review ids that count as correct answers for `query`.
+    pub relevant: Vec<usize>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct Metrics {
+    pub recall: f64,
+    pub mrr: f64,
+    pub ndcg: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct EvalResp {
+    pub k: usize,
+    pub cases: usize,
+    /// Means over all cases.
+    #[serde(flatten)]
+    pub mean: Metrics,
+    /// With `per_case`, in the order of the request's cases.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub per_case: Option<Vec<Metrics>>,
+}
+
+/// POST /ask: a question answered by the configured chat model from the reviews that
+/// match it best.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct AskReq {
+    pub question: String,
+    /// Reviews given to the model (default `[llm] top_k`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<usize>,
+    /// Alias or collection to search instead of the active one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collection: Option<String>,
+    /// Only reviews in one of these languages.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub lang: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Citation {
+    pub id: usize,
+    pub product_id: String,
+    pub review_rating: i32,
+    /// The sentence of the review closest to the question, verbatim.
+    pub snippet: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AskResp {
+    pub answer: String,
+    /// Reviews the answer cites, in order of first citation.
+    pub citations: Vec<Citation>,
+    /// Every review given to the model, best match first.
+    pub retrieved: Vec<usize>,
+    pub model: String,
+}
+
+/// GET /analytics/trending query.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(default)]
+pub struct TrendingParams {
+    /// The recent window, ending now.
+    pub window_days: u64,
+    /// The window before it that the recent one is compared with.
+    pub baseline_days: u64,
+    pub limit: usize,
+    /// Fewest recent reviews a term must appear in to be reported.
+    pub min_count: u32,
+    /// Only reviews of this product.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub product_id: Option<String>,
+}
+
+impl Default for TrendingParams {
+    fn default() -> Self { Self { window_days: 7, baseline_days: 90, limit: 20, min_count: 3, product_id: None } }
+}
+
+/// GET /analytics/trending: terms more common in recent reviews than before.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct TrendingResp {
+    /// Reviews in each window.
+    pub recent_reviews: u32,
+    pub baseline_reviews: u32,
+    pub terms: Vec<TrendingTerm>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TrendingTerm {
+    /// A word, or two adjacent words.
+    pub term: String,
+    /// Reviews containing it in the recent window, and in the baseline.
+    pub recent: u32,
+    pub baseline: u32,
+    /// Its share of recent reviews over its share of baseline reviews, add-one smoothed.
+    pub lift: f64,
+    pub example_ids: Vec<usize>,
+}
+
+/// GET /analytics/composition query.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(default)]
+pub struct CompositionParams {
+    /// Products with the most reviews to list.
+    pub top: usize,
+}
+
+impl Default for CompositionParams {
+    fn default() -> Self { Self { top: 10 } }
+}
+
+/// GET /analytics/composition: how the live reviews divide up by product, language and
+/// rating.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct CompositionResp {
+    pub reviews: u64,
+    pub products: usize,
+    /// None while there are no reviews.
+    pub per_product: Option<PerProduct>,
+    pub top_products: Vec<Share<ProductKey>>,
+    /// Most common first.
+    pub langs: Vec<Share<LangKey>>,
+    /// By rating, ascending.
+    pub ratings: Vec<Share<RatingKey>>,
+}
+
+/// The reviews with one product, language or rating, `key` flattened in.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Share<K> {
+    #[serde(flatten)]
+    pub key: K,
+    pub reviews: u64,
+    /// Of all live reviews.
+    pub share: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ProductKey { pub product_id: String }
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct LangKey { pub lang: String }
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RatingKey { pub rating: i32 }
+
+/// Review counts of the products.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PerProduct {
+    pub min: u64,
+    pub median: u64,
+    pub p90: u64,
+    pub p99: u64,
+    pub max: u64,
+    pub mean: f64,
+    /// 0 when every product has as many reviews, towards 1 as a few hold them all.
+    pub gini: f64,
+    /// Products by review count, in powers of two: `1`, `2-3`, `4-7`, ...
+    pub histogram: Vec<CountBucket>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CountBucket {
+    /// Fewest and most reviews of the products in the bucket.
+    pub from: u64,
+    pub to: u64,
+    pub products: usize,
+}
+
+/// GET /products/:id/ratings-timeline query.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct TimelineParams {
+    pub bucket: Granularity,
+    /// Seconds since the epoch; reviews created before are left out.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<u64>,
+    /// Seconds since the epoch; reviews created at or after are left out.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<u64>,
+}
+
+/// GET /products/:id/ratings-timeline: a product's average rating over time.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TimelineResp {
+    pub product_id: String,
+    pub bucket: Granularity,
+    /// Over all the buckets returned.
+    pub count: u32,
+    pub avg_rating: Option<f64>,
+    /// Oldest first; days or weeks without reviews are left out.
+    pub buckets: Vec<TimelineBucket>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Granularity {
+    #[default]
+    Day,
+    /// Monday to Sunday, UTC.
+    Week,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct TimelineBucket {
+    /// Seconds since the epoch of the bucket's first day, 00:00 UTC.
+    pub start: u64,
+    pub count: u32,
+    pub avg_rating: f64,
+}
+
+/// GET /products/:id/pros-cons query.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default)]
+pub struct ProsConsParams {
+    /// Pros, and cons, returned.
+    pub limit: usize,
+    /// Fewest distinct reviews an aspect must come up in.
+    pub min_reviews: usize,
+    /// Cosine a sentence needs with a cluster's centre to join it.
+    pub similarity: f32,
+}
+
+impl Default for ProsConsParams {
+    fn default() -> Self { Self { limit: 5, min_reviews: 2, similarity: 0.3 } }
+}
+
+/// GET /products/:id/pros-cons: what a product's reviews praise and complain about,
+/// clustered from their sentences.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ProsConsResp {
+    pub product_id: String,
+    /// Reviews and sentences analysed.
+    pub reviews: usize,
+    pub sentences: usize,
+    /// Most widely mentioned first.
+    pub pros: Vec<SentenceCluster>,
+    pub cons: Vec<SentenceCluster>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SentenceCluster {
+    /// The most common words of its sentences, stopwords and sentiment words left out.
+    pub terms: Vec<String>,
+    /// The sentence closest to the cluster's centre.
+    pub example: String,
+    /// Mean sentiment of its sentences, from -1 to 1.
+    pub sentiment: f64,
+    pub sentences: usize,
+    /// Distinct reviews with a sentence in it.
+    pub reviews: usize,
+    /// Up to 20 of those, most recent first.
+    pub review_ids: Vec<usize>,
+}
+
+/// GET /products/:id/aspects query.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(default)]
+pub struct AspectsParams {
+    /// Fewest reviews an aspect must come up in to be listed.
+    pub min_reviews: usize,
+}
+
+impl Default for AspectsParams {
+    fn default() -> Self { Self { min_reviews: 1 } }
+}
+
+/// GET /products/:id/aspects: the aspects a product's reviews bring up, and how they rate
+/// when they do.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct AspectsResp {
+    pub product_id: String,
+    pub reviews: usize,
+    pub avg_rating: Option<f64>,
+    /// Widest negative `rating_gap` first: the aspects dragging ratings down the most.
+    pub aspects: Vec<AspectSummary>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct AspectSummary {
+    pub aspect: String,
+    /// Reviews mentioning it, and how they feel about it.
+    pub reviews: usize,
+    pub positive: usize,
+    pub neutral: usize,
+    pub negative: usize,
+    /// Mean sentiment, from -1 to 1.
+    pub sentiment: f64,
+    /// Average stars of the reviews mentioning it.
+    pub avg_rating: Option<f64>,
+    /// `avg_rating` less that of the product's other reviews: how far reviews bringing the
+    /// aspect up rate below (or above) the rest. None when either side has no ratings.
+    pub rating_gap: Option<f64>,
+}
+
+/// POST /admin/generate: synthetic reviews, for load tests and demos.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct GenerateReq {
+    pub count: usize,
+    /// Defaults to the current time, i.e. different reviews on every call.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub products: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GenerateResp { pub inserted: usize, pub seed: u64 }
+
+/// POST /admin/reindex; the body is optional.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ReindexReq {
+    /// Make the rebuilt collection the active one (default). With `false` it is only
+    /// built, to be reached through an alias or `collection` in search requests.
+    #[serde(default = "default_swap")]
+    pub swap: bool,
+    /// Registered embedder to rebuild with, instead of the one in the config file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embedder: Option<String>,
+}
+
+impl Default for ReindexReq {
+    fn default() -> Self { Self { swap: default_swap(), embedder: None } }
+}
+
+fn default_swap() -> bool { true }
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ReindexResp {
+    pub job_id: uuid::Uuid,
+    /// The collection being built, `index-<job id>`.
+    pub collection: String,
+}
+
+/// POST /admin/purge: the job erasing deleted reviews.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PurgeResp { pub job_id: uuid::Uuid }
+
+/// POST /admin/idf; the body is optional.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct IdfReq {
+    /// Instead of `[idf] half_life_days` for this run.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub half_life_days: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IdfResp { pub job_id: uuid::Uuid }
+
+/// POST /admin/compact.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CompactResp { pub compaction_requested: bool }
+
+/// POST /admin/snapshot.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SnapshotResp {
+    pub job_id: uuid::Uuid,
+    /// Relative to the data dir.
+    pub dir: String,
+}
+
+/// GET /admin/drift: how the query and document vectors have moved since the baseline
+/// windows.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct DriftResp {
+    pub dim: usize,
+    /// Share of dimensions some indexed document has used, for embedders with a fixed
+    /// set of term dimensions (TF-IDF); near 1, terms share dimensions or find none.
+    pub dims_used: Option<f64>,
+    pub query: SideReport,
+    pub document: SideReport,
+    /// Every threshold crossed, on either side or by `dims_used`.
+    pub alerts: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SideReport {
+    /// Vectors seen since startup.
+    pub vectors: u64,
+    pub window: usize,
+    /// Vectors in the window being filled.
+    pub filling: usize,
+    pub baseline: Option<DriftWindow>,
+    pub recent: Option<DriftWindow>,
+    /// 1 - cosine of the baseline and recent centroids.
+    pub centroid_shift: Option<f64>,
+    pub alerts: Vec<String>,
+}
+
+/// Statistics of one full window of vectors.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct DriftWindow {
+    /// Seconds since the epoch it filled up.
+    pub closed_at: u64,
+    pub mean_norm: f64,
+    /// Share of all-zero vectors, e.g. queries without a single known term.
+    pub zero_rate: f64,
+    /// Share of tokens unseen by the index; embedders without their own tokens have none.
+    pub oov_rate: Option<f64>,
+    /// Mean of the vectors; kept by the service, left out of GET /admin/drift.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub centroid: Vec<f32>,
+}
+
+/// What an audit log entry records.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Insert,
+    Upsert,
+    Delete,
+    Patch,
+    Reembed,
+    Import,
+    Reindex,
+    PutAlias,
+    DeleteAlias,
+    RegisterEmbedder,
+    Compact,
+    RefreshIdf,
+    Approve,
+    Reject,
+    Purge,
+    Snapshot,
+    ReadOnly,
+}
+
+/// Review ids touched by one mutation, as inclusive `[first, last]` runs so a bulk insert
+/// of consecutive ids stays one short entry.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct IdRanges(pub Vec<[usize; 2]>);
+
+impl IdRanges {
+    pub fn push(&mut self, id: usize) {
+        match self.0.last_mut() {
+            Some(r) if (r[0]..=r[1]).contains(&id) => {}
+            Some(r) if r[1] + 1 == id => r[1] = id,
+            _ => self.0.push([id, id]),
+        }
+    }
+    pub fn contains(&self, id: usize) -> bool { self.0.iter().any(|r| (r[0]..=r[1]).contains(&id)) }
+    pub fn is_empty(&self) -> bool { self.0.is_empty() }
+}
+
+impl FromIterator<usize> for IdRanges {
+    fn from_iter<I: IntoIterator<Item = usize>>(iter: I) -> Self {
+        let mut out = Self::default();
+        for id in iter { out.push(id); }
+        out
+    }
+}
+
+/// One line of the audit log.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AuditEntry {
+    pub ts_ms: u64,
+    pub principal: String,
+    pub request_id: String,
+    pub action: Action,
+    #[serde(default, skip_serializing_if = "IdRanges::is_empty")]
+    pub ids: IdRanges,
+    /// What the mutation was about besides review ids: alias name, reindex job id, ...
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subject: Option<String>,
+}
+
+/// GET /admin/audit query: filters, unset ones matching everything, and the page.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct AuditQuery {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub principal: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action: Option<Action>,
+    /// Entries touching this review id.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub since_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+}
+
+impl AuditQuery {
+    pub fn matches(&self, e: &AuditEntry) -> bool {
+        self.principal.as_ref().is_none_or(|p| *p == e.principal)
+            && self.action.is_none_or(|a| a == e.action)
+            && self.id.is_none_or(|id| e.ids.contains(id))
+            && self.since_ms.is_none_or(|t| e.ts_ms >= t)
+            && self.until_ms.is_none_or(|t| e.ts_ms < t)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct AuditResp {
+    pub entries: Vec<AuditEntry>,
+    /// Present when the page was full; pass it back as `cursor` for the next page.
+    pub next_cursor: Option<u64>,
+}
+
+/// POST /aliases, and what it returns.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AliasReq { pub name: String, pub collection: String }
+
+/// GET /collections: one index directory.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CollectionInfo {
+    pub name: String,
+    pub vectors: usize,
+    pub dim: usize,
+    pub active: bool,
+    pub aliases: Vec<String>,
+    /// Registered name of the embedder that built it (see /embedders).
+    pub embedder: Option<String>,
+}
+
+/// GET /embedders: one registered model. `E` is the embedder section of the service's
+/// config (e.g. `{"type": "tfidf", "dim": 4096}`), which this crate leaves to the service;
+/// clients read it as free-form JSON.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ModelInfo<E> {
+    pub name: String,
+    pub embedder: E,
+    pub registered_at: u64,
+    pub collections: Vec<String>,
+}
+
+/// POST /embedders, `E` as in `ModelInfo`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RegisterEmbedderReq<E> { pub name: String, pub embedder: E }
+
+/// GET /stats.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct StatsResp {
+    pub reviews: usize,
+    pub vectors: usize,
+    pub vector_cache: CacheStats,
+    /// Unix seconds before which every append is on disk, as of the background flusher's
+    /// last pass; None until one completes, or without a flusher.
+    pub last_flush_at: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub budget_bytes: usize,
+    pub resident_bytes: usize,
+    pub segment_vectors: usize,
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    pub mirrors: Vec<MirrorResidency>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct MirrorResidency {
+    pub mirror: String,
+    pub segments: usize,
+    pub resident_segments: usize,
+}
+
+/// GET /admin/storage: disk usage of the data dir, and when the volume fills up at the
+/// current rate.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct StorageReport {
+    pub data_dir: String,
+    pub files: Vec<FileUsage>,
+    pub total_bytes: u64,
+    pub free_bytes: u64,
+    pub volume_bytes: u64,
+    /// Null until two samples a few minutes apart exist (samples are kept in memory only).
+    pub growth_bytes_per_day: Option<f64>,
+    pub days_until_full: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct FileUsage {
+    /// Relative to the data dir.
+    pub path: String,
+    /// `metadata`, `mirror`, `spfresh`, `wal`, `snapshot` or `other`.
+    pub kind: String,
+    pub bytes: u64,
+}
+
+/// GET /admin/usage: one tenant (an `x-principal`).
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct TenantUsage {
+    pub tenant: String,
+    #[serde(flatten)]
+    pub usage: Usage,
+    pub requests_this_minute: u64,
+    /// Its effective limits; unset ones are unlimited.
+    pub quota: TenantQuota,
+}
+
+/// What a tenant's reviews take up.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Usage {
+    pub documents: u64,
+    pub bytes: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TenantQuota {
+    pub max_documents: Option<u64>,
+    pub max_bytes: Option<u64>,
+    pub requests_per_minute: Option<u64>,
+}
@@ -1,22 +1,413 @@
-﻿use std::error::Error;
+﻿//! A flat, file-backed stand-in for the SPFresh bindings: the same API, with every search
+//! an exact scan over the vectors held in memory. Distances are cosine distance
+//! (`1 - cos`), so 0 is identical and 2 is opposite.
+//!
+//! File layout: `SPF2`, then the dimension and `max_payload` as little-endian u32s, then one
+//! fixed-size record per id: the payload's length as a u16, the payload padded to
+//! `max_payload` bytes, and `dim` little-endian f32s. `SPF1` files (dimension only, bare
+//! vectors) are rewritten on open. Appends and updates reach the file on `flush`.
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
 
-pub struct Index;
-pub struct OpenOptions { pub create: bool, pub append: bool }
+const MAGIC: &[u8; 4] = b"SPF2";
+const HEADER_LEN: u64 = 12;
+const V1_MAGIC: &[u8; 4] = b"SPF1";
+const V1_HEADER_LEN: usize = 8;
+
+pub struct Index {
+    file: File,
+    dim: usize,
+    max_payload: usize,
+    vecs: Vec<f32>,
+    /// Per id; empty for none.
+    payloads: Vec<Vec<u8>>,
+    /// Ids below this are on disk; the rest are appends waiting for `flush`.
+    flushed: usize,
+    /// On-disk ids updated since the last `flush`.
+    dirty: BTreeSet<usize>,
+}
+/// `max_payload` bounds the bytes `append_with_payload` takes per vector; every record
+/// reserves that much.
+#[derive(Default)]
+pub struct OpenOptions { pub create: bool, pub append: bool, pub max_payload: usize }
 impl OpenOptions {
-    pub fn new() -> Self { Self { create: false, append: false } }
+    pub fn new() -> Self { Self { create: false, append: false, max_payload: 0 } }
     pub fn create(mut self, b: bool) -> Self { self.create = b; self }
     pub fn append(mut self, b: bool) -> Self { self.append = b; self }
+    pub fn max_payload(mut self, n: usize) -> Self { self.max_payload = n; self }
 }
+/// `allowed`, if set, is asked about each id before it is scored; the ids it rejects are
+/// skipped, so a narrow filter still fills `top_k`.
 #[derive(Default)]
-pub struct SearchParams { pub top_k: usize }
+pub struct SearchParams<'a> { pub top_k: usize, pub allowed: Option<&'a (dyn Fn(usize) -> bool + Sync)> }
+pub struct Hit { pub id: usize, pub distance: f32, pub payload: Option<Vec<u8>> }
+#[derive(Default)]
+pub struct RangeParams { pub max_distance: f32, pub limit: usize }
 
 impl Index {
-    pub fn open(_path: &str, _dim: usize, _opts: &OpenOptions)
-        -> Result<Self, Box<dyn Error>> { Ok(Self) }
-    pub fn append(&mut self, _vec: &[f32])
-        -> Result<usize, Box<dyn Error>> { Ok(0) }
-    pub fn get(&self, _id: usize)
-        -> Result<Vec<f32>, Box<dyn Error>> { Ok(vec![]) }
-    pub fn search(&self, _q: &[f32], _p: &SearchParams)
-        -> Result<Vec<(usize,f32)>, Box<dyn Error>> { Ok(vec![]) }
+    /// Opens `path`, keeping what is there when `append` is set and starting empty otherwise.
+    /// A torn record at the end (a crash mid-flush) is cut off. A file laid out for another
+    /// `max_payload` is rewritten for this one.
+    pub fn open(path: &str, dim: usize, opts: &OpenOptions)
+        -> Result<Self, Box<dyn Error>> {
+        if dim == 0 { return Err("dim must be positive".into()); }
+        if opts.max_payload > u16::MAX as usize { return Err("max_payload must fit in a u16".into()); }
+        let mut file = std::fs::OpenOptions::new().read(true).write(true).create(opts.create).open(path)?;
+        if !opts.append { file.set_len(0)?; }
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+        let mut idx = Self {
+            file, dim, max_payload: opts.max_payload,
+            vecs: Vec::new(), payloads: Vec::new(), flushed: 0, dirty: BTreeSet::new(),
+        };
+        if bytes.is_empty() {
+            idx.write_header()?;
+            return Ok(idx);
+        }
+        let (stored_dim, stored_max, header) = match bytes.get(..4) {
+            Some(m) if m == MAGIC && bytes.len() >= HEADER_LEN as usize =>
+                (le_u32(&bytes[4..8]), le_u32(&bytes[8..12]), HEADER_LEN as usize),
+            Some(m) if m == V1_MAGIC && bytes.len() >= V1_HEADER_LEN => (le_u32(&bytes[4..8]), 0, V1_HEADER_LEN),
+            _ => return Err(format!("{path} is not a spfresh index").into()),
+        };
+        if stored_dim != dim { return Err(format!("{path} holds dim {stored_dim}, not {dim}").into()); }
+        let stored_rec = record_len(dim, stored_max, header == V1_HEADER_LEN);
+        for rec in bytes[header..].chunks_exact(stored_rec) {
+            let (payload, vec) = if header == V1_HEADER_LEN {
+                (&[][..], rec)
+            } else {
+                let len = u16::from_le_bytes([rec[0], rec[1]]) as usize;
+                (&rec[2..2 + len.min(stored_max)], &rec[2 + stored_max..])
+            };
+            if payload.len() > idx.max_payload {
+                return Err(format!("{path} holds a {}-byte payload, over max_payload", payload.len()).into());
+            }
+            idx.vecs.extend(vec.chunks_exact(4).map(|b| f32::from_le_bytes(b.try_into().unwrap())));
+            idx.payloads.push(payload.to_vec());
+        }
+        if header == HEADER_LEN as usize && stored_max == idx.max_payload {
+            idx.flushed = idx.len();
+            let end = HEADER_LEN + (idx.flushed * idx.record_len()) as u64;
+            if bytes.len() as u64 != end { idx.file.set_len(end)?; }
+            return Ok(idx);
+        }
+        // Another layout: write every record out again in this one, then swap the files.
+        let tmp = format!("{path}.relayout");
+        let mut out = Self { file: File::create(&tmp)?, ..idx };
+        out.write_header()?;
+        out.flush()?;
+        std::fs::rename(&tmp, path)?;
+        out.file = std::fs::OpenOptions::new().read(true).write(true).open(path)?;
+        Ok(out)
+    }
+    /// Number of vectors, flushed or not.
+    pub fn len(&self) -> usize { self.vecs.len() / self.dim }
+    pub fn is_empty(&self) -> bool { self.vecs.is_empty() }
+    pub fn append(&mut self, vec: &[f32])
+        -> Result<usize, Box<dyn Error>> {
+        self.append_with_payload(vec, &[])
+    }
+    /// Appends `vec` with up to `max_payload` opaque bytes stored next to it; an empty
+    /// payload is the same as none.
+    pub fn append_with_payload(&mut self, vec: &[f32], payload: &[u8])
+        -> Result<usize, Box<dyn Error>> {
+        self.check_dim(vec)?;
+        if payload.len() > self.max_payload {
+            return Err(format!("payload of {} bytes is over max_payload {}", payload.len(), self.max_payload).into());
+        }
+        self.vecs.extend_from_slice(vec);
+        self.payloads.push(payload.to_vec());
+        Ok(self.len() - 1)
+    }
+    /// Replaces vector `id`, keeping its payload.
+    pub fn update(&mut self, id: usize, vec: &[f32])
+        -> Result<(), Box<dyn Error>> {
+        self.check_dim(vec)?;
+        self.check_id(id)?;
+        self.vecs[id * self.dim..(id + 1) * self.dim].copy_from_slice(vec);
+        if id < self.flushed { self.dirty.insert(id); }
+        Ok(())
+    }
+    /// Replaces the payload of `id`, keeping its vector; an empty payload clears it.
+    pub fn set_payload(&mut self, id: usize, payload: &[u8])
+        -> Result<(), Box<dyn Error>> {
+        self.check_id(id)?;
+        if payload.len() > self.max_payload {
+            return Err(format!("payload of {} bytes is over max_payload {}", payload.len(), self.max_payload).into());
+        }
+        self.payloads[id] = payload.to_vec();
+        if id < self.flushed { self.dirty.insert(id); }
+        Ok(())
+    }
+    /// Drops every id from `len` on, on disk too.
+    pub fn truncate(&mut self, len: usize)
+        -> Result<(), Box<dyn Error>> {
+        if len >= self.len() { return Ok(()); }
+        self.vecs.truncate(len * self.dim);
+        self.payloads.truncate(len);
+        self.dirty.retain(|&id| id < len);
+        if self.flushed > len {
+            self.flushed = len;
+            self.file.set_len(HEADER_LEN + (len * self.record_len()) as u64)?;
+            self.file.sync_data()?;
+        }
+        Ok(())
+    }
+    pub fn payload(&self, id: usize)
+        -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+        self.check_id(id)?;
+        Ok(self.payload_of(id))
+    }
+    pub fn flush(&mut self)
+        -> Result<(), Box<dyn Error>> {
+        let rec = self.record_len() as u64;
+        for id in std::mem::take(&mut self.dirty) {
+            self.file.seek(SeekFrom::Start(HEADER_LEN + id as u64 * rec))?;
+            self.file.write_all(&self.encode(id))?;
+        }
+        if self.flushed < self.len() {
+            let bytes: Vec<u8> = (self.flushed..self.len()).flat_map(|id| self.encode(id)).collect();
+            self.file.seek(SeekFrom::Start(HEADER_LEN + self.flushed as u64 * rec))?;
+            self.file.write_all(&bytes)?;
+            self.flushed = self.len();
+        }
+        self.file.sync_data()?;
+        Ok(())
+    }
+    pub fn get(&self, id: usize)
+        -> Result<Vec<f32>, Box<dyn Error>> {
+        self.check_id(id)?;
+        Ok(self.slot(id).to_vec())
+    }
+    /// The `top_k` nearest ids that `allowed` accepts, nearest first.
+    pub fn search(&self, q: &[f32], p: &SearchParams<'_>)
+        -> Result<Vec<(usize,f32)>, Box<dyn Error>> {
+        let mut hits = self.scan(q, |id| p.allowed.is_none_or(|a| a(id)), |_| true)?;
+        hits.truncate(p.top_k);
+        Ok(hits)
+    }
+    /// `search`, with each hit's payload.
+    pub fn search_with_payloads(&self, q: &[f32], p: &SearchParams<'_>)
+        -> Result<Vec<Hit>, Box<dyn Error>> {
+        Ok(self.search(q, p)?.into_iter()
+            .map(|(id, distance)| Hit { id, distance, payload: self.payload_of(id) })
+            .collect())
+    }
+    /// Every id within `max_distance` of `q`, nearest first, stopping at `limit`.
+    pub fn range_search(&self, q: &[f32], p: &RangeParams)
+        -> Result<Vec<(usize,f32)>, Box<dyn Error>> {
+        let mut hits = self.scan(q, |_| true, |d| d <= p.max_distance)?;
+        hits.truncate(p.limit);
+        Ok(hits)
+    }
+
+    fn scan(&self, q: &[f32], allowed: impl Fn(usize) -> bool, keep: impl Fn(f32) -> bool)
+        -> Result<Vec<(usize,f32)>, Box<dyn Error>> {
+        self.check_dim(q)?;
+        let qn = norm(q);
+        let mut hits: Vec<(usize, f32)> = self.vecs.chunks_exact(self.dim).enumerate()
+            .filter(|&(id, _)| allowed(id))
+            .map(|(id, v)| (id, distance(q, qn, v)))
+            .filter(|&(_, d)| keep(d))
+            .collect();
+        hits.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+        Ok(hits)
+    }
+    fn write_header(&mut self) -> Result<(), Box<dyn Error>> {
+        self.file.write_all(MAGIC)?;
+        self.file.write_all(&(self.dim as u32).to_le_bytes())?;
+        self.file.write_all(&(self.max_payload as u32).to_le_bytes())?;
+        self.file.sync_data()?;
+        Ok(())
+    }
+    fn record_len(&self) -> usize { record_len(self.dim, self.max_payload, false) }
+    fn encode(&self, id: usize) -> Vec<u8> {
+        let payload = &self.payloads[id];
+        let mut rec = Vec::with_capacity(self.record_len());
+        rec.extend_from_slice(&(payload.len() as u16).to_le_bytes());
+        rec.extend_from_slice(payload);
+        rec.resize(2 + self.max_payload, 0);
+        rec.extend(self.slot(id).iter().flat_map(|x| x.to_le_bytes()));
+        rec
+    }
+    fn payload_of(&self, id: usize) -> Option<Vec<u8>> {
+        let p = &self.payloads[id];
+        (!p.is_empty()).then(|| p.clone())
+    }
+    fn check_dim(&self, v: &[f32]) -> Result<(), Box<dyn Error>> {
+        if v.len() == self.dim { Ok(()) } else { Err(format!("dim mismatch: {} != {}", v.len(), self.dim).into()) }
+    }
+    fn check_id(&self, id: usize) -> Result<(), Box<dyn Error>> {
+        if id < self.len() { Ok(()) } else { Err(format!("no vector {id}").into()) }
+    }
+    fn slot(&self, id: usize) -> &[f32] {
+        &self.vecs[id * self.dim..(id + 1) * self.dim]
+    }
+}
+
+fn record_len(dim: usize, max_payload: usize, v1: bool) -> usize {
+    if v1 { dim * 4 } else { 2 + max_payload + dim * 4 }
+}
+
+fn le_u32(b: &[u8]) -> usize { u32::from_le_bytes(b.try_into().unwrap()) as usize }
+
+fn norm(v: &[f32]) -> f32 { v.iter().map(|x| x * x).sum::<f32>().sqrt() }
+
+/// Cosine distance; a zero vector is orthogonal to everything.
+fn distance(q: &[f32], qn: f32, v: &[f32]) -> f32 {
+    let d = qn * norm(v);
+    if d == 0.0 { return 1.0; }
+    1.0 - q.iter().zip(v).map(|(a, b)| a * b).sum::<f32>() / d
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp(name: &str) -> String {
+        let p = std::env::temp_dir().join(format!("spfresh-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_file(&p);
+        p.to_string_lossy().into_owned()
+    }
+
+    fn open(path: &str) -> Index {
+        Index::open(path, 2, &OpenOptions::new().create(true).append(true).max_payload(8)).unwrap()
+    }
+
+    /// Unit vectors at 0°, 10°, 20°, ... so distance grows with the id.
+    fn fan(idx: &mut Index, n: usize) {
+        for i in 0..n {
+            let a = (i as f32 * 10.0).to_radians();
+            idx.append(&[a.cos(), a.sin()]).unwrap();
+        }
+    }
+
+    #[test]
+    fn range_search_stops_at_max_distance_and_limit() {
+        let path = temp("range");
+        let mut idx = open(&path);
+        fan(&mut idx, 10);
+        let max = 1.0 - 35f32.to_radians().cos();
+        let hits = idx.range_search(&[1.0, 0.0], &RangeParams { max_distance: max, limit: 100 }).unwrap();
+        assert_eq!(hits.iter().map(|h| h.0).collect::<Vec<_>>(), [0, 1, 2, 3]);
+        assert!(hits.iter().all(|h| h.1 <= max));
+        let hits = idx.range_search(&[1.0, 0.0], &RangeParams { max_distance: max, limit: 2 }).unwrap();
+        assert_eq!(hits.iter().map(|h| h.0).collect::<Vec<_>>(), [0, 1]);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn search_fills_top_k_from_the_allowed_ids() {
+        let path = temp("allowed");
+        let mut idx = open(&path);
+        fan(&mut idx, 10);
+        let odd = |id: usize| id % 2 == 1;
+        let hits = idx.search(&[1.0, 0.0], &SearchParams { top_k: 3, allowed: Some(&odd) }).unwrap();
+        assert_eq!(hits.iter().map(|h| h.0).collect::<Vec<_>>(), [1, 3, 5]);
+        let hits = idx.search(&[1.0, 0.0], &SearchParams { top_k: 3, allowed: None }).unwrap();
+        assert_eq!(hits.iter().map(|h| h.0).collect::<Vec<_>>(), [0, 1, 2]);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn payloads_round_trip() {
+        let path = temp("payload");
+        let mut idx = open(&path);
+        idx.append_with_payload(&[1.0, 0.0], b"ext-1").unwrap();
+        idx.append(&[0.0, 1.0]).unwrap();
+        assert!(idx.append_with_payload(&[1.0, 1.0], b"too-long-9").is_err());
+        idx.flush().unwrap();
+        idx.update(0, &[1.0, 0.1]).unwrap();
+        idx.flush().unwrap();
+        drop(idx);
+        let idx = open(&path);
+        assert_eq!(idx.len(), 2);
+        assert_eq!(idx.payload(0).unwrap().as_deref(), Some(&b"ext-1"[..]));
+        assert_eq!(idx.payload(1).unwrap(), None);
+        let hits = idx.search_with_payloads(&[1.0, 0.0], &SearchParams { top_k: 2, allowed: None }).unwrap();
+        assert_eq!(hits.iter().map(|h| (h.id, h.payload.clone())).collect::<Vec<_>>(), [(0, Some(b"ext-1".to_vec())), (1, None)]);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn set_payload_overwrites_and_clears() {
+        let path = temp("set-payload");
+        let mut idx = open(&path);
+        idx.append_with_payload(&[1.0, 0.0], b"ext-1").unwrap();
+        idx.append_with_payload(&[0.0, 1.0], b"ext-2").unwrap();
+        idx.flush().unwrap();
+        idx.set_payload(0, b"e").unwrap();
+        idx.set_payload(1, &[]).unwrap();
+        assert!(idx.set_payload(0, b"too-long-9").is_err());
+        assert!(idx.set_payload(2, b"x").is_err());
+        idx.flush().unwrap();
+        drop(idx);
+        let idx = open(&path);
+        assert_eq!(idx.payload(0).unwrap().as_deref(), Some(&b"e"[..]));
+        assert_eq!(idx.payload(1).unwrap(), None);
+        // The old bytes are gone from the file, not just hidden behind a shorter length.
+        let bytes = std::fs::read(&path).unwrap();
+        assert!(!bytes.windows(5).any(|w| w == b"ext-1" || w == b"ext-2"));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn truncate_drops_the_tail_on_disk() {
+        let path = temp("truncate");
+        let mut idx = open(&path);
+        fan(&mut idx, 4);
+        idx.flush().unwrap();
+        idx.update(3, &[0.0, 1.0]).unwrap();
+        idx.append(&[1.0, 1.0]).unwrap();
+        idx.truncate(2).unwrap();
+        idx.flush().unwrap();
+        assert_eq!(idx.append(&[0.0, 1.0]).unwrap(), 2);
+        idx.flush().unwrap();
+        drop(idx);
+        let idx = open(&path);
+        assert_eq!(idx.len(), 3);
+        assert_eq!(idx.get(2).unwrap(), [0.0, 1.0]);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn spf1_files_are_rewritten() {
+        let path = temp("spf1");
+        let mut v1 = b"SPF1".to_vec();
+        v1.extend(2u32.to_le_bytes());
+        v1.extend([1.0f32, 0.0, 0.0, 1.0].iter().flat_map(|x| x.to_le_bytes()));
+        std::fs::write(&path, &v1).unwrap();
+        let mut idx = open(&path);
+        assert_eq!(idx.get(1).unwrap(), [0.0, 1.0]);
+        idx.append_with_payload(&[1.0, 1.0], b"ext").unwrap();
+        idx.flush().unwrap();
+        drop(idx);
+        let idx = open(&path);
+        assert_eq!((idx.len(), idx.payload(0).unwrap()), (3, None));
+        assert_eq!(idx.payload(2).unwrap().as_deref(), Some(&b"ext"[..]));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn flushed_vectors_and_updates_survive_reopen() {
+        let path = temp("reopen");
+        let mut idx = open(&path);
+        fan(&mut idx, 3);
+        idx.flush().unwrap();
+        idx.update(1, &[0.0, 1.0]).unwrap();
+        idx.append(&[-1.0, 0.0]).unwrap();
+        idx.flush().unwrap();
+        idx.append(&[0.5, 0.5]).unwrap();
+        drop(idx);
+        // A torn record at the end is dropped.
+        std::fs::OpenOptions::new().append(true).open(&path).unwrap().write_all(&[1, 2, 3]).unwrap();
+        let idx = open(&path);
+        assert_eq!(idx.len(), 4);
+        assert_eq!(idx.get(1).unwrap(), [0.0, 1.0]);
+        assert_eq!(idx.search(&[-1.0, 0.0], &SearchParams { top_k: 1, allowed: None }).unwrap()[0].0, 3);
+        assert!(idx.get(4).is_err());
+        let _ = std::fs::remove_file(&path);
+    }
 }
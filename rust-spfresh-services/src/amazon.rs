@@ -0,0 +1,108 @@
+use crate::Review;
+use serde::Deserialize;
+
+const DAY_SECS: u64 = 86_400;
+
+/// One line of the Amazon review JSON dumps: the 2014/2018 field names, with the 2023
+/// release's names as aliases.
+#[derive(Deserialize)]
+struct JsonReview {
+    #[serde(rename = "reviewerID", alias = "user_id")]
+    reviewer_id: Option<String>,
+    asin: String,
+    #[serde(rename = "reviewText", alias = "text", default)]
+    review_text: String,
+    #[serde(alias = "title", default)]
+    summary: String,
+    #[serde(alias = "rating")]
+    overall: f64,
+    /// Seconds since the epoch (2014/2018).
+    #[serde(rename = "unixReviewTime")]
+    unix_review_time: Option<u64>,
+    /// Milliseconds since the epoch (2023).
+    timestamp: Option<u64>,
+}
+
+/// `summary` -> title, `reviewText` -> body, `asin` -> product, `overall` (1.0-5.0) ->
+/// rating, the review time -> `created_at`. Reviewer and product make the external_id,
+/// so importing the same dump again replaces reviews instead of duplicating them.
+pub fn from_json(line: &[u8]) -> Result<Review, String> {
+    let r: JsonReview = serde_json::from_slice(line).map_err(|e| e.to_string())?;
+    Ok(Review {
+        review_title: r.summary,
+        review_body: r.review_text,
+        review_rating: rating(r.overall)?,
+        created_at: r.unix_review_time.or(r.timestamp.map(|ms| ms / 1000)),
+        external_id: r.reviewer_id.map(|who| format!("amazon:{who}:{}", r.asin)),
+        product_id: r.asin,
+        ..Default::default()
+    })
+}
+
+/// Columns of the Amazon Customer Reviews TSV files, found by name in the header line.
+pub struct TsvColumns {
+    count: usize,
+    product: usize,
+    rating: usize,
+    title: usize,
+    body: usize,
+    date: Option<usize>,
+    id: Option<usize>,
+}
+
+impl TsvColumns {
+    pub fn from_header(line: &[u8]) -> Result<Self, String> {
+        let names: Vec<&str> = std::str::from_utf8(line).map_err(|e| e.to_string())?.split('\t').map(str::trim).collect();
+        let find = |name: &str| names.iter().position(|n| *n == name);
+        let need = |name: &str| find(name).ok_or_else(|| format!("TSV header has no {name} column"));
+        Ok(Self {
+            count: names.len(),
+            product: need("product_id")?,
+            rating: need("star_rating")?,
+            title: need("review_headline")?,
+            body: need("review_body")?,
+            date: find("review_date"),
+            id: find("review_id"),
+        })
+    }
+
+    /// `review_headline` -> title, `review_body` (its `<br />`s as line breaks) -> body,
+    /// `product_id`, `star_rating`, `review_date` -> `created_at`, `review_id` -> external_id.
+    pub fn review(&self, line: &[u8]) -> Result<Review, String> {
+        let fields: Vec<&str> = std::str::from_utf8(line).map_err(|e| e.to_string())?.split('\t').collect();
+        if fields.len() != self.count {
+            return Err(format!("{} fields, the header has {}", fields.len(), self.count));
+        }
+        let stars: f64 = fields[self.rating].trim().parse().map_err(|_| format!("bad star_rating {:?}", fields[self.rating]))?;
+        Ok(Review {
+            review_title: fields[self.title].to_string(),
+            review_body: fields[self.body].replace("<br />", "\n"),
+            product_id: fields[self.product].to_string(),
+            review_rating: rating(stars)?,
+            created_at: self.date.map(|i| parse_date(fields[i])).transpose()?,
+            external_id: self.id.map(|i| format!("amazon:{}", fields[i])),
+            ..Default::default()
+        })
+    }
+}
+
+fn rating(stars: f64) -> Result<i32, String> {
+    if !(1.0..=5.0).contains(&stars) { return Err(format!("rating {stars} is not 1-5")); }
+    Ok(stars.round() as i32)
+}
+
+/// `YYYY-MM-DD` as seconds since the epoch, midnight UTC.
+fn parse_date(s: &str) -> Result<u64, String> {
+    let bad = || format!("bad review_date {s:?}");
+    let mut parts = s.trim().splitn(3, '-').map(|p| p.parse::<i64>().map_err(|_| bad()));
+    let (y, m, d) = (parts.next().ok_or_else(bad)??, parts.next().ok_or_else(bad)??, parts.next().ok_or_else(bad)??);
+    if !(1..=12).contains(&m) || !(1..=31).contains(&d) { return Err(bad()); }
+    // Days from civil, proleptic Gregorian (Howard Hinnant's algorithm).
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * ((m + 9) % 12) + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+    u64::try_from(days).map(|d| d * DAY_SECS).map_err(|_| bad())
+}
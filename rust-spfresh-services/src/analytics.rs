@@ -0,0 +1,150 @@
+use crate::{blocking, ApiError, AppState};
+use axum::{extract::State, Json};
+use polars::{
+    prelude::{col, AnyValue, DataFrame, DataType, IntoLazy, LazyFrame, NamedFrom, Series, TimeUnit},
+    sql::SQLContext,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlparser::{
+    ast::{visit_expressions, visit_relations, Expr, SetExpr, Statement},
+    dialect::GenericDialect,
+    parser::Parser,
+};
+use std::{collections::HashSet, ops::ControlFlow};
+
+/// The one table queries can read.
+const TABLE: &str = "reviews";
+/// Rows returned at most; a query producing more is cut off and flagged `truncated`.
+const MAX_ROWS: usize = 10_000;
+/// Functions a query may call: aggregates, arithmetic, text and date helpers. Anything
+/// else, table functions that read files included, is refused before the engine sees it.
+const FUNCTIONS: &[&str] = &[
+    "count", "sum", "avg", "min", "max", "median", "stddev", "variance", "first", "last",
+    "round", "floor", "ceil", "abs", "sqrt", "ln", "log10", "greatest", "least",
+    "lower", "upper", "length", "concat", "substr", "starts_with", "ends_with",
+    "strftime", "date_part", "coalesce", "nullif",
+];
+
+#[derive(Deserialize)]
+pub struct AnalyticsReq { sql: String }
+
+#[derive(Serialize)]
+pub struct AnalyticsColumn { name: String, r#type: String }
+
+#[derive(Serialize)]
+pub struct AnalyticsResp {
+    columns: Vec<AnalyticsColumn>,
+    rows: Vec<Vec<Value>>,
+    /// More than `MAX_ROWS` rows matched.
+    truncated: bool,
+}
+
+/// POST /analytics/query — one read-only SQL `SELECT` (CTEs and subqueries allowed) over
+/// the live reviews, e.g. average rating by product and month, run by an embedded engine
+/// (Polars SQL) so such questions need no export. The table is built from the metadata on
+/// each query. Columns: id, review_title, review_body, product_id, review_rating,
+/// created_at (datetime, UTC), created_month ('YYYY-MM'), external_id, version, lang.
+pub async fn query(State(st): State<AppState>, Json(req): Json<AnalyticsReq>) -> Result<Json<AnalyticsResp>, ApiError> {
+    check(&req.sql)?;
+    let resp = blocking(move || {
+        let mut ctx = SQLContext::new();
+        ctx.register(TABLE, table(&st)?);
+        let df = ctx.execute(&req.sql)
+            .and_then(|q| q.limit(MAX_ROWS as u32 + 1).collect())
+            .map_err(|e| ApiError::bad_request(format!("query failed: {e}")))?;
+        Ok(response(&df))
+    }).await?;
+    Ok(Json(resp))
+}
+
+/// Refuses anything but a single query reading `reviews` (or its own CTEs) through the
+/// allowed functions.
+fn check(sql: &str) -> Result<(), ApiError> {
+    let stmts = Parser::parse_sql(&GenericDialect {}, sql).map_err(|e| ApiError::bad_request(format!("invalid SQL: {e}")))?;
+    let [Statement::Query(q)] = stmts.as_slice() else {
+        return Err(ApiError::bad_request("exactly one SELECT statement is allowed"));
+    };
+    if !matches!(*q.body, SetExpr::Select(_) | SetExpr::Query(_) | SetExpr::SetOperation { .. }) {
+        return Err(ApiError::bad_request("only SELECT queries are allowed"));
+    }
+    let ctes: HashSet<String> = q.with.iter()
+        .flat_map(|w| &w.cte_tables)
+        .map(|c| c.alias.name.value.to_lowercase())
+        .collect();
+    let stmt = &stmts[0];
+    if let ControlFlow::Break(name) = visit_relations(stmt, |rel| {
+        let name = rel.to_string().to_lowercase();
+        if name == TABLE || ctes.contains(&name) { ControlFlow::Continue(()) } else { ControlFlow::Break(name) }
+    }) {
+        return Err(ApiError::bad_request(format!("unknown table '{name}'; the table is '{TABLE}'")));
+    }
+    if let ControlFlow::Break(name) = visit_expressions(stmt, |e| match e {
+        Expr::Function(f) => {
+            let name = f.name.to_string().to_lowercase();
+            if FUNCTIONS.contains(&name.as_str()) { ControlFlow::Continue(()) } else { ControlFlow::Break(name) }
+        }
+        _ => ControlFlow::Continue(()),
+    }) {
+        return Err(ApiError::bad_request(format!("function '{name}' is not allowed; allowed: {}", FUNCTIONS.join(", "))));
+    }
+    Ok(())
+}
+
+/// The live reviews as a frame.
+fn table(st: &AppState) -> anyhow::Result<LazyFrame> {
+    let (mut id, mut title, mut body, mut product, mut rating) = (Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new());
+    let (mut created_ms, mut external, mut version, mut lang) = (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+    st.meta.for_each_in(0..usize::MAX, |i, r| {
+        let Some(r) = r.filter(|_| st.meta.is_live(i)) else { return Ok(()) };
+        id.push(i as u64);
+        rating.push(r.review_rating);
+        created_ms.push(r.created_at.map(|s| s as i64 * 1000));
+        version.push(r.version.unwrap_or(1));
+        title.push(r.review_title);
+        body.push(r.review_body);
+        product.push(r.product_id);
+        external.push(r.external_id);
+        lang.push(r.lang);
+        Ok(())
+    })?;
+    let created = Series::new("created_at".into(), created_ms).cast(&DataType::Datetime(TimeUnit::Milliseconds, None))?;
+    let df = DataFrame::new(vec![
+        Series::new("id".into(), id).into(),
+        Series::new("review_title".into(), title).into(),
+        Series::new("review_body".into(), body).into(),
+        Series::new("product_id".into(), product).into(),
+        Series::new("review_rating".into(), rating).into(),
+        created.into(),
+        Series::new("external_id".into(), external).into(),
+        Series::new("version".into(), version).into(),
+        Series::new("lang".into(), lang).into(),
+    ])?;
+    Ok(df.lazy().with_column(col("created_at").dt().strftime("%Y-%m").alias("created_month")))
+}
+
+fn response(df: &DataFrame) -> AnalyticsResp {
+    let columns = df.get_columns().iter()
+        .map(|c| AnalyticsColumn { name: c.name().to_string(), r#type: c.dtype().to_string() })
+        .collect();
+    let n = df.height().min(MAX_ROWS);
+    let rows = (0..n)
+        .map(|i| df.get_columns().iter().map(|c| c.get(i).map_or(Value::Null, json)).collect())
+        .collect();
+    AnalyticsResp { columns, rows, truncated: df.height() > MAX_ROWS }
+}
+
+/// Numbers, strings and booleans as themselves; dates and anything else as text.
+fn json(v: AnyValue) -> Value {
+    let dtype = v.dtype();
+    match v {
+        AnyValue::Null => Value::Null,
+        AnyValue::Boolean(b) => b.into(),
+        AnyValue::String(s) => s.into(),
+        AnyValue::StringOwned(s) => s.as_str().into(),
+        v if dtype.is_unsigned_integer() => v.extract::<u64>().into(),
+        v if dtype.is_integer() => v.extract::<i64>().into(),
+        v if dtype.is_float() => v.extract::<f64>().and_then(serde_json::Number::from_f64).map_or(Value::Null, Value::Number),
+        v => v.to_string().into(),
+    }
+}
@@ -0,0 +1,195 @@
+use crate::{lang, sentiment, ApiError, AppState, Review};
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use parking_lot::RwLock;
+use reviews_types::{AspectMention, AspectsParams, AspectsResp, AspectSummary, ReviewStatus};
+use std::collections::{BTreeMap, HashMap};
+
+/// The aspects found in reviews: a canonical name, the words that mention it, and the
+/// Thai substrings that do (Thai is written without spaces, so words are not split out).
+const ASPECTS: &[(&str, &[&str], &[&str])] = &[
+    ("battery", &["battery", "batteries", "charge", "charged", "charger", "charging", "recharge"], &["แบต", "ชาร์จ"]),
+    ("shipping", &["shipping", "shipped", "shipment", "delivery", "delivered", "arrived", "arrival", "courier", "package", "packaging", "packaged", "box"], &["จัดส่ง", "ขนส่ง", "พัสดุ", "แพ็ค"]),
+    ("quality", &["quality", "build", "built", "material", "materials", "construction", "durability", "durable", "sturdy", "flimsy"], &["คุณภาพ", "วัสดุ"]),
+    ("price", &["price", "priced", "pricey", "cost", "costs", "value", "money", "expensive", "cheap", "overpriced", "affordable"], &["ราคา", "คุ้ม", "แพง"]),
+    ("size", &["size", "sizes", "sizing", "fit", "fits", "tight", "loose"], &["ขนาด", "ไซส์"]),
+    ("screen", &["screen", "display", "brightness", "resolution"], &["จอ"]),
+    ("sound", &["sound", "audio", "bass", "speaker", "speakers", "volume", "microphone", "mic"], &["เสียง"]),
+    ("camera", &["camera", "photo", "photos", "picture", "pictures", "lens"], &["กล้อง"]),
+    ("performance", &["performance", "lag", "laggy", "lags", "crash", "crashes", "freezes", "processor"], &["ค้าง"]),
+    ("comfort", &["comfort", "comfortable", "uncomfortable", "padding", "cushion"], &["ใส่สบาย"]),
+    ("design", &["design", "look", "looks", "color", "colour", "colors", "style", "appearance"], &["ดีไซน์", "สีสวย"]),
+    ("setup", &["setup", "install", "installation", "instructions", "manual", "app"], &["ติดตั้ง", "คู่มือ"]),
+    ("service", &["service", "support", "seller", "refund", "warranty", "customer"], &["บริการ", "ร้านค้า", "แม่ค้า", "พนักงาน"]),
+    ("taste", &["taste", "tastes", "flavor", "flavour", "flavors"], &["รสชาติ"]),
+    ("smell", &["smell", "smells", "scent", "odor"], &["กลิ่น"]),
+];
+/// Words that join clauses about different things ("the battery is great but the screen
+/// is dim"); each clause gets its own sentiment.
+const CONJUNCTIONS: &[&str] = &["and", "but", "although", "though", "however", "while", "whereas", "yet"];
+const THAI_BUT: &str = "แต่";
+
+/// The aspects `title` and `body` talk about, by name, each with the mean sentiment of the
+/// clauses mentioning it: the clause's own sentiment words, else its sentence's, else what
+/// the review's stars say.
+pub fn extract(title: &str, body: &str, rating: i32) -> Vec<AspectMention> {
+    let mut found: BTreeMap<&str, (f64, usize)> = BTreeMap::new();
+    for sentence in lang::sentences(title).chain(lang::sentences(body)) {
+        let whole = sentiment::polarity(sentence);
+        for clause in clauses(sentence) {
+            let aspects = mentioned(&clause);
+            if aspects.is_empty() { continue; }
+            let s = sentiment::polarity(&clause).or(whole).unwrap_or_else(|| sentiment::from_rating(rating));
+            for aspect in aspects {
+                let (sum, n) = found.entry(aspect).or_default();
+                *sum += s;
+                *n += 1;
+            }
+        }
+    }
+    found.into_iter()
+        .map(|(aspect, (sum, n))| AspectMention { aspect: aspect.to_string(), sentiment: ((sum / n as f64) * 100.0).round() as f32 / 100.0 })
+        .collect()
+}
+
+/// `sentence` lowercased and split at commas and conjunctions.
+fn clauses(sentence: &str) -> Vec<String> {
+    let lower = sentence.to_lowercase();
+    if lang::detect(&lower) == lang::THAI {
+        return lower.split(|c: char| c == ',' || c.is_whitespace()).flat_map(|s| s.split(THAI_BUT))
+            .filter(|s| !s.trim().is_empty()).map(str::to_string).collect();
+    }
+    let mut out = Vec::new();
+    for part in lower.split(',') {
+        let mut clause: Vec<&str> = Vec::new();
+        for word in part.split_whitespace() {
+            if CONJUNCTIONS.contains(&word) {
+                if !clause.is_empty() { out.push(clause.join(" ")); }
+                clause.clear();
+            } else {
+                clause.push(word);
+            }
+        }
+        if !clause.is_empty() { out.push(clause.join(" ")); }
+    }
+    out
+}
+
+/// Names of the aspects `clause` (lowercased) mentions, each once.
+fn mentioned(clause: &str) -> Vec<&'static str> {
+    if lang::detect(clause) == lang::THAI {
+        return ASPECTS.iter().filter(|(_, _, thai)| thai.iter().any(|w| clause.contains(w))).map(|&(name, _, _)| name).collect();
+    }
+    let terms: Vec<String> = lang::terms(clause).collect();
+    ASPECTS.iter().filter(|(_, words, _)| terms.iter().any(|t| words.contains(&t.as_str()))).map(|&(name, _, _)| name).collect()
+}
+
+/// A review's aspects: interned name and sentiment.
+type Row = Box<[(u32, f32)]>;
+
+/// The aspects of every review by id, kept in step with the metadata by `MetaStore`, so
+/// a breakdown never costs a metadata read. Extracted again for reviews written before
+/// `Review::aspects` existed.
+#[derive(Default)]
+pub struct Mentions {
+    rows: RwLock<Vec<Row>>,
+    /// Aspect name -> the small number stored in `rows`, and back.
+    ids: RwLock<HashMap<String, u32>>,
+    names: RwLock<Vec<String>>,
+}
+
+impl Mentions {
+    /// Records the next review; `None` (an unreadable metadata line) gets no aspects.
+    pub fn push(&self, review: Option<&Review>) {
+        let row = review.map(|r| {
+            let extracted;
+            let aspects = match &r.aspects {
+                Some(a) => a,
+                None => { extracted = extract(&r.review_title, &r.review_body, r.review_rating); &extracted }
+            };
+            aspects.iter().map(|a| (self.intern(&a.aspect), a.sentiment)).collect()
+        }).unwrap_or_default();
+        self.rows.write().push(row);
+    }
+
+    fn intern(&self, name: &str) -> u32 {
+        if let Some(&n) = self.ids.read().get(name) { return n; }
+        let mut ids = self.ids.write();
+        if let Some(&n) = ids.get(name) { return n; }
+        let mut names = self.names.write();
+        let next = names.len() as u32;
+        names.push(name.to_string());
+        ids.insert(name.to_string(), next);
+        next
+    }
+}
+
+#[derive(Default)]
+struct Tally { reviews: usize, positive: usize, neutral: usize, negative: usize, sentiment: f64, stars: i64, rated: usize }
+
+impl Tally {
+    fn rating(&mut self, rating: i32) {
+        if (1..=5).contains(&rating) {
+            self.stars += rating as i64;
+            self.rated += 1;
+        }
+    }
+
+    fn avg(&self) -> Option<f64> { (self.rated > 0).then(|| self.stars as f64 / self.rated as f64) }
+}
+
+/// GET /products/:id/aspects?min_reviews= — per-aspect sentiment of a product's live,
+/// approved reviews, from the aspects extracted when each was written: how many reviews
+/// bring each aspect up, positively (sentiment >= 0.2), negatively (<= -0.2) or neither,
+/// and how their ratings compare with the rest. 404 for an unknown product.
+pub async fn product_aspects(State(st): State<AppState>, Path(product_id): Path<String>, Query(p): Query<AspectsParams>) -> Result<Json<AspectsResp>, ApiError> {
+    let Some(product) = st.meta.attrs.product(&product_id) else {
+        return Err(ApiError::not_found(format!("product {product_id} not found")));
+    };
+    let mentions = &st.meta.aspects;
+    let mut total = Tally::default();
+    let mut by_aspect: HashMap<u32, Tally> = HashMap::new();
+    {
+        let rows = mentions.rows.read();
+        st.meta.attrs.each_of_product(product, ReviewStatus::Approved, |id, rating| {
+            if !st.meta.is_live(id) { return; }
+            total.reviews += 1;
+            total.rating(rating);
+            for &(aspect, s) in rows.get(id).map_or(&[][..], |r| &r[..]) {
+                let t = by_aspect.entry(aspect).or_default();
+                t.reviews += 1;
+                t.rating(rating);
+                t.sentiment += s as f64;
+                match s as f64 {
+                    s if s >= sentiment::MIN_LEAN => t.positive += 1,
+                    s if s <= -sentiment::MIN_LEAN => t.negative += 1,
+                    _ => t.neutral += 1,
+                }
+            }
+        });
+    }
+    let names = mentions.names.read();
+    let mut aspects: Vec<AspectSummary> = by_aspect.into_iter()
+        .filter(|(_, t)| t.reviews >= p.min_reviews.max(1))
+        .map(|(aspect, t)| {
+            let rest = Tally { stars: total.stars - t.stars, rated: total.rated - t.rated, ..Default::default() };
+            AspectSummary {
+                aspect: names[aspect as usize].clone(),
+                reviews: t.reviews,
+                positive: t.positive,
+                neutral: t.neutral,
+                negative: t.negative,
+                sentiment: t.sentiment / t.reviews as f64,
+                avg_rating: t.avg(),
+                rating_gap: t.avg().zip(rest.avg()).map(|(a, b)| a - b),
+            }
+        })
+        .collect();
+    aspects.sort_by(|a, b| {
+        let gap = |s: &AspectSummary| s.rating_gap.unwrap_or(f64::INFINITY);
+        gap(a).total_cmp(&gap(b)).then(b.reviews.cmp(&a.reviews)).then(a.aspect.cmp(&b.aspect))
+    });
+    Ok(Json(AspectsResp { product_id, reviews: total.reviews, avg_rating: total.avg(), aspects }))
+}
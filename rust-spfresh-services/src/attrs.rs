@@ -0,0 +1,120 @@
+use crate::{lang, Review};
+use reviews_types::ReviewStatus;
+use parking_lot::RwLock;
+use std::collections::HashMap;
+
+/// Per-review fields that ranking needs for every scored candidate, kept in memory by id
+/// so they never cost a metadata read. Maintained next to the keyword index.
+#[derive(Default)]
+pub struct Attributes {
+    rows: RwLock<Vec<Attr>>,
+    /// product_id -> the small number stored in `Attr::product`.
+    products: RwLock<HashMap<String, u32>>,
+    /// Language code -> the small number stored in `Attr::lang`.
+    langs: RwLock<HashMap<String, u32>>,
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct Attr {
+    /// Seconds since the epoch; None for reviews written before timestamps existed.
+    pub created_at: Option<u64>,
+    pub rating: i32,
+    /// Interned product_id, for grouping hits by product.
+    pub product: Option<u32>,
+    pub version: u64,
+    /// Interned language, for search filters; detected for reviews stored without one.
+    pub lang: Option<u32>,
+    /// Current moderation status: as written, until a decision in the moderation log.
+    pub status: ReviewStatus,
+}
+
+impl Attributes {
+    /// Records the next review; `None` (an unreadable metadata line) gets empty attributes.
+    pub fn push(&self, review: Option<&Review>) {
+        let attr = review.map(|r| Attr {
+            created_at: r.created_at,
+            rating: r.review_rating,
+            product: Some(intern(&self.products, &r.product_id)),
+            version: r.version.unwrap_or(1),
+            lang: Some(intern(&self.langs, r.lang.as_deref().unwrap_or_else(|| lang::detect(&r.embed_text())))),
+            status: r.status.unwrap_or_default(),
+        }).unwrap_or_default();
+        self.rows.write().push(attr);
+    }
+
+    /// Reviews recorded, i.e. the id the next one gets.
+    pub fn len(&self) -> usize {
+        self.rows.read().len()
+    }
+
+    /// The interned number of `product_id`, if any review ever had it.
+    pub fn product(&self, product_id: &str) -> Option<u32> {
+        self.products.read().get(product_id).copied()
+    }
+
+    /// The interned number of language `code`, if any review has been in it.
+    pub fn lang(&self, code: &str) -> Option<u32> {
+        self.langs.read().get(code).copied()
+    }
+
+    pub fn get(&self, id: usize) -> Attr {
+        self.rows.read().get(id).copied().unwrap_or_default()
+    }
+
+    pub fn set_status(&self, id: usize, status: ReviewStatus) {
+        if let Some(a) = self.rows.write().get_mut(id) { a.status = status; }
+    }
+
+    /// Review `id` at `version`, with the rating and product given.
+    pub fn patch(&self, id: usize, version: u64, rating: Option<i32>, product_id: Option<&str>) {
+        let product = product_id.map(|p| intern(&self.products, p));
+        if let Some(a) = self.rows.write().get_mut(id) {
+            a.version = version;
+            if let Some(r) = rating { a.rating = r; }
+            if product.is_some() { a.product = product; }
+        }
+    }
+
+    /// Ids from `from` on in `status`, in order, while `f` returns true. `f` must not call
+    /// back into the attributes.
+    pub fn each_in_status(&self, from: usize, status: ReviewStatus, mut f: impl FnMut(usize) -> bool) {
+        let rows = self.rows.read();
+        for (id, a) in rows.iter().enumerate().skip(from) {
+            if a.status == status && !f(id) { break; }
+        }
+    }
+
+    /// Every review's attributes, in id order. `f` must not call back into the attributes.
+    pub fn each(&self, mut f: impl FnMut(usize, &Attr)) {
+        for (id, a) in self.rows.read().iter().enumerate() { f(id, a); }
+    }
+
+    /// Product ids by interned number.
+    pub fn product_names(&self) -> Vec<String> { names_by_number(&self.products) }
+
+    /// Language codes by interned number.
+    pub fn lang_names(&self) -> Vec<String> { names_by_number(&self.langs) }
+
+    /// Ids of `product` in `status`, with their ratings, in order. `f` must not call back
+    /// into the attributes.
+    pub fn each_of_product(&self, product: u32, status: ReviewStatus, mut f: impl FnMut(usize, i32)) {
+        let rows = self.rows.read();
+        for (id, a) in rows.iter().enumerate() {
+            if a.product == Some(product) && a.status == status { f(id, a.rating); }
+        }
+    }
+}
+
+fn names_by_number(names: &RwLock<HashMap<String, u32>>) -> Vec<String> {
+    let names = names.read();
+    let mut out = vec![String::new(); names.len()];
+    for (name, &n) in names.iter() { out[n as usize] = name.clone(); }
+    out
+}
+
+fn intern(names: &RwLock<HashMap<String, u32>>, name: &str) -> u32 {
+    if let Some(&n) = names.read().get(name) { return n; }
+    let mut names = names.write();
+    let next = names.len() as u32;
+    *names.entry(name.to_string()).or_insert(next)
+}
@@ -0,0 +1,103 @@
+use crate::{blocking, codec, ApiError, AppState};
+use anyhow::Result;
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Query, State},
+    http::request::Parts,
+    Json,
+};
+use parking_lot::Mutex;
+pub use reviews_types::{Action, IdRanges};
+use reviews_types::{AuditEntry, AuditQuery, AuditResp};
+use std::{
+    convert::Infallible,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const DEFAULT_LIMIT: usize = 100;
+const MAX_LIMIT: usize = 1000;
+/// Set by the gateway in front of the service, which does its own authentication.
+const PRINCIPAL_HEADER: &str = "x-principal";
+const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Who made a request, as far as the audit log is concerned. Extracted from headers; a
+/// request without `x-request-id` gets a fresh one so its entries can still be grouped.
+#[derive(Clone)]
+pub struct Actor {
+    pub principal: String,
+    pub request_id: String,
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Actor {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        let header = |name| parts.headers.get(name).and_then(|v| v.to_str().ok()).filter(|v| !v.is_empty());
+        Ok(Self {
+            principal: header(PRINCIPAL_HEADER).unwrap_or("anonymous").to_string(),
+            request_id: header(REQUEST_ID_HEADER).map(str::to_string).unwrap_or_else(|| uuid::Uuid::new_v4().to_string()),
+        })
+    }
+}
+
+/// Append-only record of every mutation, `data/audit.log`, framed like reviews.jsonl.
+/// Entries are written and synced after the mutation has committed; if that fails the
+/// request fails too, so nothing is acknowledged without an audit entry.
+pub struct AuditLog {
+    path: PathBuf,
+    file: Mutex<File>,
+}
+
+impl AuditLog {
+    pub fn open(data_dir: &Path) -> Result<Self> {
+        let path = data_dir.join("audit.log");
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(Self { path, file: Mutex::new(file) })
+    }
+
+    pub fn record(&self, actor: &Actor, action: Action, ids: IdRanges, subject: Option<String>) -> Result<()> {
+        let ts_ms = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0);
+        let entry = AuditEntry { ts_ms, principal: actor.principal.clone(), request_id: actor.request_id.clone(), action, ids, subject };
+        let line = codec::encode_line(&serde_json::to_vec(&entry)?);
+        let mut f = self.file.lock();
+        f.write_all(&line)?;
+        f.sync_data()?;
+        Ok(())
+    }
+
+    /// Entries matching `q` starting at byte `offset`, plus the offset to resume from if
+    /// `limit` was reached. Damaged lines are skipped with a warning.
+    fn query(&self, q: &AuditQuery, offset: u64, limit: usize) -> Result<(Vec<AuditEntry>, Option<u64>)> {
+        let mut rdr = BufReader::new(File::open(&self.path)?);
+        rdr.seek(SeekFrom::Start(offset))?;
+        let (mut pos, mut line, mut out) = (offset, Vec::new(), Vec::new());
+        loop {
+            line.clear();
+            let n = rdr.read_until(b'\n', &mut line)?;
+            if n == 0 || line.last() != Some(&b'\n') { return Ok((out, None)); }
+            pos += n as u64;
+            let entry = codec::decode_line(&line[..n - 1])
+                .and_then(|json| Ok(serde_json::from_slice::<AuditEntry>(json)?));
+            match entry {
+                Ok(e) if q.matches(&e) => {
+                    out.push(e);
+                    if out.len() == limit { return Ok((out, Some(pos))); }
+                }
+                Ok(_) => {}
+                Err(e) => tracing::warn!("{}: damaged entry before offset {pos}: {e}", self.path.display()),
+            }
+        }
+    }
+}
+
+/// GET /admin/audit?principal=&action=&id=&since_ms=&until_ms=&cursor=&limit= — entries
+/// in the order they were written, filtered; each page resumes where the last one stopped.
+pub async fn query_audit(State(st): State<AppState>, Query(q): Query<AuditQuery>) -> Result<Json<AuditResp>, ApiError> {
+    let limit = q.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let (entries, next_cursor) = blocking(move || Ok(st.audit.query(&q, q.cursor.unwrap_or(0), limit)?)).await?;
+    Ok(Json(AuditResp { entries, next_cursor }))
+}
@@ -0,0 +1,34 @@
+use crate::{
+    blocking,
+    negotiate::{Negotiated, Reply},
+    ApiError, AppState, Review,
+};
+use axum::extract::State;
+use reviews_types::{BatchGetReq, BatchGetResp, BatchItem};
+use std::collections::HashMap;
+
+const MAX_IDS: usize = 1000;
+
+/// POST /reviews/batch — the reviews with the given ids, at most 1000, in one response,
+/// each with its current moderation status. Reads them in one pass in id order (see
+/// `MetaStore::read_reviews`): sealed zstd blocks are found through the block index and
+/// read once each, but reviews.jsonl has no line index and is read from its start up to
+/// the highest id wanted there. Under the default `compression = "none"` that is all of
+/// the metadata.
+pub async fn batch_get(State(st): State<AppState>, Negotiated(req, fmt): Negotiated<BatchGetReq>) -> Result<Reply<BatchGetResp>, ApiError> {
+    if req.ids.len() > MAX_IDS {
+        return Err(ApiError::bad_request(format!("at most {MAX_IDS} ids")));
+    }
+    let resp = blocking(move || {
+        let mut ids = req.ids.clone();
+        ids.sort_unstable();
+        ids.dedup();
+        let found: HashMap<usize, Review> = st.meta.read_reviews(&ids)?.into_iter().collect();
+        let reviews = req.ids.iter().map(|&id| {
+            let review = found.get(&id).cloned();
+            BatchItem { id, found: review.is_some(), review }
+        }).collect();
+        Ok(BatchGetResp { reviews })
+    }).await?;
+    Ok(Reply(fmt, resp))
+}
@@ -0,0 +1,153 @@
+use crate::{config::Config, open_state, router, synth::Generator, Review};
+use anyhow::{Context, Result};
+use axum::{
+    body::{to_bytes, Body},
+    http::{header, Request},
+    Router,
+};
+use std::{
+    io::{BufRead, BufReader},
+    path::PathBuf,
+    time::Instant,
+};
+use tower::ServiceExt;
+
+/// `bench` options, from `--name value` pairs.
+struct BenchOpts {
+    /// Corpus sizes to measure at, ascending; the corpus grows from one to the next.
+    sizes: Vec<usize>,
+    top_k: Vec<usize>,
+    /// Searches per (size, top_k) cell.
+    queries: usize,
+    /// Reviews per bulk insert request.
+    batch: usize,
+    /// NDJSON reviews to insert (cycled if shorter than the largest size) instead of
+    /// generated ones.
+    corpus: Option<PathBuf>,
+    /// Leave the scratch data directory in place for inspection.
+    keep: bool,
+}
+
+impl BenchOpts {
+    fn parse(args: &[String]) -> Result<Self> {
+        let mut o = Self { sizes: vec![1_000, 10_000, 50_000], top_k: vec![5, 10, 100], queries: 200, batch: 500, corpus: None, keep: false };
+        let mut it = args.iter();
+        while let Some(flag) = it.next() {
+            if flag == "--keep" { o.keep = true; continue; }
+            let value = it.next().with_context(|| format!("{flag} needs a value"))?;
+            let list = |v: &str| v.split(',').map(|n| n.trim().parse::<usize>()).collect::<Result<Vec<_>, _>>();
+            match flag.as_str() {
+                "--sizes" => o.sizes = list(value)?,
+                "--top-k" => o.top_k = list(value)?,
+                "--queries" => o.queries = value.parse()?,
+                "--batch" => o.batch = value.parse()?,
+                "--corpus" => o.corpus = Some(value.into()),
+                _ => anyhow::bail!("unknown bench option {flag} (--sizes, --top-k, --queries, --batch, --corpus, --keep)"),
+            }
+        }
+        o.sizes.sort_unstable();
+        o.sizes.dedup();
+        anyhow::ensure!(o.sizes.first().is_some_and(|&n| n > 0), "--sizes must list positive sizes");
+        anyhow::ensure!(o.queries > 0 && o.batch > 0 && !o.top_k.is_empty(), "--queries, --batch and --top-k must be positive");
+        Ok(o)
+    }
+}
+
+/// Reviews to insert: a loaded file, or generated on the fly.
+enum Corpus {
+    File(Vec<Review>),
+    Synthetic(Generator),
+}
+
+impl Corpus {
+    fn load(path: Option<&PathBuf>) -> Result<Self> {
+        // Fixed seed, so runs are comparable.
+        let Some(path) = path else { return Ok(Self::Synthetic(Generator::new(42, 500))) };
+        let file = std::fs::File::open(path).with_context(|| format!("open {}", path.display()))?;
+        let mut reviews = Vec::new();
+        for (n, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() { continue; }
+            reviews.push(serde_json::from_str(&line).with_context(|| format!("{}:{}", path.display(), n + 1))?);
+        }
+        anyhow::ensure!(!reviews.is_empty(), "{} holds no reviews", path.display());
+        Ok(Self::File(reviews))
+    }
+
+    fn review(&self, i: usize) -> Review {
+        match self {
+            Self::File(reviews) => reviews[i % reviews.len()].clone(),
+            Self::Synthetic(g) => g.review(i as u64),
+        }
+    }
+
+    /// Search text for query `i`: the title of a review spread across the corpus.
+    fn query(&self, i: usize) -> String {
+        self.review(i.wrapping_mul(7919)).review_title
+    }
+}
+
+/// `bench` subcommand: builds a service on a scratch data directory with the configured
+/// embedder and index, grows it through the bulk insert endpoint to each size in turn,
+/// and times searches at each size and top_k. Requests go through the full router, so the
+/// numbers include JSON handling and middleware, but not the network.
+pub async fn run(config: Config, args: &[String]) -> Result<()> {
+    let opts = BenchOpts::parse(args)?;
+    let corpus = Corpus::load(opts.corpus.as_ref())?;
+    let dir = std::env::temp_dir().join(format!("spfresh-bench-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir)?;
+    let result = measure(config, &dir, &opts, &corpus).await;
+    if opts.keep {
+        println!("data left in {}", dir.display());
+    } else if let Err(e) = std::fs::remove_dir_all(&dir) {
+        tracing::warn!("remove {}: {e}", dir.display());
+    }
+    result
+}
+
+async fn measure(config: Config, dir: &std::path::Path, opts: &BenchOpts, corpus: &Corpus) -> Result<()> {
+    let app = router(open_state(config, dir, &Default::default())?)?;
+    println!("{:>9} {:>13} {:>6} {:>9} {:>9} {:>9} {:>9}", "size", "insert doc/s", "top_k", "p50 ms", "p95 ms", "p99 ms", "mean ms");
+    let mut inserted = 0;
+    for &size in &opts.sizes {
+        let started = Instant::now();
+        let added = size - inserted;
+        while inserted < size {
+            let n = opts.batch.min(size - inserted);
+            let reviews: Vec<Review> = (inserted..inserted + n).map(|i| corpus.review(i)).collect();
+            call(&app, "/v1/reviews/bulk", serde_json::json!({ "reviews": reviews })).await?;
+            inserted += n;
+        }
+        let rate = added as f64 / started.elapsed().as_secs_f64();
+        for &k in &opts.top_k {
+            let mut ms = Vec::with_capacity(opts.queries);
+            for q in 0..opts.queries {
+                let t = Instant::now();
+                call(&app, "/v1/search", serde_json::json!({ "query": corpus.query(q), "top_k": k })).await?;
+                ms.push(t.elapsed().as_secs_f64() * 1e3);
+            }
+            ms.sort_by(f64::total_cmp);
+            let mean = ms.iter().sum::<f64>() / ms.len() as f64;
+            println!("{size:>9} {rate:>13.0} {k:>6} {:>9.2} {:>9.2} {:>9.2} {mean:>9.2}", pct(&ms, 0.50), pct(&ms, 0.95), pct(&ms, 0.99));
+        }
+    }
+    Ok(())
+}
+
+/// `p`-quantile of sorted samples (nearest rank).
+fn pct(sorted: &[f64], p: f64) -> f64 {
+    sorted[((sorted.len() - 1) as f64 * p).round() as usize]
+}
+
+/// Sends one JSON POST through the router and reads the whole response.
+async fn call(app: &Router, path: &str, body: serde_json::Value) -> Result<()> {
+    let req = Request::post(path)
+        .header(header::CONTENT_TYPE, "application/json")
+        .header("x-principal", "bench")
+        .body(Body::from(serde_json::to_vec(&body)?))?;
+    let resp = app.clone().oneshot(req).await?;
+    let status = resp.status();
+    let bytes = to_bytes(resp.into_body(), usize::MAX).await?;
+    anyhow::ensure!(status.is_success(), "{path}: {status} {}", String::from_utf8_lossy(&bytes));
+    Ok(())
+}
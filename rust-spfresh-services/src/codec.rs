@@ -0,0 +1,361 @@
+//! On-disk record encoding. A vector is `dim` IEEE-754 f32 values, little-endian, packed
+//! with no padding; files are read byte-wise, so neither host endianness nor alignment
+//! matters. Every path that writes or reads vectors or metadata lines goes through here.
+//!
+//! Vector files start with a `FileHeader`, followed by fixed-size framed records
+//! (`len u32 | crc32 u32 | vector`), so record `i` lives at `HEADER_LEN + i * record_len`
+//! and a torn or corrupt record is detected without shifting the ids after it.
+//! Metadata lines are framed as `<len hex8> <crc hex8> <json>\n`.
+
+use anyhow::{anyhow, Result};
+use serde::de::DeserializeOwned;
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+/// Bytes per encoded f32.
+pub const F32_BYTES: usize = 4;
+
+pub fn bytes_per_vec(dim: usize) -> usize { dim * F32_BYTES }
+
+pub fn encode(vec: &[f32]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(bytes_per_vec(vec.len()));
+    for x in vec { out.extend_from_slice(&x.to_le_bytes()); }
+    out
+}
+
+/// Appends the values encoded in `bytes` to `out`. A trailing partial value is ignored.
+pub fn decode_into(bytes: &[u8], out: &mut Vec<f32>) {
+    out.reserve(bytes.len() / F32_BYTES);
+    out.extend(
+        bytes.chunks_exact(F32_BYTES).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+    );
+}
+
+/// Length + CRC32 in front of every vector record.
+pub const RECORD_OVERHEAD: usize = 8;
+
+/// On-disk size of one framed vector record.
+pub fn record_len(dim: usize) -> usize { RECORD_OVERHEAD + bytes_per_vec(dim) }
+
+pub fn encode_record(vec: &[f32]) -> Vec<u8> {
+    frame_payload(&encode(vec))
+}
+
+fn frame_payload(payload: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(RECORD_OVERHEAD + payload.len());
+    out.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    out.extend_from_slice(&crc32fast::hash(payload).to_le_bytes());
+    out.extend_from_slice(payload);
+    out
+}
+
+/// Decodes one framed record of `record_len(dim)` bytes into `out`. Returns false, leaving
+/// `out` untouched, if the length or checksum does not match (torn or corrupt record).
+pub fn decode_record_into(rec: &[u8], dim: usize, out: &mut Vec<f32>) -> bool {
+    let payload = &rec[RECORD_OVERHEAD..];
+    let len = u32::from_le_bytes([rec[0], rec[1], rec[2], rec[3]]) as usize;
+    let crc = u32::from_le_bytes([rec[4], rec[5], rec[6], rec[7]]);
+    if len != bytes_per_vec(dim) || payload.len() != len || crc32fast::hash(payload) != crc {
+        return false;
+    }
+    decode_into(payload, out);
+    true
+}
+
+/// Bytes of a framed metadata line before the JSON: `<len hex8> <crc hex8> `.
+const LINE_PREFIX: usize = 18;
+
+/// Frames one JSON document as a metadata line, trailing newline included.
+pub fn encode_line(json: &[u8]) -> Vec<u8> {
+    let mut out = format!("{:08x} {:08x} ", json.len(), crc32fast::hash(json)).into_bytes();
+    out.extend_from_slice(json);
+    out.push(b'\n');
+    out
+}
+
+/// The JSON of one metadata line (without its newline). Lines written before framing
+/// (bare JSON objects) are passed through unchecked.
+pub fn decode_line(line: &[u8]) -> Result<&[u8]> {
+    if line.first() == Some(&b'{') { return Ok(line); }
+    anyhow::ensure!(line.len() >= LINE_PREFIX && line[8] == b' ' && line[17] == b' ', "unframed metadata line");
+    let hex = |b: &[u8]| std::str::from_utf8(b).ok().and_then(|s| u32::from_str_radix(s, 16).ok());
+    let (len, crc) = hex(&line[..8]).zip(hex(&line[9..17])).ok_or_else(|| anyhow!("bad metadata frame"))?;
+    let json = &line[LINE_PREFIX..];
+    anyhow::ensure!(json.len() == len as usize, "metadata line length {} != framed {}", json.len(), len);
+    anyhow::ensure!(crc32fast::hash(json) == crc, "metadata line checksum mismatch");
+    Ok(json)
+}
+
+/// Entries of the framed log at `path` (one JSON document per line, framed like metadata
+/// lines) after byte `offset`, in order, stopping before the first whose `id_of` is at or
+/// past `below`, for a replica to apply only what concerns reviews it already has. Damaged
+/// entries are skipped, a missing log reads as empty. Returns them and the offset to resume from.
+pub fn read_log_from<T: DeserializeOwned>(path: &Path, offset: u64, below: usize, id_of: impl Fn(&T) -> usize) -> Result<(Vec<T>, u64)> {
+    let mut rdr = match File::open(path) {
+        Ok(f) => BufReader::new(f),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok((Vec::new(), offset)),
+        Err(e) => return Err(e.into()),
+    };
+    rdr.seek(SeekFrom::Start(offset))?;
+    let (mut pos, mut line, mut out) = (offset, Vec::new(), Vec::new());
+    loop {
+        line.clear();
+        let n = rdr.read_until(b'\n', &mut line)?;
+        if n == 0 || line.last() != Some(&b'\n') { return Ok((out, pos)); }
+        match decode_line(&line[..n - 1]).and_then(|json| Ok(serde_json::from_slice::<T>(json)?)) {
+            Ok(e) if id_of(&e) >= below => return Ok((out, pos)),
+            Ok(e) => out.push(e),
+            Err(e) => tracing::warn!("{}: damaged entry at offset {pos}: {e}", path.display()),
+        }
+        pos += n as u64;
+    }
+}
+
+/// Fixed-size header at the start of every vector file:
+/// magic (8) | version u32 | dim u32 | metric u32 | reserved (12), integers little-endian.
+pub const HEADER_LEN: u64 = 32;
+/// v1: unframed vectors after the header. v2: framed records.
+pub const FORMAT_VERSION: u32 = 2;
+
+/// Which file a header belongs to; each kind has its own magic so files can't be swapped.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FileKind {
+    /// reviews.index, the raw vector mirror.
+    Mirror,
+    /// reviews.spfresh.hdr, sidecar describing the spfresh-owned reviews.spfresh.
+    Spfresh,
+}
+
+impl FileKind {
+    fn magic(self) -> &'static [u8; 8] {
+        match self {
+            FileKind::Mirror => b"SPFRMIR\0",
+            FileKind::Spfresh => b"SPFRIDX\0",
+        }
+    }
+}
+
+/// Similarity the vectors were written for. Only cosine (dot over L2-normalised vectors) exists.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Metric {
+    Cosine = 1,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct FileHeader {
+    pub kind: FileKind,
+    pub version: u32,
+    pub dim: u32,
+    pub metric: u32,
+}
+
+impl FileHeader {
+    pub fn new(kind: FileKind, dim: usize) -> Self {
+        Self { kind, version: FORMAT_VERSION, dim: dim as u32, metric: Metric::Cosine as u32 }
+    }
+
+    pub fn encode(&self) -> [u8; HEADER_LEN as usize] {
+        let mut out = [0u8; HEADER_LEN as usize];
+        out[..8].copy_from_slice(self.kind.magic());
+        out[8..12].copy_from_slice(&self.version.to_le_bytes());
+        out[12..16].copy_from_slice(&self.dim.to_le_bytes());
+        out[16..20].copy_from_slice(&self.metric.to_le_bytes());
+        out
+    }
+
+    /// None if `bytes` does not start with the magic of `kind`.
+    pub fn decode(kind: FileKind, bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < HEADER_LEN as usize || &bytes[..8] != kind.magic() { return None; }
+        let u32_at = |i: usize| u32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+        Some(Self { kind, version: u32_at(8), dim: u32_at(12), metric: u32_at(16) })
+    }
+
+    /// Checks the header against what the service is about to read/write.
+    pub fn validate(&self, path: &Path, dim: usize) -> Result<()> {
+        anyhow::ensure!(
+            self.version <= FORMAT_VERSION,
+            "{}: format version {} is newer than supported {}", path.display(), self.version, FORMAT_VERSION
+        );
+        anyhow::ensure!(
+            self.dim as usize == dim,
+            "{}: written with dim {}, configured embedder has dim {}", path.display(), self.dim, dim
+        );
+        anyhow::ensure!(
+            self.metric == Metric::Cosine as u32,
+            "{}: unknown metric {}", path.display(), self.metric
+        );
+        Ok(())
+    }
+}
+
+/// Makes sure the mirror at `path` is in the current format for `dim`: writes a header into
+/// an empty file, validates an existing one, and migrates older files (headerless or v1,
+/// i.e. unframed vectors) by rewriting every vector as a framed record — via a temp file
+/// and rename, so a crash midway leaves the original untouched.
+pub fn ensure_mirror_header(path: &Path, dim: usize) -> Result<()> {
+    let mut f = File::open(path)?;
+    let len = f.metadata()?.len();
+    let header = FileHeader::new(FileKind::Mirror, dim);
+    if len == 0 {
+        return write_synced(path, &header.encode());
+    }
+    let mut head = vec![0u8; len.min(HEADER_LEN) as usize];
+    f.read_exact(&mut head)?;
+    let body_start = match FileHeader::decode(FileKind::Mirror, &head) {
+        Some(h) if h.version == FORMAT_VERSION => return h.validate(path, dim),
+        Some(h) => { h.validate(path, dim)?; HEADER_LEN }
+        None => 0,
+    };
+    let body = len - body_start;
+    anyhow::ensure!(
+        body % bytes_per_vec(dim) as u64 == 0,
+        "{}: {} bytes of unframed vectors is not a whole number of dim-{} vectors", path.display(), body, dim
+    );
+    let tmp = path.with_extension("index.migrate");
+    {
+        let mut out = std::io::BufWriter::new(File::create(&tmp)?);
+        out.write_all(&header.encode())?;
+        f.seek(SeekFrom::Start(body_start))?;
+        let mut rdr = std::io::BufReader::new(f);
+        let mut vec = vec![0u8; bytes_per_vec(dim)];
+        for _ in 0..body / vec.len() as u64 {
+            rdr.read_exact(&mut vec)?;
+            out.write_all(&frame_payload(&vec))?;
+        }
+        out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+    }
+    std::fs::rename(&tmp, path)?;
+    tracing::info!("{}: migrated {} vectors to mirror format v{}", path.display(), body / bytes_per_vec(dim) as u64, FORMAT_VERSION);
+    Ok(())
+}
+
+/// Writes or validates the sidecar header at `path` for a library-owned data file, whose
+/// own layout we can't prefix. A missing sidecar next to an existing index is adopted with
+/// the configured dim.
+pub fn ensure_sidecar_header(path: &Path, kind: FileKind, dim: usize) -> Result<()> {
+    match std::fs::read(path) {
+        Ok(b) => FileHeader::decode(kind, &b)
+            .ok_or_else(|| anyhow!("{}: bad magic", path.display()))?
+            .validate(path, dim),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            write_synced(path, &FileHeader::new(kind, dim).encode())
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn write_synced(path: &Path, bytes: &[u8]) -> Result<()> {
+    let mut f = std::fs::OpenOptions::new().write(true).create(true).truncate(true).open(path)?;
+    f.write_all(bytes)?;
+    f.sync_all()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_round_trip() {
+        let v = vec![0.5, -1.25, f32::MIN_POSITIVE, 3.0e7];
+        let rec = encode_record(&v);
+        assert_eq!(rec.len(), record_len(v.len()));
+        let mut out = Vec::new();
+        assert!(decode_record_into(&rec, v.len(), &mut out));
+        assert_eq!(out, v);
+    }
+
+    #[test]
+    fn record_crc_mismatch() {
+        let mut rec = encode_record(&[1.0, 2.0]);
+        rec[RECORD_OVERHEAD] ^= 1;
+        let mut out = vec![9.0];
+        assert!(!decode_record_into(&rec, 2, &mut out));
+        assert_eq!(out, [9.0]);
+    }
+
+    #[test]
+    fn record_torn_tail() {
+        let rec = encode_record(&[1.0, 2.0, 3.0]);
+        let mut out = Vec::new();
+        assert!(!decode_record_into(&rec[..rec.len() - 2], 3, &mut out));
+        assert!(!decode_record_into(&rec, 2, &mut out));
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn decode_ignores_partial_value() {
+        let mut bytes = encode(&[4.0, 5.0]);
+        bytes.push(0xff);
+        let mut out = Vec::new();
+        decode_into(&bytes, &mut out);
+        assert_eq!(out, [4.0, 5.0]);
+    }
+
+    #[test]
+    fn line_round_trip() {
+        let json = br#"{"review_title":"ok"}"#;
+        let line = encode_line(json);
+        assert_eq!(line.last(), Some(&b'\n'));
+        assert_eq!(decode_line(&line[..line.len() - 1]).unwrap(), json);
+        assert_eq!(decode_line(json).unwrap(), json, "bare JSON passes through");
+    }
+
+    #[test]
+    fn line_corrupt_or_torn() {
+        let line = encode_line(br#"{"a":1}"#);
+        let body = &line[..line.len() - 1];
+        let mut flipped = body.to_vec();
+        *flipped.last_mut().unwrap() = b']';
+        assert!(decode_line(&flipped).is_err());
+        assert!(decode_line(&body[..body.len() - 1]).is_err());
+        assert!(decode_line(&body[..10]).is_err());
+        assert!(decode_line(b"zzzzzzzz 00000000 {}").is_err());
+    }
+
+    #[test]
+    fn header_round_trip() {
+        let h = FileHeader::new(FileKind::Mirror, 384);
+        let bytes = h.encode();
+        assert_eq!(FileHeader::decode(FileKind::Mirror, &bytes), Some(h));
+        assert_eq!(FileHeader::decode(FileKind::Spfresh, &bytes), None, "magic is per kind");
+    }
+
+    #[test]
+    fn header_truncated_or_mismatched() {
+        let h = FileHeader::new(FileKind::Spfresh, 8);
+        let bytes = h.encode();
+        assert_eq!(FileHeader::decode(FileKind::Spfresh, &bytes[..HEADER_LEN as usize - 1]), None);
+        let path = Path::new("reviews.spfresh.hdr");
+        assert!(h.validate(path, 8).is_ok());
+        assert!(h.validate(path, 16).is_err());
+        let newer = FileHeader { version: FORMAT_VERSION + 1, ..h };
+        assert!(newer.validate(path, 8).is_err());
+    }
+
+    #[test]
+    fn mirror_migrates_unframed_vectors() {
+        let dir = std::env::temp_dir().join(format!("codec-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("reviews.index");
+        let vecs = [[1.0f32, 2.0], [3.0, 4.0]];
+        std::fs::write(&path, vecs.iter().flat_map(|v| encode(v)).collect::<Vec<u8>>()).unwrap();
+        ensure_mirror_header(&path, 2).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        assert_eq!(bytes.len() as u64, HEADER_LEN + 2 * record_len(2) as u64);
+        let h = FileHeader::decode(FileKind::Mirror, &bytes).unwrap();
+        assert_eq!((h.version, h.dim), (FORMAT_VERSION, 2));
+        for (i, rec) in bytes[HEADER_LEN as usize..].chunks(record_len(2)).enumerate() {
+            let mut out = Vec::new();
+            assert!(decode_record_into(rec, 2, &mut out));
+            assert_eq!(out, vecs[i]);
+        }
+        ensure_mirror_header(&path, 2).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), bytes, "current format is left alone");
+        assert!(ensure_mirror_header(&path, 3).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,266 @@
+use crate::{
+    audit::{Action, Actor, IdRanges},
+    blocking, build_embedder, config::EmbedderConfig, spfresh_index, Active, ApiError, AppState, VecIndex,
+};
+use anyhow::Result;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use parking_lot::{Mutex, RwLock};
+use reviews_types::{AliasReq, CollectionInfo};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::{Path as FsPath, PathBuf},
+    sync::Arc,
+};
+
+/// Collection name of the index living directly in the data dir.
+pub const ROOT_COLLECTION: &str = "default";
+/// Written next to each index so it can be reopened with the embedder that built it.
+const EMBEDDER_FILE: &str = "embedder.json";
+const ALIASES_FILE: &str = "aliases.json";
+
+/// A collection is an index directory under the data dir (the data dir itself is
+/// `default`; reindex produces `index-<job id>`). Aliases give collections stable names
+/// that can be re-pointed atomically, e.g. `reviews-prod` -> `index-1f2e...`.
+pub struct Collections {
+    data_dir: PathBuf,
+    aliases: RwLock<BTreeMap<String, String>>,
+    /// Non-active collections opened for reads, keyed by collection name.
+    opened: Mutex<HashMap<String, Active>>,
+}
+
+impl Collections {
+    pub fn open(data_dir: &FsPath) -> Result<Self> {
+        let aliases = match std::fs::read(data_dir.join(ALIASES_FILE)) {
+            Ok(b) => serde_json::from_slice(&b)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self { data_dir: data_dir.to_path_buf(), aliases: RwLock::new(aliases), opened: Mutex::new(HashMap::new()) })
+    }
+
+    fn dir_of(&self, collection: &str) -> PathBuf {
+        if collection == ROOT_COLLECTION { self.data_dir.clone() } else { self.data_dir.join(collection) }
+    }
+
+    fn is_collection(&self, name: &str) -> bool {
+        !name.is_empty()
+            && !name.contains(['/', '\\', '.'])
+            && self.dir_of(name).join("reviews.index").is_file()
+    }
+
+    /// Follows an alias if `name` is one, otherwise treats it as a collection name.
+    pub fn resolve_name(&self, name: &str) -> String {
+        self.aliases.read().get(name).cloned().unwrap_or_else(|| name.to_string())
+    }
+
+    /// Index + embedder to query for `name` (alias or collection). The active collection
+    /// resolves to the live `Active`, so it always sees the latest writes.
+    pub fn resolve(&self, st: &AppState, name: &str) -> Result<Active, ApiError> {
+        let collection = self.resolve_name(name);
+        if !self.is_collection(&collection) {
+            return Err(ApiError::not_found(format!("unknown collection or alias '{name}'")));
+        }
+        let active = st.active.read().clone();
+        let mirror = self.mirror_of(&collection).map_err(anyhow::Error::from)?;
+        if mirror == active.vindex.mirror_path() {
+            return Ok(active);
+        }
+        let mut opened = self.opened.lock();
+        if let Some(a) = opened.get(&collection) {
+            return Ok(a.clone());
+        }
+        let dir = self.dir_of(&collection);
+        let emb_cfg = read_embedder(&dir)?.unwrap_or_else(|| st.config.embedder.clone());
+        let vindex: Arc<dyn VecIndex> = Arc::new(spfresh_index::DefaultIndex::open(&dir, emb_cfg.dim(), &st.config.durability)?);
+        let a = Active { vindex, embedder: build_embedder(&emb_cfg, &dir)? };
+        opened.insert(collection, a.clone());
+        Ok(a)
+    }
+
+    /// Index + embedder for a request naming `model`, a registered embedder: `collection`
+    /// (alias or collection) if given, else the active collection if `model` built it, else
+    /// the one collection bound to it. 409 when the collection's recorded embedder is not
+    /// `model`'s, so a request pinned to a model is never served from another one's vectors.
+    pub fn resolve_model(&self, st: &AppState, collection: Option<&str>, model: &str) -> Result<Active, ApiError> {
+        let wanted = registered(st, model)?;
+        let name = match collection {
+            Some(name) => self.resolve_name(name),
+            None => {
+                let mut bound = st.embedders.collections_of(model);
+                let active = st.vindex();
+                match bound.iter().position(|c| self.mirror_of(c).is_ok_and(|m| m == active.mirror_path())) {
+                    Some(i) => bound.swap_remove(i),
+                    None if bound.len() == 1 => bound.remove(0),
+                    None if bound.is_empty() => return Err(ApiError::not_found(format!("no collection was built by embedder '{model}'"))),
+                    None => return Err(ApiError::conflict(format!(
+                        "embedder '{model}' built several collections ({}); name one as collection", bound.join(", ")
+                    ))),
+                }
+            }
+        };
+        if !self.is_collection(&name) {
+            return Err(ApiError::not_found(format!("unknown collection or alias '{}'", collection.unwrap_or(&name))));
+        }
+        check_built_by(st, &self.dir_of(&name), &format!("collection '{name}'"), model, &wanted)?;
+        self.resolve(st, &name)
+    }
+
+    /// 409 unless the active index was built by `model`, a registered embedder: a vector
+    /// computed by another model would not be comparable with the rest. `vindex` is the
+    /// active index, read under the write gate so a reindex cannot swap it in between.
+    pub fn check_active_model(&self, st: &AppState, vindex: &dyn VecIndex, model: &str) -> Result<(), ApiError> {
+        let dir = vindex.mirror_path().parent().map(FsPath::to_path_buf).unwrap_or_else(|| self.data_dir.clone());
+        check_built_by(st, &dir, "the active index", model, &registered(st, model)?)
+    }
+
+    /// Runs `f` on the index of every collection whose mirror is not in `skip`, opening the
+    /// ones no read has opened yet just for the call. `opened` stays locked throughout, so
+    /// `resolve` cannot open a second copy of an index meanwhile.
+    pub fn for_each_index(&self, st: &AppState, skip: &HashSet<PathBuf>, mut f: impl FnMut(&dyn VecIndex) -> Result<()>) -> Result<()> {
+        let opened = self.opened.lock();
+        for name in self.list_collections()? {
+            let Ok(mirror) = self.mirror_of(&name) else { continue };
+            if skip.contains(&mirror) { continue; }
+            match opened.get(&name) {
+                Some(a) => f(a.vindex.as_ref())?,
+                None => {
+                    let dir = self.dir_of(&name);
+                    let dim = read_embedder(&dir)?.unwrap_or_else(|| st.config.embedder.clone()).dim();
+                    f(&spfresh_index::DefaultIndex::open(&dir, dim, &st.config.durability)?)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn mirror_of(&self, collection: &str) -> std::io::Result<PathBuf> {
+        std::fs::canonicalize(self.dir_of(collection).join("reviews.index"))
+    }
+
+    fn save_aliases(&self, aliases: &BTreeMap<String, String>) -> Result<()> {
+        let tmp = self.data_dir.join(format!("{ALIASES_FILE}.tmp"));
+        std::fs::write(&tmp, serde_json::to_vec_pretty(aliases)?)?;
+        std::fs::rename(&tmp, self.data_dir.join(ALIASES_FILE))?;
+        Ok(())
+    }
+
+    fn list_collections(&self) -> Result<Vec<String>> {
+        let mut out = Vec::new();
+        if self.is_collection(ROOT_COLLECTION) { out.push(ROOT_COLLECTION.to_string()); }
+        for entry in std::fs::read_dir(&self.data_dir)? {
+            let name = entry?.file_name().to_string_lossy().into_owned();
+            if self.is_collection(&name) { out.push(name); }
+        }
+        out.sort();
+        Ok(out)
+    }
+
+    /// Every collection whose embedder is recorded, with that embedder.
+    pub fn embedders(&self) -> Result<Vec<(String, EmbedderConfig)>> {
+        let mut out = Vec::new();
+        for name in self.list_collections()? {
+            if let Some(cfg) = read_embedder(&self.dir_of(&name))? { out.push((name, cfg)); }
+        }
+        Ok(out)
+    }
+}
+
+fn registered(st: &AppState, model: &str) -> Result<EmbedderConfig, ApiError> {
+    st.embedders.get(model).ok_or_else(|| ApiError::not_found(format!("unknown embedder '{model}'")))
+}
+
+/// 409 unless the index in `dir` (`what`, for the message) was built by `wanted`, the
+/// configuration of `model`.
+fn check_built_by(st: &AppState, dir: &FsPath, what: &str, model: &str, wanted: &EmbedderConfig) -> Result<(), ApiError> {
+    // As in `Collections::resolve`: an index without a record was built by the configured embedder.
+    let recorded = read_embedder(dir)?.unwrap_or_else(|| st.config.embedder.clone());
+    if recorded != *wanted {
+        let built_by = st.embedders.name_of(&recorded).map_or_else(|| format!("{recorded:?}"), |n| format!("'{n}'"));
+        return Err(ApiError::conflict(format!("{what} was built by {built_by}, not by embedder '{model}'")));
+    }
+    Ok(())
+}
+
+/// Records which embedder built the index in `dir` (no-op if already recorded).
+pub fn record_embedder(dir: &FsPath, cfg: &EmbedderConfig) -> Result<()> {
+    let p = dir.join(EMBEDDER_FILE);
+    if !p.exists() { std::fs::write(p, serde_json::to_vec_pretty(cfg)?)?; }
+    Ok(())
+}
+
+pub fn read_embedder(dir: &FsPath) -> Result<Option<EmbedderConfig>> {
+    match std::fs::read(dir.join(EMBEDDER_FILE)) {
+        Ok(b) => Ok(Some(serde_json::from_slice(&b)?)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// POST /aliases — create or re-point an alias. Takes effect for the next request.
+pub async fn put_alias(State(st): State<AppState>, actor: Actor, Json(req): Json<AliasReq>) -> Result<Json<AliasReq>, ApiError> {
+    blocking(move || {
+        let c = &st.collections;
+        if req.name.is_empty() || c.is_collection(&req.name) {
+            return Err(ApiError::bad_request(format!("alias name '{}' is empty or names a collection", req.name)));
+        }
+        if !c.is_collection(&req.collection) {
+            return Err(ApiError::not_found(format!("unknown collection '{}'", req.collection)));
+        }
+        let mut aliases = c.aliases.write();
+        let mut next = aliases.clone();
+        next.insert(req.name.clone(), req.collection.clone());
+        c.save_aliases(&next)?;
+        *aliases = next;
+        tracing::info!("alias {} -> {}", req.name, req.collection);
+        st.audit.record(&actor, Action::PutAlias, IdRanges::default(), Some(format!("{} -> {}", req.name, req.collection)))?;
+        Ok(Json(req))
+    }).await
+}
+
+pub async fn list_aliases(State(st): State<AppState>) -> Json<BTreeMap<String, String>> {
+    Json(st.collections.aliases.read().clone())
+}
+
+pub async fn delete_alias(State(st): State<AppState>, actor: Actor, Path(name): Path<String>) -> Result<StatusCode, ApiError> {
+    blocking(move || {
+        let c = &st.collections;
+        let mut aliases = c.aliases.write();
+        if !aliases.contains_key(&name) {
+            return Err(ApiError::not_found(format!("alias '{name}' not found")));
+        }
+        let mut next = aliases.clone();
+        next.remove(&name);
+        c.save_aliases(&next)?;
+        *aliases = next;
+        st.audit.record(&actor, Action::DeleteAlias, IdRanges::default(), Some(name))?;
+        Ok(StatusCode::NO_CONTENT)
+    }).await
+}
+
+/// GET /collections
+pub async fn list_collections(State(st): State<AppState>) -> Result<Json<Vec<CollectionInfo>>, ApiError> {
+    blocking(move || collection_infos(&st).map(Json)).await
+}
+
+fn collection_infos(st: &AppState) -> Result<Vec<CollectionInfo>, ApiError> {
+    let c = &st.collections;
+    let aliases = c.aliases.read().clone();
+    let active_mirror = st.vindex().mirror_path().to_path_buf();
+    let mut out = Vec::new();
+    for name in c.list_collections()? {
+        let a = c.resolve(st, &name)?;
+        out.push(CollectionInfo {
+            vectors: a.vindex.len()?,
+            dim: a.vindex.dim(),
+            active: a.vindex.mirror_path() == active_mirror,
+            aliases: aliases.iter().filter(|(_, v)| **v == name).map(|(k, _)| k.clone()).collect(),
+            embedder: st.embedders.binding(&name),
+            name,
+        });
+    }
+    Ok(out)
+}
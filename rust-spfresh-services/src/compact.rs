@@ -0,0 +1,242 @@
+use crate::{
+    audit::{self, Action, Actor, IdRanges},
+    codec, config::{Config, DurabilityConfig}, dir_lock, moderation, open_state, patches, payload_of, purge, reembed,
+    spfresh_index, startup, VecIndex,
+};
+use anyhow::{Context, Result};
+use std::{
+    collections::BTreeSet,
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Where the rewritten files are built, at their paths relative to the data dir.
+const STAGING_DIR: &str = "compact";
+/// Written into the staging dir once everything there is synced: from then on the
+/// compaction is finished, on the next start if need be, instead of discarded.
+const DONE_FILE: &str = "DONE";
+/// Left in the data dir by a purge: the next start compacts before serving.
+const REQUEST_FILE: &str = "COMPACT_REQUESTED";
+/// Rebuilt in every index directory; the rest of it (embedder.json, vocab.txt) stays.
+const INDEX_FILES: [&str; 3] = ["reviews.index", "reviews.spfresh", "reviews.spfresh.hdr"];
+/// Only describe the old ids: the tombstones are all dropped, the moderation log is
+/// rewritten with the new ones, edits are written into the compacted metadata, and that is
+/// written as reviews.jsonl, to be sealed into blocks again on open.
+const OBSOLETE_FILES: [&str; 7] = ["tombstones.log", moderation::LOG_FILE, patches::LOG_FILE, purge::LOG_FILE, reembed::LOG_FILE, "reviews.zst", "reviews.zst.idx"];
+
+/// `compact [--dry-run]`: rewrites the metadata and every index without deleted and
+/// superseded reviews, so the survivors get new, dense ids. Runs offline; the data dir
+/// lock keeps a server from using it meanwhile.
+pub fn run(config: Config, args: &[String]) -> Result<()> {
+    let dry_run = match args {
+        [] => false,
+        [flag] if flag == "--dry-run" => true,
+        _ => anyhow::bail!("usage: compact [--dry-run]"),
+    };
+    let data_dir = std::env::current_dir()?.join(&config.data_dir);
+    anyhow::ensure!(data_dir.is_dir(), "no data dir at {}", data_dir.display());
+    let _dir_lock = dir_lock::acquire(&data_dir)?;
+    compact(config, &data_dir, dry_run, "cli", |msg| println!("{msg}"))
+}
+
+/// Runs the compaction a purge or /admin/compact asked for, if there is one, as startup's
+/// compaction stage; the server calls this before opening the data dir, whose lock it holds.
+pub fn run_requested(config: &Config, data_dir: &Path, progress: &startup::Progress) -> Result<()> {
+    if !data_dir.join(REQUEST_FILE).is_file() { return Ok(()); }
+    if config.replica.is_some() {
+        tracing::warn!("a compaction was requested, but replicas are not compacted; ignoring it");
+        return Ok(std::fs::remove_file(data_dir.join(REQUEST_FILE))?);
+    }
+    progress.stage(startup::Stage::Compaction);
+    tracing::info!("running the requested compaction");
+    compact(config.clone(), data_dir, false, "purge", |msg| tracing::info!("compact: {msg}"))
+}
+
+/// Asks for a compaction on the next start.
+pub fn request(data_dir: &Path) -> Result<()> {
+    write_synced(&data_dir.join(REQUEST_FILE), b"")
+}
+
+/// The compaction proper; the caller holds the data dir lock. `principal` is who the audit
+/// entry names; progress goes to `say`.
+fn compact(config: Config, data_dir: &Path, dry_run: bool, principal: &str, say: impl Fn(String)) -> Result<()> {
+    anyhow::ensure!(config.replica.is_none(), "a replica's ids are its leader's; compact the leader and re-seed the replica");
+    let data_dir = data_dir.to_path_buf();
+    let st = open_state(config, &data_dir, &Default::default())?;
+    let config = st.config.clone();
+
+    let staging = data_dir.join(STAGING_DIR);
+    let mut out = match dry_run {
+        true => None,
+        false => {
+            std::fs::create_dir_all(&staging)?;
+            Some(BufWriter::new(File::create(staging.join("reviews.jsonl"))?))
+        }
+    };
+    // New id of every old one; None for the reviews left out.
+    let mut map = Vec::new();
+    // Index payload of every kept review, by new id.
+    let mut payloads = Vec::new();
+    let (mut kept, mut unreadable) = (0, 0);
+    let total = st.meta.for_each_in(0..usize::MAX, |id, r| {
+        let Some(r) = r else {
+            unreadable += 1;
+            map.push(None);
+            return Ok(());
+        };
+        if !st.meta.is_live(id) {
+            map.push(None);
+            return Ok(());
+        }
+        if let Some(out) = &mut out { out.write_all(&codec::encode_line(&serde_json::to_vec(&r)?))?; }
+        payloads.push(payload_of(&r).map(<[u8]>::to_vec));
+        map.push(Some(kept));
+        kept += 1;
+        Ok(())
+    })?;
+    if out.is_some() && kept < total { st.meta.moderation.write_remapped(&staging.join(moderation::LOG_FILE), &map)?; }
+    drop(st);
+    let verb = if dry_run { "would be kept" } else { "kept" };
+    say(format!("{kept} of {total} reviews {verb} ({unreadable} unreadable lines dropped)"));
+    let Some(out) = out else { return Ok(()) };
+    out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+    if kept == total {
+        std::fs::remove_dir_all(&staging)?;
+        remove_request(&data_dir)?;
+        say("nothing to compact".into());
+        return Ok(());
+    }
+
+    for rel in index_dirs(&data_dir, &config)? {
+        let n = compact_index(&data_dir.join(&rel).join("reviews.index"), &staging.join(&rel), &map, &payloads, &config.durability)?;
+        say(format!("{}: {n} vectors", data_dir.join(&rel).display()));
+    }
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let map_file = format!("id-map-{secs}.tsv");
+    write_id_map(&staging.join(&map_file), &map)?;
+    write_synced(&staging.join(DONE_FILE), b"")?;
+    finish(&data_dir)?;
+    remove_request(&data_dir)?;
+
+    let actor = Actor { principal: principal.into(), request_id: uuid::Uuid::new_v4().to_string() };
+    let subject = format!("kept {kept} of {total}, id map {map_file}");
+    audit::AuditLog::open(&data_dir)?.record(&actor, Action::Compact, IdRanges::default(), Some(subject))?;
+    say(format!("old -> new ids in {}", data_dir.join(&map_file).display()));
+    Ok(())
+}
+
+fn remove_request(data_dir: &Path) -> Result<()> {
+    match std::fs::remove_file(data_dir.join(REQUEST_FILE)) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Completes a compaction that got as far as `DONE`, or discards one that did not. Runs
+/// before anything else in the data dir is opened.
+pub fn finish(data_dir: &Path) -> Result<()> {
+    let staging = data_dir.join(STAGING_DIR);
+    if !staging.is_dir() { return Ok(()); }
+    if !staging.join(DONE_FILE).is_file() {
+        tracing::warn!("discarding unfinished compaction in {}", staging.display());
+        return Ok(std::fs::remove_dir_all(&staging)?);
+    }
+    for name in OBSOLETE_FILES {
+        match std::fs::remove_file(data_dir.join(name)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+    }
+    move_into(&staging, data_dir)?;
+    std::fs::remove_file(staging.join(DONE_FILE))?;
+    std::fs::remove_dir_all(&staging)?;
+    tracing::info!("compacted files moved into {}", data_dir.display());
+    Ok(())
+}
+
+/// Moves every file under `from` but `DONE` to the same place under `to`.
+fn move_into(from: &Path, to: &Path) -> Result<()> {
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            std::fs::create_dir_all(&target)?;
+            move_into(&entry.path(), &target)?;
+        } else if entry.file_name() != DONE_FILE {
+            std::fs::rename(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Index directories sharing the metadata's ids, relative to the data dir: the data dir
+/// itself, every collection and `config`'s secondary indexes (shadow, fields).
+pub fn index_dirs(data_dir: &Path, config: &Config) -> Result<BTreeSet<PathBuf>> {
+    let mut dirs = BTreeSet::new();
+    if data_dir.join("reviews.index").is_file() { dirs.insert(PathBuf::new()); }
+    for entry in std::fs::read_dir(data_dir)? {
+        let entry = entry?;
+        if entry.file_name() != STAGING_DIR && entry.path().join("reviews.index").is_file() {
+            dirs.insert(PathBuf::from(entry.file_name()));
+        }
+    }
+    dirs.extend(config.secondary_dirs().into_iter()
+        .map(|(d, _)| d)
+        .filter(|d| data_dir.join(d).join("reviews.index").is_file()));
+    Ok(dirs)
+}
+
+/// Copies the vectors of kept ids from `mirror` into a fresh index in `to`, each with its
+/// review's payload from `payloads` (by new id). A mirror shorter than the metadata (a
+/// collection no longer written to, a lagging shadow or field index) keeps its shorter
+/// prefix; a vector failing its checksum becomes a zero placeholder.
+fn compact_index(mirror: &Path, to: &Path, map: &[Option<usize>], payloads: &[Option<Vec<u8>>], durability: &DurabilityConfig) -> Result<usize> {
+    let mut rdr = BufReader::new(File::open(mirror)?);
+    let mut head = [0u8; codec::HEADER_LEN as usize];
+    rdr.read_exact(&mut head)?;
+    let dim = codec::FileHeader::decode(codec::FileKind::Mirror, &head)
+        .with_context(|| format!("{}: not a vector mirror", mirror.display()))?
+        .dim as usize;
+    let index = spfresh_index::DefaultIndex::open(to, dim, durability)?;
+    let (mut rec, mut v, mut last, mut n) = (vec![0u8; codec::record_len(dim)], Vec::with_capacity(dim), None, 0);
+    for (id, new) in map.iter().enumerate() {
+        match rdr.read_exact(&mut rec) {
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            r => r?,
+        }
+        let Some(new) = *new else { continue };
+        v.clear();
+        if !codec::decode_record_into(&rec, dim, &mut v) {
+            tracing::warn!("{}: vector {id} fails its checksum; written as zeros", mirror.display());
+            v.resize(dim, 0.0);
+        }
+        last = Some(index.append_pending(&v, payloads[new].as_deref())?.1);
+        n += 1;
+    }
+    if let Some(c) = last { c.wait()?; }
+    drop(index);
+    // Whatever the configured durability, nothing is moved into place unsynced.
+    for name in INDEX_FILES { File::open(to.join(name))?.sync_all()?; }
+    Ok(n)
+}
+
+/// `old<TAB>new` per kept review, for translating ids held elsewhere (audit and feedback
+/// logs, client bookmarks).
+fn write_id_map(path: &Path, map: &[Option<usize>]) -> Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    writeln!(out, "old\tnew")?;
+    for (old, new) in map.iter().enumerate() {
+        if let Some(new) = new { writeln!(out, "{old}\t{new}")?; }
+    }
+    out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+    Ok(())
+}
+
+fn write_synced(path: &Path, bytes: &[u8]) -> Result<()> {
+    let mut f = File::create(path)?;
+    f.write_all(bytes)?;
+    f.sync_all()?;
+    Ok(())
+}
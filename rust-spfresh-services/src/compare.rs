@@ -0,0 +1,58 @@
+use crate::{blocking, negotiate::{Negotiated, Reply}, search_in, slow_log, ApiError, AppState, RankOpts};
+use axum::extract::State;
+use reviews_types::{CompareReq, CompareResp, GroupBy, ProductComparison, RatingSummary, ReviewStatus, SearchHit, SearchStats};
+use std::time::Instant;
+
+/// POST /compare — the `top_k` reviews of each of two products most relevant to a topic,
+/// with rating and sentiment summaries of those hits and of each product as a whole, for
+/// a side-by-side view. One scan ranks both products: hits are grouped by product, at
+/// most `top_k` each. Approved reviews only; 404 for a product without reviews.
+pub async fn compare(State(st): State<AppState>, Negotiated(req, fmt): Negotiated<CompareReq>) -> Result<Reply<CompareResp>, ApiError> {
+    if req.query.trim().is_empty() {
+        return Err(ApiError::bad_request("query must not be empty"));
+    }
+    if req.products[0] == req.products[1] {
+        return Err(ApiError::bad_request("compare two different products"));
+    }
+    let [a, b] = req.products.clone()
+        .map(|id| st.meta.attrs.product(&id).ok_or_else(|| ApiError::not_found(format!("product {id} not found"))));
+    let (a, b) = (a?, b?);
+    let k = st.config.search.top_k(req.top_k);
+    let started = Instant::now();
+    let resp = blocking(move || {
+        let mut stats = SearchStats::default();
+        let active = st.target(req.collection.as_deref())?;
+        let opts = RankOpts {
+            group: Some((GroupBy::ProductId, k)),
+            product_ids: req.products.to_vec(),
+            ..RankOpts::plain(&req.query, 2 * k)
+        };
+        let mut hits = search_in(&st.meta, &st.vcache, &active, &opts, &mut stats);
+        // Every review of a product matches, so the count collapsed into its best hit is
+        // just the rest of the product.
+        for hit in &mut hits { hit.collapsed = None; }
+        let elapsed = started.elapsed();
+        stats.total_ms = elapsed.as_secs_f64() * 1e3;
+        st.slow_log.record(elapsed, slow_log::SlowQuery {
+            endpoint: "/compare",
+            query: &req.query,
+            collection: req.collection.as_deref(),
+            top_k: k,
+            hits: hits.len(),
+            stages: &stats,
+        });
+        let [id_a, id_b] = req.products;
+        let (hits_a, hits_b) = hits.into_iter().partition(|h| h.review.product_id == id_a);
+        Ok(CompareResp { products: [side(&st, a, id_a, hits_a), side(&st, b, id_b, hits_b)] })
+    }).await?;
+    Ok(Reply(fmt, resp))
+}
+
+fn side(st: &AppState, product: u32, product_id: String, hits: Vec<SearchHit>) -> ProductComparison {
+    let mut ratings = Vec::new();
+    st.meta.attrs.each_of_product(product, ReviewStatus::Approved, |id, rating| {
+        if st.meta.is_live(id) { ratings.push(rating); }
+    });
+    let overall = RatingSummary::of(ratings);
+    ProductComparison { product_id, topic: RatingSummary::of(hits.iter().map(|h| h.review.review_rating)), hits, overall }
+}
@@ -0,0 +1,118 @@
+use crate::{attrs::Attr, blocking, ApiError, AppState};
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use parking_lot::RwLock;
+use reviews_types::{CompositionParams, CompositionResp, CountBucket, LangKey, PerProduct, ProductKey, RatingKey, Share};
+use std::collections::{BTreeMap, HashMap};
+
+const MAX_TOP: usize = 100;
+
+/// Counts of the live reviews by product, language and rating, kept in step with the
+/// metadata by `MetaStore` like the ratings timeline (added on append, taken away again on
+/// delete or replacement), so the corpus's make-up never costs a metadata read. Unreadable
+/// lines are left out.
+#[derive(Default)]
+pub struct Composition {
+    counts: RwLock<Counts>,
+}
+
+#[derive(Default)]
+struct Counts {
+    reviews: u64,
+    /// Interned product -> live reviews; products without any are removed.
+    products: HashMap<u32, u64>,
+    /// Interned language -> live reviews.
+    langs: HashMap<u32, u64>,
+    ratings: BTreeMap<i32, u64>,
+}
+
+fn bump(map: &mut HashMap<u32, u64>, key: u32, sign: i64) {
+    let n = map.entry(key).or_default();
+    *n = n.saturating_add_signed(sign);
+    if *n == 0 { map.remove(&key); }
+}
+
+impl Composition {
+    pub fn add(&self, attr: Attr) { self.apply(attr, 1); }
+
+    pub fn remove(&self, attr: Attr) { self.apply(attr, -1); }
+
+    fn apply(&self, attr: Attr, sign: i64) {
+        let Some(product) = attr.product else { return };
+        let mut c = self.counts.write();
+        c.reviews = c.reviews.saturating_add_signed(sign);
+        bump(&mut c.products, product, sign);
+        if let Some(lang) = attr.lang { bump(&mut c.langs, lang, sign); }
+        let n = c.ratings.entry(attr.rating).or_default();
+        *n = n.saturating_add_signed(sign);
+        if *n == 0 { c.ratings.remove(&attr.rating); }
+    }
+}
+
+/// GET /analytics/composition?top= — how the live reviews are spread over products,
+/// languages and ratings, from the in-memory `Composition`, to see how skewed the corpus
+/// is: the distinct products, the distribution of reviews per product (quantiles, Gini
+/// coefficient, power-of-two histogram) and its `top` largest products, and the language
+/// and rating mix. Every moderation status counts.
+pub async fn composition(State(st): State<AppState>, Query(p): Query<CompositionParams>) -> Result<Json<CompositionResp>, ApiError> {
+    if p.top > MAX_TOP { return Err(ApiError::bad_request(format!("top must be at most {MAX_TOP}"))); }
+    blocking(move || {
+        let (reviews, mut per_product, langs, ratings) = {
+            let c = st.meta.composition.counts.read();
+            let per_product: Vec<(u32, u64)> = c.products.iter().map(|(&p, &n)| (p, n)).collect();
+            let langs: Vec<(u32, u64)> = c.langs.iter().map(|(&l, &n)| (l, n)).collect();
+            (c.reviews, per_product, langs, c.ratings.iter().map(|(&r, &n)| (r, n)).collect::<Vec<_>>())
+        };
+        let share = |n: u64| if reviews > 0 { n as f64 / reviews as f64 } else { 0.0 };
+        per_product.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        let (product_names, lang_names) = (st.meta.attrs.product_names(), st.meta.attrs.lang_names());
+        let top_products = per_product.iter().take(p.top)
+            .map(|&(p, n)| Share { key: ProductKey { product_id: product_names[p as usize].clone() }, reviews: n, share: share(n) })
+            .collect();
+        let mut langs: Vec<_> = langs.into_iter()
+            .map(|(l, n)| Share { key: LangKey { lang: lang_names[l as usize].clone() }, reviews: n, share: share(n) })
+            .collect();
+        langs.sort_by(|a, b| b.reviews.cmp(&a.reviews).then_with(|| a.key.lang.cmp(&b.key.lang)));
+        let ratings = ratings.into_iter().map(|(r, n)| Share { key: RatingKey { rating: r }, reviews: n, share: share(n) }).collect();
+        let counts: Vec<u64> = per_product.iter().rev().map(|&(_, n)| n).collect();
+        Ok(Json(CompositionResp {
+            reviews,
+            products: counts.len(),
+            per_product: distribution(&counts),
+            top_products,
+            langs,
+            ratings,
+        }))
+    }).await
+}
+
+/// Summary of the review counts of every product, `counts` ascending.
+fn distribution(counts: &[u64]) -> Option<PerProduct> {
+    let (&min, &max) = (counts.first()?, counts.last()?);
+    let n = counts.len();
+    let total: u64 = counts.iter().sum();
+    let quantile = |q: f64| counts[((n - 1) as f64 * q).round() as usize];
+    // Mean absolute difference over twice the mean, from the sorted counts.
+    let weighted: f64 = counts.iter().enumerate().map(|(i, &c)| (2 * i + 1) as f64 * c as f64).sum();
+    let gini = weighted / (n as f64 * total as f64) - 1.0;
+    let mut histogram: Vec<CountBucket> = Vec::new();
+    for &c in counts {
+        let from = 1u64 << c.ilog2();
+        match histogram.last_mut() {
+            Some(b) if b.from == from => b.products += 1,
+            _ => histogram.push(CountBucket { from, to: from * 2 - 1, products: 1 }),
+        }
+    }
+    Some(PerProduct {
+        min,
+        median: quantile(0.5),
+        p90: quantile(0.9),
+        p99: quantile(0.99),
+        max,
+        mean: total as f64 / n as f64,
+        gini: gini.max(0.0),
+        histogram,
+    })
+}
@@ -0,0 +1,702 @@
+use anyhow::{Context, Result};
+use reviews_types::{FieldScoring, ReviewStatus};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, path::PathBuf};
+
+/// Service configuration, read from `config.toml` (or `$SPFRESH_CONFIG`).
+/// Every field has a default so the file is optional.
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub data_dir: PathBuf,
+    pub bind: String,
+    /// Addresses to listen on instead of `bind`: `host:port` or `unix:/path/to.sock`.
+    pub listen: Vec<String>,
+    /// Permissions of Unix sockets, e.g. `0o660` to let a proxy in the same group connect.
+    pub unix_socket_mode: Option<u32>,
+    /// Serve HTTPS on the TCP addresses instead of plain HTTP.
+    pub tls: Option<TlsConfig>,
+    pub embedder: EmbedderConfig,
+    /// Secondary embedder + index that mirrors every write, for A/B comparison.
+    pub shadow: Option<ShadowConfig>,
+    /// Separate title and body vectors, embedded with `embedder`, for field-aware scoring.
+    pub fields: Option<FieldsConfig>,
+    /// Several vectors per review, for re-ranking with late interaction (max-sim).
+    pub late_interaction: Option<LateInteractionConfig>,
+    /// Router mode: answer /search from these shard instances instead of the local index.
+    pub shards: Option<ShardsConfig>,
+    /// Read replica: follow this leader's write log and refuse writes.
+    pub replica: Option<ReplicaConfig>,
+    /// Chat model that answers POST /ask from retrieved reviews.
+    pub llm: Option<LlmConfig>,
+    pub limits: LimitsConfig,
+    pub search: SearchConfig,
+    pub metadata: MetadataConfig,
+    pub idf: IdfConfig,
+    pub moderation: ModerationConfig,
+    pub hf_import: HfImportConfig,
+    pub memory: MemoryConfig,
+    pub durability: DurabilityConfig,
+    pub slow_query: SlowQueryConfig,
+    pub drift: DriftConfig,
+    pub quotas: QuotasConfig,
+    pub cors: CorsConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            data_dir: PathBuf::from("data"),
+            bind: "0.0.0.0:8000".into(),
+            listen: Vec::new(),
+            unix_socket_mode: None,
+            tls: None,
+            embedder: EmbedderConfig::default(),
+            shadow: None,
+            fields: None,
+            late_interaction: None,
+            shards: None,
+            replica: None,
+            llm: None,
+            limits: LimitsConfig::default(),
+            search: SearchConfig::default(),
+            metadata: MetadataConfig::default(),
+            idf: IdfConfig::default(),
+            moderation: ModerationConfig::default(),
+            hf_import: HfImportConfig::default(),
+            memory: MemoryConfig::default(),
+            durability: DurabilityConfig::default(),
+            slow_query: SlowQueryConfig::default(),
+            drift: DriftConfig::default(),
+            quotas: QuotasConfig::default(),
+            cors: CorsConfig::default(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum EmbedderConfig {
+    /// Hashed term counts weighted by IDF. With `vocabulary`, every term gets a dimension
+    /// of its own (up to `dim` terms, recorded in the index's `vocab.txt`) instead of a
+    /// hash bucket, so scores can be explained term by term.
+    Tfidf {
+        dim: usize,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        vocabulary: bool,
+    },
+    /// BERT-style sentence-transformer run locally with candle (cargo feature `candle`).
+    /// `model_dir` holds `config.json`, `tokenizer.json` and `model.safetensors`; `dim` must
+    /// match the model's hidden size.
+    Candle {
+        model_dir: PathBuf,
+        dim: usize,
+        #[serde(default = "default_max_tokens")]
+        max_tokens: usize,
+    },
+    /// ONNX model run by fastembed (cargo feature `fastembed`). `model` is a fastembed model
+    /// code such as `sentence-transformers/all-MiniLM-L6-v2`, downloaded into `cache_dir`
+    /// on first use; `dim` must match the model.
+    Fastembed {
+        model: String,
+        dim: usize,
+        #[serde(default)]
+        cache_dir: Option<PathBuf>,
+        /// Texts per ONNX run when embedding a batch; fastembed's default when unset.
+        #[serde(default)]
+        batch_size: Option<usize>,
+    },
+    /// OpenAI-compatible `/embeddings` endpoint (cargo feature `remote-embedder`). The key is
+    /// read from the environment variable named by `api_key_env`, so it never ends up in
+    /// `embedder.json` or the registry.
+    Remote {
+        /// Full endpoint URL, e.g. `https://api.openai.com/v1/embeddings`.
+        url: String,
+        model: String,
+        dim: usize,
+        #[serde(default = "default_api_key_env")]
+        api_key_env: String,
+        #[serde(default = "default_remote_batch")]
+        batch_size: usize,
+        #[serde(default = "default_remote_timeout_ms")]
+        timeout_ms: u64,
+        /// Retries of a failed request, at most `embed_remote::MAX_RETRIES`; the wait doubles
+        /// from 200 ms up to 30 s.
+        #[serde(default = "default_remote_retries")]
+        max_retries: u32,
+    },
+}
+
+fn default_api_key_env() -> String { "OPENAI_API_KEY".into() }
+fn default_remote_batch() -> usize { 64 }
+fn default_remote_timeout_ms() -> u64 { 30_000 }
+fn default_remote_retries() -> u32 { 3 }
+
+fn default_max_tokens() -> usize { 256 }
+
+impl Default for EmbedderConfig {
+    fn default() -> Self { EmbedderConfig::Tfidf { dim: 4096, vocabulary: false } }
+}
+
+impl EmbedderConfig {
+    pub fn dim(&self) -> usize {
+        match self {
+            EmbedderConfig::Tfidf { dim, .. }
+            | EmbedderConfig::Candle { dim, .. }
+            | EmbedderConfig::Fastembed { dim, .. }
+            | EmbedderConfig::Remote { dim, .. } => *dim,
+        }
+    }
+
+    /// Registry name given to this configuration when nobody registered it by name.
+    pub fn default_name(&self) -> String {
+        match self {
+            EmbedderConfig::Tfidf { dim, vocabulary: false } => format!("tfidf-{dim}"),
+            EmbedderConfig::Tfidf { dim, vocabulary: true } => format!("tfidf-vocab-{dim}"),
+            EmbedderConfig::Candle { model_dir, dim, .. } => {
+                let model = model_dir.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+                format!("candle-{model}-{dim}")
+            }
+            EmbedderConfig::Fastembed { model, dim, .. } => {
+                format!("fastembed-{}-{dim}", model.rsplit('/').next().unwrap_or(model))
+            }
+            EmbedderConfig::Remote { model, dim, .. } => format!("remote-{model}-{dim}"),
+        }
+    }
+}
+
+/// Request body caps in bytes (over the cap: 413), timeouts and the global concurrency cap.
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitsConfig {
+    pub search_body_bytes: usize,
+    pub insert_body_bytes: usize,
+    pub bulk_body_bytes: usize,
+    /// Longest single NDJSON line accepted by the streaming import.
+    pub import_line_bytes: usize,
+    /// Requests beyond this many in flight are shed with 429 instead of queued.
+    pub max_in_flight: usize,
+    /// Per-route deadlines; a request exceeding them gets 503.
+    pub search_timeout_ms: u64,
+    pub insert_timeout_ms: u64,
+    pub bulk_timeout_ms: u64,
+    /// Writes queued for the writer (see `writer`) before further ones wait to queue.
+    pub write_queue: usize,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            search_body_bytes: 64 * 1024,
+            insert_body_bytes: 1024 * 1024,
+            bulk_body_bytes: 16 * 1024 * 1024,
+            import_line_bytes: 1024 * 1024,
+            max_in_flight: 256,
+            search_timeout_ms: 5_000,
+            insert_timeout_ms: 10_000,
+            bulk_timeout_ms: 120_000,
+            write_queue: 64,
+        }
+    }
+}
+
+/// How many hits a search returns when `top_k` is left out, and the most it may ask for;
+/// a larger `top_k` is lowered to the cap.
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct SearchConfig {
+    pub default_k: usize,
+    /// Cap for /search and GraphQL `search`.
+    pub max_k: usize,
+    /// Cap for /search/stream, which never holds all hits at once.
+    pub max_stream_k: usize,
+    /// Binary sketches: a Hamming-distance first pass picks the vectors scored exactly.
+    pub sketch: Option<SketchConfig>,
+    /// Plain searches take their hits from the spfresh index instead of scanning the mirror.
+    pub ann: bool,
+    /// With `ann`, hits taken from the spfresh index per hit asked for when a score formula
+    /// or recency decay re-ranks them.
+    pub rerank_overfetch: usize,
+    /// With `ann`, hits taken from the spfresh index per hit asked for when `group_by` keeps
+    /// only the best of each product.
+    pub diversify_overfetch: usize,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        Self {
+            default_k: 5, max_k: 100, max_stream_k: 10_000, sketch: None, ann: false,
+            rerank_overfetch: 5, diversify_overfetch: 10,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct SketchConfig {
+    /// Random projections whose signs make up a sketch; a multiple of 64.
+    pub bits: usize,
+    /// Vectors closest by Hamming distance that get their exact cosine (at least `top_k`).
+    pub candidates: usize,
+}
+
+impl Default for SketchConfig {
+    fn default() -> Self {
+        Self { bits: 256, candidates: 1000 }
+    }
+}
+
+impl SearchConfig {
+    /// The number of hits to return for a request's `top_k`.
+    pub fn top_k(&self, requested: Option<usize>) -> usize {
+        requested.unwrap_or(self.default_k).min(self.max_k)
+    }
+
+    /// Hits to take from the spfresh index for `k` asked for, re-ranked and/or grouped.
+    pub fn ann_candidates(&self, k: usize, reranked: bool, grouped: bool) -> usize {
+        let overfetch = [(reranked, self.rerank_overfetch), (grouped, self.diversify_overfetch)]
+            .into_iter()
+            .filter_map(|(on, n)| on.then_some(n))
+            .max()
+            .unwrap_or(1);
+        k.saturating_mul(overfetch)
+    }
+
+    pub fn stream_top_k(&self, requested: Option<usize>) -> usize {
+        requested.unwrap_or(self.default_k).min(self.max_stream_k)
+    }
+}
+
+/// Storage of review metadata. With `zstd`, reviews.jsonl only keeps the newest reviews:
+/// every `block_lines` of them are compressed into a block of reviews.zst. Blocks already
+/// written stay readable when compression is turned off again.
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetadataConfig {
+    pub compression: Compression,
+    pub block_lines: usize,
+    /// zstd level, 1 (fastest) to 22.
+    pub level: i32,
+}
+
+impl Default for MetadataConfig {
+    fn default() -> Self {
+        Self { compression: Compression::None, block_lines: 256, level: 3 }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Compression {
+    None,
+    Zstd,
+}
+
+/// Recomputing the TF-IDF document frequencies from the live reviews (see `idf`).
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct IdfConfig {
+    /// Recompute on startup and then this often; 0 leaves it to POST /admin/idf.
+    pub refresh_interval_secs: u64,
+    /// A review counts half as much per this many days of age; 0 counts every review fully.
+    pub half_life_days: f64,
+}
+
+impl Default for IdfConfig {
+    fn default() -> Self {
+        Self { refresh_interval_secs: 0, half_life_days: 0.0 }
+    }
+}
+
+/// Checks run on every inserted review (see `moderation`); nothing is checked by default.
+#[derive(Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct ModerationConfig {
+    /// Case-insensitive regular expressions matched against title and body.
+    pub patterns: Vec<String>,
+    /// Files of blocked words or phrases, one per line (`#` starts a comment).
+    pub word_lists: Vec<PathBuf>,
+    /// What a match does to the review.
+    pub action: ModerationAction,
+    /// Status of reviews the checks pass: `approved`, or `pending` to have every review
+    /// approved by hand before it is searched.
+    pub default_status: ReviewStatus,
+    /// External classifier (cargo feature `moderation-classifier`), asked about reviews
+    /// the local lists let through.
+    pub classifier: Option<ClassifierConfig>,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ModerationAction {
+    /// Searchable as usual, but listed for review.
+    Flag,
+    /// Written as `pending`, so kept out of search until approved.
+    #[default]
+    Quarantine,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+#[cfg_attr(not(feature = "moderation-classifier"), allow(dead_code))]
+pub struct ClassifierConfig {
+    /// Receives `{"text": ...}` and answers `{"flagged": bool, "reason": "..."}`.
+    pub url: String,
+    #[serde(default = "default_classifier_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+fn default_classifier_timeout_ms() -> u64 { 2_000 }
+
+/// `import-hf` (cargo feature `hf-import`): where datasets come from and which of their
+/// columns fill which review fields.
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+#[cfg_attr(not(feature = "hf-import"), allow(dead_code))]
+pub struct HfImportConfig {
+    /// The hub, or anything serving its API.
+    pub endpoint: String,
+    /// Environment variable holding an access token, for gated and private datasets.
+    pub token_env: String,
+    /// Review field -> dataset column; `--map field=column` overrides them per run. An
+    /// unmapped `product_id` is the dataset's name, an unmapped `review_rating` 0.
+    pub fields: BTreeMap<String, String>,
+    /// Added to the rating column, e.g. 1 for a dataset labelling 1-5 stars as 0-4.
+    pub rating_offset: f64,
+    /// Connecting, and between reads of a response.
+    pub timeout_ms: u64,
+}
+
+impl Default for HfImportConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: "https://huggingface.co".into(),
+            token_env: "HF_TOKEN".into(),
+            fields: BTreeMap::from([("review_body".to_string(), "text".to_string())]),
+            rating_offset: 0.0,
+            timeout_ms: 30_000,
+        }
+    }
+}
+
+/// RAM budget for mirror vectors cached by /search; segments beyond it are read via mmap.
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct MemoryConfig {
+    pub vector_cache_bytes: usize,
+    /// Vectors per cached segment, the unit of eviction.
+    pub segment_vectors: usize,
+}
+
+impl Default for MemoryConfig {
+    fn default() -> Self {
+        Self { vector_cache_bytes: 256 * 1024 * 1024, segment_vectors: 4096 }
+    }
+}
+
+/// Mirror appends are group-committed: those arriving within the window share one write.
+/// `sync` decides which writes are fsynced, for the mirror and the spfresh index alike.
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct DurabilityConfig {
+    pub sync: SyncPolicy,
+    pub group_commit_window_us: u64,
+    /// A batch is flushed early once it holds this many bytes.
+    pub group_commit_max_bytes: usize,
+    /// How often the server's background flusher syncs what `every_n` left unsynced
+    /// (0: not at all); under `interval`, its `ms` is used instead.
+    pub flush_interval_ms: u64,
+}
+
+impl Default for DurabilityConfig {
+    fn default() -> Self {
+        Self {
+            sync: SyncPolicy::default(),
+            group_commit_window_us: 2_000,
+            group_commit_max_bytes: 8 * 1024 * 1024,
+            flush_interval_ms: 1_000,
+        }
+    }
+}
+
+/// When appended data is forced to disk. Anything weaker than `every_write` can lose the
+/// last acknowledged writes on power loss (not on a process crash).
+#[derive(Deserialize, Clone, Debug, PartialEq, Default)]
+#[serde(tag = "mode", rename_all = "snake_case", deny_unknown_fields)]
+pub enum SyncPolicy {
+    /// fsync after every group-committed batch.
+    #[default]
+    EveryWrite,
+    /// fsync once at least `n` appends are unsynced.
+    EveryN { n: usize },
+    /// fsync only from the background flusher, every `ms` milliseconds while there are
+    /// unsynced appends; writes never wait for it.
+    Interval { ms: u64 },
+    /// Never fsync; the OS writes back on its own schedule. For benchmarks.
+    NoSync,
+}
+
+/// Searches taking at least `threshold_ms` (0 disables) go to a size-rotated JSON-lines file.
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct SlowQueryConfig {
+    pub threshold_ms: u64,
+    /// Relative to `data_dir`.
+    pub path: PathBuf,
+    pub max_bytes: u64,
+    /// Rotated files kept next to the live one.
+    pub keep: usize,
+}
+
+impl Default for SlowQueryConfig {
+    fn default() -> Self {
+        Self { threshold_ms: 1_000, path: PathBuf::from("slow_queries.log"), max_bytes: 10 * 1024 * 1024, keep: 5 }
+    }
+}
+
+/// When the embedding drift statistics (see `drift`) raise an alert.
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct DriftConfig {
+    /// 1 - cosine between the baseline and the last window's centroids.
+    pub alert_shift: f64,
+    /// Share of tokens in the last window that the index has not seen.
+    pub alert_oov_rate: f64,
+    /// Share of the embedder's dimensions in use (TF-IDF).
+    pub alert_dims_used: f64,
+}
+
+impl Default for DriftConfig {
+    fn default() -> Self {
+        Self { alert_shift: 0.25, alert_oov_rate: 0.3, alert_dims_used: 0.9 }
+    }
+}
+
+/// Cross-origin access for browser clients served from another origin. Nothing is
+/// allowed by default; `allow_any` opens everything up and is meant for development.
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct CorsConfig {
+    /// Any origin, method and header. Cannot be combined with `allow_credentials`.
+    pub allow_any: bool,
+    /// Exact origins, e.g. `https://admin.example.com`.
+    pub allow_origins: Vec<String>,
+    pub allow_methods: Vec<String>,
+    pub allow_headers: Vec<String>,
+    /// Let browsers send cookies and `Authorization` cross-origin.
+    pub allow_credentials: bool,
+    /// How long browsers may cache a preflight response; None leaves it to the browser.
+    pub max_age_secs: Option<u64>,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            allow_any: false,
+            allow_origins: Vec::new(),
+            allow_methods: ["GET", "POST", "PATCH", "DELETE"].map(String::from).to_vec(),
+            allow_headers: ["content-type", "accept", "x-principal", "x-request-id", "x-api-version"].map(String::from).to_vec(),
+            allow_credentials: false,
+            max_age_secs: None,
+        }
+    }
+}
+
+/// Limits per tenant (the `x-principal` of a request). Unset limits are unlimited.
+#[derive(Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct QuotasConfig {
+    /// Applies to every tenant without an entry in `tenants`.
+    pub default: Quota,
+    /// Per-tenant overrides; limits left unset fall back to `default`.
+    pub tenants: BTreeMap<String, Quota>,
+}
+
+impl QuotasConfig {
+    pub fn for_tenant(&self, tenant: &str) -> Quota {
+        match self.tenants.get(tenant) {
+            Some(q) => Quota {
+                max_documents: q.max_documents.or(self.default.max_documents),
+                max_bytes: q.max_bytes.or(self.default.max_bytes),
+                requests_per_minute: q.requests_per_minute.or(self.default.requests_per_minute),
+            },
+            None => self.default.clone(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Quota {
+    /// Reviews the tenant may have written.
+    pub max_documents: Option<u64>,
+    /// Metadata plus mirror bytes of those reviews.
+    pub max_bytes: Option<u64>,
+    pub requests_per_minute: Option<u64>,
+}
+
+/// PEM files for HTTPS (cargo feature `tls`).
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+#[cfg_attr(not(feature = "tls"), allow(dead_code))]
+pub struct TlsConfig {
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+    /// How often the files are checked for a renewed certificate; 0 disables reloading.
+    #[serde(default = "default_tls_reload_interval_secs")]
+    pub reload_interval_secs: u64,
+}
+
+fn default_tls_reload_interval_secs() -> u64 { 60 }
+
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ShadowConfig {
+    /// Index directory, relative to `data_dir`.
+    #[serde(default = "default_shadow_dir")]
+    pub dir: PathBuf,
+    pub embedder: EmbedderConfig,
+}
+
+fn default_shadow_dir() -> PathBuf { PathBuf::from("shadow") }
+
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct FieldsConfig {
+    /// Holds the `title` and `body` index directories; relative to `data_dir`.
+    #[serde(default = "default_fields_dir")]
+    pub dir: PathBuf,
+    /// How searches that do not say score the two fields.
+    #[serde(default)]
+    pub scoring: FieldScoring,
+}
+
+fn default_fields_dir() -> PathBuf { PathBuf::from("fields") }
+
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct LateInteractionConfig {
+    /// Holds the `vectors` index directory; relative to `data_dir`.
+    #[serde(default = "default_late_interaction_dir")]
+    pub dir: PathBuf,
+    pub embedder: EmbedderConfig,
+    /// Vectors kept per review, each embedding one of as many equal runs of its words.
+    /// Every review takes `vectors * dim` floats, however short it is.
+    #[serde(default = "default_late_interaction_vectors")]
+    pub vectors: usize,
+    /// Hits re-ranked per hit asked for: the first `top_k * overfetch` by the single vector.
+    #[serde(default = "default_late_interaction_overfetch")]
+    pub overfetch: usize,
+}
+
+fn default_late_interaction_dir() -> PathBuf { PathBuf::from("late_interaction") }
+fn default_late_interaction_vectors() -> usize { 8 }
+fn default_late_interaction_overfetch() -> usize { 10 }
+
+impl LateInteractionConfig {
+    pub fn index_dir(&self) -> PathBuf { self.dir.join("vectors") }
+    /// Floats per review in the index: all of its vectors, one after the other.
+    pub fn record_dim(&self) -> usize { self.vectors * self.embedder.dim() }
+}
+
+impl FieldsConfig {
+    pub fn title_dir(&self) -> PathBuf { self.dir.join("title") }
+    pub fn body_dir(&self) -> PathBuf { self.dir.join("body") }
+}
+
+/// Shard instances a router fans /search out to (cargo feature `shards`).
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+#[cfg_attr(not(feature = "shards"), allow(dead_code))]
+pub struct ShardsConfig {
+    /// Base URLs, e.g. `http://10.0.0.5:8000`.
+    pub urls: Vec<String>,
+    /// Per-shard request deadline.
+    #[serde(default = "default_shard_timeout_ms")]
+    pub timeout_ms: u64,
+    /// Answer from the shards that responded when some fail (listed in `failed_shards`),
+    /// rather than failing the search with 502.
+    #[serde(default = "default_allow_partial")]
+    pub allow_partial: bool,
+}
+
+fn default_shard_timeout_ms() -> u64 { 2_000 }
+fn default_allow_partial() -> bool { true }
+
+/// Leader a read replica follows (cargo feature `replica`).
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+#[cfg_attr(not(feature = "replica"), allow(dead_code))]
+pub struct ReplicaConfig {
+    /// Base URL of the leader, e.g. `http://10.0.0.5:8000`.
+    pub leader: String,
+    /// Pause between polls once caught up.
+    #[serde(default = "default_replica_poll_ms")]
+    pub poll_ms: u64,
+    /// Reviews requested per poll.
+    #[serde(default = "default_replica_batch")]
+    pub batch: usize,
+    #[serde(default = "default_replica_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+fn default_replica_poll_ms() -> u64 { 1_000 }
+fn default_replica_batch() -> usize { 500 }
+fn default_replica_timeout_ms() -> u64 { 5_000 }
+
+/// OpenAI-compatible `/chat/completions` endpoint behind POST /ask (cargo feature `llm`).
+/// The key is read from the environment variable named by `api_key_env`.
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+#[cfg_attr(not(feature = "llm"), allow(dead_code))]
+pub struct LlmConfig {
+    /// Full endpoint URL, e.g. `https://api.openai.com/v1/chat/completions`.
+    pub url: String,
+    pub model: String,
+    #[serde(default = "default_api_key_env")]
+    pub api_key_env: String,
+    /// Reviews retrieved for a question unless it asks for `top_k`; capped by `[search] max_k`.
+    #[serde(default = "default_llm_top_k")]
+    pub top_k: usize,
+    /// Characters of each review put in the prompt.
+    #[serde(default = "default_llm_review_chars")]
+    pub review_chars: usize,
+    #[serde(default = "default_llm_max_tokens")]
+    pub max_tokens: u32,
+    #[serde(default = "default_llm_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+fn default_llm_top_k() -> usize { 8 }
+fn default_llm_review_chars() -> usize { 1_000 }
+fn default_llm_max_tokens() -> u32 { 512 }
+fn default_llm_timeout_ms() -> u64 { 60_000 }
+
+impl Config {
+    pub fn path() -> PathBuf {
+        std::env::var_os("SPFRESH_CONFIG").map(PathBuf::from).unwrap_or_else(|| "config.toml".into())
+    }
+
+    pub fn load() -> Result<Self> {
+        let path = Self::path();
+        let cfg = match std::fs::read_to_string(&path) {
+            Ok(s) => toml::from_str(&s).with_context(|| format!("parse {}", path.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Config::default(),
+            Err(e) => return Err(e).with_context(|| format!("read {}", path.display())),
+        };
+        Ok(cfg)
+    }
+
+    /// Index directories, relative to the data dir, that follow the metadata's ids but may
+    /// lag it (they are back-filled on startup), with their dimensions: the shadow index, the
+    /// per-field ones and the late interaction one.
+    pub fn secondary_dirs(&self) -> Vec<(PathBuf, usize)> {
+        let mut dirs: Vec<_> = self.shadow.iter().map(|sc| (sc.dir.clone(), sc.embedder.dim())).collect();
+        if let Some(fc) = &self.fields {
+            dirs.push((fc.title_dir(), self.embedder.dim()));
+            dirs.push((fc.body_dir(), self.embedder.dim()));
+        }
+        dirs.extend(self.late_interaction.iter().map(|lc| (lc.index_dir(), lc.record_dim())));
+        dirs
+    }
+}
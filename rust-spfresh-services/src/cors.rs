@@ -0,0 +1,35 @@
+use crate::config::CorsConfig;
+use anyhow::{Context, Result};
+use axum::http::{HeaderName, HeaderValue, Method};
+use std::time::Duration;
+use tower_http::cors::{Any, CorsLayer};
+
+/// The CORS layer `[cors]` describes. Every entry is parsed here, at startup, so a typo
+/// fails the boot instead of silently blocking browsers.
+pub fn layer(cfg: &CorsConfig) -> Result<CorsLayer> {
+    if cfg.allow_any {
+        // Browsers reject a wildcard with credentials, and tower-http panics on it.
+        anyhow::ensure!(!cfg.allow_credentials, "[cors] allow_any cannot be combined with allow_credentials");
+        tracing::warn!("CORS allows any origin ([cors] allow_any); do not use this in production");
+        return Ok(CorsLayer::new().allow_origin(Any).allow_methods(Any).allow_headers(Any));
+    }
+    let origins = cfg.allow_origins.iter()
+        .map(|o| {
+            anyhow::ensure!(o != "*", "[cors] allow_origins cannot contain \"*\"; set allow_any = true instead");
+            HeaderValue::from_str(o.trim_end_matches('/')).with_context(|| format!("[cors] invalid origin '{o}'"))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let methods = cfg.allow_methods.iter()
+        .map(|m| Method::from_bytes(m.to_ascii_uppercase().as_bytes()).with_context(|| format!("[cors] invalid method '{m}'")))
+        .collect::<Result<Vec<_>>>()?;
+    let headers = cfg.allow_headers.iter()
+        .map(|h| HeaderName::from_bytes(h.as_bytes()).with_context(|| format!("[cors] invalid header '{h}'")))
+        .collect::<Result<Vec<_>>>()?;
+    let mut layer = CorsLayer::new()
+        .allow_origin(origins)
+        .allow_methods(methods)
+        .allow_headers(headers)
+        .allow_credentials(cfg.allow_credentials);
+    if let Some(secs) = cfg.max_age_secs { layer = layer.max_age(Duration::from_secs(secs)); }
+    Ok(layer)
+}
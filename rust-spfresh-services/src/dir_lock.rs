@@ -0,0 +1,33 @@
+use anyhow::{bail, Result};
+use fs2::FileExt;
+use std::{
+    fs::{File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+const LOCK_FILE: &str = "LOCK";
+
+/// Exclusive lock on `<data dir>/LOCK`, held until dropped. Two processes appending to the
+/// same files would interleave records, so a second instance refuses to start instead.
+/// The lock is advisory (flock/LockFileEx) and released by the OS if the process dies,
+/// so a stale LOCK file left behind by a crash never blocks a restart.
+pub struct DirLock {
+    _file: File,
+}
+
+pub fn acquire(data_dir: &Path) -> Result<DirLock> {
+    let path = data_dir.join(LOCK_FILE);
+    let mut file = OpenOptions::new().create(true).truncate(false).read(true).write(true).open(&path)?;
+    if file.try_lock_exclusive().is_err() {
+        let mut holder = String::new();
+        let _ = file.read_to_string(&mut holder);
+        let pid = if holder.trim().is_empty() { "unknown" } else { holder.trim() };
+        bail!("data dir {} is in use by another process (pid {pid}); refusing to start", data_dir.display());
+    }
+    file.set_len(0)?;
+    file.seek(SeekFrom::Start(0))?;
+    write!(file, "{}", std::process::id())?;
+    file.sync_all()?;
+    Ok(DirLock { _file: file })
+}
@@ -0,0 +1,221 @@
+use crate::{config::DriftConfig, ApiError, AppState};
+use axum::{extract::State, http::StatusCode, Json};
+use parking_lot::Mutex;
+use reviews_types::{DriftResp, DriftWindow, SideReport};
+use serde::{Deserialize, Serialize};
+use std::{
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+/// Vectors per window: statistics are taken over this many queries, or documents, at a
+/// time.
+const WINDOW: usize = 1_000;
+/// Holds the baselines, next to the index.
+const BASELINE_FILE: &str = "drift.json";
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Kind {
+    Query,
+    Document,
+}
+
+/// Tokens an embedder has seen, and how many of them no indexed document had used (for
+/// a query) or no earlier one (for a document). Running totals; `Drift` takes the
+/// difference per window.
+#[derive(Default)]
+pub struct TokenCounter { tokens: AtomicU64, unseen: AtomicU64 }
+
+#[derive(Clone, Copy, Default)]
+pub struct TokenCounts { tokens: u64, unseen: u64 }
+
+impl TokenCounter {
+    pub fn add(&self, tokens: u64, unseen: u64) {
+        self.tokens.fetch_add(tokens, Ordering::Relaxed);
+        self.unseen.fetch_add(unseen, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> TokenCounts {
+        TokenCounts { tokens: self.tokens.load(Ordering::Relaxed), unseen: self.unseen.load(Ordering::Relaxed) }
+    }
+}
+
+/// The window being filled.
+#[derive(Default)]
+struct Acc { count: usize, sum: Vec<f32>, norms: f64, zeros: usize }
+
+#[derive(Default)]
+struct Side {
+    /// The first full window, kept until reset, to compare later ones with.
+    baseline: Option<DriftWindow>,
+    /// The last full window.
+    recent: Option<DriftWindow>,
+    acc: Acc,
+    /// Token counts when `acc` started.
+    tokens_at_start: TokenCounts,
+    seen: u64,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Baselines { query: Option<DriftWindow>, document: Option<DriftWindow> }
+
+/// Distribution statistics of the query and document vectors an embedder produces, by
+/// windows of `WINDOW` vectors: mean norm, share of zero vectors, share of unseen tokens,
+/// and how far the centroid of the last window has moved from that of a baseline window
+/// (the first one, until reset). Baselines are kept in the index directory, so they
+/// survive restarts.
+pub struct Drift {
+    path: PathBuf,
+    query: Mutex<Side>,
+    document: Mutex<Side>,
+    /// What `path` holds. Taken after a side's lock, never before.
+    saved: Mutex<Baselines>,
+}
+
+impl Drift {
+    pub fn open(dir: &Path) -> Self {
+        let path = dir.join(BASELINE_FILE);
+        let saved: Baselines = match std::fs::read(&path) {
+            Ok(b) => serde_json::from_slice(&b).unwrap_or_else(|e| {
+                tracing::warn!("{}: {e}; drift baselines start over", path.display());
+                Baselines::default()
+            }),
+            Err(_) => Baselines::default(),
+        };
+        let side = |baseline: &Option<DriftWindow>| Mutex::new(Side { baseline: baseline.clone(), ..Default::default() });
+        Self { query: side(&saved.query), document: side(&saved.document), saved: Mutex::new(saved), path }
+    }
+
+    fn side(&self, kind: Kind) -> &Mutex<Side> {
+        match kind {
+            Kind::Query => &self.query,
+            Kind::Document => &self.document,
+        }
+    }
+
+    /// Adds vectors to the current window of `kind`; `tokens` reads the embedder's running
+    /// token counts when a window fills up.
+    pub fn record(&self, kind: Kind, vectors: &[Vec<f32>], tokens: impl Fn() -> Option<TokenCounts>) {
+        let mut side = self.side(kind).lock();
+        for v in vectors {
+            let acc = &mut side.acc;
+            if acc.sum.len() != v.len() { *acc = Acc { sum: vec![0.0; v.len()], ..Default::default() }; }
+            for (s, x) in acc.sum.iter_mut().zip(v) { *s += x; }
+            let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt() as f64;
+            acc.norms += norm;
+            if norm == 0.0 { acc.zeros += 1; }
+            acc.count += 1;
+            side.seen += 1;
+            if side.acc.count >= WINDOW { self.close(kind, &mut side, tokens()); }
+        }
+    }
+
+    fn close(&self, kind: Kind, side: &mut Side, tokens: Option<TokenCounts>) {
+        let acc = std::mem::take(&mut side.acc);
+        let n = acc.count as f64;
+        let oov_rate = tokens.and_then(|now| {
+            let (t, u) = (now.tokens - side.tokens_at_start.tokens, now.unseen - side.tokens_at_start.unseen);
+            side.tokens_at_start = now;
+            (t > 0).then(|| u as f64 / t as f64)
+        });
+        let window = DriftWindow {
+            closed_at: crate::now_secs(),
+            mean_norm: acc.norms / n,
+            zero_rate: acc.zeros as f64 / n,
+            oov_rate,
+            centroid: acc.sum.iter().map(|s| (*s as f64 / n) as f32).collect(),
+        };
+        if side.baseline.is_none() {
+            side.baseline = Some(window.clone());
+            let mut saved = self.saved.lock();
+            match kind {
+                Kind::Query => saved.query = side.baseline.clone(),
+                Kind::Document => saved.document = side.baseline.clone(),
+            }
+            if let Err(e) = self.save(&saved) { tracing::warn!("saving drift baselines failed: {e:#}"); }
+        }
+        side.recent = Some(window);
+    }
+
+    fn save(&self, file: &Baselines) -> anyhow::Result<()> {
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec(file)?)?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+
+    /// Drops both baselines; the next full windows become the new ones.
+    pub fn reset(&self) -> anyhow::Result<()> {
+        let (mut q, mut d) = (self.query.lock(), self.document.lock());
+        q.baseline = None;
+        d.baseline = None;
+        *self.saved.lock() = Baselines::default();
+        match std::fs::remove_file(&self.path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn report(&self, kind: Kind, cfg: &DriftConfig) -> SideReport {
+        let side = self.side(kind).lock();
+        let centroid_shift = side.baseline.as_ref().zip(side.recent.as_ref())
+            .filter(|(b, r)| b.centroid.len() == r.centroid.len())
+            .map(|(b, r)| 1.0 - cosine(&b.centroid, &r.centroid));
+        let mut alerts = Vec::new();
+        if let Some(s) = centroid_shift.filter(|&s| s > cfg.alert_shift) {
+            alerts.push(format!("centroid shift {s:.3} above {}", cfg.alert_shift));
+        }
+        if let Some(r) = side.recent.as_ref().and_then(|w| w.oov_rate).filter(|&r| r > cfg.alert_oov_rate) {
+            alerts.push(format!("oov rate {r:.3} above {}", cfg.alert_oov_rate));
+        }
+        let strip = |w: &DriftWindow| DriftWindow { centroid: Vec::new(), ..w.clone() };
+        SideReport {
+            vectors: side.seen,
+            window: WINDOW,
+            filling: side.acc.count,
+            baseline: side.baseline.as_ref().map(strip),
+            recent: side.recent.as_ref().map(strip),
+            centroid_shift,
+            alerts,
+        }
+    }
+}
+
+/// Centroids are means, not unit vectors, so this one divides by the norms.
+fn cosine(a: &[f32], b: &[f32]) -> f64 {
+    let dot: f64 = a.iter().zip(b).map(|(x, y)| *x as f64 * *y as f64).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| *x as f64 * *x as f64).sum::<f64>().sqrt();
+    let n = norm(a) * norm(b);
+    if n == 0.0 { 0.0 } else { dot / n }
+}
+
+/// Drift report of the active collection's embedder; None for an embedder without one.
+pub fn report(st: &AppState) -> Option<DriftResp> {
+    let active = st.active.read().clone();
+    let drift = active.embedder.drift()?;
+    let cfg = &st.config.drift;
+    let dim = active.vindex.dim();
+    let dims_used = active.embedder.dims_used().map(|n| n as f64 / dim.max(1) as f64);
+    let (query, document) = (drift.report(Kind::Query, cfg), drift.report(Kind::Document, cfg));
+    let mut alerts: Vec<String> = query.alerts.iter().map(|a| format!("query {a}"))
+        .chain(document.alerts.iter().map(|a| format!("document {a}")))
+        .collect();
+    if let Some(u) = dims_used.filter(|&u| u > cfg.alert_dims_used) {
+        alerts.push(format!("dims used {u:.3} above {}", cfg.alert_dims_used));
+    }
+    Some(DriftResp { dim, dims_used, query, document, alerts })
+}
+
+/// GET /admin/drift — embedding drift of the active collection (see `Drift`), with the
+/// `[drift]` thresholds it crosses.
+pub async fn get_drift(State(st): State<AppState>) -> Json<Option<DriftResp>> {
+    Json(report(&st))
+}
+
+/// POST /admin/drift/reset — starts new baselines, e.g. once a change in the corpus has
+/// been dealt with.
+pub async fn reset_drift(State(st): State<AppState>) -> Result<StatusCode, ApiError> {
+    let embedder = st.embedder();
+    if let Some(drift) = embedder.drift() { drift.reset()?; }
+    Ok(StatusCode::NO_CONTENT)
+}
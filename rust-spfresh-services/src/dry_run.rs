@@ -0,0 +1,36 @@
+use crate::{
+    blocking, keyword,
+    negotiate::{Negotiated, Reply},
+    ApiError, AppState,
+};
+use axum::extract::State;
+use reviews_types::{DryRunResp, InsertReq, TermWeight};
+
+/// Dimensions listed, largest first.
+const MAX_DIMS: usize = 20;
+
+/// POST /reviews/dry-run — runs an insert's checks, tokenization and embedding on the
+/// review in the body (as for POST /reviews) and returns what would be stored, without
+/// writing anything or counting the text anywhere: a TF-IDF embedder's document
+/// frequencies and vocabulary are left as they are, so terms the vocabulary lacks yet are
+/// missing from the vector.
+pub async fn dry_run(State(st): State<AppState>, Negotiated(req, fmt): Negotiated<InsertReq>) -> Result<Reply<DryRunResp>, ApiError> {
+    let resp = blocking(move || {
+        let moderation = st.moderator.check(&req.review);
+        let review = st.meta.prepare(&req.review, st.moderator.initial_status(moderation.as_ref()));
+        let text = review.embed_text();
+        let embedder = st.embedder();
+        let vector = embedder.embed_index_dry(&text)?;
+        let mut dims: Vec<(usize, f32)> = vector.iter().copied().enumerate().filter(|&(_, w)| w != 0.0).collect();
+        dims.sort_by(|a, b| b.1.abs().total_cmp(&a.1.abs()));
+        dims.truncate(MAX_DIMS);
+        Ok(DryRunResp {
+            terms: keyword::terms(&text).collect(),
+            top_dims: dims.into_iter().map(|(dim, weight)| TermWeight { dim, term: embedder.term(dim), weight }).collect(),
+            review,
+            moderation,
+            vector,
+        })
+    }).await?;
+    Ok(Reply(fmt, resp))
+}
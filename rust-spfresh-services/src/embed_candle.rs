@@ -0,0 +1,54 @@
+use crate::{l2_normalize, Embedder};
+use anyhow::{anyhow, Result};
+use candle_core::{Device, Tensor};
+use candle_nn::VarBuilder;
+use candle_transformers::models::bert::{BertModel, Config as BertConfig, DTYPE};
+use std::path::Path;
+use tokenizers::{Tokenizer, TruncationParams};
+
+/// Sentence-transformer (BERT family, e.g. all-MiniLM-L6-v2) run on the CPU with candle:
+/// mean-pooled last hidden state, L2-normalised. Weights are memory-mapped from
+/// `model.safetensors`, so nothing is downloaded at runtime.
+pub struct CandleEmbedder {
+    model: BertModel,
+    tokenizer: Tokenizer,
+    device: Device,
+}
+
+impl CandleEmbedder {
+    pub fn load(dir: &Path, dim: usize, max_tokens: usize) -> Result<Self> {
+        let device = Device::Cpu;
+        let config: BertConfig = serde_json::from_slice(&std::fs::read(dir.join("config.json"))?)?;
+        anyhow::ensure!(
+            config.hidden_size == dim,
+            "{}: model hidden size {} != configured dim {}", dir.display(), config.hidden_size, dim
+        );
+        let mut tokenizer = Tokenizer::from_file(dir.join("tokenizer.json")).map_err(|e| anyhow!("tokenizer: {e}"))?;
+        tokenizer
+            .with_truncation(Some(TruncationParams { max_length: max_tokens, ..Default::default() }))
+            .map_err(|e| anyhow!("tokenizer: {e}"))?;
+        // SAFETY: the weights file is not modified while the service runs.
+        let vb = unsafe { VarBuilder::from_mmaped_safetensors(&[dir.join("model.safetensors")], DTYPE, &device)? };
+        let model = BertModel::load(vb, &config)?;
+        tracing::info!("candle embedder loaded from {} (dim {})", dir.display(), dim);
+        Ok(Self { model, tokenizer, device })
+    }
+
+    fn embed(&self, text: &str) -> Result<Vec<f32>> {
+        let enc = self.tokenizer.encode(text, true).map_err(|e| anyhow!("tokenize: {e}"))?;
+        let ids = Tensor::new(enc.get_ids(), &self.device)?.unsqueeze(0)?;
+        let type_ids = ids.zeros_like()?;
+        let mask = Tensor::new(enc.get_attention_mask(), &self.device)?.unsqueeze(0)?;
+        let hidden = self.model.forward(&ids, &type_ids, Some(&mask))?;
+        // One unpadded sequence, so the mean over tokens is the masked mean.
+        let tokens = hidden.dim(1)? as f64;
+        let mut v: Vec<f32> = (hidden.sum(1)? / tokens)?.squeeze(0)?.to_vec1()?;
+        l2_normalize(&mut v);
+        Ok(v)
+    }
+}
+
+impl Embedder for CandleEmbedder {
+    fn embed_index(&self, text: &str) -> Result<Vec<f32>> { self.embed(text) }
+    fn embed_query(&self, text: &str) -> Result<Vec<f32>> { self.embed(text) }
+}
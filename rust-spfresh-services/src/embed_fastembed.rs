@@ -0,0 +1,43 @@
+use crate::{l2_normalize, Embedder};
+use anyhow::{anyhow, Result};
+use fastembed::{EmbeddingModel, InitOptions, TextEmbedding};
+use parking_lot::Mutex;
+use std::path::Path;
+
+/// ONNX sentence embeddings through fastembed. The session needs `&mut` to run, so calls
+/// are serialised; `embed_index_batch` sends a whole batch through one call instead.
+pub struct FastEmbedder {
+    model: Mutex<TextEmbedding>,
+    batch_size: Option<usize>,
+}
+
+impl FastEmbedder {
+    pub fn load(model: &str, dim: usize, cache_dir: Option<&Path>, batch_size: Option<usize>) -> Result<Self> {
+        let which: EmbeddingModel = model.parse().map_err(|e| {
+            let known: Vec<String> = TextEmbedding::list_supported_models().into_iter().map(|m| m.model_code).collect();
+            anyhow!("fastembed model '{model}': {e}; supported: {}", known.join(", "))
+        })?;
+        let info = TextEmbedding::get_model_info(&which)?;
+        anyhow::ensure!(info.dim == dim, "fastembed model '{model}' has dim {} != configured dim {}", info.dim, dim);
+        let mut opts = InitOptions::new(which).with_show_download_progress(false);
+        if let Some(dir) = cache_dir { opts = opts.with_cache_dir(dir.to_path_buf()); }
+        let embedding = TextEmbedding::try_new(opts)?;
+        tracing::info!("fastembed model {model} loaded (dim {dim})");
+        Ok(Self { model: Mutex::new(embedding), batch_size })
+    }
+
+    fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let mut out = self.model.lock().embed(texts, self.batch_size)?;
+        for v in &mut out { l2_normalize(v); }
+        Ok(out)
+    }
+}
+
+impl Embedder for FastEmbedder {
+    fn embed_index(&self, text: &str) -> Result<Vec<f32>> {
+        self.embed(&[text.to_string()])?.pop().ok_or_else(|| anyhow!("fastembed returned no embedding"))
+    }
+    fn embed_query(&self, text: &str) -> Result<Vec<f32>> { self.embed_index(text) }
+    fn embed_index_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> { self.embed(texts) }
+    fn embed_query_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> { self.embed(texts) }
+}
@@ -0,0 +1,114 @@
+use crate::{l2_normalize, Embedder};
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use std::time::Duration;
+
+/// First retry delay; doubled on each further attempt unless the server sends Retry-After.
+const BACKOFF_BASE: Duration = Duration::from_millis(200);
+/// Longest wait between two attempts, Retry-After included; the writer waits with them.
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+/// Most retries `max_retries` may ask for.
+pub const MAX_RETRIES: u32 = 10;
+
+/// Calls an OpenAI-compatible `/embeddings` endpoint. Batches are split into requests of
+/// at most `batch_size` inputs; 429s, 5xxs and transport errors are retried with backoff.
+/// Runs on the blocking pool like every embedder, hence the blocking HTTP client.
+pub struct RemoteEmbedder {
+    agent: ureq::Agent,
+    url: String,
+    model: String,
+    api_key: Option<String>,
+    dim: usize,
+    batch_size: usize,
+    max_retries: u32,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingsResp { data: Vec<EmbeddingItem> }
+
+#[derive(Deserialize)]
+struct EmbeddingItem { index: usize, embedding: Vec<f32> }
+
+pub struct RemoteOptions<'a> {
+    pub url: &'a str,
+    pub model: &'a str,
+    pub dim: usize,
+    pub api_key_env: &'a str,
+    pub batch_size: usize,
+    pub timeout_ms: u64,
+    pub max_retries: u32,
+}
+
+impl RemoteEmbedder {
+    pub fn new(o: RemoteOptions<'_>) -> Result<Self> {
+        anyhow::ensure!(o.max_retries <= MAX_RETRIES, "remote embedder: max_retries is {}, at most {MAX_RETRIES}", o.max_retries);
+        let api_key = std::env::var(o.api_key_env).ok().filter(|k| !k.is_empty());
+        if api_key.is_none() {
+            tracing::warn!("remote embedder: ${} is not set, calling {} without a key", o.api_key_env, o.url);
+        }
+        let agent = ureq::AgentBuilder::new().timeout(Duration::from_millis(o.timeout_ms)).build();
+        Ok(Self {
+            agent,
+            url: o.url.to_string(),
+            model: o.model.to_string(),
+            api_key,
+            dim: o.dim,
+            batch_size: o.batch_size.max(1),
+            max_retries: o.max_retries,
+        })
+    }
+
+    fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let mut out = Vec::with_capacity(texts.len());
+        for chunk in texts.chunks(self.batch_size) {
+            out.extend(self.request(chunk)?);
+        }
+        Ok(out)
+    }
+
+    /// One request, retried; returns the embeddings in input order.
+    fn request(&self, input: &[String]) -> Result<Vec<Vec<f32>>> {
+        let body = serde_json::json!({ "model": self.model, "input": input });
+        let mut attempt = 0;
+        let resp: EmbeddingsResp = loop {
+            let mut req = self.agent.post(&self.url);
+            if let Some(key) = &self.api_key { req = req.set("Authorization", &format!("Bearer {key}")); }
+            let (err, retry_after) = match req.send_json(&body) {
+                Ok(r) => break r.into_json()?,
+                Err(ureq::Error::Status(code, r)) if code == 429 || code >= 500 => {
+                    let after = r.header("retry-after").and_then(|v| v.parse().ok()).map(Duration::from_secs);
+                    (anyhow!("{} returned {code}: {}", self.url, r.into_string().unwrap_or_default()), after)
+                }
+                Err(ureq::Error::Status(code, r)) => {
+                    return Err(anyhow!("{} returned {code}: {}", self.url, r.into_string().unwrap_or_default()));
+                }
+                Err(e @ ureq::Error::Transport(_)) => (anyhow!("{}: {e}", self.url), None),
+            };
+            if attempt >= self.max_retries { return Err(err.context(format!("gave up after {} attempts", attempt + 1))); }
+            let wait = retry_after.unwrap_or(BACKOFF_BASE * 2u32.saturating_pow(attempt)).min(MAX_BACKOFF);
+            tracing::warn!("remote embedder: {err}; retrying in {:?}", wait);
+            std::thread::sleep(wait);
+            attempt += 1;
+        };
+
+        anyhow::ensure!(resp.data.len() == input.len(), "{} returned {} embeddings for {} inputs", self.url, resp.data.len(), input.len());
+        let mut vecs = vec![Vec::new(); input.len()];
+        for item in resp.data {
+            anyhow::ensure!(item.embedding.len() == self.dim, "remote embedding dim {} != configured dim {}", item.embedding.len(), self.dim);
+            let slot = vecs.get_mut(item.index).ok_or_else(|| anyhow!("embedding index {} out of range", item.index))?;
+            *slot = item.embedding;
+        }
+        anyhow::ensure!(vecs.iter().all(|v| !v.is_empty()), "{} returned duplicate embedding indices", self.url);
+        for v in &mut vecs { l2_normalize(v); }
+        Ok(vecs)
+    }
+}
+
+impl Embedder for RemoteEmbedder {
+    fn embed_index(&self, text: &str) -> Result<Vec<f32>> {
+        self.embed(&[text.to_string()])?.pop().ok_or_else(|| anyhow!("remote embedder returned no embedding"))
+    }
+    fn embed_query(&self, text: &str) -> Result<Vec<f32>> { self.embed_index(text) }
+    fn embed_index_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> { self.embed(texts) }
+    fn embed_query_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> { self.embed(texts) }
+}
@@ -0,0 +1,145 @@
+use crate::{audit::{Action, Actor, IdRanges}, blocking, config::EmbedderConfig, ApiError, AppState};
+use anyhow::Result;
+use axum::{extract::State, http::StatusCode, Json};
+use parking_lot::RwLock;
+use reviews_types::{ModelInfo, RegisterEmbedderReq};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const REGISTRY_FILE: &str = "embedders.json";
+
+/// Named embedder configurations (`data/embedders.json`) and which one built each
+/// collection. Every collection is bound when it is created (or, for collections that
+/// predate the registry, on startup from its `embedder.json`); a configuration nobody
+/// registered gets a generated name such as `tfidf-4096`.
+pub struct Registry {
+    path: PathBuf,
+    inner: RwLock<RegistryFile>,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+struct RegistryFile {
+    models: BTreeMap<String, Model>,
+    /// Collection name -> model name.
+    bindings: BTreeMap<String, String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct Model {
+    embedder: EmbedderConfig,
+    registered_at: u64,
+}
+
+impl Registry {
+    pub fn open(data_dir: &Path) -> Result<Self> {
+        let path = data_dir.join(REGISTRY_FILE);
+        let inner = match std::fs::read(&path) {
+            Ok(b) => serde_json::from_slice(&b)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => RegistryFile::default(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self { path, inner: RwLock::new(inner) })
+    }
+
+    fn save(&self, file: &RegistryFile) -> Result<()> {
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(file)?)?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<EmbedderConfig> {
+        self.inner.read().models.get(name).map(|m| m.embedder.clone())
+    }
+
+    /// The name `embedder` is registered under, if any.
+    pub fn name_of(&self, embedder: &EmbedderConfig) -> Option<String> {
+        self.inner.read().models.iter().find(|(_, m)| m.embedder == *embedder).map(|(n, _)| n.clone())
+    }
+
+    pub fn binding(&self, collection: &str) -> Option<String> {
+        self.inner.read().bindings.get(collection).cloned()
+    }
+
+    /// The collections `model` built, by name.
+    pub fn collections_of(&self, model: &str) -> Vec<String> {
+        self.inner.read().bindings.iter().filter(|(_, m)| *m == model).map(|(c, _)| c.clone()).collect()
+    }
+
+    /// Registers `embedder` as `name`. Re-registering the same configuration is a no-op;
+    /// a name already taken by a different one is a conflict.
+    pub fn register(&self, name: &str, embedder: &EmbedderConfig) -> Result<bool, ApiError> {
+        let mut inner = self.inner.write();
+        match inner.models.get(name) {
+            Some(m) if m.embedder == *embedder => return Ok(false),
+            Some(m) => return Err(ApiError::conflict(format!("embedder '{name}' is already registered as {:?}", m.embedder))),
+            None => {}
+        }
+        let mut next = inner.clone();
+        next.models.insert(name.to_string(), Model { embedder: embedder.clone(), registered_at: now_secs() });
+        self.save(&next)?;
+        *inner = next;
+        Ok(true)
+    }
+
+    /// Records that `collection` was built with `embedder`, registering the configuration
+    /// under a generated name if it has none. Returns the model name.
+    pub fn bind(&self, collection: &str, embedder: &EmbedderConfig) -> Result<String> {
+        let mut inner = self.inner.write();
+        let existing = inner.models.iter().find(|(_, m)| m.embedder == *embedder).map(|(n, _)| n.clone());
+        if let Some(name) = &existing
+            && inner.bindings.get(collection) == Some(name)
+        {
+            return Ok(name.clone());
+        }
+        let mut next = inner.clone();
+        let name = existing.unwrap_or_else(|| {
+            let base = embedder.default_name();
+            let name = (1..).map(|i| if i == 1 { base.clone() } else { format!("{base}-{i}") })
+                .find(|n| !next.models.contains_key(n))
+                .expect("unbounded");
+            next.models.insert(name.clone(), Model { embedder: embedder.clone(), registered_at: now_secs() });
+            name
+        });
+        next.bindings.insert(collection.to_string(), name.clone());
+        self.save(&next)?;
+        *inner = next;
+        Ok(name)
+    }
+
+    fn list(&self) -> Vec<ModelInfo<EmbedderConfig>> {
+        let inner = self.inner.read();
+        inner.models.iter().map(|(name, m)| ModelInfo {
+            name: name.clone(),
+            embedder: m.embedder.clone(),
+            registered_at: m.registered_at,
+            collections: inner.bindings.iter().filter(|(_, b)| *b == name).map(|(c, _)| c.clone()).collect(),
+        }).collect()
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// GET /embedders — registered models and the collections each one built.
+pub async fn list_embedders(State(st): State<AppState>) -> Json<Vec<ModelInfo<EmbedderConfig>>> {
+    Json(st.embedders.list())
+}
+
+/// POST /embedders — register a named configuration, to be used by `/admin/reindex`.
+pub async fn register_embedder(State(st): State<AppState>, actor: Actor, Json(req): Json<RegisterEmbedderReq<EmbedderConfig>>) -> Result<StatusCode, ApiError> {
+    if req.name.is_empty() {
+        return Err(ApiError::bad_request("embedder name is empty"));
+    }
+    blocking(move || {
+        if !st.embedders.register(&req.name, &req.embedder)? { return Ok(StatusCode::OK); }
+        tracing::info!("embedder {} registered: {:?}", req.name, req.embedder);
+        st.audit.record(&actor, Action::RegisterEmbedder, IdRanges::default(), Some(req.name))?;
+        Ok(StatusCode::CREATED)
+    }).await
+}
@@ -0,0 +1,50 @@
+use crate::{blocking, rank_in, ApiError, AppState, RankOpts, SearchStats};
+use axum::{extract::State, Json};
+use reviews_types::{EvalReq, EvalResp, Metrics};
+use std::collections::HashSet;
+
+const DEFAULT_K: usize = 10;
+/// Each case is a full search; keep a single request bounded.
+const MAX_CASES: usize = 1000;
+
+/// recall@k, reciprocal rank of the first relevant hit (0 if none in the top k) and
+/// nDCG@k with binary relevance, for one ranked list of ids.
+fn score(ranked: &[usize], relevant: &HashSet<usize>, k: usize) -> Metrics {
+    if relevant.is_empty() { return Metrics::default(); }
+    let gain = |i: usize| 1.0 / (i as f64 + 2.0).log2();
+    let (mut found, mut mrr, mut dcg) = (0usize, 0.0, 0.0);
+    for (i, id) in ranked.iter().take(k).enumerate() {
+        if !relevant.contains(id) { continue; }
+        if found == 0 { mrr = 1.0 / (i + 1) as f64; }
+        found += 1;
+        dcg += gain(i);
+    }
+    let ideal: f64 = (0..relevant.len().min(k)).map(gain).sum();
+    Metrics { recall: found as f64 / relevant.len() as f64, mrr, ndcg: dcg / ideal }
+}
+
+/// POST /eval — runs labeled queries against the live index and reports recall@k, MRR and
+/// nDCG@k, to quantify an embedder or tokenizer change before rolling it out.
+pub async fn evaluate(State(st): State<AppState>, Json(req): Json<EvalReq>) -> Result<Json<EvalResp>, ApiError> {
+    if req.cases.is_empty() || req.cases.len() > MAX_CASES {
+        return Err(ApiError::bad_request(format!("cases must hold 1 to {MAX_CASES} entries")));
+    }
+    let k = req.k.unwrap_or(DEFAULT_K).clamp(1, st.config.search.max_k);
+    blocking(move || {
+        let active = st.target(req.collection.as_deref())?;
+        let mut stats = SearchStats::default();
+        let per_case: Vec<Metrics> = req.cases.iter().map(|c| {
+            let ranked: Vec<usize> = rank_in(&st.meta, &st.vcache, &active, &RankOpts::plain(&c.query, k), &mut stats)
+                .into_iter().map(|r| r.id).collect();
+            score(&ranked, &c.relevant.iter().copied().collect(), k)
+        }).collect();
+        let n = per_case.len() as f64;
+        let mean = Metrics {
+            recall: per_case.iter().map(|m| m.recall).sum::<f64>() / n,
+            mrr: per_case.iter().map(|m| m.mrr).sum::<f64>() / n,
+            ndcg: per_case.iter().map(|m| m.ndcg).sum::<f64>() / n,
+        };
+        let cases = per_case.len();
+        Ok(Json(EvalResp { k, cases, mean, per_case: req.per_case.then_some(per_case) }))
+    }).await
+}
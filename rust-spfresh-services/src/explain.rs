@@ -0,0 +1,43 @@
+use crate::{fusion, now_secs, score_parts, Active, MetaStore, RankOpts, SearchHit};
+use anyhow::Result;
+use reviews_types::TermWeight;
+
+/// Dimensions listed per hit, largest contribution first.
+const MAX_TERMS: usize = 10;
+
+/// Fills in `explain` and `score_parts` for each hit from its stored vector. The term
+/// list is only meaningful for sparse embedders (TF-IDF), where a dimension is a term or
+/// a bucket of terms; the stages apply to any embedder. With fused queries, terms are
+/// those of the query vector that decided the hit's similarity; with per-field scoring,
+/// those of the deciding field, or of both weighted. With late interaction, `cosine` is its
+/// score, and the terms still those of the single vector.
+pub fn annotate(meta: &MetaStore, active: &Active, opts: &RankOpts, hits: &mut [SearchHit]) -> Result<()> {
+    let body;
+    let active = match &opts.fields {
+        Some(fs) => { body = fs.body(); &body }
+        None => active,
+    };
+    let qvs = fusion::query_vectors(active, opts)?;
+    let late = opts.late.as_ref().map(|late| late.query(&opts.text()).map(|q| (late, q))).transpose()?;
+    let now = now_secs();
+    for hit in hits {
+        let scored = match &opts.fields {
+            Some(fs) => fs.explain(&qvs, hit.id)?,
+            None => active.vindex.read_mirror(hit.id)?.map(|v| (fusion::similarity(&qvs, &v).0, v)),
+        };
+        let Some((mut cosine, v)) = scored else { continue };
+        if let Some((late, q)) = &late { cosine = late.score(q, hit.id)?; }
+        let best = fusion::similarity(&qvs, &v).1;
+        hit.score_parts = Some(score_parts(opts, &meta.attrs.get(hit.id), cosine, now));
+        let mut parts: Vec<(usize, f32)> = qvs[best].iter().zip(&v).enumerate()
+            .map(|(i, (q, d))| (i, q * d))
+            .filter(|&(_, w)| w > 0.0)
+            .collect();
+        parts.sort_by(|a, b| b.1.total_cmp(&a.1));
+        parts.truncate(MAX_TERMS);
+        hit.explain = Some(parts.into_iter()
+            .map(|(dim, weight)| TermWeight { dim, term: active.embedder.term(dim), weight })
+            .collect());
+    }
+    Ok(())
+}
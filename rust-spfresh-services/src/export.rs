@@ -0,0 +1,79 @@
+use crate::{blocking, codec, Active, ApiError, AppState};
+use axum::{
+    body::Body,
+    extract::{Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use futures_util::stream;
+use reviews_types::FullRecord;
+use serde::Deserialize;
+use std::{
+    convert::Infallible,
+    fs::File,
+    io::{BufReader, Read, Seek, SeekFrom},
+    path::Path,
+};
+use tokio::sync::mpsc::{Receiver, Sender};
+
+/// Bytes gathered before a chunk is handed to the client.
+pub const CHUNK_BYTES: usize = 64 << 10;
+
+#[derive(Deserialize)]
+pub struct ExportParams {
+    /// Alias or collection whose vectors to export instead of the active index's.
+    pub collection: Option<String>,
+}
+
+/// GET /export/full?collection= — every review with its stored vector, one NDJSON line
+/// each, in id order, so a pipeline can take the corpus without re-embedding it.
+/// Covers the live reviews present when the export starts. Vectors are read sequentially from
+/// the mirror file rather than through the vector cache, so an export does not evict the
+/// segments searches rely on. An error partway through ends the stream early and is logged.
+pub async fn export_full(State(st): State<AppState>, Query(p): Query<ExportParams>) -> Result<Response, ApiError> {
+    let (active, n) = start(&st, p.collection).await?;
+    let (mirror, dim) = (active.vindex.mirror_path().to_path_buf(), active.vindex.dim());
+
+    let (tx, rx) = tokio::sync::mpsc::channel::<Vec<u8>>(4);
+    tokio::task::spawn_blocking(move || {
+        if let Err(e) = write_full(&st, &mirror, dim, n, &tx) { tracing::warn!("export stopped: {e}"); }
+    });
+    Ok(([(header::CONTENT_TYPE, "application/x-ndjson")], body(rx)).into_response())
+}
+
+/// The index to export vectors from and how many ids the export covers.
+pub async fn start(st: &AppState, collection: Option<String>) -> Result<(Active, usize), ApiError> {
+    let st = st.clone();
+    blocking(move || {
+        let active = st.target(collection.as_deref())?;
+        let n = st.meta.count()?.min(active.vindex.len()?);
+        Ok((active, n))
+    }).await
+}
+
+/// Response body streaming the chunks sent through `rx` until its sender is dropped.
+pub fn body(rx: Receiver<Vec<u8>>) -> Body {
+    Body::from_stream(stream::unfold(rx, |mut rx| async move { rx.recv().await.map(|chunk| (Ok::<_, Infallible>(chunk), rx)) }))
+}
+
+/// Sends the first `n` reviews joined with their vectors through `tx`, in chunks.
+fn write_full(st: &AppState, mirror: &Path, dim: usize, n: usize, tx: &Sender<Vec<u8>>) -> anyhow::Result<()> {
+    let mut vectors = BufReader::new(File::open(mirror)?);
+    vectors.seek(SeekFrom::Start(codec::HEADER_LEN))?;
+    let (mut rec, mut vec, mut buf) = (vec![0u8; codec::record_len(dim)], Vec::with_capacity(dim), Vec::new());
+    st.meta.for_each_in(0..n, |id, review| {
+        vectors.read_exact(&mut rec)?;
+        let Some(review) = review.filter(|_| st.meta.is_live(id)) else { return Ok(()) };
+        vec.clear();
+        let ok = codec::decode_record_into(&rec, dim, &mut vec);
+        if !ok { tracing::warn!("{}: record {id} fails its checksum, exported without vector", mirror.display()); }
+        serde_json::to_writer(&mut buf, &FullRecord { id, review, vector: ok.then(|| vec.clone()) })?;
+        buf.push(b'\n');
+        if buf.len() >= CHUNK_BYTES {
+            tx.blocking_send(std::mem::take(&mut buf)).map_err(|_| anyhow::anyhow!("client disconnected"))?;
+        }
+        Ok(())
+    })?;
+    if !buf.is_empty() { tx.blocking_send(buf).map_err(|_| anyhow::anyhow!("client disconnected"))?; }
+    Ok(())
+}
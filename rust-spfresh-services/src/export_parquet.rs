@@ -0,0 +1,230 @@
+use crate::{codec, config::Config, dir_lock, export, open_state, Active, ApiError, AppState, Review};
+use anyhow::Context;
+use arrow_array::{
+    builder::{FixedSizeListBuilder, Float32Builder, Int32Builder, StringBuilder, TimestampSecondBuilder, UInt64Builder},
+    ArrayRef, RecordBatch,
+};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use axum::{
+    extract::{Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use parquet::{
+    arrow::ArrowWriter,
+    basic::{Compression, ZstdLevel},
+    file::properties::WriterProperties,
+};
+use serde::Deserialize;
+use std::{
+    fs::File,
+    io::{BufReader, Read, Seek, SeekFrom, Write},
+    sync::Arc,
+};
+use tokio::sync::mpsc::Sender;
+
+/// Rough in-memory size of a row group; rows with vectors are mostly vector, so a group
+/// of them holds fewer rows.
+const ROW_GROUP_BYTES: usize = 64 << 20;
+/// Assumed size of a row's text columns when sizing row groups.
+const TEXT_BYTES: usize = 512;
+
+#[derive(Deserialize)]
+pub struct ParquetParams {
+    /// Alias or collection whose vectors to export instead of the active index's.
+    collection: Option<String>,
+    /// Adds the `vector` column.
+    #[serde(default)]
+    vectors: bool,
+}
+
+/// GET /export/parquet?vectors=&collection= — the live reviews as one Parquet file
+/// (zstd-compressed), in id order. Same snapshot and vector reads as /export/full.
+pub async fn export_parquet(State(st): State<AppState>, Query(p): Query<ParquetParams>) -> Result<Response, ApiError> {
+    let (active, n) = export::start(&st, p.collection).await?;
+    let (tx, rx) = tokio::sync::mpsc::channel::<Vec<u8>>(4);
+    tokio::task::spawn_blocking(move || {
+        let out = ChunkWriter { tx, buf: Vec::new() };
+        if let Err(e) = write(&st, &active, n, p.vectors, out) { tracing::warn!("parquet export stopped: {e}"); }
+    });
+    let headers = [
+        (header::CONTENT_TYPE, "application/vnd.apache.parquet"),
+        (header::CONTENT_DISPOSITION, "attachment; filename=\"reviews.parquet\""),
+    ];
+    Ok((headers, export::body(rx)).into_response())
+}
+
+/// `export-parquet --out <file> [--vectors] [--collection <name>]`: the same file, written
+/// offline (the service must be stopped; it holds the data dir lock).
+pub fn run(config: Config, args: &[String]) -> anyhow::Result<()> {
+    let (mut out, mut vectors, mut collection) = (None, false, None);
+    let mut it = args.iter();
+    while let Some(flag) = it.next() {
+        match flag.as_str() {
+            "--vectors" => vectors = true,
+            "--out" => out = Some(it.next().context("--out needs a value")?),
+            "--collection" => collection = Some(it.next().context("--collection needs a value")?.clone()),
+            _ => anyhow::bail!("unknown export-parquet option {flag} (--out, --vectors, --collection)"),
+        }
+    }
+    let out = out.context("export-parquet needs --out")?;
+    let data_dir = std::env::current_dir()?.join(&config.data_dir);
+    anyhow::ensure!(data_dir.is_dir(), "no data dir at {}", data_dir.display());
+    let _dir_lock = dir_lock::acquire(&data_dir)?;
+    let st = open_state(config, &data_dir, &Default::default())?;
+    let active = st.target(collection.as_deref()).map_err(|e| anyhow::anyhow!(e.msg))?;
+    let n = st.meta.count()?.min(active.vindex.len()?);
+    let rows = write(&st, &active, n, vectors, File::create(out)?)?;
+    println!("{rows} reviews -> {out}");
+    Ok(())
+}
+
+fn schema(dim: Option<usize>) -> SchemaRef {
+    let mut fields = vec![
+        Field::new("id", DataType::UInt64, false),
+        Field::new("review_title", DataType::Utf8, false),
+        Field::new("review_body", DataType::Utf8, false),
+        Field::new("product_id", DataType::Utf8, false),
+        Field::new("review_rating", DataType::Int32, false),
+        Field::new("created_at", DataType::Timestamp(TimeUnit::Second, Some("UTC".into())), true),
+        Field::new("external_id", DataType::Utf8, true),
+        Field::new("version", DataType::UInt64, false),
+        Field::new("lang", DataType::Utf8, true),
+    ];
+    if let Some(dim) = dim {
+        // Null when the mirror record fails its checksum.
+        fields.push(Field::new("vector", DataType::FixedSizeList(vector_item(), dim as i32), true));
+    }
+    Arc::new(Schema::new(fields))
+}
+
+fn vector_item() -> Arc<Field> { Arc::new(Field::new("item", DataType::Float32, false)) }
+
+/// Column builders for one row group.
+struct Columns {
+    id: UInt64Builder,
+    title: StringBuilder,
+    body: StringBuilder,
+    product: StringBuilder,
+    rating: Int32Builder,
+    created_at: TimestampSecondBuilder,
+    external_id: StringBuilder,
+    version: UInt64Builder,
+    lang: StringBuilder,
+    vector: Option<FixedSizeListBuilder<Float32Builder>>,
+    rows: usize,
+}
+
+impl Columns {
+    fn new(dim: Option<usize>) -> Self {
+        Self {
+            id: UInt64Builder::new(),
+            title: StringBuilder::new(),
+            body: StringBuilder::new(),
+            product: StringBuilder::new(),
+            rating: Int32Builder::new(),
+            created_at: TimestampSecondBuilder::new().with_timezone("UTC"),
+            external_id: StringBuilder::new(),
+            version: UInt64Builder::new(),
+            lang: StringBuilder::new(),
+            vector: dim.map(|d| FixedSizeListBuilder::new(Float32Builder::new(), d as i32).with_field(vector_item())),
+            rows: 0,
+        }
+    }
+
+    fn push(&mut self, id: usize, r: &Review, vector: Option<&[f32]>) {
+        self.id.append_value(id as u64);
+        self.title.append_value(&r.review_title);
+        self.body.append_value(&r.review_body);
+        self.product.append_value(&r.product_id);
+        self.rating.append_value(r.review_rating);
+        self.created_at.append_option(r.created_at.map(|t| t as i64));
+        self.external_id.append_option(r.external_id.as_deref());
+        self.version.append_value(r.version.unwrap_or(1));
+        self.lang.append_option(r.lang.as_deref());
+        if let Some(b) = &mut self.vector {
+            match vector {
+                Some(v) => { b.values().append_slice(v); b.append(true); }
+                None => {
+                    // A null list still takes up `dim` slots in the child array.
+                    for _ in 0..b.value_length() { b.values().append_value(0.0); }
+                    b.append(false);
+                }
+            }
+        }
+        self.rows += 1;
+    }
+
+    /// The rows pushed so far as a batch; the builders start over empty.
+    fn finish(&mut self, schema: &SchemaRef) -> anyhow::Result<RecordBatch> {
+        let mut cols: Vec<ArrayRef> = vec![
+            Arc::new(self.id.finish()),
+            Arc::new(self.title.finish()),
+            Arc::new(self.body.finish()),
+            Arc::new(self.product.finish()),
+            Arc::new(self.rating.finish()),
+            Arc::new(self.created_at.finish()),
+            Arc::new(self.external_id.finish()),
+            Arc::new(self.version.finish()),
+            Arc::new(self.lang.finish()),
+        ];
+        if let Some(b) = &mut self.vector { cols.push(Arc::new(b.finish())); }
+        self.rows = 0;
+        Ok(RecordBatch::try_new(schema.clone(), cols)?)
+    }
+}
+
+/// Writes the live reviews among the first `n` ids, with the vectors of `active` if
+/// `vectors`, to `out` as Parquet. Returns the number of rows.
+fn write<W: Write + Send>(st: &AppState, active: &Active, n: usize, vectors: bool, out: W) -> anyhow::Result<usize> {
+    let dim = active.vindex.dim();
+    let schema = schema(vectors.then_some(dim));
+    let row_bytes = TEXT_BYTES + if vectors { codec::bytes_per_vec(dim) } else { 0 };
+    let group_rows = (ROW_GROUP_BYTES / row_bytes).max(1);
+    let props = WriterProperties::builder()
+        .set_compression(Compression::ZSTD(ZstdLevel::default()))
+        .set_max_row_group_size(group_rows)
+        .build();
+    let mut writer = ArrowWriter::try_new(out, schema.clone(), Some(props))?;
+
+    let mirror = active.vindex.mirror_path();
+    let mut vecs = BufReader::new(File::open(mirror)?);
+    vecs.seek(SeekFrom::Start(codec::HEADER_LEN))?;
+    let (mut rec, mut vec) = (vec![0u8; codec::record_len(dim)], Vec::with_capacity(dim));
+    let (mut cols, mut rows) = (Columns::new(vectors.then_some(dim)), 0);
+    st.meta.for_each_in(0..n, |id, review| {
+        if vectors { vecs.read_exact(&mut rec)?; }
+        let Some(review) = review.filter(|_| st.meta.is_live(id)) else { return Ok(()) };
+        vec.clear();
+        let ok = vectors && codec::decode_record_into(&rec, dim, &mut vec);
+        if vectors && !ok { tracing::warn!("{}: record {id} fails its checksum, exported without vector", mirror.display()); }
+        cols.push(id, &review, ok.then_some(&vec[..]));
+        rows += 1;
+        if cols.rows == group_rows { writer.write(&cols.finish(&schema)?)?; }
+        Ok(())
+    })?;
+    if cols.rows > 0 { writer.write(&cols.finish(&schema)?)?; }
+    // Writes the footer; the last chunk of a streamed export goes out on the flush.
+    writer.into_inner()?.flush()?;
+    Ok(rows)
+}
+
+/// Hands what the Parquet writer produces to the response body in chunks.
+struct ChunkWriter {
+    tx: Sender<Vec<u8>>,
+    buf: Vec<u8>,
+}
+
+impl Write for ChunkWriter {
+    fn write(&mut self, b: &[u8]) -> std::io::Result<usize> {
+        self.buf.extend_from_slice(b);
+        if self.buf.len() >= export::CHUNK_BYTES { self.flush()?; }
+        Ok(b.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        if self.buf.is_empty() { return Ok(()); }
+        self.tx.blocking_send(std::mem::take(&mut self.buf))
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::BrokenPipe, "client disconnected"))
+    }
+}
@@ -0,0 +1,48 @@
+use crate::Review;
+use parking_lot::RwLock;
+use std::collections::HashMap;
+
+/// external_id -> internal id of the review currently holding it, plus which ids have
+/// been superseded. Derived entirely from reviews.jsonl (rebuilt on startup, extended by
+/// every append like the keyword index), so it cannot drift from the metadata: the last
+/// line carrying an external_id wins and every earlier line with it is dead.
+#[derive(Default)]
+pub struct ExternalIds {
+    inner: RwLock<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    by_external: HashMap<String, usize>,
+    /// Indexed by id; true once a later review took over its external_id.
+    superseded: Vec<bool>,
+}
+
+impl ExternalIds {
+    /// Records the next review; returns the id it supersedes, if any. A `deleted` review
+    /// (tombstoned before this index was rebuilt) still supersedes but holds nothing.
+    pub fn push(&self, review: Option<&Review>, deleted: bool) -> Option<usize> {
+        let mut inner = self.inner.write();
+        let id = inner.superseded.len();
+        inner.superseded.push(false);
+        let ext = review.and_then(|r| r.external_id.clone())?;
+        let old = if deleted { inner.by_external.remove(&ext) } else { inner.by_external.insert(ext, id) }?;
+        inner.superseded[old] = true;
+        Some(old)
+    }
+
+    /// Releases `external_id` if review `id` holds it (the review was deleted).
+    pub fn forget(&self, external_id: &str, id: usize) {
+        let mut inner = self.inner.write();
+        if inner.by_external.get(external_id) == Some(&id) { inner.by_external.remove(external_id); }
+    }
+
+    /// Internal id currently holding `external_id`.
+    pub fn get(&self, external_id: &str) -> Option<usize> {
+        self.inner.read().by_external.get(external_id).copied()
+    }
+
+    pub fn is_superseded(&self, id: usize) -> bool {
+        self.inner.read().superseded.get(id).copied().unwrap_or(false)
+    }
+}
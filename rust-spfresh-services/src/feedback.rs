@@ -0,0 +1,74 @@
+use crate::{audit::Actor, blocking, codec, ApiError, AppState};
+use anyhow::Result;
+use axum::{extract::State, http::StatusCode, Json};
+use parking_lot::Mutex;
+use reviews_types::FeedbackReq;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Ids a single event may list as shown; more than a results page is not a click log.
+const MAX_SHOWN: usize = 1000;
+
+/// One click, as stored. `rank` is the 1-based position of `clicked` in `shown`.
+#[derive(Serialize, Deserialize)]
+pub struct FeedbackEvent {
+    pub ts_ms: u64,
+    pub principal: String,
+    pub request_id: String,
+    pub query: String,
+    pub shown: Vec<usize>,
+    pub clicked: usize,
+    pub rank: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collection: Option<String>,
+}
+
+/// Append-only click log, `data/feedback.log`, framed like reviews.jsonl. Raw material
+/// for training rerankers and for evaluation sets. Not synced per event: losing the
+/// last few clicks in a crash is acceptable, an fsync per click is not.
+pub struct FeedbackLog {
+    file: Mutex<File>,
+}
+
+impl FeedbackLog {
+    pub fn open(data_dir: &Path) -> Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(data_dir.join("feedback.log"))?;
+        Ok(Self { file: Mutex::new(file) })
+    }
+
+    fn append(&self, event: &FeedbackEvent) -> Result<()> {
+        let line = codec::encode_line(&serde_json::to_vec(event)?);
+        self.file.lock().write_all(&line)?;
+        Ok(())
+    }
+}
+
+/// POST /feedback — the UI reports which hit a user clicked for a query.
+pub async fn post_feedback(State(st): State<AppState>, actor: Actor, Json(req): Json<FeedbackReq>) -> Result<StatusCode, ApiError> {
+    if req.query.is_empty() { return Err(ApiError::bad_request("query must not be empty")); }
+    if req.shown.len() > MAX_SHOWN {
+        return Err(ApiError::bad_request(format!("shown lists more than {MAX_SHOWN} ids")));
+    }
+    let Some(pos) = req.shown.iter().position(|&id| id == req.clicked) else {
+        return Err(ApiError::bad_request(format!("clicked id {} is not in shown", req.clicked)));
+    };
+    let event = FeedbackEvent {
+        ts_ms: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0),
+        principal: actor.principal,
+        request_id: actor.request_id,
+        query: req.query,
+        shown: req.shown,
+        clicked: req.clicked,
+        rank: pos + 1,
+        collection: req.collection,
+    };
+    blocking(move || {
+        st.feedback.append(&event)?;
+        Ok(StatusCode::NO_CONTENT)
+    }).await
+}
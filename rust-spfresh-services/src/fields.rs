@@ -0,0 +1,131 @@
+use crate::{
+    build_embedder, collections,
+    config::{DurabilityConfig, EmbedderConfig, FieldsConfig},
+    fusion, payload_of, reembed, spfresh_index, startup, vcache::VectorCache, Active, Embedder, MetaStore, VecIndex,
+};
+use anyhow::Result;
+use reviews_types::{FieldScoring, Review};
+use std::{path::Path, sync::Arc};
+
+/// A vector of each review's title and one of its body, next to the combined one the
+/// active index holds, so a query can find reviews whose match is only in one of them.
+/// Like the shadow index, both follow the metadata's ids: back-filled on startup,
+/// appended to on every write, and allowed to lag but never lead.
+pub struct Fields {
+    embedder: Arc<dyn Embedder>,
+    title: Arc<dyn VecIndex>,
+    body: Arc<dyn VecIndex>,
+    /// Scoring of searches that do not ask for one.
+    pub default: FieldScoring,
+}
+
+impl Fields {
+    /// Opens both indexes, embedding with `embedder` (the primary's config) the titles and
+    /// bodies they are missing.
+    pub fn open(
+        data_dir: &Path,
+        fc: &FieldsConfig,
+        embedder: &EmbedderConfig,
+        meta: &MetaStore,
+        durability: &DurabilityConfig,
+        progress: &startup::Progress,
+    ) -> Result<Self> {
+        let open = |dir: &Path| -> Result<Arc<dyn VecIndex>> {
+            let index = spfresh_index::DefaultIndex::open(dir, embedder.dim(), durability)?;
+            collections::record_embedder(dir, embedder)?;
+            Ok(Arc::new(index))
+        };
+        let (title, body) = (open(&data_dir.join(fc.title_dir()))?, open(&data_dir.join(fc.body_dir()))?);
+        let fields = Self { embedder: build_embedder(embedder, &data_dir.join(&fc.dir))?, title, body, default: fc.scoring };
+
+        let have = [fields.title.len()?, fields.body.len()?];
+        let from = have[0].min(have[1]);
+        progress.set_total(meta.attrs.len().saturating_sub(from));
+        let mut last = [None, None];
+        let filled = meta.for_each_in(from..usize::MAX, |id, r| {
+            let texts = r.as_ref().map(|r| [r.review_title.as_str(), r.review_body.as_str()]);
+            for (i, index) in [&fields.title, &fields.body].into_iter().enumerate() {
+                if id < have[i] { continue; }
+                let v = match texts {
+                    Some(texts) => fields.embedder.embed_index(texts[i])?,
+                    None => vec![0.0; index.dim()], // placeholder keeps ids aligned with the metadata
+                };
+                last[i] = Some(index.append_pending(&v, r.as_ref().and_then(payload_of))?.1);
+            }
+            progress.set_done(id + 1 - from);
+            Ok(())
+        })?;
+        for c in last.into_iter().flatten() { c.wait()?; }
+        tracing::info!("field indexes {}: {} reviews, back-filled {}", data_dir.join(&fc.dir).display(), from + filled, filled);
+        Ok(fields)
+    }
+
+    /// Mirrors a primary write; like the shadow's, failures are logged and not waited on.
+    pub fn append(&self, review: &Review) {
+        for (index, text) in [(&self.title, &review.review_title), (&self.body, &review.review_body)] {
+            let res = self.embedder.embed_index(text).and_then(|v| index.append_pending(&v, payload_of(review)));
+            if let Err(e) = res { tracing::warn!("field index append failed: {e}"); }
+        }
+    }
+
+    /// The title and body indexes.
+    pub fn indexes(&self) -> [Arc<dyn VecIndex>; 2] { [self.title.clone(), self.body.clone()] }
+
+    /// Both indexes, each with `review`'s vector for it, to re-embed the review in place.
+    pub fn embed(&self, review: &Review) -> Result<[reembed::Target; 2]> {
+        Ok([
+            (self.title.clone(), self.embedder.embed_index(&review.review_title)?),
+            (self.body.clone(), self.embedder.embed_index(&review.review_body)?),
+        ])
+    }
+}
+
+/// A search scoring titles and bodies separately, by `mode` (never `Combined`).
+pub struct FieldSearch {
+    pub fields: Arc<Fields>,
+    pub mode: FieldScoring,
+}
+
+impl FieldSearch {
+    /// The body index with the fields' embedder: what the ranking scans, and embeds the
+    /// query with, in place of the active index.
+    pub fn body(&self) -> Active {
+        Active { vindex: self.fields.body.clone(), embedder: self.fields.embedder.clone() }
+    }
+
+    /// Title similarity of every review below `n`, or of `ids` only (0 for the rest and for
+    /// titles not embedded yet).
+    pub fn title_scores(&self, cache: &VectorCache, qvs: &[Vec<f32>], n: usize, ids: Option<&[usize]>) -> Result<Vec<f32>> {
+        let mut scores = vec![0f32; n];
+        let visit = |id: usize, v: &[f32]| scores[id] = fusion::similarity(qvs, v).0;
+        let (mirror, dim) = (self.fields.title.mirror_path(), self.fields.title.dim());
+        match ids {
+            Some(ids) => cache.scan_ids(mirror, dim, n, ids, visit)?,
+            None => cache.scan(mirror, dim, n, visit)?,
+        }
+        Ok(scores)
+    }
+
+    /// One review's score from its title and body similarities.
+    pub fn combine(&self, title: f32, body: f32) -> f32 {
+        match self.mode {
+            FieldScoring::Weighted { title: wt, body: wb } => (wt * title + wb * body) / (wt + wb),
+            FieldScoring::Max | FieldScoring::Combined => title.max(body),
+        }
+    }
+
+    /// Review `id`'s score, and the vector whose products with the query explain it: the
+    /// better field's for `max`, the weighted mean of both for `weighted`.
+    pub fn explain(&self, qvs: &[Vec<f32>], id: usize) -> Result<Option<(f32, Vec<f32>)>> {
+        let Some(body) = self.fields.body.read_mirror(id)? else { return Ok(None) };
+        let title = self.fields.title.read_mirror(id)?.unwrap_or_else(|| vec![0.0; body.len()]);
+        let (ts, bs) = (fusion::similarity(qvs, &title).0, fusion::similarity(qvs, &body).0);
+        let v = match self.mode {
+            FieldScoring::Weighted { title: wt, body: wb } => {
+                title.iter().zip(&body).map(|(t, b)| (wt * t + wb * b) / (wt + wb)).collect()
+            }
+            FieldScoring::Max | FieldScoring::Combined => if ts > bs { title } else { body },
+        };
+        Ok(Some((self.combine(ts, bs), v)))
+    }
+}
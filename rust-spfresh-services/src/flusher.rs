@@ -0,0 +1,77 @@
+use crate::{
+    config::{DurabilityConfig, SyncPolicy},
+    AppState,
+};
+use anyhow::Result;
+use parking_lot::Mutex;
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Weak,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Appended files whose `SyncPolicy` can leave writes unsynced.
+pub trait Flush: Send + Sync {
+    /// Syncs everything appended so far.
+    fn flush(&self) -> Result<()>;
+}
+
+/// Every index opened by this process; dropped ones are pruned as new ones register.
+static OPEN: Mutex<Vec<Weak<dyn Flush>>> = Mutex::new(Vec::new());
+/// Unix seconds of the last pass that synced every index; 0 before the first.
+static LAST_FLUSH: AtomicU64 = AtomicU64::new(0);
+
+/// Puts an index in the background flusher's care for as long as it is alive.
+pub fn register(index: Weak<dyn Flush>) {
+    let mut open = OPEN.lock();
+    open.retain(|w| w.strong_count() > 0);
+    open.push(index);
+}
+
+/// When everything appended before it was synced, if a pass has completed.
+pub fn last_flush() -> Option<u64> {
+    Some(LAST_FLUSH.load(Ordering::Relaxed)).filter(|&t| t > 0)
+}
+
+/// Time between passes; None when there is nothing for the flusher to do.
+fn period(cfg: &DurabilityConfig) -> Option<Duration> {
+    let ms = match cfg.sync {
+        SyncPolicy::NoSync => return None,
+        SyncPolicy::Interval { ms } => ms.max(1),
+        SyncPolicy::EveryWrite | SyncPolicy::EveryN { .. } => cfg.flush_interval_ms,
+    };
+    (ms > 0).then(|| Duration::from_millis(ms))
+}
+
+/// Syncs every open index. A failure is logged and the others are still synced, but the
+/// pass does not count as a flush.
+fn flush_all() -> bool {
+    let open: Vec<_> = OPEN.lock().iter().filter_map(Weak::upgrade).collect();
+    let mut ok = true;
+    for index in open {
+        if let Err(e) = index.flush() {
+            tracing::error!("background flush failed: {e:#}");
+            ok = false;
+        }
+    }
+    ok
+}
+
+/// Background task syncing the indexes on the durability config's schedule, so no write
+/// waits for the fsyncs its policy does not demand of it.
+pub async fn run(st: AppState) {
+    let Some(every) = period(&st.config.durability) else { return };
+    let mut tick = tokio::time::interval(every);
+    tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        tick.tick().await;
+        let started = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        match tokio::task::spawn_blocking(flush_all).await {
+            Ok(true) => LAST_FLUSH.store(started, Ordering::Relaxed),
+            Ok(false) => {}
+            Err(e) => tracing::warn!("flush task failed: {e}"),
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use crate::{cosine, l2_normalize, Active, RankOpts, VecIndex};
+use anyhow::Result;
+use reviews_types::Fusion;
+use std::collections::HashSet;
+
+/// Most `queries` one search may fuse; each is embedded, and with `max` scored against
+/// every candidate.
+pub const MAX_QUERIES: usize = 16;
+
+/// Most example ids of each kind in a search by example.
+pub const MAX_EXAMPLES: usize = 100;
+
+/// A search by example reviews rather than by text.
+pub struct Examples {
+    pub positive: Vec<usize>,
+    pub negative: Vec<usize>,
+    pub negative_weight: f32,
+    /// Both lists, left out of the hits.
+    pub ids: HashSet<usize>,
+}
+
+impl Examples {
+    /// mean(positive) - negative_weight * mean(negative), normalised so scores stay cosines.
+    fn vector(&self, vindex: &dyn VecIndex) -> Result<Vec<f32>> {
+        let mut v = vec![0f32; vindex.dim()];
+        for (ids, scale) in [(&self.positive, 1.0), (&self.negative, -self.negative_weight)] {
+            for &id in ids {
+                let Some(ev) = vindex.read_mirror(id)? else { anyhow::bail!("review {id} has no vector") };
+                for (x, e) in v.iter_mut().zip(&ev) { *x += scale * e / ids.len() as f32; }
+            }
+        }
+        l2_normalize(&mut v);
+        Ok(v)
+    }
+}
+
+/// The vectors candidates are scored against. A weighted mean of cosines is the cosine
+/// with the weighted mean of the query vectors, so `mean` needs one vector; `max` keeps
+/// one per query.
+pub fn query_vectors(active: &Active, opts: &RankOpts) -> Result<Vec<Vec<f32>>> {
+    if let Some(ex) = &opts.examples { return Ok(vec![ex.vector(active.vindex.as_ref())?]); }
+    let qvs = opts.queries.iter()
+        .map(|(text, _)| active.embedder.embed_query(text))
+        .collect::<Result<Vec<_>>>()?;
+    if opts.fusion == Fusion::Max || qvs.len() == 1 { return Ok(qvs); }
+    let total: f32 = opts.queries.iter().map(|(_, w)| w).sum();
+    let mut mean = vec![0f32; qvs[0].len()];
+    for (qv, (_, w)) in qvs.iter().zip(&opts.queries) {
+        for (m, x) in mean.iter_mut().zip(qv) { *m += x * w / total; }
+    }
+    Ok(vec![mean])
+}
+
+/// Fused similarity of `v` and the index of the query vector it came from.
+pub fn similarity(qvs: &[Vec<f32>], v: &[f32]) -> (f32, usize) {
+    qvs.iter().enumerate()
+        .map(|(i, q)| (cosine(q, v), i))
+        .fold((f32::NEG_INFINITY, 0), |best, cur| if cur.0 > best.0 { cur } else { best })
+}
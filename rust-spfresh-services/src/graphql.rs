@@ -0,0 +1,176 @@
+use crate::{blocking, listing, search_in, slow_log::SlowQuery, ApiError, AppState, RankOpts, Review, SearchStats};
+use async_graphql::{
+    http::GraphiQLSource, Context, EmptyMutation, EmptySubscription, Object, Result, Schema,
+    SimpleObject,
+};
+use axum::{extract::State, response::Html, Json};
+use std::{collections::BTreeMap, time::Instant};
+
+pub type ReviewsSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+pub fn schema(st: AppState) -> ReviewsSchema {
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .data(st)
+        .limit_depth(8)
+        .limit_complexity(500)
+        .finish()
+}
+
+/// POST /graphql
+pub async fn graphql(State(schema): State<ReviewsSchema>, Json(req): Json<async_graphql::Request>) -> Json<async_graphql::Response> {
+    Json(schema.execute(req).await)
+}
+
+/// GET /graphql — GraphiQL explorer.
+pub async fn graphiql() -> Html<String> {
+    Html(GraphiQLSource::build().endpoint("/graphql").finish())
+}
+
+impl From<ApiError> for async_graphql::Error {
+    fn from(e: ApiError) -> Self { async_graphql::Error::new(e.msg) }
+}
+
+#[derive(SimpleObject)]
+struct GqlReview {
+    id: usize,
+    title: String,
+    body: String,
+    product_id: String,
+    rating: i32,
+    /// Seconds since the epoch, when known.
+    created_at: Option<u64>,
+}
+
+impl GqlReview {
+    fn new(id: usize, r: Review) -> Self {
+        Self {
+            id,
+            title: r.review_title,
+            body: r.review_body,
+            product_id: r.product_id,
+            rating: r.review_rating,
+            created_at: r.created_at,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+struct ReviewPage {
+    items: Vec<GqlReview>,
+    next_cursor: Option<String>,
+}
+
+#[derive(SimpleObject)]
+struct Hit {
+    score: f32,
+    review: GqlReview,
+}
+
+#[derive(SimpleObject)]
+struct RatingCount { rating: i32, count: usize }
+
+#[derive(SimpleObject)]
+struct ProductCount { product_id: String, count: usize }
+
+#[derive(SimpleObject)]
+struct ProductStats {
+    product_id: String,
+    count: usize,
+    average_rating: Option<f64>,
+    ratings: Vec<RatingCount>,
+}
+
+#[derive(SimpleObject)]
+struct Facets {
+    products: Vec<ProductCount>,
+    ratings: Vec<RatingCount>,
+}
+
+fn rating_counts(m: BTreeMap<i32, usize>) -> Vec<RatingCount> {
+    m.into_iter().map(|(rating, count)| RatingCount { rating, count }).collect()
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// None for a deleted review or a version an upsert superseded, as in GET /reviews.
+    async fn review(&self, ctx: &Context<'_>, id: usize) -> Result<Option<GqlReview>> {
+        let st = ctx.data::<AppState>()?.clone();
+        Ok(blocking(move || {
+            if !st.meta.is_live(id) { return Ok(None); }
+            Ok(st.meta.read_review_by_line(id).ok().map(|r| GqlReview::new(id, r)))
+        }).await?)
+    }
+
+    /// Cursor-paginated listing, same cursors as GET /reviews.
+    async fn reviews(&self, ctx: &Context<'_>, first: Option<usize>, after: Option<String>) -> Result<ReviewPage> {
+        let st = ctx.data::<AppState>()?.clone();
+        let (rows, next_cursor) = blocking(move || listing::page(&st, after.as_deref(), first)).await?;
+        let items = rows.into_iter().map(|(id, r)| GqlReview::new(id, r)).collect();
+        Ok(ReviewPage { items, next_cursor })
+    }
+
+    async fn search(&self, ctx: &Context<'_>, query: String, top_k: Option<usize>) -> Result<Vec<Hit>> {
+        let st = ctx.data::<AppState>()?.clone();
+        let hits = blocking(move || {
+            let (started, mut stats) = (Instant::now(), SearchStats::default());
+            let active = st.active.read().clone();
+            let k = st.config.search.top_k(top_k);
+            let opts = RankOpts::plain(&query, k);
+            let hits = search_in(&st.meta, &st.vcache, &active, &opts, &mut stats);
+            let elapsed = started.elapsed();
+            stats.total_ms = elapsed.as_secs_f64() * 1e3;
+            st.slow_log.record(elapsed, SlowQuery {
+                endpoint: "/graphql search",
+                query: &query,
+                collection: None,
+                top_k: k,
+                hits: hits.len(),
+                stages: &stats,
+            });
+            Ok(hits)
+        }).await?;
+        Ok(hits.into_iter().map(|h| Hit { score: h.score, review: GqlReview::new(h.id, h.review) }).collect())
+    }
+
+    async fn product_stats(&self, ctx: &Context<'_>, product_id: String) -> Result<ProductStats> {
+        let st = ctx.data::<AppState>()?.clone();
+        Ok(blocking(move || {
+            let (mut count, mut sum, mut ratings) = (0usize, 0i64, BTreeMap::new());
+            st.meta.for_each_in(0..usize::MAX, |id, r| {
+                let Some(r) = r.filter(|_| st.meta.is_live(id)) else { return Ok(()) };
+                if r.product_id == product_id {
+                    count += 1;
+                    sum += r.review_rating as i64;
+                    *ratings.entry(r.review_rating).or_insert(0) += 1;
+                }
+                Ok(())
+            })?;
+            let average_rating = (count > 0).then(|| sum as f64 / count as f64);
+            Ok(ProductStats { product_id, count, average_rating, ratings: rating_counts(ratings) })
+        }).await?)
+    }
+
+    /// Review counts per product (most reviewed first, capped at `limit`) and per rating.
+    async fn facets(&self, ctx: &Context<'_>, limit: Option<usize>) -> Result<Facets> {
+        let st = ctx.data::<AppState>()?.clone();
+        let (products, ratings) = blocking(move || {
+            let (mut products, mut ratings) = (BTreeMap::<String, usize>::new(), BTreeMap::new());
+            st.meta.for_each_in(0..usize::MAX, |id, r| {
+                let Some(r) = r.filter(|_| st.meta.is_live(id)) else { return Ok(()) };
+                *products.entry(r.product_id).or_insert(0) += 1;
+                *ratings.entry(r.review_rating).or_insert(0) += 1;
+                Ok(())
+            })?;
+            Ok((products, ratings))
+        }).await?;
+        let mut products: Vec<ProductCount> = products
+            .into_iter()
+            .map(|(product_id, count)| ProductCount { product_id, count })
+            .collect();
+        products.sort_by_key(|p| std::cmp::Reverse(p.count));
+        products.truncate(limit.unwrap_or(50));
+        Ok(Facets { products, ratings: rating_counts(ratings) })
+    }
+}
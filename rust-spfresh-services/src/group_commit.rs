@@ -0,0 +1,210 @@
+use crate::{
+    config::{DurabilityConfig, SyncPolicy},
+    metrics::{self, Stage},
+};
+use anyhow::{anyhow, Result};
+use std::{
+    fs::File,
+    io::{self, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+
+/// Appends to one file from a dedicated thread. Appends arriving within `window` of the
+/// first pending one are coalesced into a single write, and the `SyncPolicy` decides
+/// which writes are followed by an fsync; the rest wait for a `sync` (the background
+/// flusher's) or the writer's end.
+///
+/// Appends land in submission order, and after a failed write every later append fails
+/// too (the file tail is unknown), so waiting on the last `Commit` of a run covers it all.
+pub struct GroupCommit {
+    tx: mpsc::Sender<Msg>,
+}
+
+enum Msg {
+    Append(Req),
+    /// Sync whatever was written before it.
+    Sync(mpsc::SyncSender<io::Result<()>>),
+}
+
+struct Req {
+    bytes: Vec<u8>,
+    done: mpsc::SyncSender<io::Result<()>>,
+}
+
+/// Resolves once the bytes of one `submit` are written (and synced, if the policy syncs
+/// that batch). Dropping it without waiting leaves the append in flight; write errors
+/// are still logged by the writer.
+#[must_use = "an append is only durable once its Commit has been waited on"]
+pub struct Commit(mpsc::Receiver<io::Result<()>>);
+
+impl Commit {
+    pub fn wait(self) -> Result<()> {
+        self.0.recv().map_err(|_| anyhow!("group commit writer stopped"))?.map_err(Into::into)
+    }
+}
+
+impl GroupCommit {
+    pub fn spawn(mut file: File, path: &Path, cfg: &DurabilityConfig) -> Result<Self> {
+        file.seek(SeekFrom::End(0))?;
+        let (tx, rx) = mpsc::channel();
+        let writer = Writer {
+            file,
+            path: path.to_path_buf(),
+            window: Duration::from_micros(cfg.group_commit_window_us),
+            max_batch_bytes: cfg.group_commit_max_bytes,
+            schedule: SyncSchedule::new(cfg.sync.clone()),
+            failed: None,
+        };
+        std::thread::Builder::new()
+            .name("group-commit".into())
+            .spawn(move || writer.run(rx))?;
+        Ok(Self { tx })
+    }
+
+    /// Queues `bytes` for appending. Appends land in submission order.
+    pub fn submit(&self, bytes: Vec<u8>) -> Commit {
+        let (done, rx) = mpsc::sync_channel(1);
+        if let Err(mpsc::SendError(Msg::Append(req))) = self.tx.send(Msg::Append(Req { bytes, done })) {
+            let _ = req.done.send(Err(io::Error::other("group commit writer stopped")));
+        }
+        Commit(rx)
+    }
+
+    /// Queues an fsync of everything submitted so far, if any of it is unsynced; resolves
+    /// once it is done.
+    pub fn sync(&self) -> Commit {
+        let (done, rx) = mpsc::sync_channel(1);
+        if let Err(mpsc::SendError(Msg::Sync(done))) = self.tx.send(Msg::Sync(done)) {
+            let _ = done.send(Err(io::Error::other("group commit writer stopped")));
+        }
+        Commit(rx)
+    }
+}
+
+/// Counts appends since the last sync and decides, per `SyncPolicy`, whether the append
+/// path syncs itself; `interval` leaves every sync to the background flusher (see `flusher`).
+/// Shared by the mirror writer and the spfresh index flush.
+pub struct SyncSchedule {
+    policy: SyncPolicy,
+    pending: usize,
+}
+
+impl SyncSchedule {
+    pub fn new(policy: SyncPolicy) -> Self {
+        Self { policy, pending: 0 }
+    }
+
+    /// Records `count` appends; true if a sync is due now.
+    pub fn record(&mut self, count: usize) -> bool {
+        self.pending += count;
+        match self.policy {
+            SyncPolicy::EveryWrite => true,
+            SyncPolicy::EveryN { n } => self.pending >= n,
+            SyncPolicy::Interval { .. } | SyncPolicy::NoSync => false,
+        }
+    }
+
+    pub fn synced(&mut self) {
+        self.pending = 0;
+    }
+
+    /// True while there are appends the policy wants synced eventually.
+    pub fn is_dirty(&self) -> bool {
+        self.pending > 0 && self.policy != SyncPolicy::NoSync
+    }
+}
+
+struct Writer {
+    file: File,
+    path: PathBuf,
+    window: Duration,
+    max_batch_bytes: usize,
+    schedule: SyncSchedule,
+    failed: Option<String>,
+}
+
+impl Writer {
+    /// Writer loop; ends when the owning `GroupCommit` is dropped, syncing anything left.
+    fn run(mut self, rx: mpsc::Receiver<Msg>) {
+        while let Ok(msg) = rx.recv() {
+            let sync = match msg {
+                Msg::Append(first) => self.commit_batch(first, &rx),
+                Msg::Sync(done) => Some(done),
+            };
+            if let Some(done) = sync { let _ = done.send(self.sync_requested()); }
+        }
+        if self.schedule.is_dirty() { let _ = self.sync(); }
+    }
+
+    /// Writes `first` and the appends following it within the window; returns the sync
+    /// request that ended the batch, if one did.
+    fn commit_batch(&mut self, first: Req, rx: &mpsc::Receiver<Msg>) -> Option<mpsc::SyncSender<io::Result<()>>> {
+        let deadline = Instant::now() + self.window;
+        let mut size = first.bytes.len();
+        let mut batch = vec![first];
+        let mut sync = None;
+        while size < self.max_batch_bytes {
+            match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(Msg::Append(req)) => { size += req.bytes.len(); batch.push(req); }
+                Ok(Msg::Sync(done)) => { sync = Some(done); break; }
+                Err(_) => break,
+            }
+        }
+        let mut buf = Vec::with_capacity(size);
+        for req in &batch { buf.extend_from_slice(&req.bytes); }
+        let res = match self.failed.clone() {
+            Some(e) => Err(io::Error::other(format!("mirror writer failed earlier: {e}"))),
+            None => self.append(&buf, batch.len()),
+        };
+        match &res {
+            Ok(len) => tracing::debug!("group commit: {} appends, +{} bytes -> {} @ {}", batch.len(), size, len, self.path.display()),
+            Err(e) => {
+                tracing::error!("group commit of {} appends to {} failed: {e}", batch.len(), self.path.display());
+                self.failed.get_or_insert_with(|| e.to_string());
+            }
+        }
+        for req in batch {
+            let _ = req.done.send(res.as_ref().map(|_| ()).map_err(|e| io::Error::new(e.kind(), e.to_string())));
+        }
+        sync
+    }
+
+    /// A `GroupCommit::sync`: fails like the appends after a failed write would.
+    fn sync_requested(&mut self) -> io::Result<()> {
+        match &self.failed {
+            Some(e) => Err(io::Error::other(format!("mirror writer failed earlier: {e}"))),
+            None if self.schedule.is_dirty() => self.sync(),
+            None => Ok(()),
+        }
+    }
+
+    /// Appends one batch of `n` records, syncing if the policy says so.
+    fn append(&mut self, buf: &[u8], n: usize) -> io::Result<u64> {
+        let _t = metrics::timer(Stage::MirrorWrite);
+        let len = append_checked(&mut self.file, buf)?;
+        if self.schedule.record(n) { self.sync()?; }
+        Ok(len)
+    }
+
+    fn sync(&mut self) -> io::Result<()> {
+        let res = self.file.sync_data();
+        match &res {
+            Ok(()) => self.schedule.synced(),
+            Err(e) => tracing::error!("fsync {} failed: {e}", self.path.display()),
+        }
+        res
+    }
+}
+
+/// Writes `buf` at the end of the file and checks the file grew by exactly that much.
+fn append_checked(file: &mut File, buf: &[u8]) -> io::Result<u64> {
+    let before = file.metadata()?.len();
+    file.write_all(buf)?;
+    let after = file.metadata()?.len();
+    if after != before + buf.len() as u64 {
+        return Err(io::Error::other(format!("short append: {before} -> {after} (expect +{})", buf.len())));
+    }
+    Ok(after)
+}
@@ -0,0 +1,162 @@
+use crate::{config::Config, import::Offline, Review};
+use anyhow::{Context, Result};
+use arrow_array::{cast::AsArray, types::{Float64Type, Int64Type}, Array, ArrayRef, RecordBatch};
+use arrow_cast::cast;
+use arrow_schema::{DataType, TimeUnit};
+use indicatif::{ProgressBar, ProgressStyle};
+use parquet::arrow::{arrow_reader::ParquetRecordBatchReaderBuilder, ProjectionMask};
+use std::{collections::BTreeMap, fs::File, path::Path, time::Duration};
+
+/// Review fields a column can fill.
+const FIELDS: [&str; 7] = ["review_title", "review_body", "product_id", "review_rating", "created_at", "external_id", "lang"];
+/// Rows per batch read from a shard; each is inserted under one write-gate hold.
+const BATCH_ROWS: usize = 1000;
+const USAGE: &str = "usage: import-hf <dataset> [--config <name>] [--split <name>] [--map <field>=<column>]... [--limit <rows>]";
+
+/// What to fetch and how its columns become reviews.
+struct Source {
+    dataset: String,
+    subset: String,
+    split: String,
+    /// Review field -> column.
+    fields: BTreeMap<String, String>,
+    rating_offset: f64,
+    limit: Option<usize>,
+}
+
+/// `import-hf <dataset> [--config <name>] [--split <name>] [--map <field>=<column>]...
+/// [--limit <rows>]`: loads a dataset from the Hugging Face hub into the data dir, offline
+/// and with a progress bar. The split's parquet shards (the hub's own conversion, so any
+/// dataset it can show works) are fetched and inserted one at a time; `[hf_import] fields`
+/// and `--map` say which column fills which review field.
+pub fn run(config: Config, args: &[String]) -> Result<()> {
+    let cfg = config.hf_import.clone();
+    let mut src = Source {
+        dataset: String::new(),
+        subset: "default".into(),
+        split: "train".into(),
+        fields: cfg.fields.clone(),
+        rating_offset: cfg.rating_offset,
+        limit: None,
+    };
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        let mut value = || it.next().with_context(|| format!("{arg} needs a value"));
+        match arg.as_str() {
+            "--config" => src.subset = value()?.clone(),
+            "--split" => src.split = value()?.clone(),
+            "--limit" => src.limit = Some(value()?.parse().context("--limit needs a number")?),
+            "--map" => {
+                let (field, column) = value()?.split_once('=').context("--map needs <field>=<column>")?;
+                src.fields.insert(field.to_string(), column.to_string());
+            }
+            flag if flag.starts_with("--") => anyhow::bail!("unknown import-hf option {flag} (--config, --split, --map, --limit)"),
+            name if src.dataset.is_empty() => src.dataset = name.to_string(),
+            _ => anyhow::bail!(USAGE),
+        }
+    }
+    anyhow::ensure!(!src.dataset.is_empty(), USAGE);
+    if let Some(field) = src.fields.keys().find(|f| !FIELDS.contains(&f.as_str())) {
+        anyhow::bail!("cannot map to '{field}' (one of {})", FIELDS.join(", "));
+    }
+    src.fields.retain(|_, column| !column.is_empty());
+
+    let timeout = Duration::from_millis(cfg.timeout_ms);
+    let agent = ureq::AgentBuilder::new().timeout_connect(timeout).timeout_read(timeout).build();
+    let token = std::env::var(&cfg.token_env).ok().filter(|t| !t.is_empty());
+    let get = |url: &str| {
+        let req = agent.get(url);
+        match &token {
+            Some(t) => req.set("Authorization", &format!("Bearer {t}")),
+            None => req,
+        }
+        .call()
+        .with_context(|| format!("GET {url}"))
+    };
+    let list = format!("{}/api/datasets/{}/parquet/{}/{}", cfg.endpoint.trim_end_matches('/'), src.dataset, src.subset, src.split);
+    let shards: Vec<String> = get(&list)?.into_json().with_context(|| format!("{list}: not a list of shard URLs"))?;
+    anyhow::ensure!(!shards.is_empty(), "{} has no parquet shards for {}/{}", src.dataset, src.subset, src.split);
+
+    let bar = ProgressBar::new(shards.len() as u64)
+        .with_style(ProgressStyle::with_template("{bar:40} {pos}/{len} shards {elapsed} {msg}")?);
+    let mut import = Offline::open(config, bar)?;
+    let mut rows = 0;
+    for url in &shards {
+        let tmp = std::env::temp_dir().join(format!("hf-import-{}.parquet", uuid::Uuid::new_v4().simple()));
+        let res = std::io::copy(&mut get(url)?.into_reader(), &mut File::create(&tmp)?)
+            .with_context(|| format!("download {url}"))
+            .and_then(|_| import_shard(&mut import, &tmp, &src, &mut rows));
+        let _ = std::fs::remove_file(&tmp);
+        res.with_context(|| format!("shard {url}"))?;
+        import.bar.inc(1);
+        if src.limit.is_some_and(|l| rows >= l) { break; }
+    }
+    import.finish(&format!("{} ({}/{})", src.dataset, src.subset, src.split));
+    Ok(())
+}
+
+/// Inserts the rows of one downloaded shard, up to what is left of the limit; `rows`
+/// counts the rows read so far, for numbering failures.
+fn import_shard(import: &mut Offline, path: &Path, src: &Source, rows: &mut usize) -> Result<()> {
+    let builder = ParquetRecordBatchReaderBuilder::try_new(File::open(path)?)?;
+    let schema = builder.schema().clone();
+    let mut roots = Vec::new();
+    for column in src.fields.values() {
+        let Some((i, _)) = schema.column_with_name(column) else {
+            let have: Vec<&str> = schema.fields().iter().map(|f| f.name().as_str()).collect();
+            anyhow::bail!("no column '{column}' (the dataset has {})", have.join(", "));
+        };
+        roots.push(i);
+    }
+    let mask = ProjectionMask::roots(builder.parquet_schema(), roots);
+    for batch in builder.with_projection(mask).with_batch_size(BATCH_ROWS).build()? {
+        let mut batch = batch?;
+        let left = src.limit.map_or(usize::MAX, |l| l.saturating_sub(*rows));
+        if left == 0 { break; }
+        if batch.num_rows() > left { batch = batch.slice(0, left); }
+        let reviews = reviews(&batch, src)?;
+        let first = *rows + 1;
+        *rows += batch.num_rows();
+        import.insert(reviews.into_iter().enumerate().map(|(i, r)| (first + i, r.map(Some))))?;
+    }
+    Ok(())
+}
+
+/// The reviews in `batch`, or why a row makes none.
+fn reviews(batch: &RecordBatch, src: &Source) -> Result<Vec<Result<Review, String>>> {
+    let column = |field: &str| src.fields.get(field).and_then(|c| batch.column_by_name(c));
+    let text = |field: &str| column(field).map(|c| cast(c, &DataType::Utf8).with_context(|| format!("{field}: column not castable to text"))).transpose();
+    let (title, body, product, external_id, lang) =
+        (text("review_title")?, text("review_body")?, text("product_id")?, text("external_id")?, text("lang")?);
+    let rating = column("review_rating").map(|c| cast(c, &DataType::Float64).context("review_rating: column not numeric")).transpose()?;
+    let created_at = column("created_at").map(seconds).transpose()?;
+    let str_at = |a: &Option<ArrayRef>, i: usize| a.as_ref().filter(|a| a.is_valid(i)).map(|a| a.as_string::<i32>().value(i).to_string());
+    Ok((0..batch.num_rows()).map(|i| {
+        let review_rating = match &rating {
+            None => 0,
+            Some(a) if a.is_null(i) => return Err("review_rating is null".to_string()),
+            Some(a) => {
+                let stars = a.as_primitive::<Float64Type>().value(i) + src.rating_offset;
+                if !stars.is_finite() || stars.abs() > i32::MAX as f64 { return Err(format!("bad rating {stars}")); }
+                stars.round() as i32
+            }
+        };
+        Ok(Review {
+            review_title: str_at(&title, i).unwrap_or_default(),
+            review_body: str_at(&body, i).unwrap_or_default(),
+            product_id: str_at(&product, i).unwrap_or_else(|| src.dataset.clone()),
+            review_rating,
+            created_at: created_at.as_ref().filter(|a| a.is_valid(i)).and_then(|a| u64::try_from(a.as_primitive::<Int64Type>().value(i)).ok()),
+            external_id: str_at(&external_id, i),
+            lang: str_at(&lang, i),
+            ..Default::default()
+        })
+    }).collect())
+}
+
+/// A `created_at` column as seconds since the epoch: integers are taken as seconds,
+/// timestamps, dates and date strings converted.
+fn seconds(c: &ArrayRef) -> Result<ArrayRef> {
+    let c = if c.data_type().is_integer() { c.clone() } else { cast(c, &DataType::Timestamp(TimeUnit::Second, None)).context("created_at: not a time")? };
+    Ok(cast(&c, &DataType::Int64)?)
+}
@@ -0,0 +1,102 @@
+use crate::{
+    audit::{Action, Actor, IdRanges},
+    blocking, jobs::JobHandle, now_secs, ApiError, AppState,
+};
+use anyhow::Result;
+use axum::{extract::State, http::StatusCode, Json};
+use reviews_types::{IdfReq, IdfResp};
+use std::{sync::Arc, time::Duration};
+
+const JOB_KIND: &str = "idf";
+const DAY_SECS: f64 = 86_400.0;
+
+/// POST /admin/idf — recomputes the active embedder's document frequencies from the live
+/// reviews, as a job. Inserts only ever add to them; deletes and replacements never take
+/// anything away, and they start from zero on every restart.
+pub async fn start_refresh(State(st): State<AppState>, actor: Actor, req: Option<Json<IdfReq>>) -> Result<(StatusCode, Json<IdfResp>), ApiError> {
+    let half_life = req.and_then(|Json(r)| r.half_life_days).unwrap_or(st.config.idf.half_life_days);
+    if !half_life.is_finite() || half_life < 0.0 {
+        return Err(ApiError::bad_request("half_life_days must be 0 or more"));
+    }
+    if st.embedder().doc_dims("").is_none() {
+        return Err(ApiError::bad_request("the active embedder keeps no document frequencies"));
+    }
+    let (job, total) = start(&st, half_life).await?;
+    let job_id = job.id;
+    tokio::task::spawn_blocking(move || {
+        let res = st.audit.record(&actor, Action::RefreshIdf, IdRanges::default(), Some(format!("job {job_id}, half_life_days={half_life}")))
+            .and_then(|()| run(&st, &job, total, half_life));
+        if let Err(e) = &res { tracing::error!("idf refresh {} failed: {e}", job.id); }
+        job.finish(&res);
+    });
+    Ok((StatusCode::ACCEPTED, Json(IdfResp { job_id })))
+}
+
+/// Registers the job, 409 if one is running; returns it with the number of reviews it
+/// starts out covering.
+async fn start(st: &AppState, half_life: f64) -> Result<(JobHandle, usize), ApiError> {
+    let count_st = st.clone();
+    let total = blocking(move || Ok(count_st.meta.count()?)).await?;
+    let job = st.jobs.try_start(JOB_KIND, total).ok_or_else(|| ApiError::conflict("an idf refresh is already running"))?;
+    tracing::info!("idf refresh over {total} reviews, half_life_days={half_life}");
+    Ok((job, total))
+}
+
+/// With `[idf] refresh_interval_secs`, refreshes on startup and then on that interval,
+/// skipping a tick while a refresh is still running.
+pub async fn refresh_periodically(st: AppState) {
+    let cfg = st.config.idf.clone();
+    if cfg.refresh_interval_secs == 0 || st.embedder().doc_dims("").is_none() { return; }
+    let mut tick = tokio::time::interval(Duration::from_secs(cfg.refresh_interval_secs));
+    loop {
+        tick.tick().await;
+        let (job, total) = match start(&st, cfg.half_life_days).await {
+            Ok(started) => started,
+            Err(e) => { tracing::warn!("idf refresh not started: {}", e.msg); continue; }
+        };
+        let run_st = st.clone();
+        let res = tokio::task::spawn_blocking(move || {
+            let res = run(&run_st, &job, total, cfg.half_life_days);
+            job.finish(&res);
+            res
+        }).await;
+        match res {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => tracing::error!("idf refresh failed: {e}"),
+            Err(e) => tracing::error!("idf refresh task failed: {e}"),
+        }
+    }
+}
+
+/// Counts every live review towards the dimensions it uses, weighted by
+/// 0.5^(age / half-life) when a half-life is set (reviews without `created_at` count
+/// fully), then swaps the result in. Only query weighting changes; stored vectors keep the
+/// weights they were embedded with until a reindex.
+fn run(st: &AppState, job: &JobHandle, total: usize, half_life_days: f64) -> Result<()> {
+    let active = st.active.read().clone();
+    let now = now_secs();
+    let weight = |created_at: Option<u64>| match created_at {
+        Some(t) if half_life_days > 0.0 => 0.5f64.powf(now.saturating_sub(t) as f64 / (half_life_days * DAY_SECS)) as f32,
+        _ => 1.0,
+    };
+    let (mut df, mut docs) = (vec![0f32; active.vindex.dim()], 0f32);
+    let mut count = |range| st.meta.for_each_in(range, |id, r| {
+        if let Some(r) = r.filter(|_| st.meta.is_live(id)) {
+            let w = weight(r.created_at);
+            for i in active.embedder.doc_dims(&r.embed_text()).unwrap_or_default() { df[i] += w; }
+            docs += w;
+        }
+        job.set_processed(id + 1);
+        Ok(())
+    });
+    let done = count(0..total)?;
+    // Inserts during the scan added to the frequencies being replaced; catching up under
+    // the gate and swapping before it is released loses none of them.
+    let _w = st.write_gate.lock();
+    let tail = count(done..usize::MAX)?;
+    job.set_total(done + tail);
+    anyhow::ensure!(Arc::ptr_eq(&st.embedder(), &active.embedder), "the active embedder was swapped (reindex) during the refresh");
+    active.embedder.set_doc_freqs(df, docs);
+    tracing::info!("idf refresh {}: {} reviews, {docs:.1} weighted documents", job.id, done + tail);
+    Ok(())
+}
@@ -0,0 +1,273 @@
+use crate::{
+    amazon,
+    audit::{Action, Actor, IdRanges},
+    blocking, codec,
+    config::Config,
+    dir_lock,
+    group_commit::Commit,
+    open_state, payload_of, ApiError, AppState, Review,
+};
+use anyhow::Context;
+use axum::{
+    body::Body,
+    extract::{Query, State},
+    Json,
+};
+use futures_util::StreamExt;
+use indicatif::{ProgressBar, ProgressStyle};
+use reviews_types::{ImportResp, LineError};
+use serde::Deserialize;
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Read},
+};
+
+/// At most this many per-line errors are echoed back; the rest are only counted.
+const MAX_REPORTED_ERRORS: usize = 100;
+/// Lines the `import` command inserts per write and audit entry.
+const CLI_CHUNK_LINES: usize = 1000;
+
+/// Layout of the lines to import.
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum Format {
+    /// One `Review` as JSON per line.
+    #[default]
+    Review,
+    /// The Amazon review JSON dumps (`reviewText`, `summary`, `asin`, `overall`, ...).
+    AmazonJson,
+    /// The Amazon Customer Reviews TSV files, header line first.
+    AmazonTsv,
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "review" => Ok(Self::Review),
+            "amazon-json" => Ok(Self::AmazonJson),
+            "amazon-tsv" => Ok(Self::AmazonTsv),
+            _ => Err(format!("unknown format '{s}' (review, amazon-json or amazon-tsv)")),
+        }
+    }
+}
+
+/// Turns lines of one import into reviews; a TSV import learns its columns from its first
+/// line.
+pub struct Parser {
+    format: Format,
+    columns: Option<amazon::TsvColumns>,
+}
+
+impl Parser {
+    pub fn new(format: Format) -> Self { Self { format, columns: None } }
+
+    /// None for the TSV header line.
+    fn parse(&mut self, line: &[u8]) -> Result<Option<Review>, String> {
+        match (self.format, &self.columns) {
+            (Format::Review, _) => serde_json::from_slice(line).map(Some).map_err(|e| e.to_string()),
+            (Format::AmazonJson, _) => amazon::from_json(line).map(Some),
+            (Format::AmazonTsv, Some(columns)) => columns.review(line).map(Some),
+            (Format::AmazonTsv, None) => {
+                self.columns = Some(amazon::TsvColumns::from_header(line)?);
+                Ok(None)
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ImportParams {
+    #[serde(default)]
+    format: Format,
+}
+
+/// POST /reviews/import?format= — NDJSON body (one `Review` per line, or with `format`
+/// an Amazon dataset's lines) consumed as a stream, so arbitrarily large uploads are never
+/// buffered in full. Malformed lines are skipped and reported; valid ones are inserted
+/// chunk by chunk as they arrive.
+pub async fn import_ndjson(State(st): State<AppState>, actor: Actor, Query(p): Query<ImportParams>, body: Body) -> Result<Json<ImportResp>, ApiError> {
+    let max_line = st.config.limits.import_line_bytes;
+    let mut resp = ImportResp { inserted: 0, failed: 0, errors: Vec::new() };
+    let mut parser = Parser::new(p.format);
+    let mut pending: Vec<u8> = Vec::new();
+    let mut line_no = 0usize;
+    let mut stream = body.into_data_stream();
+
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| ApiError::bad_request(format!("read body: {e}")))?;
+        pending.extend_from_slice(&chunk);
+        let Some(last_nl) = pending.iter().rposition(|&b| b == b'\n') else {
+            if pending.len() > max_line {
+                return Err(ApiError::payload_too_large(format!(
+                    "line {} exceeds {} bytes", line_no + 1, max_line
+                )));
+            }
+            continue;
+        };
+        let rest = pending.split_off(last_nl + 1);
+        pending.truncate(last_nl);
+        (line_no, resp, parser) = ingest(&st, &actor, std::mem::replace(&mut pending, rest), line_no, resp, parser).await?;
+    }
+    if !pending.is_empty() {
+        (_, resp, _) = ingest(&st, &actor, pending, line_no, resp, parser).await?;
+    }
+    tracing::info!("import: inserted={} failed={}", resp.inserted, resp.failed);
+    Ok(Json(resp))
+}
+
+/// Runs `ingest_lines` for one chunk on the blocking pool, threading the counters and the
+/// parser through. Each chunk that inserted anything gets its own audit entry.
+async fn ingest(st: &AppState, actor: &Actor, buf: Vec<u8>, line_no: usize, resp: ImportResp, parser: Parser) -> Result<(usize, ImportResp, Parser), ApiError> {
+    let (st, actor) = (st.clone(), actor.clone());
+    blocking(move || {
+        // Blank and malformed lines make this an upper bound on what the chunk inserts.
+        let lines = buf.split(|&b| b == b'\n').filter(|l| !l.iter().all(u8::is_ascii_whitespace)).count();
+        st.tenants.admit_write(&st.config.quotas, &actor.principal, lines as u64)?;
+        let (mut line_no, mut resp, mut parser, mut ids) = (line_no, resp, parser, IdRanges::default());
+        let inserted = resp.inserted;
+        // Waited on after the write gate is released, so other writers can join the fsync.
+        if let Some((c, bytes)) = ingest_lines(&st, &buf, &mut line_no, &mut resp, &mut parser, &mut ids)? {
+            c.wait()?;
+            st.audit.record(&actor, Action::Import, ids, None)?;
+            st.tenants.charge(&actor.principal, (resp.inserted - inserted) as u64, bytes)?;
+        }
+        Ok((line_no, resp, parser))
+    }).await
+}
+
+/// Inserts the valid lines of `buf`, returning the commit of the last mirror write and
+/// the bytes written.
+fn ingest_lines(st: &AppState, buf: &[u8], line_no: &mut usize, resp: &mut ImportResp, parser: &mut Parser, ids: &mut IdRanges) -> Result<Option<(Commit, u64)>, ApiError> {
+    let reviews = buf.split(|&b| b == b'\n').filter_map(|raw| {
+        *line_no += 1;
+        let raw = raw.strip_suffix(b"\r").unwrap_or(raw);
+        if raw.iter().all(u8::is_ascii_whitespace) { return None; }
+        Some((*line_no, parser.parse(raw)))
+    });
+    insert_all(st, reviews, resp, ids)
+}
+
+/// Inserts the reviews parsed, as one write of the writer, counting and reporting the
+/// failures by their line (or row) number; `Ok(None)` is a line with no review in it.
+/// Returns the commit of the last mirror write and the bytes written. Runs off the async
+/// runtime.
+fn insert_all(st: &AppState, reviews: impl Iterator<Item = (usize, Result<Option<Review>, String>)>, resp: &mut ImportResp, ids: &mut IdRanges) -> Result<Option<(Commit, u64)>, ApiError> {
+    let mut valid = Vec::new();
+    for (line, parsed) in reviews {
+        match parsed {
+            Ok(Some(r)) => valid.push(r),
+            Ok(None) => {}
+            Err(error) => {
+                resp.failed += 1;
+                if resp.errors.len() < MAX_REPORTED_ERRORS {
+                    resp.errors.push(LineError { line, error });
+                }
+            }
+        }
+    }
+    if valid.is_empty() { return Ok(None); }
+    let (last, written, bytes) = st.writer.submit_blocking({
+        let st = st.clone();
+        move || {
+            let (embedder, vindex) = (st.embedder(), st.vindex());
+            let (mut last, mut written, mut bytes) = (None, Vec::new(), 0u64);
+            for r in valid {
+                let vec = embedder.embed_index(&r.embed_text())?;
+                let (id, commit) = vindex.append_pending(&vec, payload_of(&r))?;
+                last = Some(commit);
+                written.push(id);
+                bytes += st.append_meta(&r)? + codec::record_len(vec.len()) as u64;
+                st.append_secondary(&r);
+            }
+            Ok((last, written, bytes))
+        }
+    })?;
+    resp.inserted += written.len();
+    for id in written { ids.push(id); }
+    Ok(last.map(|c| (c, bytes)))
+}
+
+/// An import run from the command line (`import`, `import-hf`): the data dir opened under
+/// its lock, entries audited as `cli`, and a progress bar whose message counts what went in.
+pub struct Offline {
+    st: AppState,
+    actor: Actor,
+    resp: ImportResp,
+    pub bar: ProgressBar,
+    _dir_lock: dir_lock::DirLock,
+}
+
+impl Offline {
+    /// Opens the configured data dir, creating it if need be; `bar` is shown from now on.
+    pub fn open(config: Config, bar: ProgressBar) -> anyhow::Result<Self> {
+        let data_dir = std::env::current_dir()?.join(&config.data_dir);
+        std::fs::create_dir_all(&data_dir)?;
+        let dir_lock = dir_lock::acquire(&data_dir)?;
+        let st = open_state(config, &data_dir, &Default::default())?;
+        let actor = Actor { principal: "cli".into(), request_id: uuid::Uuid::new_v4().to_string() };
+        Ok(Self { st, actor, resp: ImportResp { inserted: 0, failed: 0, errors: Vec::new() }, bar, _dir_lock: dir_lock })
+    }
+
+    /// Inserts one chunk, as one write and one audit entry.
+    #[cfg(feature = "hf-import")]
+    pub fn insert(&mut self, reviews: impl Iterator<Item = (usize, Result<Option<Review>, String>)>) -> anyhow::Result<()> {
+        let mut ids = IdRanges::default();
+        let written = insert_all(&self.st, reviews, &mut self.resp, &mut ids).map_err(|e| anyhow::anyhow!(e.msg))?;
+        self.audit(written, ids)
+    }
+
+    fn audit(&mut self, written: Option<(Commit, u64)>, ids: IdRanges) -> anyhow::Result<()> {
+        if let Some((c, _)) = written {
+            c.wait()?;
+            self.st.audit.record(&self.actor, Action::Import, ids, None)?;
+        }
+        self.bar.set_message(format!("{} imported, {} failed", self.resp.inserted, self.resp.failed));
+        Ok(())
+    }
+
+    /// Ends the progress bar and prints the failures and the totals.
+    pub fn finish(self, source: &str) {
+        self.bar.finish();
+        for e in &self.resp.errors { eprintln!("line {}: {}", e.line, e.error); }
+        println!("{} reviews imported from {source}, {} failed", self.resp.inserted, self.resp.failed);
+    }
+}
+
+/// `import [--format review|amazon-json|amazon-tsv] <file>`: the same import from a file
+/// (gzipped if its name ends in .gz), offline and with a progress bar, for seeding a data
+/// dir from a dataset dump.
+pub fn run(config: Config, args: &[String]) -> anyhow::Result<()> {
+    let (mut format, mut path) = (Format::Review, None);
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        match arg.as_str() {
+            "--format" => format = it.next().context("--format needs a value")?.parse().map_err(anyhow::Error::msg)?,
+            flag if flag.starts_with("--") => anyhow::bail!("unknown import option {flag} (--format)"),
+            file if path.is_none() => path = Some(file),
+            _ => anyhow::bail!("usage: import [--format review|amazon-json|amazon-tsv] <file>"),
+        }
+    }
+    let path = path.context("usage: import [--format review|amazon-json|amazon-tsv] <file>")?;
+    let file = File::open(path).with_context(|| format!("open {path}"))?;
+    let bar = ProgressBar::new(file.metadata()?.len())
+        .with_style(ProgressStyle::with_template("{bar:40} {percent:>3}% {binary_bytes}/{binary_total_bytes} {elapsed} {msg}")?);
+    let mut import = Offline::open(config, bar)?;
+    let input = import.bar.wrap_read(file);
+    let input: Box<dyn Read> = if path.ends_with(".gz") { Box::new(flate2::read::MultiGzDecoder::new(input)) } else { Box::new(input) };
+    let mut input = BufReader::new(input);
+    let (mut parser, mut line_no, mut buf) = (Parser::new(format), 0, Vec::new());
+    loop {
+        buf.clear();
+        for _ in 0..CLI_CHUNK_LINES {
+            if input.read_until(b'\n', &mut buf)? == 0 { break; }
+        }
+        if buf.is_empty() { break; }
+        let buf = buf.strip_suffix(b"\n").unwrap_or(&buf);
+        let mut ids = IdRanges::default();
+        let written = ingest_lines(&import.st, buf, &mut line_no, &mut import.resp, &mut parser, &mut ids).map_err(|e| anyhow::anyhow!(e.msg))?;
+        import.audit(written, ids)?;
+    }
+    import.finish(path);
+    Ok(())
+}
@@ -0,0 +1,80 @@
+use parking_lot::Mutex;
+use reviews_types::{Job, JobStatus};
+use std::{collections::HashMap, sync::Arc, time::{SystemTime, UNIX_EPOCH}};
+use uuid::Uuid;
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// In-memory registry of long-running admin jobs (reindex, ...).
+#[derive(Default)]
+pub struct JobRegistry {
+    jobs: Mutex<HashMap<Uuid, Job>>,
+}
+
+impl JobRegistry {
+    /// Registers a running job of `kind`, unless one is running already: checked and inserted
+    /// under one lock, so two concurrent requests cannot both start one.
+    pub fn try_start(self: &Arc<Self>, kind: &str, total: usize) -> Option<JobHandle> {
+        let mut jobs = self.jobs.lock();
+        if jobs.values().any(|j| j.kind == kind && j.status == JobStatus::Running) { return None; }
+        let id = Uuid::new_v4();
+        let job = Job {
+            id,
+            kind: kind.to_string(),
+            status: JobStatus::Running,
+            processed: 0,
+            total,
+            error: None,
+            started_at: now_secs(),
+            finished_at: None,
+        };
+        jobs.insert(id, job);
+        Some(JobHandle { id, reg: self.clone() })
+    }
+    pub fn get(&self, id: Uuid) -> Option<Job> { self.jobs.lock().get(&id).cloned() }
+    pub fn list(&self) -> Vec<Job> {
+        let mut v: Vec<Job> = self.jobs.lock().values().cloned().collect();
+        v.sort_by_key(|j| std::cmp::Reverse(j.started_at));
+        v
+    }
+}
+
+/// Progress reporter handed to the worker running a job.
+pub struct JobHandle {
+    pub id: Uuid,
+    reg: Arc<JobRegistry>,
+}
+
+impl JobHandle {
+    fn update(&self, f: impl FnOnce(&mut Job)) {
+        if let Some(j) = self.reg.jobs.lock().get_mut(&self.id) { f(j); }
+    }
+    pub fn set_total(&self, total: usize) { self.update(|j| j.total = total); }
+    pub fn set_processed(&self, processed: usize) { self.update(|j| j.processed = processed); }
+    pub fn finish(&self, res: &anyhow::Result<()>) {
+        self.update(|j| {
+            j.finished_at = Some(now_secs());
+            match res {
+                Ok(()) => j.status = JobStatus::Done,
+                Err(e) => { j.status = JobStatus::Failed; j.error = Some(e.to_string()); }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_running_job_per_kind() {
+        let reg = Arc::new(JobRegistry::default());
+        let job = reg.try_start("reindex", 3).unwrap();
+        assert!(reg.try_start("reindex", 3).is_none());
+        assert!(reg.try_start("purge", 0).is_some());
+        job.finish(&Ok(()));
+        assert!(reg.try_start("reindex", 3).is_some());
+    }
+}
@@ -0,0 +1,62 @@
+use parking_lot::RwLock;
+use std::collections::HashMap;
+
+/// In-memory inverted index over review text, term -> ids of the reviews containing it.
+/// Built from reviews.jsonl on startup and extended by every metadata append, so its ids
+/// are metadata line numbers. Used to narrow a search to reviews sharing a query term
+/// before any vector is scored.
+#[derive(Default)]
+pub struct KeywordIndex {
+    inner: RwLock<Postings>,
+}
+
+#[derive(Default)]
+struct Postings {
+    terms: HashMap<String, Vec<u32>>,
+    /// Id the next added review gets.
+    next_id: u32,
+}
+
+/// Tokens of `text` by its language's pipeline (`lang::terms`), the same tokens the
+/// TF-IDF embedder sees.
+pub fn terms(text: &str) -> impl Iterator<Item = String> + '_ {
+    crate::lang::terms(text)
+}
+
+impl KeywordIndex {
+    /// Indexes the next review; `None` (an unreadable metadata line) only takes up its id.
+    pub fn push(&self, text: Option<&str>) {
+        let mut p = self.inner.write();
+        let id = p.next_id;
+        p.next_id += 1;
+        let Some(text) = text else { return };
+        for term in terms(text) {
+            let ids = p.terms.entry(term).or_default();
+            // Ids only grow, so a repeated term in the same review is the last entry.
+            if ids.last() != Some(&id) { ids.push(id); }
+        }
+    }
+
+    /// Up to `limit` ids of reviews sharing at least one term with `query`, those matching
+    /// the most distinct query terms first (ties: lower id first). Ascending id order.
+    pub fn candidates(&self, query: &str, limit: usize) -> Vec<usize> {
+        let p = self.inner.read();
+        let mut query_terms: Vec<String> = terms(query).collect();
+        query_terms.sort();
+        query_terms.dedup();
+        let mut hits: HashMap<u32, u32> = HashMap::new();
+        for t in &query_terms {
+            for &id in p.terms.get(t).map(Vec::as_slice).unwrap_or_default() {
+                *hits.entry(id).or_default() += 1;
+            }
+        }
+        let mut ranked: Vec<(u32, u32)> = hits.into_iter().collect();
+        if ranked.len() > limit {
+            ranked.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+            ranked.truncate(limit);
+        }
+        let mut ids: Vec<usize> = ranked.into_iter().map(|(id, _)| id as usize).collect();
+        ids.sort_unstable();
+        ids
+    }
+}
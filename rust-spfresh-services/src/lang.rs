@@ -0,0 +1,68 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Codes `detect` assigns. Clients may store any other code on insert.
+pub const THAI: &str = "th";
+pub const ENGLISH: &str = "en";
+pub const UNDETERMINED: &str = "und";
+
+/// Common English words; Latin-script text with none of them is not called English.
+const ENGLISH_WORDS: &[&str] = &[
+    "a", "about", "after", "all", "and", "are", "as", "at", "be", "been", "but", "by", "can", "did", "do", "does",
+    "for", "from", "good", "great", "had", "has", "have", "i", "if", "in", "is", "it", "its", "it's", "my", "not",
+    "of", "on", "or", "so", "than", "that", "the", "this", "to", "too", "very", "was", "we", "were", "what",
+    "when", "will", "with", "would", "you",
+];
+
+/// Thai block letters, vowel signs, tone marks and digits.
+fn is_thai(c: char) -> bool { ('\u{0E01}'..='\u{0E5B}').contains(&c) }
+
+/// The language of `text` by its script: `th` when Thai letters are at least as many as
+/// Latin ones, `en` for Latin script using common English words, `und` otherwise.
+pub fn detect(text: &str) -> &'static str {
+    let (thai, latin) = text.chars().fold((0usize, 0usize), |(t, l), c| {
+        if is_thai(c) { (t + 1, l) } else if c.is_alphabetic() && c <= '\u{024F}' { (t, l + 1) } else { (t, l) }
+    });
+    if thai > 0 && thai >= latin { return THAI; }
+    let english = latin > 0 && text.split(|c: char| !(c.is_alphanumeric() || c == '\''))
+        .any(|w| ENGLISH_WORDS.contains(&w.to_lowercase().as_str()));
+    if english { ENGLISH } else { UNDETERMINED }
+}
+
+/// The sentences of `text`, trimmed: split at `.`, `!`, `?`, `;` and line breaks. Thai,
+/// which marks sentence ends with spaces, comes back in runs between those.
+pub fn sentences(text: &str) -> impl Iterator<Item = &str> {
+    text.split(['.', '!', '?', ';', '\n']).map(str::trim).filter(|s| !s.is_empty())
+}
+
+/// Index and query tokens of `text`, routed by its detected language so a query meets
+/// reviews in the same pipeline. Latin: lowercased alphanumeric runs. Thai, written
+/// without spaces between words: overlapping pairs of characters (grapheme clusters, so
+/// vowel signs and tone marks stay on their consonant), with any non-Thai runs in it
+/// tokenized as Latin.
+pub fn terms(text: &str) -> Box<dyn Iterator<Item = String> + '_> {
+    if detect(text) != THAI { return Box::new(latin_terms(text)); }
+    let runs = text.split(|c: char| !(c.is_alphanumeric() || is_thai(c))).filter(|t| !t.is_empty());
+    Box::new(runs.flat_map(|run| {
+        let mut out = Vec::new();
+        let mut rest = run;
+        while let Some(c) = rest.chars().next() {
+            let thai = is_thai(c);
+            let end = rest.find(|c| is_thai(c) != thai).unwrap_or(rest.len());
+            let (part, tail) = rest.split_at(end);
+            if thai { out.extend(thai_pairs(part)); } else { out.extend(latin_terms(part)); }
+            rest = tail;
+        }
+        out
+    }))
+}
+
+fn latin_terms(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric()).filter(|t| !t.is_empty()).map(str::to_lowercase)
+}
+
+/// A one-cluster run is its own token.
+fn thai_pairs(run: &str) -> Vec<String> {
+    let clusters: Vec<&str> = run.graphemes(true).collect();
+    if clusters.len() < 2 { return clusters.into_iter().map(str::to_string).collect(); }
+    clusters.windows(2).map(|w| w.concat()).collect()
+}
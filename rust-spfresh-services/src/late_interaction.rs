@@ -0,0 +1,120 @@
+use crate::{
+    build_embedder, collections,
+    config::{DurabilityConfig, LateInteractionConfig},
+    cosine, payload_of, reembed, spfresh_index, startup, vcache::VectorCache, Embedder, MetaStore, VecIndex,
+};
+use anyhow::Result;
+use reviews_types::Review;
+use std::{collections::HashMap, path::Path, sync::Arc};
+
+/// Most query words embedded as query tokens; the rest of a long query is ignored.
+pub const MAX_QUERY_TOKENS: usize = 32;
+
+/// ColBERT-style late interaction. Each review keeps `vectors` embeddings, one per run of
+/// its words, back to back in one record of a mirror aligned with the metadata's ids (it
+/// may lag, like the shadow index, but never leads). A search embeds each query word on
+/// its own and scores a review by the mean, over the words, of the best cosine with any
+/// of the review's vectors. Too slow to run over every review, it re-ranks the top of the
+/// single-vector ranking.
+pub struct LateInteraction {
+    embedder: Arc<dyn Embedder>,
+    index: Arc<dyn VecIndex>,
+    vectors: usize,
+    dim: usize,
+    /// Hits re-ranked per hit asked for.
+    pub overfetch: usize,
+}
+
+impl LateInteraction {
+    /// Opens the index, embedding the reviews it is missing.
+    pub fn open(
+        data_dir: &Path,
+        lc: &LateInteractionConfig,
+        meta: &MetaStore,
+        durability: &DurabilityConfig,
+        progress: &startup::Progress,
+    ) -> Result<Self> {
+        anyhow::ensure!(lc.vectors > 0 && lc.overfetch > 0, "[late_interaction] needs vectors and overfetch of at least 1");
+        let dir = data_dir.join(lc.index_dir());
+        let index: Arc<dyn VecIndex> = Arc::new(spfresh_index::DefaultIndex::open(&dir, lc.record_dim(), durability)?);
+        collections::record_embedder(&dir, &lc.embedder)?;
+        let embedder = build_embedder(&lc.embedder, &data_dir.join(&lc.dir))?;
+        let late = Self { embedder, index, vectors: lc.vectors, dim: lc.embedder.dim(), overfetch: lc.overfetch };
+
+        let have = late.index.len()?;
+        progress.set_total(meta.attrs.len().saturating_sub(have));
+        let mut last = None;
+        let filled = meta.for_each_in(have..usize::MAX, |id, r| {
+            let rec = match &r {
+                Some(r) => late.record(&r.embed_text())?,
+                None => vec![0.0; late.index.dim()], // placeholder keeps ids aligned with the metadata
+            };
+            last = Some(late.index.append_pending(&rec, r.as_ref().and_then(payload_of))?.1);
+            progress.set_done(id + 1 - have);
+            Ok(())
+        })?;
+        if let Some(c) = last { c.wait()?; }
+        tracing::info!("late interaction index {} ({:?}): {} reviews, back-filled {}", dir.display(), lc.embedder, have + filled, filled);
+        Ok(late)
+    }
+
+    /// Mirrors a primary write; like the shadow's, failures are logged and not waited on.
+    pub fn append(&self, review: &Review) {
+        let res = self.record(&review.embed_text()).and_then(|rec| self.index.append_pending(&rec, payload_of(review)));
+        if let Err(e) = res { tracing::warn!("late interaction append failed: {e}"); }
+    }
+
+    pub fn index(&self) -> Arc<dyn VecIndex> { self.index.clone() }
+
+    /// The index with `review`'s record for it, to re-embed the review in place.
+    pub fn embed(&self, review: &Review) -> Result<reembed::Target> {
+        Ok((self.index.clone(), self.record(&review.embed_text())?))
+    }
+
+    /// `text`'s words split into at most `vectors` equal runs, each embedded, and padded
+    /// with zero vectors to a full record.
+    fn record(&self, text: &str) -> Result<Vec<f32>> {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let runs: Vec<String> = words.chunks(words.len().div_ceil(self.vectors).max(1)).map(|run| run.join(" ")).collect();
+        let mut rec = self.embedder.embed_index_batch(&runs)?.concat();
+        rec.resize(self.vectors * self.dim, 0.0);
+        Ok(rec)
+    }
+
+    /// One vector per query word; words the embedder has nothing for are dropped.
+    pub fn query(&self, text: &str) -> Result<Vec<Vec<f32>>> {
+        let words: Vec<String> = text.split_whitespace().take(MAX_QUERY_TOKENS).map(String::from).collect();
+        let qvs = self.embedder.embed_query_batch(&words)?;
+        Ok(qvs.into_iter().filter(|q| q.iter().any(|x| *x != 0.0)).collect())
+    }
+
+    /// Mean over the query vectors of the best cosine with one of `rec`'s (0 at worst).
+    fn max_sim(&self, query: &[Vec<f32>], rec: &[f32]) -> f32 {
+        if query.is_empty() { return 0.0; }
+        let total: f32 = query.iter()
+            .map(|q| rec.chunks_exact(self.dim).map(|d| cosine(q, d)).fold(0.0, f32::max))
+            .sum();
+        total / query.len() as f32
+    }
+
+    /// Review `id`'s late interaction score; 0 if its vectors are not written yet.
+    pub fn score(&self, query: &[Vec<f32>], id: usize) -> Result<f32> {
+        Ok(self.index.read_mirror(id)?.map_or(0.0, |rec| self.max_sim(query, &rec)))
+    }
+
+    /// Keeps the best `keep` of `scored` and replaces their scores with late interaction
+    /// ones against `text`. Order is left to the caller.
+    pub fn rerank(&self, cache: &VectorCache, text: &str, scored: &mut Vec<(usize, f32)>, keep: usize) -> Result<()> {
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+        scored.truncate(keep);
+        let query = self.query(text)?;
+        let mut ids: Vec<usize> = scored.iter().map(|&(id, _)| id).collect();
+        ids.sort_unstable();
+        let mut sims = HashMap::with_capacity(ids.len());
+        cache.scan_ids(self.index.mirror_path(), self.index.dim(), usize::MAX, &ids, |id, rec| {
+            sims.insert(id, self.max_sim(&query, rec));
+        })?;
+        for (id, score) in scored.iter_mut() { *score = sims.get(id).copied().unwrap_or(0.0); }
+        Ok(())
+    }
+}
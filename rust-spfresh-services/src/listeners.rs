@@ -0,0 +1,123 @@
+use crate::config::Config;
+use anyhow::{Context, Result};
+use axum::{body::Body, extract::Request, response::Response, ServiceExt as _};
+use hyper_util::{
+    rt::{TokioExecutor, TokioIo},
+    server::conn::auto,
+    service::TowerToHyperService,
+};
+use std::{convert::Infallible, path::PathBuf};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tower::{Service, ServiceExt};
+use tracing::info;
+
+/// One address the service accepts connections on: `host:port`, or `unix:/path` for a
+/// Unix domain socket (plain HTTP, for a proxy on the same host).
+enum Addr {
+    Tcp(String),
+    Unix(PathBuf),
+}
+
+impl Addr {
+    fn parse(s: &str) -> Self {
+        match s.strip_prefix("unix:") {
+            Some(path) => Addr::Unix(path.into()),
+            None => Addr::Tcp(s.to_string()),
+        }
+    }
+}
+
+/// Binds every address in `listen` (or `bind` when it is empty), all before serving so
+/// a taken port fails startup, then serves `app` on all of them until one fails.
+pub async fn serve<S>(config: &Config, app: S) -> Result<()>
+where
+    S: Service<Request, Response = Response, Error = Infallible> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    #[cfg(not(feature = "tls"))]
+    anyhow::ensure!(config.tls.is_none(), "[tls] configured, but built without the `tls` feature");
+    let addrs: Vec<Addr> = if config.listen.is_empty() {
+        vec![Addr::parse(&config.bind)]
+    } else {
+        config.listen.iter().map(|a| Addr::parse(a)).collect()
+    };
+    let mut servers = tokio::task::JoinSet::new();
+    for addr in addrs {
+        match addr {
+            Addr::Tcp(addr) => {
+                let listener = tokio::net::TcpListener::bind(&addr).await.with_context(|| format!("bind {addr}"))?;
+                #[cfg(feature = "tls")]
+                if let Some(tls) = config.tls.clone() {
+                    info!("listening on https://{addr}");
+                    let app = app.clone();
+                    servers.spawn(async move { crate::tls::serve(listener, app, &tls).await });
+                    continue;
+                }
+                info!("listening on {addr}");
+                let make = app.clone().into_make_service();
+                servers.spawn(async move { Ok(axum::serve(listener, make).await?) });
+            }
+            #[cfg(unix)]
+            Addr::Unix(path) => {
+                let listener = bind_unix(&path, config.unix_socket_mode)?;
+                info!("listening on unix:{}", path.display());
+                servers.spawn(serve_unix(listener, path, app.clone()));
+            }
+            #[cfg(not(unix))]
+            Addr::Unix(path) => anyhow::bail!("unix:{} — Unix sockets are not supported on this platform", path.display()),
+        }
+    }
+    match servers.join_next().await {
+        Some(res) => res?,
+        None => Ok(()),
+    }
+}
+
+#[cfg(unix)]
+fn bind_unix(path: &std::path::Path, mode: Option<u32>) -> Result<tokio::net::UnixListener> {
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+    // Left behind by an earlier run that did not shut down cleanly; the data dir lock
+    // guarantees no other instance of this service is using it.
+    if std::fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_socket()) {
+        std::fs::remove_file(path).with_context(|| format!("remove stale socket {}", path.display()))?;
+    }
+    let listener = tokio::net::UnixListener::bind(path).with_context(|| format!("bind unix:{}", path.display()))?;
+    if let Some(mode) = mode {
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))?;
+    }
+    Ok(listener)
+}
+
+#[cfg(unix)]
+async fn serve_unix<S>(listener: tokio::net::UnixListener, path: PathBuf, app: S) -> Result<()>
+where
+    S: Service<Request, Response = Response, Error = Infallible> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    loop {
+        let (stream, _) = match listener.accept().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                tracing::warn!("accept on unix:{} failed: {e}", path.display());
+                tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+                continue;
+            }
+        };
+        let (app, peer) = (app.clone(), format!("unix:{}", path.display()));
+        tokio::spawn(serve_connection(stream, app, peer));
+    }
+}
+
+/// Serves HTTP/1.1 or HTTP/2 on one accepted connection, for listeners `axum::serve`
+/// does not handle (Unix sockets, TLS).
+pub async fn serve_connection<I, S>(io: I, app: S, peer: String)
+where
+    I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    S: Service<Request, Response = Response, Error = Infallible> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    let svc = TowerToHyperService::new(app.map_request(|req: hyper::Request<hyper::body::Incoming>| req.map(Body::new)));
+    if let Err(e) = auto::Builder::new(TokioExecutor::new()).serve_connection_with_upgrades(TokioIo::new(io), svc).await {
+        tracing::debug!("connection from {peer}: {e}");
+    }
+}
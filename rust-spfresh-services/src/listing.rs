@@ -0,0 +1,57 @@
+use crate::{blocking, ApiError, AppState, Review};
+use axum::{extract::{Query, State}, Json};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use reviews_types::{ListItem, ListResp};
+use serde::Deserialize;
+
+const DEFAULT_LIMIT: usize = 50;
+const MAX_LIMIT: usize = 1000;
+
+/// Position in reviews.jsonl: byte offset of a line start and that line's id.
+/// Serialized as an opaque base64 token; since the file is append-only a cursor stays
+/// valid no matter how many reviews are appended after it was issued.
+struct Cursor { offset: u64, id: usize }
+
+impl Cursor {
+    fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(format!("v1:{}:{}", self.offset, self.id))
+    }
+    fn decode(s: &str) -> Option<Self> {
+        let raw = String::from_utf8(URL_SAFE_NO_PAD.decode(s).ok()?).ok()?;
+        let mut it = raw.strip_prefix("v1:")?.split(':');
+        let offset = it.next()?.parse().ok()?;
+        let id = it.next()?.parse().ok()?;
+        it.next().is_none().then_some(Cursor { offset, id })
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ListParams {
+    cursor: Option<String>,
+    limit: Option<usize>,
+}
+
+/// GET /reviews?limit=&cursor= — pages through reviews in id order. Each page seeks
+/// straight to the cursor's byte offset, so cost is proportional to the page size only.
+pub async fn list_reviews(State(st): State<AppState>, Query(p): Query<ListParams>) -> Result<Json<ListResp>, ApiError> {
+    let (rows, next_cursor) = blocking(move || page(&st, p.cursor.as_deref(), p.limit)).await?;
+    let items = rows.into_iter().map(|(id, review)| ListItem { id, review }).collect();
+    Ok(Json(ListResp { items, next_cursor }))
+}
+
+/// One page of `(id, review)` plus the cursor for the next page (None once exhausted).
+pub type Page = (Vec<(usize, Review)>, Option<String>);
+
+pub fn page(st: &AppState, cursor: Option<&str>, limit: Option<usize>) -> Result<Page, ApiError> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let start = match cursor {
+        Some(c) => Cursor::decode(c).ok_or_else(|| ApiError::bad_request("invalid cursor"))?,
+        None => Cursor { offset: 0, id: 0 },
+    };
+    let (mut rows, end, read) = st.meta.read_page(start.offset, start.id, limit)?;
+    // Superseded reviews still occupy their lines; a page may come back short.
+    rows.retain(|(id, _)| st.meta.is_live(*id));
+    let next_cursor = (read == limit)
+        .then(|| Cursor { offset: end, id: start.id + read }.encode());
+    Ok((rows, next_cursor))
+}
@@ -1,35 +1,188 @@
-use axum::{extract::State, routing::post, Json, Router};
-use serde::{Deserialize, Serialize};
+use axum::{
+    error_handling::HandleErrorLayer,
+    extract::{DefaultBodyLimit, Path, Query, State},
+    http::StatusCode,
+    middleware,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use serde::Deserialize;
 use std::{
-    collections::{hash_map::DefaultHasher, HashSet},
+    collections::{hash_map::DefaultHasher, BTreeSet, HashMap, HashSet},
     fs::{File, OpenOptions},
     hash::{Hash, Hasher},
-    io::{BufRead, BufReader, Write},
-    path::PathBuf,
-    sync::Arc,
+    io::{BufRead, Write},
+    path::{Path as FsPath, PathBuf},
+    sync::{atomic::{AtomicUsize, Ordering}, Arc},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
-use http::{header, Method};
-use parking_lot::Mutex;
+use parking_lot::{Mutex, RwLock};
 use anyhow::Result;
 use tracing::info;
 use tracing_subscriber::EnvFilter;
-use tower_http::cors::{Any, CorsLayer};
-use std::io::Read;
+use tower::{BoxError, Layer, ServiceBuilder};
+use tower_http::decompression::RequestDecompressionLayer;
+
+mod amazon;
+#[cfg(feature = "analytics")]
+mod analytics;
+#[cfg(feature = "llm")]
+mod ask;
+mod aspects;
+mod attrs;
+mod audit;
+mod batch_get;
+mod bench;
+mod codec;
+mod collections;
+mod compact;
+mod compare;
+mod composition;
+mod config;
+mod cors;
+mod dir_lock;
+mod drift;
+mod dry_run;
+#[cfg(feature = "candle")]
+mod embed_candle;
+mod embedders;
+mod eval;
+mod explain;
+mod export;
+#[cfg(feature = "parquet")]
+mod export_parquet;
+mod external;
+mod feedback;
+mod fields;
+mod flusher;
+mod fusion;
+#[cfg(feature = "fastembed")]
+mod embed_fastembed;
+#[cfg(feature = "remote-embedder")]
+mod embed_remote;
+#[cfg(feature = "graphql")]
+mod graphql;
+mod group_commit;
+#[cfg(feature = "hf-import")]
+mod hf_import;
+mod idf;
+mod import;
+mod jobs;
+mod keyword;
+mod lang;
+mod late_interaction;
+mod listeners;
+mod listing;
+mod maintenance;
+mod meta_blocks;
+mod metrics;
+mod migrate;
+mod moderation;
+mod negotiate;
+mod patches;
+mod projection;
+mod products;
+mod pros_cons;
+mod purge;
+mod range_search;
+mod ratings;
+mod recovery;
+mod reembed;
+mod reindex;
+mod replication;
+mod score_expr;
+mod search_stream;
+mod sentiment;
+#[cfg(feature = "shards")]
+mod shards;
+mod sketch;
+mod slow_log;
+mod startup;
+mod storage;
+mod synth;
+mod tenants;
+#[cfg(feature = "tls")]
+mod tls;
+mod tombstones;
+mod trending;
+#[cfg(feature = "ui")]
+mod ui;
+mod vcache;
+mod vectors;
+mod versioning;
+mod vocab;
+mod writer;
+
+use audit::{Action, Actor};
+use config::{Config, DurabilityConfig, EmbedderConfig, SearchConfig};
+use group_commit::Commit;
+use jobs::JobRegistry;
+use metrics::Stage;
+use reviews_types::{
+    BulkInsertReq, BulkResp, FieldScoring, GroupBy, InsertReq, Job, PatchReq, PatchResp, RawInsertReq, Review, ReviewResp, ReviewStatus, Fusion, ScoreParts,
+    SearchHit, SearchReq, SearchResp, SearchStats, StatsResp, UpsertResp,
+};
+use negotiate::{Negotiated, Reply};
 
 // =========== Embedding (TF-IDF hashing) ===========
+fn l2_normalize(vec: &mut [f32]) {
+    let norm = (vec.iter().map(|x| x * x).sum::<f32>()).sqrt().max(1e-6);
+    for x in vec.iter_mut() { *x /= norm; }
+}
+
 trait Embedder: Send + Sync {
     fn embed_index(&self, text: &str) -> Result<Vec<f32>>;
     fn embed_query(&self, text: &str) -> Result<Vec<f32>>;
+    /// `embed_index` without its side effects (no vocabulary growth, document frequency or
+    /// drift statistics), for previewing what a document would be stored as. Stateless
+    /// backends need not override it.
+    fn embed_index_dry(&self, text: &str) -> Result<Vec<f32>> { self.embed_index(text) }
+    /// `embed_index` over many texts, in order. Backends that batch natively override this.
+    fn embed_index_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        texts.iter().map(|t| self.embed_index(t)).collect()
+    }
+    /// `embed_query` over many texts, in order: embeds text that is not being indexed, so
+    /// it never grows a vocabulary or counts towards document frequencies.
+    fn embed_query_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        texts.iter().map(|t| self.embed_query(t)).collect()
+    }
+    /// The term dimension `i` stands for, for embedders with an exact vocabulary.
+    fn term(&self, _i: usize) -> Option<String> { None }
+    /// Dimensions `text` counts towards as a document, for embedders that keep document
+    /// frequencies (TF-IDF); None otherwise. Never grows a vocabulary.
+    fn doc_dims(&self, _text: &str) -> Option<HashSet<usize>> { None }
+    /// Replaces the document frequencies and document count (see `idf`).
+    fn set_doc_freqs(&self, _df: Vec<f32>, _docs: f32) {}
+    /// Running token counts of queries or documents, for embedders with their own tokens.
+    fn token_counts(&self, _kind: drift::Kind) -> Option<drift::TokenCounts> { None }
+    /// Dimensions some indexed document has used, for embedders whose dimensions are terms.
+    fn dims_used(&self) -> Option<usize> { None }
+    /// Distribution statistics of the vectors embedded so far (see `drift`).
+    fn drift(&self) -> Option<&drift::Drift> { None }
 }
 
 struct TfIdfEmbedder {
     dim: usize,
-    df: Mutex<Vec<u32>>,
-    docs: Mutex<u32>,
+    /// Fractional once `idf` recomputes them with decay.
+    df: Mutex<Vec<f32>>,
+    docs: Mutex<f32>,
+    /// Exact term dimensions instead of the hashing trick.
+    vocab: Option<vocab::Vocabulary>,
+    /// Query tokens without a dimension any document has used, and document tokens
+    /// without one an earlier document has (or, with a full vocabulary, without any).
+    query_tokens: drift::TokenCounter,
+    doc_tokens: drift::TokenCounter,
 }
 impl TfIdfEmbedder {
     fn new(dim: usize) -> Self {
-        Self { dim, df: Mutex::new(vec![0; dim]), docs: Mutex::new(0) }
+        Self {
+            dim, df: Mutex::new(vec![0.0; dim]), docs: Mutex::new(0.0), vocab: None,
+            query_tokens: Default::default(), doc_tokens: Default::default(),
+        }
+    }
+    fn with_vocabulary(dim: usize, dir: &FsPath) -> Result<Self> {
+        Ok(Self { vocab: Some(vocab::Vocabulary::open(dir, dim)?), ..Self::new(dim) })
     }
     #[inline]
     fn bucket(&self, token: &str) -> usize {
@@ -37,223 +190,1250 @@ impl TfIdfEmbedder {
         token.to_lowercase().hash(&mut h);
         (h.finish() as usize) % self.dim
     }
-    fn idf(&self, df_i: u32, docs_now: f32) -> f32 {
-        ((docs_now + 1.0) / (df_i as f32 + 1.0)).ln() + 1.0
+    /// Dimension of a document token; grows the vocabulary, if there is one.
+    fn index_bucket(&self, token: &str) -> Result<Option<usize>> {
+        match &self.vocab {
+            Some(v) => v.get_or_insert(&token.to_lowercase()),
+            None => Ok(Some(self.bucket(token))),
+        }
+    }
+    /// Dimension of a query token; terms no document has used have none.
+    fn query_bucket(&self, token: &str) -> Option<usize> {
+        match &self.vocab {
+            Some(v) => v.get(&token.to_lowercase()),
+            None => Some(self.bucket(token)),
+        }
     }
-    fn l2_normalize(vec: &mut [f32]) {
-        let norm = (vec.iter().map(|x| x * x).sum::<f32>()).sqrt().max(1e-6);
-        for x in vec.iter_mut() { *x /= norm; }
+    fn idf(&self, df_i: f32, docs_now: f32) -> f32 {
+        ((docs_now + 1.0) / (df_i + 1.0)).ln() + 1.0
     }
-    fn featurize_index(&self, text: &str) -> Vec<f32> {
+    fn tokens(text: &str) -> impl Iterator<Item = String> + '_ {
+        keyword::terms(text)
+    }
+    fn featurize_index(&self, text: &str) -> Result<Vec<f32>> {
         let mut v = vec![0f32; self.dim];
         let mut seen = HashSet::new();
-        for tok in text.split(|c: char| !c.is_alphanumeric()).filter(|t| !t.is_empty()) {
-            let i = self.bucket(tok);
+        let (mut tokens, mut dropped) = (0, 0);
+        for tok in Self::tokens(text) {
+            tokens += 1;
+            let Some(i) = self.index_bucket(&tok)? else { dropped += 1; continue };
             v[i] += 1.0;
             seen.insert(i);
         }
-        { let mut df = self.df.lock(); for &i in &seen { df[i] = df[i].saturating_add(1); } }
-        let docs_now = { let mut d = self.docs.lock(); *d = d.saturating_add(1); *d as f32 };
+        {
+            let mut df = self.df.lock();
+            let new: f32 = seen.iter().filter(|&&i| df[i] == 0.0).map(|&i| v[i]).sum();
+            self.doc_tokens.add(tokens, dropped + new as u64);
+            for &i in &seen { df[i] += 1.0; }
+        }
+        let docs_now = { let mut d = self.docs.lock(); *d += 1.0; *d };
         let df = self.df.lock();
         for i in 0..self.dim { if v[i] > 0.0 { v[i] *= self.idf(df[i], docs_now); } }
-        Self::l2_normalize(&mut v); v
+        l2_normalize(&mut v); Ok(v)
+    }
+    /// `featurize_index` as if the text were indexed next, without counting it; tokens the
+    /// vocabulary has no dimension for yet are left out.
+    fn featurize_dry(&self, text: &str) -> Vec<f32> {
+        let mut v = vec![0f32; self.dim];
+        for i in Self::tokens(text).filter_map(|t| self.query_bucket(&t)) { v[i] += 1.0; }
+        let docs_next = *self.docs.lock() + 1.0;
+        let df = self.df.lock();
+        for i in 0..self.dim { if v[i] > 0.0 { v[i] *= self.idf(df[i] + 1.0, docs_next); } }
+        l2_normalize(&mut v); v
     }
     fn featurize_query(&self, text: &str) -> Vec<f32> {
         let mut v = vec![0f32; self.dim];
-        for tok in text.split(|c: char| !c.is_alphanumeric()).filter(|t| !t.is_empty()) {
-            let i = self.bucket(tok); v[i] += 1.0;
+        let (mut tokens, mut missing) = (0, 0);
+        for tok in Self::tokens(text) {
+            tokens += 1;
+            match self.query_bucket(&tok) {
+                Some(i) => v[i] += 1.0,
+                None => missing += 1,
+            }
         }
-        let docs_now = (*self.docs.lock()).max(1) as f32;
+        let docs_now = self.docs.lock().max(1.0);
         let df = self.df.lock();
+        let unused: f32 = v.iter().zip(df.iter()).filter(|&(&x, &d)| x > 0.0 && d == 0.0).map(|(x, _)| x).sum();
+        self.query_tokens.add(tokens, missing + unused as u64);
         for i in 0..self.dim { if v[i] > 0.0 { v[i] *= self.idf(df[i], docs_now); } }
-        Self::l2_normalize(&mut v); v
+        l2_normalize(&mut v); v
     }
 }
 impl Embedder for TfIdfEmbedder {
-    fn embed_index(&self, text: &str) -> Result<Vec<f32>> { Ok(self.featurize_index(text)) }
+    fn embed_index(&self, text: &str) -> Result<Vec<f32>> { self.featurize_index(text) }
+    fn embed_index_dry(&self, text: &str) -> Result<Vec<f32>> { Ok(self.featurize_dry(text)) }
     fn embed_query(&self, text: &str) -> Result<Vec<f32>> { Ok(self.featurize_query(text)) }
+    fn term(&self, i: usize) -> Option<String> { self.vocab.as_ref()?.term(i) }
+    fn doc_dims(&self, text: &str) -> Option<HashSet<usize>> {
+        Some(Self::tokens(text).filter_map(|t| self.query_bucket(&t)).collect())
+    }
+    fn set_doc_freqs(&self, df: Vec<f32>, docs: f32) {
+        let mut cur = self.df.lock();
+        *cur = df;
+        *self.docs.lock() = docs;
+    }
+    fn token_counts(&self, kind: drift::Kind) -> Option<drift::TokenCounts> {
+        Some(match kind {
+            drift::Kind::Query => self.query_tokens.get(),
+            drift::Kind::Document => self.doc_tokens.get(),
+        })
+    }
+    fn dims_used(&self) -> Option<usize> { Some(self.df.lock().iter().filter(|&&d| d > 0.0).count()) }
+}
+
+/// Records every call of the wrapped embedder under `Stage::Embed`, whatever the backend,
+/// and the vectors of indexed documents and single queries in its `drift`. Query batches
+/// embed review text for analysis, not searches, so they are left out of it.
+struct TimedEmbedder<E> {
+    inner: E,
+    drift: drift::Drift,
+}
+impl<E: Embedder> TimedEmbedder<E> {
+    /// `dir` holds the drift baselines.
+    fn new(inner: E, dir: &FsPath) -> Self { Self { inner, drift: drift::Drift::open(dir) } }
+    fn record(&self, kind: drift::Kind, vectors: &[Vec<f32>]) {
+        self.drift.record(kind, vectors, || self.inner.token_counts(kind));
+    }
+}
+impl<E: Embedder> Embedder for TimedEmbedder<E> {
+    fn embed_index(&self, text: &str) -> Result<Vec<f32>> {
+        let _t = metrics::timer(Stage::Embed);
+        let v = self.inner.embed_index(text)?;
+        self.record(drift::Kind::Document, std::slice::from_ref(&v));
+        Ok(v)
+    }
+    fn embed_index_dry(&self, text: &str) -> Result<Vec<f32>> {
+        let _t = metrics::timer(Stage::Embed);
+        self.inner.embed_index_dry(text)
+    }
+    fn embed_query(&self, text: &str) -> Result<Vec<f32>> {
+        let _t = metrics::timer(Stage::Embed);
+        let v = self.inner.embed_query(text)?;
+        self.record(drift::Kind::Query, std::slice::from_ref(&v));
+        Ok(v)
+    }
+    fn embed_index_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let _t = metrics::timer(Stage::Embed);
+        let vs = self.inner.embed_index_batch(texts)?;
+        self.record(drift::Kind::Document, &vs);
+        Ok(vs)
+    }
+    fn embed_query_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let _t = metrics::timer(Stage::Embed);
+        self.inner.embed_query_batch(texts)
+    }
+    fn term(&self, i: usize) -> Option<String> { self.inner.term(i) }
+    fn doc_dims(&self, text: &str) -> Option<HashSet<usize>> { self.inner.doc_dims(text) }
+    fn set_doc_freqs(&self, df: Vec<f32>, docs: f32) { self.inner.set_doc_freqs(df, docs) }
+    fn token_counts(&self, kind: drift::Kind) -> Option<drift::TokenCounts> { self.inner.token_counts(kind) }
+    fn dims_used(&self) -> Option<usize> { self.inner.dims_used() }
+    fn drift(&self) -> Option<&drift::Drift> { Some(&self.drift) }
+}
+
+/// What an index file stores next to a review's vector: its external_id, when it has one
+/// that fits.
+fn payload_of(review: &Review) -> Option<&[u8]> {
+    review.external_id.as_deref().map(str::as_bytes).filter(|p| !p.is_empty() && p.len() <= spfresh_index::MAX_PAYLOAD)
 }
 
 trait VecIndex: Send + Sync {
     fn dim(&self) -> usize;
-    fn append(&self, vec: &[f32]) -> Result<usize>;
+    /// Assigns the next id, with `payload` (see `payload_of`) stored next to the vector, and
+    /// queues the mirror write; it is durable once the `Commit` resolves. Waiting outside the
+    /// write gate lets concurrent writers share one fsync.
+    fn append_pending(&self, vec: &[f32], payload: Option<&[u8]>) -> Result<(usize, Commit)>;
+    #[allow(dead_code)]
     fn get(&self, id: usize) -> Result<Vec<f32>>;
+    /// The `top_k` nearest ids to `q` among those `allowed` accepts, which the traversal
+    /// skips past rather than the caller filtering its top-k afterwards, so a narrow filter
+    /// still gets `top_k` hits.
+    fn search_allowed(&self, q: &[f32], top_k: usize, allowed: &(dyn Fn(usize) -> bool + Sync)) -> Result<Vec<(usize, f32)>>;
+    /// The opaque bytes stored next to vector `id` in the index file, if it was appended with
+    /// any: the review's external_id (see `payload_of`), read without touching the metadata.
+    fn payload(&self, id: usize) -> Result<Option<Vec<u8>>>;
+    /// Every id within `max_distance` of `q`, nearest first, at most `limit` of them: for
+    /// duplicate detection and clustering, where no top-k fits.
+    fn range_search(&self, q: &[f32], max_distance: f32, limit: usize) -> Result<Vec<(usize, f32)>>;
+    /// Raw vector file (see `codec`) that /search scans.
+    fn mirror_path(&self) -> &FsPath;
+    /// Number of vectors in the mirror.
+    fn len(&self) -> Result<usize> {
+        let body = std::fs::metadata(self.mirror_path())?.len().saturating_sub(codec::HEADER_LEN);
+        Ok((body / codec::record_len(self.dim()) as u64) as usize)
+    }
+    /// Vector `id` as stored in the mirror; None if it is past the end or fails its checksum.
+    fn read_mirror(&self, id: usize) -> Result<Option<Vec<f32>>> {
+        use std::io::{Read, Seek, SeekFrom};
+        let rec_len = codec::record_len(self.dim());
+        let mut f = File::open(self.mirror_path())?;
+        let mut rec = vec![0u8; rec_len];
+        f.seek(SeekFrom::Start(codec::HEADER_LEN + (id * rec_len) as u64))?;
+        if let Err(e) = f.read_exact(&mut rec) {
+            return if e.kind() == std::io::ErrorKind::UnexpectedEof { Ok(None) } else { Err(e.into()) };
+        }
+        let mut v = Vec::with_capacity(self.dim());
+        Ok(codec::decode_record_into(&rec, self.dim(), &mut v).then_some(v))
+    }
+    /// Replaces vector `id`, in the ANN index and in the mirror, under the same id. Callers
+    /// hold the write gate.
+    fn overwrite(&self, id: usize, vec: &[f32]) -> Result<()>;
+    /// Zeroes the vectors of `ids` and clears their payloads in the ANN index, then flushes
+    /// it; ids past its end are skipped. The mirror is left to the caller (see
+    /// `purge::erase`). Callers hold the write gate. Returns the vectors zeroed.
+    fn erase(&self, ids: &BTreeSet<usize>) -> Result<usize>;
+    /// The mirror half of `overwrite`: rewrites record `id` in place and syncs it.
+    fn overwrite_mirror(&self, id: usize, vec: &[f32]) -> Result<()> {
+        use std::io::{Seek, SeekFrom};
+        anyhow::ensure!(vec.len() == self.dim(), "dim mismatch: {} != {}", vec.len(), self.dim());
+        anyhow::ensure!(id < self.len()?, "no vector {id} in {}", self.mirror_path().display());
+        let mut f = OpenOptions::new().write(true).open(self.mirror_path())?;
+        f.seek(SeekFrom::Start(codec::HEADER_LEN + (id * codec::record_len(self.dim())) as u64))?;
+        f.write_all(&codec::encode_record(vec))?;
+        f.sync_data()?;
+        Ok(())
+    }
 }
 
 mod spfresh_index {
     use super::*;
     use anyhow::{anyhow, Result};
-    use spfresh::{Index as SIndex, OpenOptions as SOpen};
-    use std::io::{Seek, SeekFrom, Write};
+    use spfresh::{Index as SIndex, OpenOptions as SOpen, RangeParams, SearchParams};
+    use group_commit::{GroupCommit, SyncSchedule};
+
+    /// Longest payload stored next to a vector; a longer external_id is left out.
+    pub const MAX_PAYLOAD: usize = 256;
 
     pub struct SpfreshIndex {
         dim: usize,
-        inner: Mutex<SIndex>,
         spf_path: PathBuf,
         mirror_path: PathBuf,
-        mirror_file: Mutex<std::fs::File>,
-        bytes_per_vec: u64,
+        writes: Arc<Writes>,
+    }
+
+    /// The index and the mirror's writer, shared with the background flusher.
+    struct Writes {
+        inner: Mutex<SIndex>,
+        mirror_writer: GroupCommit,
+        /// Decides when `inner` is flushed; locked while `inner` is held.
+        index_sync: Mutex<SyncSchedule>,
+    }
+
+    impl Writes {
+        fn flush_index(&self) -> Result<()> {
+            let mut idx = self.inner.lock();
+            let mut sync = self.index_sync.lock();
+            if sync.is_dirty() {
+                idx.flush().map_err(|e| anyhow!("{}", e))?;
+                sync.synced();
+            }
+            Ok(())
+        }
+    }
+
+    impl flusher::Flush for Writes {
+        fn flush(&self) -> Result<()> {
+            self.flush_index()?;
+            self.mirror_writer.sync().wait()
+        }
+    }
+
+    /// Like the mirror writer, the index is flushed on the way out if its policy wants it.
+    impl Drop for Writes {
+        fn drop(&mut self) {
+            if let Err(e) = self.flush_index() { tracing::error!("spfresh flush on close failed: {e}"); }
+        }
     }
 
     impl SpfreshIndex {
-        pub fn open(dir: impl Into<PathBuf>, dim: usize) -> Result<Self> {
+        pub fn open(dir: impl Into<PathBuf>, dim: usize, durability: &DurabilityConfig) -> Result<Self> {
             let dir = dir.into();
             std::fs::create_dir_all(&dir)?;
             let spf_path = dir.join("reviews.spfresh");
             let mirror_path = dir.join("reviews.index");
-            let _ = std::fs::OpenOptions::new().create(true).write(true).open(&spf_path)?;
-            let _ = std::fs::OpenOptions::new().create(true).write(true).open(&mirror_path)?;
+            let _ = std::fs::OpenOptions::new().create(true).truncate(false).write(true).open(&spf_path)?;
+            let _ = std::fs::OpenOptions::new().create(true).truncate(false).write(true).open(&mirror_path)?;
             let spf_abs = std::fs::canonicalize(&spf_path).unwrap_or(spf_path.clone());
             let mir_abs = std::fs::canonicalize(&mirror_path).unwrap_or(mirror_path.clone());
             tracing::info!("spfresh data path = {}", spf_abs.display());
             tracing::info!("mirror  raw path  = {}", mir_abs.display());
-            let opts = SOpen::new().create(true).append(true);
-            let idx = SIndex::open(spf_abs.to_string_lossy().as_ref(), dim, &opts)
+            codec::ensure_mirror_header(&mir_abs, dim)?;
+            codec::ensure_sidecar_header(&dir.join("reviews.spfresh.hdr"), codec::FileKind::Spfresh, dim)?;
+            let opts = SOpen::new().create(true).append(true).max_payload(MAX_PAYLOAD);
+            let mut idx = SIndex::open(spf_abs.to_string_lossy().as_ref(), dim, &opts)
                 .map_err(|e| anyhow!("{}", e))?;
-            let mut mf = std::fs::OpenOptions::new().create(true).read(true).write(true).open(&mir_abs)?;
-            mf.seek(SeekFrom::End(0))?;
-            Ok(Self {
-                dim,
+            reconcile(&mut idx, &mir_abs, dim)?;
+            let mf = std::fs::OpenOptions::new().create(true).truncate(false).read(true).write(true).open(&mir_abs)?;
+            let writes = Arc::new(Writes {
                 inner: Mutex::new(idx),
-                spf_path: spf_abs,
-                mirror_path: mir_abs,
-                mirror_file: Mutex::new(mf),
-                bytes_per_vec: (dim * 4) as u64,
-            })
-        }
-
-        #[inline]
-        fn mirror_append_checked(&self, vec: &[f32]) -> Result<()> {
-            let mut f = self.mirror_file.lock();
-            let before = std::fs::metadata(&self.mirror_path)?.len();
-            f.seek(SeekFrom::End(0))?;
-            let bytes = unsafe {
-                std::slice::from_raw_parts(vec.as_ptr() as *const u8, vec.len() * 4)
-            };
-            f.write_all(bytes)?;
-            f.flush()?;
-            let _ = f.sync_all();
-            let after = std::fs::metadata(&self.mirror_path)?.len();
-            anyhow::ensure!(
-                after == before + self.bytes_per_vec,
-                "mirror write failed: {} -> {} (expect +{}) @ {}",
-                before, after, self.bytes_per_vec, self.mirror_path.display()
-            );
-            tracing::info!(
-                "mirror OK: +{} bytes -> {} @ {}",
-                self.bytes_per_vec, after, self.mirror_path.display()
-            );
-            Ok(())
+                mirror_writer: GroupCommit::spawn(mf, &mir_abs, durability)?,
+                index_sync: Mutex::new(SyncSchedule::new(durability.sync.clone())),
+            });
+            let weak: std::sync::Weak<Writes> = Arc::downgrade(&writes);
+            flusher::register(weak);
+            Ok(Self { dim, spf_path: spf_abs, mirror_path: mir_abs, writes })
         }
     }
 
+    /// Brings the index to the mirror's length, since its ids are the metadata's line numbers:
+    /// ids past the mirror (appends whose mirror record was lost, or cut by `recovery`) are
+    /// dropped, and vectors it lacks (appends since its last flush, or an index file from
+    /// before the index was kept) are copied back from the mirror, without payloads.
+    fn reconcile(idx: &mut SIndex, mirror: &FsPath, dim: usize) -> Result<()> {
+        use std::io::{BufReader, Read, Seek, SeekFrom};
+        let (have, rec_len) = (idx.len(), codec::record_len(dim));
+        let want = (std::fs::metadata(mirror)?.len().saturating_sub(codec::HEADER_LEN) / rec_len as u64) as usize;
+        if have > want {
+            idx.truncate(want).map_err(|e| anyhow!("{}", e))?;
+            tracing::warn!("{}: dropped {} vectors past the mirror", mirror.display(), have - want);
+        } else if have < want {
+            let mut rdr = BufReader::new(File::open(mirror)?);
+            rdr.seek(SeekFrom::Start(codec::HEADER_LEN + (have * rec_len) as u64))?;
+            let (mut rec, mut v) = (vec![0u8; rec_len], Vec::with_capacity(dim));
+            for id in have..want {
+                rdr.read_exact(&mut rec)?;
+                v.clear();
+                if !codec::decode_record_into(&rec, dim, &mut v) {
+                    tracing::warn!("{}: vector {id} fails its checksum; indexed as zeros", mirror.display());
+                    v.resize(dim, 0.0);
+                }
+                idx.append(&v).map_err(|e| anyhow!("{}", e))?;
+            }
+            idx.flush().map_err(|e| anyhow!("{}", e))?;
+            tracing::warn!("{}: indexed {} vectors the index lacked", mirror.display(), want - have);
+        }
+        Ok(())
+    }
+
     impl super::VecIndex for SpfreshIndex {
         fn dim(&self) -> usize { self.dim }
-        fn append(&self, vec: &[f32]) -> Result<usize> {
+        fn append_pending(&self, vec: &[f32], payload: Option<&[u8]>) -> Result<(usize, Commit)> {
             anyhow::ensure!(vec.len() == self.dim, "dim mismatch: {} != {}", vec.len(), self.dim);
-            let mut idx = self.inner.lock();
-            let id = idx.append(vec).map_err(|e| anyhow!("{}", e))?;
-            self.mirror_append_checked(vec)?; // เขียน reviews.index ทุกครั้ง
+            let mut idx = self.writes.inner.lock();
+            let id = {
+                let _t = metrics::timer(Stage::IndexAppend);
+                match payload {
+                    Some(p) => idx.append_with_payload(vec, p),
+                    None => idx.append(vec),
+                }.map_err(|e| anyhow!("{}", e))?
+            };
+            let mut sync = self.writes.index_sync.lock();
+            if sync.record(1) {
+                idx.flush().map_err(|e| anyhow!("{}", e))?;
+                sync.synced();
+            }
+            // Queued while `inner` is held, so mirror order matches id order.
+            let commit = self.writes.mirror_writer.submit(codec::encode_record(vec)); // เขียน reviews.index ทุกครั้ง
             tracing::info!(
                 "append OK: id={}, spf={}, mirror={}",
                 id, self.spf_path.display(), self.mirror_path.display()
             );
-            Ok(id)
+            Ok((id, commit))
         }
         fn get(&self, id: usize) -> Result<Vec<f32>> {
-            let idx = self.inner.lock();
-            Ok(idx.get(id).map_err(|e| anyhow!("{}", e))?)
+            let idx = self.writes.inner.lock();
+            idx.get(id).map_err(|e| anyhow!("{}", e))
+        }
+        fn overwrite(&self, id: usize, vec: &[f32]) -> Result<()> {
+            anyhow::ensure!(vec.len() == self.dim, "dim mismatch: {} != {}", vec.len(), self.dim);
+            let mut idx = self.writes.inner.lock();
+            idx.update(id, vec).map_err(|e| anyhow!("{}", e))?;
+            let mut sync = self.writes.index_sync.lock();
+            if sync.record(1) {
+                idx.flush().map_err(|e| anyhow!("{}", e))?;
+                sync.synced();
+            }
+            self.overwrite_mirror(id, vec)
+        }
+        fn erase(&self, ids: &BTreeSet<usize>) -> Result<usize> {
+            let mut idx = self.writes.inner.lock();
+            let zeros = vec![0.0; self.dim];
+            let mut n = 0;
+            for &id in ids.range(..idx.len()) {
+                idx.update(id, &zeros).map_err(|e| anyhow!("{}", e))?;
+                idx.set_payload(id, &[]).map_err(|e| anyhow!("{}", e))?;
+                n += 1;
+            }
+            let mut sync = self.writes.index_sync.lock();
+            idx.flush().map_err(|e| anyhow!("{}", e))?;
+            sync.synced();
+            Ok(n)
+        }
+        fn search_allowed(&self, q: &[f32], top_k: usize, allowed: &(dyn Fn(usize) -> bool + Sync)) -> Result<Vec<(usize, f32)>> {
+            anyhow::ensure!(q.len() == self.dim, "dim mismatch: {} != {}", q.len(), self.dim);
+            let idx = self.writes.inner.lock();
+            idx.search(q, &SearchParams { top_k, allowed: Some(allowed) }).map_err(|e| anyhow!("{}", e))
+        }
+        fn payload(&self, id: usize) -> Result<Option<Vec<u8>>> {
+            let idx = self.writes.inner.lock();
+            idx.payload(id).map_err(|e| anyhow!("{}", e))
         }
+        fn range_search(&self, q: &[f32], max_distance: f32, limit: usize) -> Result<Vec<(usize, f32)>> {
+            anyhow::ensure!(q.len() == self.dim, "dim mismatch: {} != {}", q.len(), self.dim);
+            let idx = self.writes.inner.lock();
+            idx.range_search(q, &RangeParams { max_distance, limit }).map_err(|e| anyhow!("{}", e))
+        }
+        fn mirror_path(&self) -> &std::path::Path { &self.mirror_path }
     }
 
     pub use SpfreshIndex as DefaultIndex;
 }
 
+/// `MetaStore::read_page`: records, offset past the last line read, lines read.
+type Page = (Vec<(usize, Review)>, u64, usize);
+
 struct MetaStore {
-    meta_path: PathBuf,
+    files: meta_blocks::Blocks,
+    /// Lines in reviews.jsonl, i.e. not yet sealed into a compressed block.
+    tail_lines: AtomicUsize,
+    /// Terms and ranking attributes of every review, kept in step with the file by `append`.
+    keywords: keyword::KeywordIndex,
+    attrs: attrs::Attributes,
+    /// Ratings of the live, approved reviews per product and day.
+    ratings: ratings::Timeline,
+    /// Live reviews per product, language and rating.
+    composition: composition::Composition,
+    external: external::ExternalIds,
+    tombstones: tombstones::Tombstones,
+    /// Reviews held by moderation; quarantined ones are left out of search.
+    moderation: moderation::Queue,
+    /// Ratings and products changed since the lines were written.
+    patches: patches::Patches,
+    aspects: aspects::Mentions,
 }
 impl MetaStore {
-    fn open(dir: impl Into<PathBuf>) -> Result<Self> {
-        let dir = dir.into();
-        std::fs::create_dir_all(&dir)?;
-        let meta_path = dir.join("reviews.jsonl");
-        if !meta_path.exists() { File::create(&meta_path)?; }
-        Ok(Self { meta_path })
-    }
-    fn append(&self, review: &Review) -> Result<()> {
-        let mut meta = OpenOptions::new().append(true).open(&self.meta_path)?;
-        let line = serde_json::to_string(review)?;
-        meta.write_all(line.as_bytes())?;
-        meta.write_all(b"\n")?;
+    /// `files` must have been through `recovery::recover`; full blocks left in reviews.jsonl
+    /// (compression just turned on) are sealed here. Lines read go to `progress`.
+    fn open(dir: &FsPath, files: meta_blocks::Blocks, progress: &startup::Progress) -> Result<Self> {
+        let tombstones = tombstones::Tombstones::open(dir)?;
+        let moderation = moderation::Queue::open(dir)?;
+        let patches = patches::Patches::open(dir)?;
+        let store = Self {
+            files, tail_lines: AtomicUsize::new(0), keywords: Default::default(), attrs: Default::default(),
+            ratings: Default::default(), composition: Default::default(), external: Default::default(), tombstones, moderation,
+            patches, aspects: Default::default(),
+        };
+        let n = store.for_each_line(0..usize::MAX, |id, r| {
+            let r = r.map(|r| store.patches.apply(id, r));
+            store.keywords.push(r.as_ref().map(|r| r.embed_text()).as_deref());
+            store.attrs.push(r.as_ref());
+            store.aspects.push(r.as_ref());
+            let deleted = store.tombstones.contains(id);
+            store.track_live(id, store.external.push(r.as_ref(), deleted), deleted);
+            progress.set_done(id + 1);
+            Ok(())
+        })?;
+        info!("keyword index: {} reviews", n);
+        for (id, status) in store.moderation.statuses() { store.set_status(id, status); }
+        let tail = n - store.files.sealed_lines();
+        let tail = store.files.seal(tail)?.unwrap_or(tail);
+        store.tail_lines.store(tail, Ordering::Relaxed);
+        Ok(store)
+    }
+    /// Appends one framed line; returns its length in bytes. Callers hold the write gate,
+    /// so the keyword index assigns the same id as the line number.
+    fn append(&self, review: &Review, status: ReviewStatus) -> Result<u64> {
+        self.append_verbatim(&self.prepare(review, status))
+    }
+    /// `review` as `append` would write it: with `status`, a creation time, its language,
+    /// aspects and version filled in.
+    fn prepare(&self, review: &Review, status: ReviewStatus) -> Review {
+        let mut review = review.clone();
+        review.created_at.get_or_insert_with(now_secs);
+        review.status = Some(status);
+        review.lang = Some(match review.lang.take().filter(|l| !l.trim().is_empty()) {
+            Some(l) => l.trim().to_lowercase(),
+            None => lang::detect(&review.embed_text()).to_string(),
+        });
+        review.aspects = Some(aspects::extract(&review.review_title, &review.review_body, review.review_rating));
+        let replaces = review.external_id.as_deref().and_then(|e| self.external.get(e));
+        review.version = Some(replaces.map_or(1, |id| self.attrs.get(id).version + 1));
+        review
+    }
+    /// Appends `review` exactly as given (a replica copying its leader's lines).
+    fn append_verbatim(&self, review: &Review) -> Result<u64> {
+        let mut meta = OpenOptions::new().append(true).open(self.files.tail_path())?;
+        let line = codec::encode_line(&serde_json::to_vec(review)?);
+        meta.write_all(&line)?;
+        self.keywords.push(Some(&review.embed_text()));
+        let id = self.attrs.len();
+        self.attrs.push(Some(review));
+        self.aspects.push(Some(review));
+        self.track_live(id, self.external.push(Some(review), false), false);
+        let tail = self.tail_lines.fetch_add(1, Ordering::Relaxed) + 1;
+        // The line is written either way; a failed seal is retried on the next append.
+        match self.files.seal(tail) {
+            Ok(Some(left)) => self.tail_lines.store(left, Ordering::Relaxed),
+            Ok(None) => {}
+            Err(e) => tracing::warn!("sealing metadata block failed: {e:#}"),
+        }
+        Ok(line.len() as u64)
+    }
+    /// False once the review was deleted or a later one took over its external_id.
+    fn is_live(&self, id: usize) -> bool {
+        !self.external.is_superseded(id) && !self.tombstones.contains(id)
+    }
+    /// Gives review `id` a moderation status in memory, moving it in or out of the ratings
+    /// timeline, which counts approved reviews only, if it is live.
+    fn set_status(&self, id: usize, status: ReviewStatus) {
+        let live = self.is_live(id);
+        if live { self.ratings.remove(self.attrs.get(id)); }
+        self.attrs.set_status(id, status);
+        if live { self.ratings.add(self.attrs.get(id)); }
+    }
+    /// Tombstones review `id`. Callers hold the write gate.
+    fn delete(&self, id: usize, review: &Review) -> Result<()> {
+        self.tombstones.add(id)?;
+        if let Some(ext) = &review.external_id { self.external.forget(ext, id); }
+        self.ratings.remove(self.attrs.get(id));
+        self.composition.remove(self.attrs.get(id));
         Ok(())
     }
+    /// Gives review `id` a new `version` with the rating and product given, in the patch log
+    /// and the attributes, and moves it in the ratings timeline and the composition. Callers
+    /// hold the write gate.
+    fn patch(&self, id: usize, version: u64, review_rating: Option<i32>, product_id: Option<String>) -> Result<()> {
+        let before = self.attrs.get(id);
+        self.patches.add(id, version, review_rating, product_id.clone())?;
+        self.attrs.patch(id, version, review_rating, product_id.as_deref());
+        self.ratings.remove(before);
+        self.composition.remove(before);
+        self.ratings.add(self.attrs.get(id));
+        self.composition.add(self.attrs.get(id));
+        Ok(())
+    }
+    /// Counts review `id`, just pushed, in the ratings timeline and the composition unless it
+    /// is `deleted`, and takes out the live review it `superseded`.
+    fn track_live(&self, id: usize, superseded: Option<usize>, deleted: bool) {
+        if let Some(old) = superseded {
+            self.ratings.remove(self.attrs.get(old));
+            self.composition.remove(self.attrs.get(old));
+        }
+        if !deleted {
+            self.ratings.add(self.attrs.get(id));
+            self.composition.add(self.attrs.get(id));
+        }
+    }
+    /// Blanks the text of lines `ids` in place (see `purge::redact`), unsealing the blocks
+    /// holding them and sealing them again. Callers hold the write gate. Returns the lines
+    /// rewritten.
+    fn redact(&self, ids: &BTreeSet<usize>) -> Result<usize> {
+        let Some(&first) = ids.first() else { return Ok(0) };
+        self.files.unseal_from(first)?;
+        let mut n = 0;
+        let lines = self.files.rewrite_tail(|id, line| {
+            let new = ids.contains(&id).then(|| purge::redact(id, line)).flatten();
+            n += new.is_some() as usize;
+            new
+        })?;
+        self.tail_lines.store(lines, Ordering::Relaxed);
+        if let Some(left) = self.files.seal(lines)? { self.tail_lines.store(left, Ordering::Relaxed); }
+        Ok(n)
+    }
+    /// Unframes and parses one line (without its '\n').
+    fn parse_line(line: &[u8]) -> Result<Review> {
+        let mut review: Review = serde_json::from_slice(codec::decode_line(line)?)?;
+        // Lines written before versions existed are each a first version.
+        review.version.get_or_insert(1);
+        Ok(review)
+    }
     fn read_review_by_line(&self, id: usize) -> Result<Review> {
-        let file = File::open(&self.meta_path)?;
-        let reader = BufReader::new(file);
+        let _t = metrics::timer(Stage::MetaRead);
+        let (reader, first) = self.files.reader_at_id(id)?;
         let line = reader
-            .lines()
-            .nth(id)
+            .split(b'\n')
+            .nth(id - first)
             .ok_or_else(|| anyhow::anyhow!("metadata line not found"))??;
-        let r: Review = serde_json::from_str(&line)?;
-        Ok(r)
+        let review = Self::parse_line(&line).map_err(|e| anyhow::anyhow!("metadata line {id}: {e}"))?;
+        Ok(self.current(id, review))
+    }
+    /// Lines `ids` (ascending, distinct) that are still live, with their current status.
+    /// Reads forward from the block or tail holding the first and seeks again through the
+    /// block index only when the next id lies in a later block, so each block is
+    /// decompressed at most once and blocks without a wanted line not at all. The tail
+    /// (reviews.jsonl, all of the metadata when uncompressed) has no line index: it is read
+    /// from its first line up to the last id wanted in it. Ids past the end, and lines
+    /// failing their checksum, are left out.
+    fn read_reviews(&self, ids: &[usize]) -> Result<Vec<(usize, Review)>> {
+        let _t = metrics::timer(Stage::MetaRead);
+        let (mut out, mut line) = (Vec::with_capacity(ids.len()), Vec::new());
+        // The open reader and the id of the line it reads next.
+        let mut at: Option<(std::io::BufReader<meta_blocks::RawReader>, usize)> = None;
+        for &id in ids {
+            if !self.is_live(id) { continue; }
+            if !matches!(&at, Some((_, next)) if *next <= id && self.files.start_of(id) <= *next) {
+                at = Some(self.files.reader_at_id(id)?);
+            }
+            let (rdr, next) = at.as_mut().expect("opened above");
+            let mut n = 0;
+            while *next <= id {
+                line.clear();
+                n = rdr.read_until(b'\n', &mut line)?;
+                if n == 0 || line.last() != Some(&b'\n') { return Ok(out); }
+                *next += 1;
+            }
+            match Self::parse_line(&line[..n - 1]) {
+                Ok(review) => out.push((id, self.current(id, review))),
+                Err(e) => tracing::warn!("metadata line {id}: {e}"),
+            }
+        }
+        Ok(out)
+    }
+    /// `review` (line `id`) with its edits and its current moderation status instead of
+    /// those written.
+    fn current(&self, id: usize, review: Review) -> Review {
+        let mut review = self.patches.apply(id, review);
+        review.status = Some(self.attrs.get(id).status);
+        review
     }
     fn count(&self) -> anyhow::Result<usize> {
-        let f = File::open(&self.meta_path)?;
-        let rdr = BufReader::new(f);
-        Ok(rdr.lines().count())
+        self.files.count_lines()
+    }
+    /// Reads up to `limit` complete lines starting at byte `offset` of the uncompressed
+    /// metadata, which must be a line start whose id is `first_id`. Returns the records, the offset just past the last line
+    /// read and how many lines that was; lines failing their checksum keep their id but are
+    /// left out of the records. A trailing line without '\n' (append in progress) is left
+    /// for the next call.
+    fn read_page(&self, offset: u64, first_id: usize, limit: usize) -> Result<Page> {
+        let _t = metrics::timer(Stage::MetaRead);
+        let mut rdr = self.files.reader_at(offset)?;
+        let (mut out, mut pos, mut read, mut line) = (Vec::with_capacity(limit), offset, 0, Vec::new());
+        while read < limit {
+            line.clear();
+            let n = rdr.read_until(b'\n', &mut line)?;
+            if n == 0 || line.last() != Some(&b'\n') { break; }
+            match Self::parse_line(&line[..n - 1]) {
+                Ok(r) => out.push((first_id + read, self.current(first_id + read, r))),
+                Err(e) => tracing::warn!("metadata line {} skipped: {e}", first_id + read),
+            }
+            read += 1;
+            pos += n as u64;
+        }
+        Ok((out, pos, read))
+    }
+    /// Calls `f(id, review)` for every line in `range` (open-ended if `range.end` is `usize::MAX`).
+    /// A line failing its checksum is logged and passed as `None`, so callers keep ids aligned.
+    fn for_each_in(&self, range: std::ops::Range<usize>, mut f: impl FnMut(usize, Option<Review>) -> Result<()>) -> Result<usize> {
+        self.for_each_line(range, |id, r| f(id, r.map(|r| self.current(id, r))))
+    }
+    /// `for_each_in` with each review as written, status included.
+    fn for_each_line(&self, range: std::ops::Range<usize>, mut f: impl FnMut(usize, Option<Review>) -> Result<()>) -> Result<usize> {
+        let (rdr, first) = self.files.reader_at_id(range.start)?;
+        let mut n = 0;
+        let take = range.end.saturating_sub(range.start);
+        for (id, line) in rdr.split(b'\n').enumerate().map(|(i, l)| (first + i, l)).skip(range.start - first).take(take) {
+            let r = match Self::parse_line(&line?) {
+                Ok(r) => Some(r),
+                Err(e) => { tracing::warn!("metadata line {id} skipped: {e}"); None }
+            };
+            f(id, r)?;
+            n += 1;
+        }
+        Ok(n)
+    }
+}
+
+// =========== Errors ===========
+struct ApiError {
+    status: StatusCode,
+    msg: String,
+}
+impl ApiError {
+    fn new(status: StatusCode, msg: impl Into<String>) -> Self {
+        Self { status, msg: msg.into() }
+    }
+    fn bad_request(msg: impl Into<String>) -> Self {
+        Self { status: StatusCode::BAD_REQUEST, msg: msg.into() }
+    }
+    fn not_found(msg: impl Into<String>) -> Self {
+        Self { status: StatusCode::NOT_FOUND, msg: msg.into() }
+    }
+    fn conflict(msg: impl Into<String>) -> Self {
+        Self { status: StatusCode::CONFLICT, msg: msg.into() }
+    }
+    fn payload_too_large(msg: impl Into<String>) -> Self {
+        Self { status: StatusCode::PAYLOAD_TOO_LARGE, msg: msg.into() }
+    }
+    fn unavailable(msg: impl Into<String>) -> Self {
+        Self { status: StatusCode::SERVICE_UNAVAILABLE, msg: msg.into() }
+    }
+    fn forbidden(msg: impl Into<String>) -> Self {
+        Self { status: StatusCode::FORBIDDEN, msg: msg.into() }
+    }
+    fn too_many_requests(msg: impl Into<String>) -> Self {
+        Self { status: StatusCode::TOO_MANY_REQUESTS, msg: msg.into() }
+    }
+}
+impl From<anyhow::Error> for ApiError {
+    fn from(e: anyhow::Error) -> Self {
+        Self { status: StatusCode::INTERNAL_SERVER_ERROR, msg: e.to_string() }
     }
 }
+/// Response extension tagging bodies that already use the JSON error shape.
+#[derive(Clone, Copy)]
+struct ApiErrorMarker;
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        if self.status.is_server_error() { tracing::error!("{}", self.msg); }
+        let mut resp = (self.status, Json(serde_json::json!({ "error": self.msg }))).into_response();
+        resp.extensions_mut().insert(ApiErrorMarker);
+        resp
+    }
+}
+
+/// Runs disk-bound work (metadata and mirror reads/writes, fsync) on tokio's blocking pool
+/// so a slow disk never stalls the executor threads serving other requests.
+async fn blocking<T, F>(f: F) -> Result<T, ApiError>
+where
+    F: FnOnce() -> Result<T, ApiError> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| ApiError::from(anyhow::anyhow!("blocking task failed: {e}")))?
+}
 
+/// Index + embedder pair that serves traffic; swapped as a unit by reindex.
 #[derive(Clone)]
-struct AppState {
-    meta: Arc<MetaStore>,
+struct Active {
     vindex: Arc<dyn VecIndex>,
     embedder: Arc<dyn Embedder>,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
-struct Review {
-    review_title: String,
-    review_body: String,
-    product_id: String,
-    review_rating: i32,
-}
-#[derive(Serialize, Deserialize)]
-struct ReviewResp { id: usize }
-#[derive(Serialize, Deserialize)]
-struct BulkResp { inserted: usize }
-#[derive(Serialize, Deserialize)]
-struct SearchReq { query: String, top_k: Option<usize> }
-#[derive(Serialize, Deserialize)]
-struct SearchHit { id: usize, score: f32, review: Review }
-#[derive(Serialize, Deserialize)]
-struct SearchResp { hits: Vec<SearchHit> }
+#[derive(Clone)]
+struct AppState {
+    config: Arc<Config>,
+    meta: Arc<MetaStore>,
+    active: Arc<RwLock<Active>>,
+    /// Receives every write alongside `active`; only read by `compare=true` searches.
+    shadow: Option<Active>,
+    /// Title and body vectors of every review, for field-scored searches of `active`.
+    fields: Option<Arc<fields::Fields>>,
+    /// Several vectors per review, for re-ranking searches by late interaction.
+    late_interaction: Option<Arc<late_interaction::LateInteraction>>,
+    /// Binary sketches of the mirrors searched, for the Hamming-distance first pass.
+    sketches: Option<Arc<sketch::Sketches>>,
+    // Held across "append vector + append metadata" so ids stay aligned and reindex
+    // can catch up on the tail before swapping.
+    write_gate: Arc<Mutex<()>>,
+    /// Runs every insert, one at a time under `write_gate`.
+    writer: Arc<writer::Writer>,
+    jobs: Arc<JobRegistry>,
+    collections: Arc<collections::Collections>,
+    growth: Arc<storage::GrowthTracker>,
+    vcache: Arc<vcache::VectorCache>,
+    slow_log: Arc<slow_log::SlowLog>,
+    audit: Arc<audit::AuditLog>,
+    feedback: Arc<feedback::FeedbackLog>,
+    tenants: Arc<tenants::Tenants>,
+    embedders: Arc<embedders::Registry>,
+    moderator: Arc<moderation::Moderator>,
+    maintenance: Arc<maintenance::Maintenance>,
+    /// Router mode (see `shards`).
+    #[cfg(feature = "shards")]
+    shards: Option<Arc<shards::ShardRouter>>,
+    /// Chat model behind /ask.
+    #[cfg(feature = "llm")]
+    llm: Option<Arc<ask::Llm>>,
+    data_dir: PathBuf,
+}
+impl AppState {
+    /// Index to search: the named alias/collection, or the active one.
+    fn target(&self, collection: Option<&str>) -> Result<Active, ApiError> {
+        match collection {
+            Some(name) => self.collections.resolve(self, name),
+            None => Ok(self.active.read().clone()),
+        }
+    }
+    /// `target`, or for a request naming a registered embedder, the collection that embedder
+    /// built (see `Collections::resolve_model`).
+    fn target_for(&self, collection: Option<&str>, embedder: Option<&str>) -> Result<Active, ApiError> {
+        match embedder {
+            Some(model) => self.collections.resolve_model(self, collection, model),
+            None => self.target(collection),
+        }
+    }
+    fn vindex(&self) -> Arc<dyn VecIndex> { self.active.read().vindex.clone() }
+    fn embedder(&self) -> Arc<dyn Embedder> { self.active.read().embedder.clone() }
 
-#[derive(Deserialize)]
-struct InsertReq { review: Review }
+    /// Mirrors a primary write into the shadow, field and late interaction indexes. Failures are logged, never
+    /// surfaced: they must not affect the primary write path, so their commits are not waited
+    /// on (the group-commit writer logs failed writes itself).
+    fn append_secondary(&self, review: &Review) {
+        if let Some(sh) = &self.shadow {
+            let res = sh.embedder.embed_index(&review.embed_text()).and_then(|v| sh.vindex.append_pending(&v, payload_of(review)));
+            if let Err(e) = res { tracing::warn!("shadow append failed: {e}"); }
+        }
+        if let Some(fields) = &self.fields { fields.append(review); }
+        if let Some(late) = &self.late_interaction { late.append(review); }
+    }
+
+    /// Writes `review`'s metadata line with its initial status, and logs what the moderation
+    /// checks made of it.
+    /// Returns the line's length in bytes. Callers hold the write gate.
+    fn append_meta(&self, review: &Review) -> Result<u64> {
+        let verdict = self.moderator.check(review);
+        let id = self.meta.attrs.len();
+        let bytes = self.meta.append(review, self.moderator.initial_status(verdict.as_ref()))?;
+        if let Some(v) = verdict { self.meta.moderation.record(id, v)?; }
+        Ok(bytes)
+    }
+}
+
+/// Embedder for the index in `dir`; embedders with state of their own keep it there.
+fn build_embedder(cfg: &EmbedderConfig, dir: &FsPath) -> Result<Arc<dyn Embedder>> {
+    Ok(match cfg {
+        EmbedderConfig::Tfidf { dim, vocabulary: false } => Arc::new(TimedEmbedder::new(TfIdfEmbedder::new(*dim), dir)),
+        EmbedderConfig::Tfidf { dim, vocabulary: true } => Arc::new(TimedEmbedder::new(TfIdfEmbedder::with_vocabulary(*dim, dir)?, dir)),
+        #[cfg(feature = "candle")]
+        EmbedderConfig::Candle { model_dir, dim, max_tokens } => {
+            Arc::new(TimedEmbedder::new(embed_candle::CandleEmbedder::load(model_dir, *dim, *max_tokens)?, dir))
+        }
+        #[cfg(not(feature = "candle"))]
+        EmbedderConfig::Candle { .. } => anyhow::bail!("candle embedder requested, but built without the `candle` feature"),
+        #[cfg(feature = "fastembed")]
+        EmbedderConfig::Fastembed { model, dim, cache_dir, batch_size } => {
+            Arc::new(TimedEmbedder::new(embed_fastembed::FastEmbedder::load(model, *dim, cache_dir.as_deref(), *batch_size)?, dir))
+        }
+        #[cfg(not(feature = "fastembed"))]
+        EmbedderConfig::Fastembed { .. } => anyhow::bail!("fastembed embedder requested, but built without the `fastembed` feature"),
+        #[cfg(feature = "remote-embedder")]
+        EmbedderConfig::Remote { url, model, dim, api_key_env, batch_size, timeout_ms, max_retries } => {
+            Arc::new(TimedEmbedder::new(embed_remote::RemoteEmbedder::new(embed_remote::RemoteOptions {
+                url,
+                model,
+                dim: *dim,
+                api_key_env,
+                batch_size: *batch_size,
+                timeout_ms: *timeout_ms,
+                max_retries: *max_retries,
+            })?, dir))
+        }
+        #[cfg(not(feature = "remote-embedder"))]
+        EmbedderConfig::Remote { .. } => anyhow::bail!("remote embedder requested, but built without the `remote-embedder` feature"),
+    })
+}
+
+/// How `rank_in` picks and orders hits.
+#[derive(Default)]
+struct RankOpts {
+    k: usize,
+    /// Query texts with their weights; a plain search has one, a search by example none.
+    queries: Vec<(String, f32)>,
+    fusion: Fusion,
+    examples: Option<fusion::Examples>,
+    /// Keyword prefilter candidate limit; None scores every vector.
+    prefilter: Option<usize>,
+    half_life_days: Option<f64>,
+    score: Option<score_expr::Expr>,
+    /// `(group_by, per_group)`.
+    group: Option<(GroupBy, usize)>,
+    /// Language codes hits must be in; empty for any.
+    langs: Vec<String>,
+    /// Pending and rejected reviews are hits too.
+    include_unapproved: bool,
+    /// Products hits must be of; empty for any.
+    product_ids: Vec<String>,
+    /// Inclusive bounds on the rating.
+    ratings: (Option<i32>, Option<i32>),
+    /// `created_at` bounds: at or after, and before.
+    created: (Option<u64>, Option<u64>),
+    /// Title and body scored separately instead of the combined vector (see `use_fields`).
+    fields: Option<fields::FieldSearch>,
+    /// Re-ranks the top `k * overfetch` (see `use_late_interaction`).
+    late: Option<Arc<late_interaction::LateInteraction>>,
+    /// Hamming-distance first pass (see `use_sketch`).
+    sketch: Option<Arc<sketch::Sketches>>,
+    /// Hits from the spfresh index's traversal instead of a scan (see `use_ann`).
+    ann: bool,
+    /// Hits taken from the spfresh index: `k`, or more when re-ranked or grouped afterwards.
+    ann_candidates: usize,
+}
+
+impl RankOpts {
+    fn from_req(req: &SearchReq, k: usize) -> Result<Self, ApiError> {
+        if req.half_life_days.is_some_and(|h| !(h > 0.0 && h.is_finite())) {
+            return Err(ApiError::bad_request("half_life_days must be a positive number"));
+        }
+        let score = req.score.as_deref().map(score_expr::Expr::parse).transpose().map_err(ApiError::bad_request)?;
+        if let Some(FieldScoring::Weighted { title, body }) = req.fields
+            && !(title.is_finite() && body.is_finite() && title >= 0.0 && body >= 0.0 && title + body > 0.0)
+        {
+            return Err(ApiError::bad_request("fields weights must be non-negative and not both zero"));
+        }
+        let per_group = req.per_group.unwrap_or(1);
+        if per_group == 0 { return Err(ApiError::bad_request("per_group must be at least 1")); }
+        let group = req.group_by.map(|g| (g, per_group));
+        let examples = Self::examples(req)?;
+        let queries = if examples.is_some() { Vec::new() } else { Self::queries(req)? };
+        let fusion = req.fusion.unwrap_or_default();
+        let langs = req.lang.iter().map(|l| l.trim().to_lowercase()).collect();
+        Ok(Self {
+            k, queries, fusion, examples, prefilter: req.candidates, half_life_days: req.half_life_days, score, group, langs,
+            include_unapproved: req.include_unapproved,
+            product_ids: req.product_ids.clone(),
+            ratings: (req.min_rating, req.max_rating),
+            created: (req.created_after, req.created_before),
+            fields: None,
+            late: None,
+            sketch: None,
+            ann: false,
+            ann_candidates: k,
+        })
+    }
+
+    /// A single query with no other options.
+    fn plain(query: &str, k: usize) -> Self {
+        Self { k, queries: vec![(query.to_string(), 1.0)], ..Default::default() }
+    }
+
+    fn queries(req: &SearchReq) -> Result<Vec<(String, f32)>, ApiError> {
+        if req.queries.is_empty() {
+            if req.weights.is_some() { return Err(ApiError::bad_request("weights need queries")); }
+            return Ok(vec![(req.query.clone(), 1.0)]);
+        }
+        if !req.query.is_empty() { return Err(ApiError::bad_request("send either query or queries, not both")); }
+        if req.queries.len() > fusion::MAX_QUERIES {
+            return Err(ApiError::bad_request(format!("at most {} queries", fusion::MAX_QUERIES)));
+        }
+        let weights = match &req.weights {
+            None => vec![1.0; req.queries.len()],
+            Some(w) if w.len() != req.queries.len() => return Err(ApiError::bad_request("weights must have one entry per query")),
+            Some(w) if w.iter().any(|x| !(x.is_finite() && *x >= 0.0)) || w.iter().sum::<f32>() <= 0.0 => {
+                return Err(ApiError::bad_request("weights must be non-negative and not all zero"));
+            }
+            Some(w) => w.clone(),
+        };
+        Ok(req.queries.iter().cloned().zip(weights).collect())
+    }
+
+    fn examples(req: &SearchReq) -> Result<Option<fusion::Examples>, ApiError> {
+        if req.positive_ids.is_empty() {
+            if !req.negative_ids.is_empty() || req.negative_weight.is_some() {
+                return Err(ApiError::bad_request("negative_ids need positive_ids"));
+            }
+            return Ok(None);
+        }
+        if !req.query.is_empty() || !req.queries.is_empty() {
+            return Err(ApiError::bad_request("send one of query, queries or positive_ids"));
+        }
+        if req.candidates.is_some() { return Err(ApiError::bad_request("candidates needs a text query")); }
+        if req.positive_ids.len() > fusion::MAX_EXAMPLES || req.negative_ids.len() > fusion::MAX_EXAMPLES {
+            return Err(ApiError::bad_request(format!("at most {} positive and {} negative ids", fusion::MAX_EXAMPLES, fusion::MAX_EXAMPLES)));
+        }
+        let negative_weight = req.negative_weight.unwrap_or(0.5);
+        if !(negative_weight.is_finite() && negative_weight >= 0.0) {
+            return Err(ApiError::bad_request("negative_weight must be a non-negative number"));
+        }
+        Ok(Some(fusion::Examples {
+            positive: req.positive_ids.clone(),
+            negative: req.negative_ids.clone(),
+            negative_weight,
+            ids: req.positive_ids.iter().chain(&req.negative_ids).copied().collect(),
+        }))
+    }
+
+    /// 400 unless every example review exists in `active`'s index and is live.
+    fn check_examples(&self, meta: &MetaStore, active: &Active) -> Result<(), ApiError> {
+        let Some(ex) = &self.examples else { return Ok(()) };
+        for &id in &ex.ids {
+            if !meta.is_live(id) || active.vindex.read_mirror(id)?.is_none() {
+                return Err(ApiError::bad_request(format!("review {id} does not exist")));
+            }
+        }
+        Ok(())
+    }
+
+    /// Scores titles and bodies separately when the request asks to, or `[fields]` does by
+    /// default. Only the active index has field vectors, and a search by example has no text
+    /// to embed per field: 400 if the request asks anyway, the combined vector if only the
+    /// default does.
+    fn use_fields(&mut self, fields: Option<&Arc<fields::Fields>>, req: &SearchReq) -> Result<(), ApiError> {
+        let Some(mode) = req.fields.or(fields.map(|f| f.default)) else { return Ok(()) };
+        if mode == FieldScoring::Combined { return Ok(()); }
+        let Some(fields) = fields else {
+            return Err(ApiError::bad_request("per-field scoring needs a [fields] section in the service config"));
+        };
+        if self.examples.is_some() || req.collection.is_some() || req.embedder.is_some() {
+            if req.fields.is_none() { return Ok(()); }
+            return Err(ApiError::bad_request("per-field scoring only applies to text searches of the active index"));
+        }
+        self.fields = Some(fields::FieldSearch { fields: fields.clone(), mode });
+        Ok(())
+    }
+
+    /// Re-ranks by late interaction when the request asks to; 400 without
+    /// `[late_interaction]` or a single text query to split into words.
+    fn use_late_interaction(&mut self, late: Option<&Arc<late_interaction::LateInteraction>>, req: &SearchReq) -> Result<(), ApiError> {
+        if !req.late_interaction { return Ok(()); }
+        let Some(late) = late else {
+            return Err(ApiError::bad_request("late_interaction needs a [late_interaction] section in the service config"));
+        };
+        if self.queries.len() != 1 { return Err(ApiError::bad_request("late_interaction needs a single query")); }
+        self.late = Some(late.clone());
+        Ok(())
+    }
+
+    /// Runs the sketch first pass when `[search.sketch]` is configured, unless the request
+    /// turns it off; 400 if it asks for it without.
+    fn use_sketch(&mut self, sketches: Option<&Arc<sketch::Sketches>>, req: &SearchReq) -> Result<(), ApiError> {
+        match (sketches, req.sketch) {
+            (None, Some(true)) => Err(ApiError::bad_request("sketch needs a [search.sketch] section in the service config")),
+            (Some(sketches), None | Some(true)) => {
+                self.sketch = Some(sketches.clone());
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Searches the spfresh index when `[search] ann` is set, unless the request turns it off;
+    /// 400 if it asks for it without, or with anything beyond one text query, filters, a
+    /// score formula, decay and grouping, which only a scan can score. Re-ranked or grouped
+    /// searches take the over-fetch `search` configures from the index.
+    fn use_ann(&mut self, search: &SearchConfig, req: &SearchReq) -> Result<(), ApiError> {
+        let plain = self.queries.len() == 1 && self.prefilter.is_none() && self.fields.is_none() && self.late.is_none();
+        match (search.ann, req.ann) {
+            (false, Some(true)) => Err(ApiError::bad_request("ann needs `ann = true` in the [search] section of the service config")),
+            (true, Some(true)) if !plain => Err(ApiError::bad_request(
+                "ann only applies to a single text query with filters, a score formula, decay or grouping",
+            )),
+            (true, None | Some(true)) => {
+                self.ann = plain;
+                let reranked = self.score.is_some() || self.half_life_days.is_some();
+                self.ann_candidates = search.ann_candidates(self.k, reranked, self.group.is_some());
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Everything the service config adds to the request's options.
+    fn attach(&mut self, st: &AppState, req: &SearchReq) -> Result<(), ApiError> {
+        self.use_fields(st.fields.as_ref(), req)?;
+        self.use_late_interaction(st.late_interaction.as_ref(), req)?;
+        self.use_sketch(st.sketches.as_ref(), req)?;
+        self.use_ann(&st.config.search, req)
+    }
+
+    fn excludes(&self, id: usize) -> bool {
+        self.examples.as_ref().is_some_and(|ex| ex.ids.contains(&id))
+    }
 
-async fn insert_one(State(st): State<AppState>, Json(req): Json<InsertReq>) -> Json<ReviewResp> {
+    /// The query texts as one string, for the keyword prefilter and the slow-query log.
+    fn text(&self) -> String {
+        match &self.examples {
+            Some(ex) => format!("positive_ids {:?} negative_ids {:?}", ex.positive, ex.negative),
+            None => self.queries.iter().map(|(q, _)| q.as_str()).collect::<Vec<_>>().join(" | "),
+        }
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+async fn insert_one(State(st): State<AppState>, actor: Actor, Negotiated(req, fmt): Negotiated<InsertReq>) -> Result<Reply<ReviewResp>, ApiError> {
     tracing::info!("insert_one: {}", req.review.review_title);
-    let txt = format!("{} {}", req.review.review_title, req.review.review_body);
-    let vec = st.embedder.embed_index(&txt).expect("embed fail");
-    let id = st.vindex.append(&vec).expect("append vec fail");
-    st.meta.append(&req.review).expect("append meta fail");
-    Json(ReviewResp { id })
+    let (txt, return_vector) = (req.review.embed_text(), req.return_vector);
+    st.tenants.admit_write(&st.config.quotas, &actor.principal, 1)?;
+    let (id, commit, bytes, vec) = st.writer.submit({
+        let st = st.clone();
+        move || {
+            let vec = st.embedder().embed_index(&txt)?;
+            let (id, commit) = st.vindex().append_pending(&vec, payload_of(&req.review))?;
+            let bytes = st.append_meta(&req.review)? + codec::record_len(vec.len()) as u64;
+            st.append_secondary(&req.review);
+            Ok((id, commit, bytes, vec))
+        }
+    }).await?;
+    let id = blocking(move || {
+        // Waited on off the writer, so the writes queued behind this one can share the fsync.
+        commit.wait()?;
+        st.audit.record(&actor, Action::Insert, [id].into_iter().collect(), None)?;
+        st.tenants.charge(&actor.principal, 1, bytes)?;
+        Ok(id)
+    }).await?;
+    Ok(Reply(fmt, ReviewResp { id, vector: return_vector.then_some(vec) }))
 }
 
 #[derive(Deserialize)]
-struct BulkInsertReq { reviews: Vec<Review> }
+struct DeleteParams { version: Option<u64> }
+
+/// DELETE /reviews/:id?version= — tombstones a review; `version` must be the one last read.
+async fn delete_review(State(st): State<AppState>, actor: Actor, Path(id): Path<usize>, Query(p): Query<DeleteParams>) -> Result<StatusCode, ApiError> {
+    let Some(version) = p.version else { return Err(ApiError::bad_request("version is required")) };
+    blocking(move || {
+        let review = {
+            let _w = st.write_gate.lock();
+            if id >= st.meta.count()? || !st.meta.is_live(id) {
+                return Err(ApiError::not_found(format!("review {id} not found")));
+            }
+            let current = st.meta.attrs.get(id).version;
+            if current != version {
+                return Err(ApiError::conflict(format!("version {version} of review {id} is stale; current is {current}")));
+            }
+            let review = st.meta.read_review_by_line(id)?;
+            st.meta.delete(id, &review)?;
+            review
+        };
+        st.audit.record(&actor, Action::Delete, [id].into_iter().collect(), review.external_id)?;
+        Ok(StatusCode::NO_CONTENT)
+    }).await
+}
 
-async fn insert_bulk(State(st): State<AppState>, Json(req): Json<BulkInsertReq>) -> Json<BulkResp> {
-    let mut ok = 0usize;
-    for r in req.reviews {
-        let txt = format!("{} {}", r.review_title, r.review_body);
-        let vec = st.embedder.embed_index(&txt).expect("embed fail");
-        let _ = st.vindex.append(&vec).expect("append vec fail");
-        st.meta.append(&r).expect("append meta fail");
-        ok += 1;
+/// PATCH /reviews/:id — changes a review's rating, product or status where it is, without
+/// re-embedding it or giving it a new id; `version` must be the one last read. Any patch
+/// gives the review the next version (see `patches`); a status is also logged as a
+/// moderation decision.
+async fn patch_review(State(st): State<AppState>, actor: Actor, Path(id): Path<usize>, Negotiated(req, fmt): Negotiated<PatchReq>) -> Result<Reply<PatchResp>, ApiError> {
+    let fields: Vec<&str> = [
+        ("review_rating", req.review_rating.is_some()),
+        ("product_id", req.product_id.is_some()),
+        ("status", req.status.is_some()),
+    ].into_iter().filter_map(|(name, given)| given.then_some(name)).collect();
+    if fields.is_empty() {
+        return Err(ApiError::bad_request("nothing to change; send review_rating, product_id or status"));
     }
-    Json(BulkResp { inserted: ok })
+    if req.product_id.as_deref() == Some("") {
+        return Err(ApiError::bad_request("product_id must not be empty"));
+    }
+    blocking(move || {
+        let review = {
+            let _w = st.write_gate.lock();
+            if id >= st.meta.count()? || !st.meta.is_live(id) {
+                return Err(ApiError::not_found(format!("review {id} not found")));
+            }
+            let current = st.meta.attrs.get(id).version;
+            if current != req.version {
+                return Err(ApiError::conflict(format!("version {} of review {id} is stale; current is {current}", req.version)));
+            }
+            // Every accepted patch bumps the version, a status-only one too, so a client holding
+            // the old version cannot overwrite a change it has not seen.
+            st.meta.patch(id, current + 1, req.review_rating, req.product_id)?;
+            if let Some(status) = req.status {
+                st.meta.moderation.set_status(id, status, Some("patched".into()))?;
+                st.meta.set_status(id, status);
+            }
+            st.meta.read_review_by_line(id)?
+        };
+        st.audit.record(&actor, Action::Patch, [id].into_iter().collect(), Some(fields.join(", ")))?;
+        Ok(Reply(fmt, PatchResp { id, review }))
+    }).await
+}
+
+/// POST /reviews/upsert — inserts a review keyed by its `external_id`, or replaces the
+/// review currently holding it if the request carries that review's `version`. Storage is append-only, so a replacement gets a new id
+/// with its new vector and the old id stops being served. Unless the request sets it,
+/// `created_at` carries over from the replaced review.
+async fn upsert(State(st): State<AppState>, actor: Actor, Negotiated(req, fmt): Negotiated<InsertReq>) -> Result<Reply<UpsertResp>, ApiError> {
+    let Some(external_id) = req.review.external_id.clone().filter(|e| !e.is_empty()) else {
+        return Err(ApiError::bad_request("external_id is required"));
+    };
+    let (txt, return_vector) = (req.review.embed_text(), req.return_vector);
+    let (id, replaced, commit, bytes, vec) = st.writer.submit({
+        let (st, actor, external_id) = (st.clone(), actor.clone(), external_id.clone());
+        move || {
+            let replaced = st.meta.external.get(&external_id);
+            let current = replaced.map(|id| st.meta.attrs.get(id).version);
+            match (current, req.review.version) {
+                (None, Some(v)) => return Err(ApiError::conflict(format!("external_id '{external_id}' does not exist (version {v} given)"))),
+                (Some(c), None) => return Err(ApiError::conflict(format!("external_id '{external_id}' exists at version {c}; send that version to replace it"))),
+                (Some(c), Some(v)) if c != v => return Err(ApiError::conflict(format!("version {v} of '{external_id}' is stale; current is {c}"))),
+                _ => {}
+            }
+            st.tenants.admit_write(&st.config.quotas, &actor.principal, replaced.is_none() as u64)?;
+            let mut review = req.review;
+            if let Some(old) = replaced {
+                review.created_at = review.created_at.or(st.meta.attrs.get(old).created_at);
+            }
+            let vec = st.embedder().embed_index(&txt)?;
+            let (id, commit) = st.vindex().append_pending(&vec, payload_of(&review))?;
+            let bytes = st.append_meta(&review)? + codec::record_len(vec.len()) as u64;
+            st.append_secondary(&review);
+            Ok((id, replaced, commit, bytes, vec))
+        }
+    }).await?;
+    let resp = blocking(move || {
+        commit.wait()?;
+        st.audit.record(&actor, Action::Upsert, [id].into_iter().collect(), Some(external_id.clone()))?;
+        st.tenants.charge(&actor.principal, replaced.is_none() as u64, bytes)?;
+        let version = st.meta.attrs.get(id).version;
+        Ok(UpsertResp { id, external_id, replaced, version, vector: return_vector.then_some(vec) })
+    }).await?;
+    Ok(Reply(fmt, resp))
+}
+
+
+async fn insert_bulk(State(st): State<AppState>, actor: Actor, Negotiated(req, fmt): Negotiated<BulkInsertReq>) -> Result<Reply<BulkResp>, ApiError> {
+    let ok = blocking(move || insert_batch(&st, &actor, req.reviews, None)).await?;
+    Ok(Reply(fmt, BulkResp { inserted: ok }))
+}
+
+/// Embeds and appends `reviews` as one write of the writer, then waits for the group
+/// commit; audited as a single insert with `subject`. Returns how many were written. Runs
+/// off the async runtime.
+fn insert_batch(st: &AppState, actor: &Actor, reviews: Vec<Review>, subject: Option<String>) -> Result<usize, ApiError> {
+    st.tenants.admit_write(&st.config.quotas, &actor.principal, reviews.len() as u64)?;
+    let (ok, last, ids, bytes) = st.writer.submit_blocking({
+        let st = st.clone();
+        move || {
+            let (mut ok, mut last, mut ids, mut bytes) = (0usize, None, audit::IdRanges::default(), 0u64);
+            let (embedder, vindex) = (st.embedder(), st.vindex());
+            let texts: Vec<String> = reviews.iter().map(Review::embed_text).collect();
+            let vecs = embedder.embed_index_batch(&texts)?;
+            for (r, vec) in reviews.into_iter().zip(vecs) {
+                let (id, commit) = vindex.append_pending(&vec, payload_of(&r))?;
+                last = Some(commit);
+                bytes += st.append_meta(&r)? + codec::record_len(vec.len()) as u64;
+                st.append_secondary(&r);
+                ids.push(id);
+                ok += 1;
+            }
+            Ok((ok, last, ids, bytes))
+        }
+    })?;
+    // The whole batch is group-committed; the last commit resolving covers every vector.
+    if let Some(c) = last { c.wait()?; }
+    st.audit.record(actor, Action::Insert, ids, subject)?;
+    st.tenants.charge(&actor.principal, ok as u64, bytes)?;
+    Ok(ok)
+}
+
+
+// Pre-computed embeddings skip the embedder; they are only checked and normalised
+// so that dot-product scoring in /search stays a cosine.
+async fn insert_raw(State(st): State<AppState>, actor: Actor, Negotiated(req, fmt): Negotiated<RawInsertReq>) -> Result<Reply<ReviewResp>, ApiError> {
+    st.tenants.admit_write(&st.config.quotas, &actor.principal, 1)?;
+    let return_vector = req.return_vector;
+    let (id, commit, bytes, vec) = st.writer.submit({
+        let st = st.clone();
+        move || {
+            let vindex = st.vindex();
+            if let Some(model) = &req.embedder { st.collections.check_active_model(&st, vindex.as_ref(), model)?; }
+            let dim = vindex.dim();
+            if req.vector.len() != dim {
+                return Err(ApiError::bad_request(format!("vector dim mismatch: {} != {}", req.vector.len(), dim)));
+            }
+            if req.vector.iter().any(|x| !x.is_finite()) {
+                return Err(ApiError::bad_request("vector contains NaN or infinite values"));
+            }
+            let mut vec = req.vector;
+            l2_normalize(&mut vec);
+            let (id, commit) = vindex.append_pending(&vec, payload_of(&req.review))?;
+            let bytes = st.append_meta(&req.review)? + codec::record_len(dim) as u64;
+            // The raw vector belongs to the primary model's space; the shadow embeds the text itself.
+            st.append_secondary(&req.review);
+            Ok((id, commit, bytes, vec))
+        }
+    }).await?;
+    let id = blocking(move || {
+        commit.wait()?;
+        st.audit.record(&actor, Action::Insert, [id].into_iter().collect(), Some("raw".into()))?;
+        st.tenants.charge(&actor.principal, 1, bytes)?;
+        Ok(id)
+    }).await?;
+    Ok(Reply(fmt, ReviewResp { id, vector: return_vector.then_some(vec) }))
 }
 
 fn cosine(a: &[f32], b: &[f32]) -> f32 {
@@ -264,69 +1444,329 @@ fn cosine(a: &[f32], b: &[f32]) -> f32 {
     s
 }
 
-async fn search(State(st): State<AppState>, Json(req): Json<SearchReq>) -> Json<SearchResp> {
-    let k = req.top_k.unwrap_or(5).min(100);
-    let qv = match st.embedder.embed_query(&req.query) {
+async fn search(State(st): State<AppState>, Negotiated(req, fmt): Negotiated<SearchReq>) -> Result<Reply<SearchResp>, ApiError> {
+    let k = st.config.search.top_k(req.top_k);
+    #[cfg(feature = "shards")]
+    if let Some(router) = st.shards.clone() {
+        let resp = blocking(move || router.search(req, k)).await?;
+        return Ok(Reply(fmt, resp));
+    }
+    let started = Instant::now();
+    let resp = blocking(move || {
+        let mut stats = SearchStats::default();
+        let active = st.target_for(req.collection.as_deref(), req.embedder.as_deref())?;
+        let mut opts = RankOpts::from_req(&req, k)?;
+        opts.check_examples(&st.meta, &active)?;
+        opts.attach(&st, &req)?;
+        let mut hits = search_in(&st.meta, &st.vcache, &active, &opts, &mut stats);
+        if req.explain { explain::annotate(&st.meta, &active, &opts, &mut hits)?; }
+        // The shadow has no field vectors; it is compared on its combined one.
+        opts.fields = None;
+        let shadow_hits = match (&st.shadow, req.compare) {
+            (Some(sh), true) => Some(search_in(&st.meta, &st.vcache, sh, &opts, &mut stats)),
+            (None, true) => { tracing::warn!("compare=true but no shadow index is configured"); None }
+            _ => None,
+        };
+        let elapsed = started.elapsed();
+        stats.total_ms = elapsed.as_secs_f64() * 1e3;
+        st.slow_log.record(elapsed, slow_log::SlowQuery {
+            endpoint: "/search",
+            query: &opts.text(),
+            collection: req.collection.as_deref(),
+            top_k: k,
+            hits: hits.len(),
+            stages: &stats,
+        });
+        Ok(SearchResp { hits, shadow_hits, failed_shards: Vec::new(), stats: Some(stats) })
+    }).await?;
+    Ok(Reply(fmt, resp))
+}
+
+fn ms_since(t: Instant) -> f64 { t.elapsed().as_secs_f64() * 1e3 }
+
+/// Brute-force cosine scan over one index's mirror, or with `opts.ann` the spfresh index's
+/// traversal; errors are logged and yield no hits.
+fn search_in(meta: &MetaStore, cache: &vcache::VectorCache, active: &Active, opts: &RankOpts, stats: &mut SearchStats) -> Vec<SearchHit> {
+    let scored = if opts.ann { rank_ann(meta, active, opts, stats) } else { rank_in(meta, cache, active, opts, stats) };
+    let fetch = Instant::now();
+    let mut out = Vec::with_capacity(scored.len());
+    for Ranked { id, score, collapsed } in scored {
+        if let Ok(rev) = meta.read_review_by_line(id) {
+            out.push(SearchHit { id, score, review: rev, explain: None, score_parts: None, collapsed, shard: None });
+        } else {
+            tracing::warn!("meta read id={} failed", id);
+        }
+    }
+    stats.fetch_ms += ms_since(fetch);
+    out
+}
+
+/// One hit picked by `rank_in`.
+struct Ranked {
+    id: usize,
+    score: f32,
+    collapsed: Option<usize>,
+}
+
+/// Top-`k` hits, best first, without touching review metadata.
+/// With a prefilter, only the (at most that many) reviews sharing a term with the query
+/// are scored, instead of every vector. With per-field scoring, the body vectors are scanned
+/// in place of `active`'s, and each review's title similarity combined with its body's.
+/// With late interaction, the best `k * overfetch` are re-scored by it before any formula,
+/// decay or grouping. With sketches, only the vectors closest by Hamming distance (among the
+/// prefilter's candidates, if any) are scored.
+fn rank_in(meta: &MetaStore, cache: &vcache::VectorCache, active: &Active, opts: &RankOpts, stats: &mut SearchStats) -> Vec<Ranked> {
+    let body;
+    let active = match &opts.fields {
+        Some(fs) => { body = fs.body(); &body }
+        None => active,
+    };
+    let vindex = &active.vindex;
+    let embed = Instant::now();
+    let qvs = match fusion::query_vectors(active, opts) {
         Ok(v) => v,
         Err(e) => {
             tracing::error!("embed_query fail: {e}");
-            return Json(SearchResp { hits: vec![] });
+            return vec![];
         }
     };
-    let dim = qv.len();
-    let meta_count = match st.meta.count() {
+    stats.embed_ms += ms_since(embed);
+    let dim = qvs[0].len();
+    let meta_count = match meta.count() {
         Ok(n) => n,
-        Err(e) => { tracing::error!("meta count fail: {e}"); return Json(SearchResp { hits: vec![] }); }
+        Err(e) => { tracing::error!("meta count fail: {e}"); return vec![]; }
     };
 
-    // อ่านเวกเตอร์จากไฟล์ mirror ที่เราเขียนไว้ทุกครั้ง: data/reviews.index
-    let data_path = std::env::current_dir().unwrap_or_else(|_| ".".into())
-        .join("data").join("reviews.index");
-    let mut buf = Vec::new();
-    match std::fs::File::open(&data_path).and_then(|mut f| f.read_to_end(&mut buf)) {
-        Ok(_) => {},
-        Err(e) => {
-            tracing::error!("open/read {} fail: {}", data_path.display(), e);
-            return Json(SearchResp { hits: vec![] });
+    let _t = metrics::timer(Stage::Scoring);
+    let retrieve = Instant::now();
+    // อ่านเวกเตอร์จากไฟล์ mirror ที่เราเขียนไว้ทุกครั้ง: <index dir>/reviews.index
+    // Segments inside the memory budget come from RAM, the rest from an mmap of the file.
+    // ป้องกัน meta กับ mirror ไม่เท่ากัน: scan ไม่เกิน meta_count
+    let mut scored: Vec<(usize, f32)> = Vec::new();
+    let mut considered = 0;
+    let keep = filter(meta, opts);
+    let mut ids = opts.prefilter.map(|limit| meta.keywords.candidates(&opts.text(), limit));
+    if let Some(sketches) = &opts.sketch {
+        let pool: Box<dyn Iterator<Item = usize>> = match &ids {
+            Some(ids) => Box::new(ids.iter().copied()),
+            None => Box::new(0..meta_count),
+        };
+        match sketches.survivors(cache, vindex.mirror_path(), meta_count, &qvs, pool.filter(|&id| keep(id)), opts.k) {
+            Ok(near) => ids = Some(near),
+            Err(e) => { tracing::error!("sketch pass fail: {e}"); return vec![]; }
         }
     }
-
-    let bytes_per_vec = (dim * 4) as usize;
-    if buf.len() < bytes_per_vec {
-        tracing::warn!("mirror empty or dim mismatch: {} bytes, need {}", buf.len(), bytes_per_vec);
-        return Json(SearchResp { hits: vec![] });
+    let titles = match &opts.fields {
+        Some(fs) => match fs.title_scores(cache, &qvs, meta_count, ids.as_deref()) {
+            Ok(t) => t,
+            Err(e) => { tracing::error!("title scan fail: {e}"); return vec![]; }
+        },
+        None => Vec::new(),
+    };
+    let mut visit = |id: usize, v: &[f32]| {
+        considered += 1;
+        if keep(id) {
+            let sim = fusion::similarity(&qvs, v).0;
+            scored.push((id, opts.fields.as_ref().map_or(sim, |fs| fs.combine(titles[id], sim))));
+        }
+    };
+    let res = match &ids {
+        Some(ids) => cache.scan_ids(vindex.mirror_path(), dim, meta_count, ids, &mut visit),
+        None => cache.scan(vindex.mirror_path(), dim, meta_count, &mut visit),
+    };
+    if let Err(e) = res {
+        tracing::error!("scan {} fail: {}", vindex.mirror_path().display(), e);
+        return vec![];
     }
-    let total_vecs = buf.len() / bytes_per_vec;
-    // ป้องกัน meta กับ mirror ไม่เท่ากัน: ใช้อันที่น้อยกว่า
-    let n = std::cmp::min(meta_count, total_vecs);
+    stats.candidates += considered;
+    stats.matched += scored.len();
+    if let Some(late) = &opts.late
+        && let Err(e) = late.rerank(cache, &opts.queries[0].0, &mut scored, opts.k.saturating_mul(late.overfetch))
+    {
+        tracing::error!("late interaction rerank fail: {e}");
+        return vec![];
+    }
+    stats.retrieve_ms += ms_since(retrieve);
+    finish(meta, opts, scored, stats)
+}
 
-    let mut scored: Vec<(usize, f32)> = Vec::with_capacity(n);
-    for id in 0..n {
-        let off = id * bytes_per_vec;
-        let chunk = &buf[off..off + bytes_per_vec];
-        let mut v = vec![0f32; dim];
-        // SAFETY: chunk size = dim * 4 bytes (LE)
-        let src = unsafe {
-            std::slice::from_raw_parts(chunk.as_ptr() as *const f32, dim)
-        };
-        v.copy_from_slice(src);
+/// Applies `opts`' score formula and decay to `scored`, sorts it best first and keeps the
+/// top `k`, at most `per_group` of each product when grouped.
+fn finish(meta: &MetaStore, opts: &RankOpts, mut scored: Vec<(usize, f32)>, stats: &mut SearchStats) -> Vec<Ranked> {
+    let score = Instant::now();
+    if opts.score.is_some() || opts.half_life_days.is_some() {
+        let now = now_secs();
+        for (id, score) in scored.iter_mut() {
+            *score = score_parts(opts, &meta.attrs.get(*id), *score, now).total();
+        }
+    }
+    scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+    let ranked = match opts.group {
+        Some((GroupBy::ProductId, per_group)) => collapse(meta, scored, opts.k, per_group),
+        None => scored.into_iter().take(opts.k).map(|(id, score)| Ranked { id, score, collapsed: None }).collect(),
+    };
+    stats.score_ms += ms_since(score);
+    ranked
+}
 
-        let s = cosine(&qv, &v);
-        scored.push((id, s));
+/// Whether a review passes `opts`' filters (language, status, product, rating, date) and is
+/// live; the scan checks it per vector, the spfresh traversal per node.
+fn filter<'a>(meta: &'a MetaStore, opts: &'a RankOpts) -> impl Fn(usize) -> bool + Sync + 'a {
+    // A language no review is in matches nothing.
+    let langs: Option<Vec<u32>> = (!opts.langs.is_empty()).then(|| opts.langs.iter().filter_map(|l| meta.attrs.lang(l)).collect());
+    let products: Option<Vec<u32>> = (!opts.product_ids.is_empty()).then(|| opts.product_ids.iter().filter_map(|p| meta.attrs.product(p)).collect());
+    let dated = opts.created != (None, None);
+    move |id: usize| {
+        let attr = meta.attrs.get(id);
+        let in_lang = langs.as_ref().is_none_or(|ls| attr.lang.is_some_and(|l| ls.contains(&l)));
+        let visible = opts.include_unapproved || attr.status == ReviewStatus::Approved;
+        let of_product = products.as_ref().is_none_or(|ps| attr.product.is_some_and(|p| ps.contains(&p)));
+        let rated = opts.ratings.0.is_none_or(|r| attr.rating >= r) && opts.ratings.1.is_none_or(|r| attr.rating <= r);
+        let in_range = !dated || attr.created_at.is_some_and(|at| {
+            opts.created.0.is_none_or(|from| at >= from) && opts.created.1.is_none_or(|to| at < to)
+        });
+        in_lang && visible && of_product && rated && in_range && meta.is_live(id) && !opts.excludes(id)
     }
+}
+
+/// Top-`k` hits from the spfresh index (`VecIndex::search_allowed`) for a plain search (see
+/// `RankOpts::use_ann`). The filters go into the traversal as the allowed ids, so a narrow
+/// filter still gets `k` hits rather than whatever of an unfiltered top-`k` survives it.
+/// With a score formula, decay or grouping, the nearest `ann_candidates` are taken and
+/// ranked as the scan ranks its hits.
+fn rank_ann(meta: &MetaStore, active: &Active, opts: &RankOpts, stats: &mut SearchStats) -> Vec<Ranked> {
+    let embed = Instant::now();
+    let qvs = match fusion::query_vectors(active, opts) {
+        Ok(v) => v,
+        Err(e) => {
+            tracing::error!("embed_query fail: {e}");
+            return vec![];
+        }
+    };
+    stats.embed_ms += ms_since(embed);
+    let _t = metrics::timer(Stage::Scoring);
+    let retrieve = Instant::now();
+    let keep = filter(meta, opts);
+    let found = match active.vindex.search_allowed(&qvs[0], opts.ann_candidates, &keep) {
+        Ok(found) => found,
+        Err(e) => {
+            tracing::error!("spfresh search fail: {e}");
+            return vec![];
+        }
+    };
+    stats.candidates += found.len();
+    stats.matched += found.len();
+    stats.retrieve_ms += ms_since(retrieve);
+    // spfresh reports cosine distance, nearest first; hits carry the scan's cosine.
+    finish(meta, opts, found.into_iter().map(|(id, distance)| (id, 1.0 - distance)).collect(), stats)
+}
 
-    scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
-    scored.truncate(k);
+/// The `score` formula and recency decay of `opts` applied to one review's cosine; the
+/// ranking and `explain` both go through here so they cannot disagree.
+fn score_parts(opts: &RankOpts, attr: &attrs::Attr, cosine: f32, now: u64) -> ScoreParts {
+    let age_days = attr.created_at.map(|at| now.saturating_sub(at) as f64 / 86_400.0);
+    let formula = opts.score.as_ref().map(|expr| {
+        let vars = score_expr::Vars { cosine: cosine as f64, rating: attr.rating as f64, age_days: age_days.unwrap_or(0.0) };
+        expr.score(&vars)
+    });
+    let decay = opts.half_life_days.zip(age_days).map(|(half_life, age)| 0.5f64.powf(age / half_life) as f32);
+    ScoreParts { cosine, formula, decay }
+}
 
-    let mut out = Vec::with_capacity(scored.len());
+/// Keeps the best `per_group` hits of each product, up to `k` in all, and counts the
+/// rest of each shown product on its best hit. `scored` must be sorted best first.
+/// Reviews whose product is unknown (unreadable metadata) are each their own group.
+fn collapse(meta: &MetaStore, scored: Vec<(usize, f32)>, k: usize, per_group: usize) -> Vec<Ranked> {
+    let mut out: Vec<Ranked> = Vec::with_capacity(k);
+    // product -> (hits kept, index of its best hit in `out`)
+    let mut groups: HashMap<u32, (usize, usize)> = HashMap::new();
     for (id, score) in scored {
-        if let Ok(rev) = st.meta.read_review_by_line(id) {
-            out.push(SearchHit { id, score, review: rev });
-        } else {
-            tracing::warn!("meta read id={} failed", id);
+        let Some(product) = meta.attrs.get(id).product else {
+            if out.len() < k { out.push(Ranked { id, score, collapsed: None }); }
+            continue;
+        };
+        match groups.get_mut(&product) {
+            Some((kept, best)) if *kept >= per_group || out.len() >= k => {
+                *out[*best].collapsed.get_or_insert(0) += 1;
+            }
+            Some((kept, _)) => {
+                *kept += 1;
+                out.push(Ranked { id, score, collapsed: None });
+            }
+            // Once `out` is full, products not yet shown are not tracked.
+            None if out.len() < k => {
+                groups.insert(product, (1, out.len()));
+                out.push(Ranked { id, score, collapsed: Some(0) });
+            }
+            None => {}
         }
     }
-    Json(SearchResp { hits: out })
+    out
+}
+
+/// GET /stats — record counts, how much of each mirror is resident in the vector cache,
+/// and when the indexes were last flushed.
+async fn stats(State(st): State<AppState>) -> Result<Json<StatsResp>, ApiError> {
+    blocking(move || Ok(Json(StatsResp {
+        reviews: st.meta.count()?,
+        vectors: st.vindex().len()?,
+        vector_cache: st.vcache.stats(),
+        last_flush_at: flusher::last_flush(),
+    }))).await
+}
+
+async fn list_jobs(State(st): State<AppState>) -> Json<Vec<Job>> {
+    Json(st.jobs.list())
+}
+
+async fn get_job(State(st): State<AppState>, Path(id): Path<uuid::Uuid>) -> Result<Json<Job>, ApiError> {
+    st.jobs.get(id).map(Json).ok_or_else(|| ApiError::not_found(format!("job {id} not found")))
+}
+
+/// Rewrites axum's plain-text 413 (body over `DefaultBodyLimit`) into our JSON error shape,
+/// pointing large uploads at the streaming import.
+async fn explain_payload_too_large(resp: Response) -> Response {
+    if resp.status() != StatusCode::PAYLOAD_TOO_LARGE || resp.extensions().get::<ApiErrorMarker>().is_some() {
+        return resp;
+    }
+    ApiError::payload_too_large(
+        "request body too large; send large batches as NDJSON to POST /reviews/import, which streams",
+    ).into_response()
+}
+
+/// Maps errors from the timeout / load-shed layers onto HTTP statuses.
+async fn handle_overload(err: BoxError) -> ApiError {
+    if err.is::<tower::timeout::error::Elapsed>() {
+        ApiError::unavailable("request timed out")
+    } else if err.is::<tower::load_shed::error::Overloaded>() {
+        ApiError::too_many_requests("server busy, retry later")
+    } else {
+        ApiError::from(anyhow::anyhow!("middleware: {err}"))
+    }
+}
+
+/// Opens the shadow index and back-fills it from reviews.jsonl if it was configured
+/// after reviews already existed, so its ids line up with the metadata.
+fn open_shadow(data_dir: &FsPath, sc: &config::ShadowConfig, meta: &MetaStore, durability: &DurabilityConfig, progress: &startup::Progress) -> Result<Active> {
+    let dir = data_dir.join(&sc.dir);
+    let vindex: Arc<dyn VecIndex> = Arc::new(spfresh_index::DefaultIndex::open(&dir, sc.embedder.dim(), durability)?);
+    collections::record_embedder(&dir, &sc.embedder)?;
+    let embedder = build_embedder(&sc.embedder, &dir)?;
+    let have = vindex.len()?;
+    progress.set_total(meta.attrs.len().saturating_sub(have));
+    let mut last = None;
+    let filled = meta.for_each_in(have..usize::MAX, |id, r| {
+        let v = match &r {
+            Some(r) => embedder.embed_index(&r.embed_text())?,
+            None => vec![0.0; vindex.dim()], // placeholder keeps ids aligned with the metadata
+        };
+        last = Some(vindex.append_pending(&v, r.as_ref().and_then(payload_of))?.1);
+        progress.set_done(id + 1 - have);
+        Ok(())
+    })?;
+    if let Some(c) = last { c.wait()?; }
+    info!("shadow index {} ({:?}): {} vectors, back-filled {}", dir.display(), sc.embedder, have + filled, filled);
+    Ok(Active { vindex, embedder })
 }
 
 #[tokio::main]
@@ -335,30 +1775,451 @@ async fn main() -> Result<()> {
         .with_env_filter(EnvFilter::from_default_env())
         .init();
 
-    let data_dir: PathBuf = std::env::current_dir()?.join("data");
+    let config = Config::load()?;
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        None | Some("serve") => {}
+        Some("bench") => return bench::run(config, &args[1..]).await,
+        Some("generate") => return synth::run(&args[1..]),
+        Some("compact") => return compact::run(config, &args[1..]),
+        Some("migrate") => return migrate::run(config, &args[1..]),
+        // Imports wait on the writer with blocking calls, which a runtime thread may only make
+        // from inside `block_in_place`.
+        Some("import") => return tokio::task::block_in_place(|| import::run(config, &args[1..])),
+        #[cfg(feature = "hf-import")]
+        Some("import-hf") => return tokio::task::block_in_place(|| hf_import::run(config, &args[1..])),
+        #[cfg(feature = "parquet")]
+        Some("export-parquet") => return export_parquet::run(config, &args[1..]),
+        Some(other) => anyhow::bail!("unknown command '{other}' (expected serve, bench, generate, import, import-hf, compact, migrate or export-parquet)"),
+    }
+    let data_dir: PathBuf = std::env::current_dir()?.join(&config.data_dir);
     std::fs::create_dir_all(&data_dir)?;
     info!("data dir = {}", std::fs::canonicalize(&data_dir)?.display());
+    // Held for the life of the process; taken before any data file is opened.
+    let _dir_lock = dir_lock::acquire(&data_dir)?;
+
+    // Listening from the start, so /readyz reports progress while the data is recovered and
+    // opened; every other request gets 503 until then.
+    let startup = Arc::new(startup::Startup::default());
+    let mut serving = tokio::spawn({
+        let (config, app) = (config.clone(), startup::router(startup.clone()));
+        async move { listeners::serve(&config, app).await }
+    });
+    // A thread of its own rather than the blocking pool, which the runtime would wait for
+    // on the way out if serving fails first.
+    let (opened, opening) = tokio::sync::oneshot::channel();
+    std::thread::spawn({
+        let startup = startup.clone();
+        move || {
+            let progress = &startup.progress;
+            let open = || -> Result<AppState> {
+                compact::run_requested(&config, &data_dir, progress)?;
+                let state = open_state(config, &data_dir, progress)?;
+                migrate::check(&data_dir, state.meta.count()?)?;
+                Ok(state)
+            };
+            let _ = opened.send(open());
+        }
+    });
+    let state = tokio::select! {
+        state = opening => state??,
+        served = &mut serving => {
+            served??;
+            anyhow::bail!("stopped serving before startup finished");
+        }
+    };
+
+    tokio::spawn(storage::sample_growth(state.clone()));
+    tokio::spawn(flusher::run(state.clone()));
+    tokio::spawn(idf::refresh_periodically(state.clone()));
+    #[cfg(feature = "replica")]
+    if let Some(rc) = state.config.replica.clone() {
+        tokio::spawn(replication::follow(state.clone(), rc));
+    }
+
+    // Outside the router, so the rewritten path is what gets routed.
+    let app = middleware::from_fn(versioning::negotiate).layer(router(state)?);
+    #[cfg(feature = "ui")]
+    let app = middleware::from_fn(ui::serve).layer(app);
+    startup.ready(Router::new().fallback_service(app));
+    info!("ready");
+    serving.await?
+}
 
-    let dim = 4096;
-    let meta = Arc::new(MetaStore::open(&data_dir)?);
-    let vindex: Arc<dyn VecIndex> = Arc::new(spfresh_index::DefaultIndex::open(&data_dir, dim)?);
-    let embedder: Arc<dyn Embedder> = Arc::new(TfIdfEmbedder::new(dim));
-
-    let state = AppState { meta, vindex, embedder };
-
-    let cors = CorsLayer::new()
-        .allow_origin(Any)
-        .allow_methods(Any)
-        .allow_headers(Any);
-
-    let app = Router::new()
-        .route("/reviews", post(insert_one))
-        .route("/reviews/bulk", post(insert_bulk))
-        .route("/search", post(search))
-        .with_state(state)
-        .layer(cors);
-    
-    info!("listening on 0.0.0.0:8000");
-    axum::serve(tokio::net::TcpListener::bind("0.0.0.0:8000").await?, app).await?;
-    Ok(())
+/// Recovers and opens everything under `data_dir`, reporting each stage to `progress`; the
+/// caller holds the directory lock.
+fn open_state(config: Config, data_dir: &FsPath, progress: &startup::Progress) -> Result<AppState> {
+    let data_dir = data_dir.to_path_buf();
+    progress.stage(startup::Stage::Recovery);
+    compact::finish(&data_dir)?;
+    migrate::finish(&data_dir)?;
+    let index_dir = reindex::current_index_dir(&data_dir)?;
+    let secondary: Vec<_> = config.secondary_dirs().into_iter()
+        .map(|(dir, dim)| (data_dir.join(dir).join("reviews.index"), dim))
+        .collect();
+    let meta_files = meta_blocks::Blocks::open(&data_dir, &config.metadata)?;
+    let lines = recovery::recover(
+        &meta_files,
+        (&index_dir.join("reviews.index"), config.embedder.dim()),
+        &secondary,
+    )?;
+    progress.stage(startup::Stage::Metadata);
+    progress.set_total(lines);
+    let meta = Arc::new(MetaStore::open(&data_dir, meta_files, progress)?);
+    progress.stage(startup::Stage::Index);
+    let vindex: Arc<dyn VecIndex> = Arc::new(spfresh_index::DefaultIndex::open(&index_dir, config.embedder.dim(), &config.durability)?);
+    collections::record_embedder(&index_dir, &config.embedder)?;
+    let embedder = build_embedder(&config.embedder, &index_dir)?;
+    let shadow = match &config.shadow {
+        Some(sc) => {
+            progress.stage(startup::Stage::Shadow);
+            Some(open_shadow(&data_dir, sc, &meta, &config.durability, progress)?)
+        }
+        None => None,
+    };
+    let fields = match &config.fields {
+        Some(fc) => {
+            progress.stage(startup::Stage::Fields);
+            Some(Arc::new(fields::Fields::open(&data_dir, fc, &config.embedder, &meta, &config.durability, progress)?))
+        }
+        None => None,
+    };
+    let late_interaction = match &config.late_interaction {
+        Some(lc) => {
+            progress.stage(startup::Stage::LateInteraction);
+            Some(Arc::new(late_interaction::LateInteraction::open(&data_dir, lc, &meta, &config.durability, progress)?))
+        }
+        None => None,
+    };
+
+    let vcache = vcache::VectorCache::new(config.memory.vector_cache_bytes, config.memory.segment_vectors);
+    let sketches = match &config.search.sketch {
+        Some(sc) => {
+            progress.stage(startup::Stage::Sketches);
+            let sketches = sketch::Sketches::new(sc)?;
+            sketches.warm(&vcache, vindex.mirror_path(), vindex.dim(), meta.count()?, progress)?;
+            Some(Arc::new(sketches))
+        }
+        None => None,
+    };
+    let slow_log = slow_log::SlowLog::new(&config.slow_query, &data_dir);
+    let audit = audit::AuditLog::open(&data_dir)?;
+    let feedback = feedback::FeedbackLog::open(&data_dir)?;
+    let tenants = tenants::Tenants::open(&data_dir)?;
+    let collections = collections::Collections::open(&data_dir)?;
+    let embedders = embedders::Registry::open(&data_dir)?;
+    for (collection, cfg) in collections.embedders()? {
+        embedders.bind(&collection, &cfg)?;
+    }
+    let moderator = moderation::Moderator::new(&config.moderation)?;
+    let search = &config.search;
+    anyhow::ensure!(
+        search.max_k > 0 && search.default_k <= search.max_k && search.max_k <= search.max_stream_k,
+        "[search] needs 0 < default_k <= max_k <= max_stream_k"
+    );
+    anyhow::ensure!(
+        search.rerank_overfetch > 0 && search.diversify_overfetch > 0,
+        "[search] needs rerank_overfetch and diversify_overfetch of at least 1"
+    );
+    #[cfg(feature = "shards")]
+    let shards = config.shards.as_ref().map(shards::ShardRouter::new).transpose()?.map(Arc::new);
+    #[cfg(not(feature = "shards"))]
+    anyhow::ensure!(config.shards.is_none(), "[shards] configured, but built without the `shards` feature");
+    #[cfg(not(feature = "replica"))]
+    anyhow::ensure!(config.replica.is_none(), "[replica] configured, but built without the `replica` feature");
+    #[cfg(feature = "llm")]
+    let llm = config.llm.as_ref().map(|c| Arc::new(ask::Llm::new(c)));
+    #[cfg(not(feature = "llm"))]
+    anyhow::ensure!(config.llm.is_none(), "[llm] configured, but built without the `llm` feature");
+    // Query terms are looked up in a vocabulary only inserts grow, and a replica copies vectors.
+    anyhow::ensure!(
+        config.replica.is_none() || !matches!(config.embedder, EmbedderConfig::Tfidf { vocabulary: true, .. }),
+        "a replica cannot use the tfidf vocabulary; it is only grown by embedding inserts"
+    );
+    let write_gate = Arc::new(Mutex::new(()));
+    let writer = writer::Writer::spawn(write_gate.clone(), config.limits.write_queue)?;
+    Ok(AppState {
+        config: Arc::new(config),
+        meta,
+        active: Arc::new(RwLock::new(Active { vindex, embedder })),
+        shadow,
+        fields,
+        late_interaction,
+        sketches,
+        write_gate,
+        writer: Arc::new(writer),
+        jobs: Arc::new(JobRegistry::default()),
+        collections: Arc::new(collections),
+        growth: Arc::new(storage::GrowthTracker::default()),
+        vcache: Arc::new(vcache),
+        slow_log: Arc::new(slow_log),
+        audit: Arc::new(audit),
+        feedback: Arc::new(feedback),
+        tenants: Arc::new(tenants),
+        embedders: Arc::new(embedders),
+        moderator: Arc::new(moderator),
+        maintenance: Default::default(),
+        #[cfg(feature = "shards")]
+        shards,
+        #[cfg(feature = "llm")]
+        llm,
+        data_dir,
+    })
+}
+
+/// Every route under /v1 plus the service-wide layers. Paths without a version prefix are
+/// handled by `versioning::negotiate`, which wraps this router.
+fn router(state: AppState) -> Result<Router> {
+    let cors = cors::layer(&state.config.cors)?;
+
+    let limits = state.config.limits.clone();
+    let meter = middleware::from_fn_with_state(state.clone(), tenants::meter);
+    // Per-route body cap + deadline.
+    let guard = |max_bytes: usize, timeout_ms: u64| {
+        ServiceBuilder::new()
+            .layer(HandleErrorLayer::new(handle_overload))
+            .timeout(Duration::from_millis(timeout_ms))
+            .layer(DefaultBodyLimit::max(max_bytes))
+    };
+    // Every route is mounted under /v1; `versioning::negotiate` maps unprefixed paths onto it.
+    let v1 = Router::new()
+        .route("/reviews", post(insert_one).layer(guard(limits.insert_body_bytes, limits.insert_timeout_ms))
+            .get(listing::list_reviews))
+        // Bulk payloads may arrive with `Content-Encoding: gzip`; the body cap applies to the
+        // decompressed size.
+        .route("/reviews/bulk", post(insert_bulk).layer(
+            guard(limits.bulk_body_bytes, limits.bulk_timeout_ms)
+                .layer(RequestDecompressionLayer::new().gzip(true)),
+        ))
+        .route("/reviews/batch", post(batch_get::batch_get).layer(guard(limits.search_body_bytes, limits.search_timeout_ms)))
+        .route("/reviews/:id", axum::routing::patch(patch_review).layer(guard(limits.insert_body_bytes, limits.insert_timeout_ms))
+            .delete(delete_review))
+        .route("/reviews/:id/reembed", post(reembed::reembed))
+        .route("/reviews/upsert", post(upsert).layer(guard(limits.insert_body_bytes, limits.insert_timeout_ms)))
+        .route("/reviews/raw", post(insert_raw).layer(guard(limits.insert_body_bytes, limits.insert_timeout_ms)))
+        .route("/reviews/dry-run", post(dry_run::dry_run).layer(guard(limits.insert_body_bytes, limits.insert_timeout_ms)))
+        .route("/reviews/import", post(import::import_ndjson)
+            .layer(RequestDecompressionLayer::new().gzip(true)))
+        .route("/search", post(search).layer(guard(limits.search_body_bytes, limits.search_timeout_ms)))
+        .route("/compare", post(compare::compare).layer(guard(limits.search_body_bytes, limits.search_timeout_ms)))
+        .route("/search/stream", post(search_stream::search_stream)
+            .layer(guard(limits.search_body_bytes, limits.bulk_timeout_ms)))
+        .route("/search/range", post(range_search::range_search).layer(guard(limits.search_body_bytes, limits.search_timeout_ms)))
+        .route("/export/full", get(export::export_full))
+        .route("/vectors/:id", get(vectors::get_vector))
+        .route("/replication/log", get(replication::log))
+        .route("/eval", post(eval::evaluate))
+        .route("/feedback", post(feedback::post_feedback))
+        .route("/admin/generate", post(synth::generate))
+        .route("/admin/reindex", post(reindex::start_reindex))
+        .route("/admin/purge", post(purge::start_purge))
+        .route("/admin/idf", post(idf::start_refresh))
+        .route("/admin/compact", post(maintenance::request_compaction))
+        .route("/admin/snapshot", post(maintenance::start_snapshot))
+        .route("/admin/verify", get(maintenance::verify))
+        .route("/admin/read-only", get(maintenance::get_read_only).post(maintenance::set_read_only))
+        .route("/admin/moderation", get(moderation::list))
+        .route("/admin/moderation/:id/approve", post(moderation::approve))
+        .route("/admin/moderation/:id/reject", post(moderation::reject))
+        .route("/analytics/trending", get(trending::trending))
+        .route("/analytics/composition", get(composition::composition))
+        .route("/analytics/projection", post(projection::projection)
+            .layer(guard(limits.search_body_bytes, limits.bulk_timeout_ms)))
+        .route("/products", get(products::list_products))
+        .route("/products/:id/ratings-timeline", get(ratings::ratings_timeline))
+        .route("/products/:id/pros-cons", get(pros_cons::pros_cons))
+        .route("/products/:id/aspects", get(aspects::product_aspects))
+        .route("/stats", get(stats))
+        .route("/metrics", get(metrics::render))
+        .route("/admin/storage", get(storage::storage_report))
+        .route("/admin/drift", get(drift::get_drift))
+        .route("/admin/drift/reset", post(drift::reset_drift))
+        .route("/admin/audit", get(audit::query_audit))
+        .route("/admin/usage", get(tenants::usage_report))
+        .route("/aliases", get(collections::list_aliases).post(collections::put_alias))
+        .route("/aliases/:name", axum::routing::delete(collections::delete_alias))
+        .route("/collections", get(collections::list_collections))
+        .route("/embedders", get(embedders::list_embedders).post(embedders::register_embedder))
+        .route("/jobs", get(list_jobs))
+        .route("/jobs/:id", get(get_job))
+        .with_state(state.clone());
+    #[cfg(feature = "graphql")]
+    let v1 = v1.route(
+        "/graphql",
+        get(graphql::graphiql)
+            .post(graphql::graphql)
+            .layer(guard(limits.search_body_bytes, limits.search_timeout_ms))
+            .with_state(graphql::schema(state.clone())),
+    );
+    #[cfg(feature = "analytics")]
+    let v1 = v1.route(
+        "/analytics/query",
+        post(analytics::query)
+            .layer(guard(limits.search_body_bytes, limits.bulk_timeout_ms))
+            .with_state(state.clone()),
+    );
+    #[cfg(feature = "llm")]
+    let v1 = v1.route(
+        "/ask",
+        post(ask::ask)
+            .layer(guard(limits.search_body_bytes, limits.bulk_timeout_ms))
+            .with_state(state.clone()),
+    );
+    #[cfg(feature = "parquet")]
+    let v1 = v1.route("/export/parquet", get(export_parquet::export_parquet).with_state(state.clone()));
+    let app = Router::new().nest("/v1", v1);
+    let app = app.layer(middleware::from_fn_with_state(state.clone(), maintenance::read_only_gate));
+    let app = match state.config.replica {
+        Some(_) => app.layer(middleware::from_fn_with_state(state.clone(), replication::read_only)),
+        None => app,
+    };
+    Ok(app
+        .layer(meter)
+        .layer(
+            ServiceBuilder::new()
+                .layer(HandleErrorLayer::new(handle_overload))
+                .load_shed()
+                .concurrency_limit(limits.max_in_flight),
+        )
+        .layer(middleware::map_response(explain_payload_too_large))
+        .layer(cors))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reviews_types::RangeSearchReq;
+
+    fn state(name: &str) -> AppState {
+        let dir = std::env::temp_dir().join(format!("service-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        reopen(&dir)
+    }
+
+    fn reopen(dir: &FsPath) -> AppState {
+        let config = Config { data_dir: dir.to_path_buf(), ..Config::default() };
+        open_state(config, dir, &startup::Progress::default()).unwrap()
+    }
+
+    fn insert(st: &AppState, text: &str, rating: i32, external_id: Option<&str>) -> usize {
+        let review: Review = serde_json::from_value(serde_json::json!({
+            "review_title": text, "review_body": text, "product_id": "p1", "review_rating": rating,
+            "external_id": external_id,
+        })).unwrap();
+        let vec = st.embedder().embed_index(&review.embed_text()).unwrap();
+        let (id, commit) = st.vindex().append_pending(&vec, payload_of(&review)).unwrap();
+        st.append_meta(&review).unwrap();
+        commit.wait().unwrap();
+        id
+    }
+
+    fn ranked(st: &AppState, req: &SearchReq, ann: bool) -> Vec<(usize, f32)> {
+        let Ok(mut opts) = RankOpts::from_req(req, 4) else { panic!("bad request") };
+        let Ok(()) = opts.use_ann(&SearchConfig { ann, ..Default::default() }, req) else { panic!("bad request") };
+        let hits = search_in(&st.meta, &st.vcache, &st.active.read().clone(), &opts, &mut SearchStats::default());
+        hits.into_iter().map(|h| (h.id, h.score)).collect()
+    }
+
+    #[test]
+    fn ann_and_scan_rank_alike() {
+        let st = state("ann");
+        for (text, rating) in [
+            ("charger stopped working", 1), ("charger broke after a week", 2), ("the charger cable frayed", 5),
+            ("lovely soft blanket", 5), ("battery lasts all day", 4), ("charger works with my old phone", 4),
+            ("phone battery swelled", 1), ("blanket faded in the wash", 2),
+        ] {
+            insert(&st, text, rating, None);
+        }
+        for req in [SearchReq::new("charger stopped working"), SearchReq { min_rating: Some(4), ..SearchReq::new("charger battery") }] {
+            let (scan, ann) = (ranked(&st, &req, false), ranked(&st, &req, true));
+            assert_eq!(scan.len(), 4);
+            assert_eq!(scan.iter().map(|h| h.0).collect::<Vec<_>>(), ann.iter().map(|h| h.0).collect::<Vec<_>>());
+            assert!(scan.iter().zip(&ann).all(|(s, a)| (s.1 - a.1).abs() < 1e-4), "{scan:?} vs {ann:?}");
+        }
+        // Re-ranked and grouped searches over-fetch from the index, here past every review.
+        let reranked = SearchReq { score: Some("cosine * rating".into()), ..SearchReq::new("charger battery") };
+        let grouped = SearchReq { group_by: Some(GroupBy::ProductId), per_group: Some(2), ..SearchReq::new("charger") };
+        for req in [reranked, grouped] {
+            let (scan, ann) = (ranked(&st, &req, false), ranked(&st, &req, true));
+            assert!(!scan.is_empty());
+            assert_eq!(scan.iter().map(|h| h.0).collect::<Vec<_>>(), ann.iter().map(|h| h.0).collect::<Vec<_>>());
+            assert!(scan.iter().zip(&ann).all(|(s, a)| (s.1 - a.1).abs() < 1e-4), "{scan:?} vs {ann:?}");
+        }
+    }
+
+    #[test]
+    fn range_search_honours_max_distance_limit_and_payloads() {
+        let st = state("range");
+        insert(&st, "charger stopped working", 1, Some("ext-1"));
+        insert(&st, "charger stopped working today", 2, None);
+        insert(&st, "lovely soft blanket", 5, Some("ext-3"));
+        let run = |req: &RangeSearchReq, limit| {
+            let Ok(resp) = range_search::search(&st, req, limit) else { panic!("range search failed") };
+            resp
+        };
+        let all = RangeSearchReq { include_unapproved: true, ..RangeSearchReq::new("charger stopped working", 2.0) };
+        let resp = run(&all, 10);
+        assert_eq!(resp.hits.iter().map(|h| h.id).collect::<Vec<_>>(), [0, 1, 2]);
+        assert!(!resp.truncated && resp.hits.iter().all(|h| h.review.is_some() && h.external_id.is_none()));
+        let resp = run(&all, 2);
+        assert_eq!((resp.hits.len(), resp.truncated), (2, true));
+        let near = RangeSearchReq { max_distance: resp.hits[1].distance / 2.0, ..all.clone() };
+        assert_eq!(run(&near, 10).hits.iter().map(|h| h.id).collect::<Vec<_>>(), [0]);
+        let ids = RangeSearchReq { ids_only: true, ..all };
+        let hits: Vec<_> = run(&ids, 10).hits.into_iter().map(|h| (h.id, h.external_id, h.review.is_none())).collect();
+        assert_eq!(hits, [(0, Some("ext-1".into()), true), (1, None, true), (2, Some("ext-3".into()), true)]);
+    }
+
+    #[test]
+    fn purge_erases_vectors_and_payloads_from_the_open_index() {
+        let st = state("purge");
+        insert(&st, "charger stopped working", 1, Some("ext-1"));
+        insert(&st, "lovely soft blanket", 5, Some("ext-2"));
+        st.meta.delete(0, &st.meta.read_review_by_line(0).unwrap()).unwrap();
+        let (_, vectors) = purge::erase(&st, &[0].into_iter().collect()).unwrap();
+        assert_eq!(vectors, 1);
+        let vindex = st.vindex();
+        assert!(vindex.get(0).unwrap().iter().all(|&x| x == 0.0));
+        assert_eq!(vindex.payload(0).unwrap(), None);
+        assert_eq!(vindex.payload(1).unwrap().as_deref(), Some(&b"ext-2"[..]));
+        let dir = st.config.data_dir.clone();
+        drop((st, vindex));
+        // Flushed, so a reopen without a compaction reads the zeros back.
+        let st = reopen(&dir);
+        assert!(st.vindex().get(0).unwrap().iter().all(|&x| x == 0.0));
+        assert_eq!(st.vindex().payload(0).unwrap(), None);
+    }
+
+    /// Cuts `by` bytes off the end of `path`.
+    fn tear(path: &FsPath, by: u64) {
+        let f = OpenOptions::new().write(true).open(path).unwrap();
+        f.set_len(f.metadata().unwrap().len() - by).unwrap();
+    }
+
+    #[test]
+    fn recovery_keeps_spfresh_ids_on_the_metadata_lines() {
+        let st = state("recover");
+        for text in ["charger stopped working", "lovely soft blanket", "battery lasts all day", "phone battery swelled"] {
+            insert(&st, text, 4, None);
+        }
+        let (dir, tail) = (st.config.data_dir.clone(), st.meta.files.tail_path().to_path_buf());
+        drop(st);
+        // A torn last metadata line: recovery drops the review from the metadata and the
+        // mirror, and the index must forget it too.
+        tear(&tail, 5);
+        let st = reopen(&dir);
+        assert_eq!(insert(&st, "the blanket faded in the wash", 4, None), 3);
+        let top = ranked(&st, &SearchReq::new("the blanket faded in the wash"), true);
+        assert_eq!(top[0].0, 3);
+        assert_eq!(st.meta.read_review_by_line(3).unwrap().review_title, "the blanket faded in the wash");
+        let dim = st.vindex().dim();
+        drop(st);
+        // An index that lost its unflushed appends is filled back in from the mirror.
+        let record = 2 + spfresh_index::MAX_PAYLOAD + 4 * dim;
+        tear(&reindex::current_index_dir(&dir).unwrap().join("reviews.spfresh"), 2 * record as u64);
+        let st = reopen(&dir);
+        let req = SearchReq::new("battery");
+        assert_eq!(ranked(&st, &req, true).iter().map(|h| h.0).collect::<Vec<_>>(), ranked(&st, &req, false).iter().map(|h| h.0).collect::<Vec<_>>());
+        assert_eq!(insert(&st, "charger works with my old phone", 4, None), 4);
+    }
 }
@@ -0,0 +1,176 @@
+use crate::{
+    audit::{Action, Actor, IdRanges},
+    blocking, codec, compact, jobs::JobHandle, replication, ApiError, AppState,
+};
+use anyhow::{Context, Result};
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::Response,
+    Json,
+};
+use reviews_types::{CompactResp, IndexCheck, ReadOnly, SnapshotResp, VerifyResp};
+use std::{
+    fs::File,
+    io::{BufReader, Read},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+const SNAPSHOT_JOB: &str = "snapshot";
+/// Snapshots go here, one directory each, named by the time they were taken.
+const SNAPSHOTS_DIR: &str = "snapshots";
+/// Never copied into a snapshot: the snapshots themselves, a compaction's staging dir and
+/// the running server's lock.
+const NOT_SNAPSHOTTED: [&str; 3] = [SNAPSHOTS_DIR, "compact", "LOCK"];
+/// Under /v1/admin, but writing reviews like any client; refused in read-only mode.
+const ADMIN_WRITES: &[&str] = &["/v1/admin/generate"];
+
+/// Switches kept in memory for maintenance; a restart clears them.
+#[derive(Default)]
+pub struct Maintenance {
+    read_only: AtomicBool,
+}
+
+/// Read-only mode: refuses writes with 503 while maintenance (a snapshot, a copy of the
+/// data dir) needs the data to hold still. Admin operations still run, so it can be lifted
+/// and maintenance jobs started.
+pub async fn read_only_gate(State(st): State<AppState>, req: Request, next: Next) -> Result<Response, ApiError> {
+    if st.maintenance.read_only.load(Ordering::Relaxed) && !replication::is_read(&req) {
+        let path = req.uri().path();
+        if !path.starts_with("/v1/admin/") || ADMIN_WRITES.contains(&path) {
+            return Err(ApiError::unavailable("read-only for maintenance; writes are refused until it is lifted"));
+        }
+    }
+    Ok(next.run(req).await)
+}
+
+/// GET /admin/read-only
+pub async fn get_read_only(State(st): State<AppState>) -> Json<ReadOnly> {
+    Json(ReadOnly { read_only: st.maintenance.read_only.load(Ordering::Relaxed) })
+}
+
+/// POST /admin/read-only — turns read-only mode (see `read_only_gate`) on or off, until
+/// the next restart.
+pub async fn set_read_only(State(st): State<AppState>, actor: Actor, Json(req): Json<ReadOnly>) -> Result<Json<ReadOnly>, ApiError> {
+    let subject = format!("read_only={}", req.read_only);
+    blocking(move || st.audit.record(&actor, Action::ReadOnly, IdRanges::default(), Some(subject)).map_err(ApiError::from)).await?;
+    st.maintenance.read_only.store(req.read_only, Ordering::Relaxed);
+    Ok(Json(req))
+}
+
+/// POST /admin/compact — asks for a compaction (see `compact`) on the next start, the
+/// same request a purge leaves; compaction itself needs the server stopped.
+pub async fn request_compaction(State(st): State<AppState>, actor: Actor) -> Result<Json<CompactResp>, ApiError> {
+    blocking(move || {
+        st.audit.record(&actor, Action::Compact, IdRanges::default(), Some("requested for the next start".to_string()))?;
+        compact::request(&st.data_dir)?;
+        Ok(Json(CompactResp { compaction_requested: true }))
+    }).await
+}
+
+/// POST /admin/snapshot — copies the data dir into `snapshots/<seconds since the epoch>`,
+/// as a job counting files. Writes wait while it runs, so the copy is what a crash at that
+/// moment would leave: a server started on it repairs any unacknowledged tail as after a
+/// crash. Restore by stopping the server and copying the snapshot over the data dir.
+pub async fn start_snapshot(State(st): State<AppState>, actor: Actor) -> Result<(StatusCode, Json<SnapshotResp>), ApiError> {
+    let dir = PathBuf::from(SNAPSHOTS_DIR).join(crate::now_secs().to_string());
+    if st.data_dir.join(&dir).exists() {
+        return Err(ApiError::conflict(format!("{} already exists", dir.display())));
+    }
+    let job = st.jobs.try_start(SNAPSHOT_JOB, 0).ok_or_else(|| ApiError::conflict("a snapshot is already running"))?;
+    let job_id = job.id;
+    let resp = SnapshotResp { job_id, dir: dir.display().to_string() };
+    tokio::task::spawn_blocking(move || {
+        let res = st.audit.record(&actor, Action::Snapshot, IdRanges::default(), Some(format!("job {job_id}, {}", dir.display())))
+            .and_then(|()| snapshot(&st, &job, &dir));
+        if let Err(e) = &res { tracing::error!("snapshot {} failed: {e}", job.id); }
+        job.finish(&res);
+    });
+    Ok((StatusCode::ACCEPTED, Json(resp)))
+}
+
+fn snapshot(st: &AppState, job: &JobHandle, dir: &Path) -> Result<()> {
+    let _w = st.write_gate.lock();
+    let mut files = Vec::new();
+    list_files(&st.data_dir, Path::new(""), &mut files)?;
+    job.set_total(files.len());
+    let to = st.data_dir.join(dir);
+    for (n, rel) in files.iter().enumerate() {
+        let dest = to.join(rel);
+        if let Some(parent) = dest.parent() { std::fs::create_dir_all(parent)?; }
+        std::fs::copy(st.data_dir.join(rel), &dest).with_context(|| format!("copying {}", rel.display()))?;
+        job.set_processed(n + 1);
+    }
+    tracing::info!("snapshot {}: {} files into {}", job.id, files.len(), to.display());
+    Ok(())
+}
+
+/// Files under `root/rel`, relative to `root`, leaving out `NOT_SNAPSHOTTED` at the top.
+fn list_files(root: &Path, rel: &Path, out: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in std::fs::read_dir(root.join(rel))? {
+        let entry = entry?;
+        let name = entry.file_name();
+        if rel.as_os_str().is_empty() && NOT_SNAPSHOTTED.iter().any(|n| name == *n) { continue; }
+        let path = rel.join(&name);
+        if entry.file_type()?.is_dir() { list_files(root, &path, out)?; } else { out.push(path); }
+    }
+    Ok(())
+}
+
+/// GET /admin/verify — reads every vector of every index directory and checks it against
+/// its checksum and the metadata: the active index must have one vector per review, the
+/// others (collections, the shadow and field indexes) may lag but never lead. Torn tails and count
+/// mismatches are repaired by the recovery every start runs; corrupt vectors need a
+/// reindex.
+pub async fn verify(State(st): State<AppState>) -> Result<Json<VerifyResp>, ApiError> {
+    let resp = blocking(move || {
+        let reviews = st.meta.count()?;
+        let active_mirror = st.vindex().mirror_path().to_path_buf();
+        let (mut indexes, mut problems) = (Vec::new(), Vec::new());
+        for rel in compact::index_dirs(&st.data_dir, &st.config)? {
+            let mirror = st.data_dir.join(&rel).join("reviews.index");
+            let active = same_file(&mirror, &active_mirror);
+            let check = check_mirror(&mirror, rel.display().to_string(), active)?;
+            let name = if check.dir.is_empty() { "the data dir" } else { &check.dir };
+            if check.corrupt > 0 {
+                problems.push(format!("{name}: {} corrupt vectors; reindex to rebuild them", check.corrupt));
+            }
+            if check.partial_bytes > 0 {
+                problems.push(format!("{name}: {} bytes of a torn record; repaired on the next start", check.partial_bytes));
+            }
+            if check.vectors > reviews || (active && check.vectors != reviews) {
+                problems.push(format!("{name}: {} vectors for {reviews} reviews; repaired on the next start", check.vectors));
+            }
+            indexes.push(check);
+        }
+        Ok(VerifyResp { reviews, indexes, problems })
+    }).await?;
+    Ok(Json(resp))
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+fn check_mirror(mirror: &Path, dir: String, active: bool) -> Result<IndexCheck> {
+    let mut rdr = BufReader::new(File::open(mirror)?);
+    let mut head = [0u8; codec::HEADER_LEN as usize];
+    rdr.read_exact(&mut head)?;
+    let dim = codec::FileHeader::decode(codec::FileKind::Mirror, &head)
+        .with_context(|| format!("{}: not a vector mirror", mirror.display()))?
+        .dim as usize;
+    let rec_len = codec::record_len(dim);
+    let body = std::fs::metadata(mirror)?.len().saturating_sub(codec::HEADER_LEN);
+    let vectors = (body / rec_len as u64) as usize;
+    let (mut rec, mut v, mut corrupt) = (vec![0u8; rec_len], Vec::with_capacity(dim), 0);
+    for _ in 0..vectors {
+        rdr.read_exact(&mut rec)?;
+        if !codec::decode_record_into(&rec, dim, &mut v) { corrupt += 1; }
+    }
+    Ok(IndexCheck { dir, active, vectors, corrupt, partial_bytes: body % rec_len as u64 })
+}
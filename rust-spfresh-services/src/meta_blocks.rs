@@ -0,0 +1,491 @@
+use crate::config::{Compression, MetadataConfig};
+use anyhow::{Context, Result};
+use parking_lot::RwLock;
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+/// Bytes per entry of reviews.zst.idx.
+const ENTRY_LEN: usize = 56;
+
+/// The metadata file set. reviews.jsonl holds the newest lines; with zstd compression,
+/// every `block_lines` of them are moved into a zstd frame appended to reviews.zst and
+/// listed in reviews.zst.idx, so old reviews take a fraction of the space and a review
+/// is still found by decompressing one block.
+///
+/// Offsets handed out (listing cursors, the replication log) are positions in the
+/// uncompressed metadata, sealed blocks first, so they survive a block being sealed.
+pub struct Blocks {
+    tail_path: PathBuf,
+    zst_path: PathBuf,
+    idx_path: PathBuf,
+    blocks: RwLock<Vec<Block>>,
+    /// `(block_lines, level)`; None reads existing blocks but seals no new ones.
+    seal: Option<(usize, i32)>,
+}
+
+/// One sealed block: lines `first_id..first_id + lines`, bytes `raw_offset..raw_offset + raw_len`
+/// of the uncompressed metadata.
+#[derive(Clone, Copy, Debug)]
+struct Block {
+    zst_offset: u64,
+    zst_len: u64,
+    raw_offset: u64,
+    raw_len: u64,
+    first_id: u64,
+    lines: u32,
+    raw_crc: u32,
+    /// Of the block's first line, to spot a tail that still starts with sealed lines.
+    first_line_crc: u32,
+}
+
+impl Block {
+    fn raw_end(&self) -> u64 { self.raw_offset + self.raw_len }
+    fn end_id(&self) -> usize { self.first_id as usize + self.lines as usize }
+
+    fn encode(&self) -> [u8; ENTRY_LEN] {
+        let mut out = [0u8; ENTRY_LEN];
+        let fields = [self.zst_offset, self.zst_len, self.raw_offset, self.raw_len, self.first_id];
+        for (i, f) in fields.iter().enumerate() { out[i * 8..i * 8 + 8].copy_from_slice(&f.to_le_bytes()); }
+        out[40..44].copy_from_slice(&self.lines.to_le_bytes());
+        out[44..48].copy_from_slice(&self.raw_crc.to_le_bytes());
+        out[48..52].copy_from_slice(&self.first_line_crc.to_le_bytes());
+        let crc = crc32fast::hash(&out[..52]);
+        out[52..].copy_from_slice(&crc.to_le_bytes());
+        out
+    }
+
+    /// None for a torn or corrupt entry.
+    fn decode(b: &[u8]) -> Option<Self> {
+        let u64_at = |i: usize| u64::from_le_bytes(b[i..i + 8].try_into().unwrap());
+        let u32_at = |i: usize| u32::from_le_bytes(b[i..i + 4].try_into().unwrap());
+        if b.len() != ENTRY_LEN || crc32fast::hash(&b[..52]) != u32_at(52) { return None; }
+        Some(Self {
+            zst_offset: u64_at(0),
+            zst_len: u64_at(8),
+            raw_offset: u64_at(16),
+            raw_len: u64_at(24),
+            first_id: u64_at(32),
+            lines: u32_at(40),
+            raw_crc: u32_at(44),
+            first_line_crc: u32_at(48),
+        })
+    }
+}
+
+impl Blocks {
+    /// Opens the file set in `dir`, dropping what an interrupted seal left behind: a torn
+    /// index entry, compressed bytes no entry covers, and sealed lines still at the head
+    /// of reviews.jsonl. Runs before `recovery::recover`, which repairs reviews.jsonl.
+    pub fn open(dir: &Path, cfg: &MetadataConfig) -> Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        let tail_path = dir.join("reviews.jsonl");
+        if !tail_path.exists() { File::create(&tail_path)?; }
+        let seal = match cfg.compression {
+            Compression::None => None,
+            Compression::Zstd => {
+                anyhow::ensure!(cfg.block_lines > 0, "[metadata] block_lines must be at least 1");
+                Some((cfg.block_lines, cfg.level))
+            }
+        };
+        let this = Self {
+            tail_path,
+            zst_path: dir.join("reviews.zst"),
+            idx_path: dir.join("reviews.zst.idx"),
+            blocks: RwLock::new(Vec::new()),
+            seal,
+        };
+        let blocks = this.load_index()?;
+        *this.blocks.write() = blocks;
+        this.drop_sealed_head()?;
+        Ok(this)
+    }
+
+    /// The uncompressed newest lines.
+    pub fn tail_path(&self) -> &Path { &self.tail_path }
+
+    /// Lines moved into blocks; ids below this are read from reviews.zst.
+    pub fn sealed_lines(&self) -> usize {
+        self.blocks.read().last().map_or(0, Block::end_id)
+    }
+
+    fn load_index(&self) -> Result<Vec<Block>> {
+        let idx = match std::fs::read(&self.idx_path) {
+            Ok(b) => b,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e).with_context(|| format!("read {}", self.idx_path.display())),
+        };
+        let zst_len = std::fs::metadata(&self.zst_path).map(|m| m.len()).unwrap_or(0);
+        let mut blocks: Vec<Block> = Vec::with_capacity(idx.len() / ENTRY_LEN);
+        for chunk in idx.chunks(ENTRY_LEN) {
+            let Some(b) = Block::decode(chunk) else { break };
+            let (zst_end, raw_end, end_id) = blocks.last().map_or((0, 0, 0), |p| (p.zst_offset + p.zst_len, p.raw_end(), p.end_id()));
+            if b.zst_offset != zst_end || b.raw_offset != raw_end || b.first_id as usize != end_id || b.zst_offset + b.zst_len > zst_len {
+                break;
+            }
+            blocks.push(b);
+        }
+        let keep_idx = (blocks.len() * ENTRY_LEN) as u64;
+        let keep_zst = blocks.last().map_or(0, |b| b.zst_offset + b.zst_len);
+        if keep_idx < idx.len() as u64 || keep_zst < zst_len {
+            tracing::warn!("metadata blocks: dropping an unfinished seal ({} index bytes, {} compressed bytes)", idx.len() as u64 - keep_idx, zst_len - keep_zst);
+            truncate(&self.idx_path, keep_idx)?;
+            truncate(&self.zst_path, keep_zst)?;
+        }
+        Ok(blocks)
+    }
+
+    /// A crash between writing a block's index entry and rewriting reviews.jsonl leaves the
+    /// block's lines in both places; the copies at the head of reviews.jsonl are removed.
+    fn drop_sealed_head(&self) -> Result<()> {
+        let blocks = self.blocks.read().clone();
+        if blocks.is_empty() { return Ok(()); }
+        let mut first = Vec::new();
+        BufReader::new(File::open(&self.tail_path)?).read_until(b'\n', &mut first)?;
+        if first.last() != Some(&b'\n') { return Ok(()); }
+        let crc = crc32fast::hash(&first[..first.len() - 1]);
+        let Some(from) = blocks.iter().rposition(|b| b.first_line_crc == crc) else { return Ok(()) };
+        let mut tail = File::open(&self.tail_path)?;
+        let mut buf = Vec::new();
+        for b in &blocks[from..] {
+            buf.resize(b.raw_len as usize, 0);
+            if tail.read_exact(&mut buf).is_err() || crc32fast::hash(&buf) != b.raw_crc { return Ok(()); }
+        }
+        let dup = blocks[from..].iter().map(|b| b.raw_len).sum::<u64>();
+        tracing::warn!("metadata blocks: {} still held {} sealed bytes, removed", self.tail_path.display(), dup);
+        let mut rest = Vec::new();
+        tail.read_to_end(&mut rest)?;
+        self.replace_tail(&rest)
+    }
+
+    /// Moves complete blocks from the head of reviews.jsonl, which holds `tail_lines` lines,
+    /// into reviews.zst, if sealing is on and at least one block is full. Callers hold the
+    /// write gate. Should a crash lose the vectors of sealed lines, recovery takes the lines
+    /// back with `unseal_from`. Returns the lines left in reviews.jsonl, None if nothing was
+    /// sealed.
+    pub fn seal(&self, tail_lines: usize) -> Result<Option<usize>> {
+        let Some((block_lines, level)) = self.seal else { return Ok(None) };
+        if tail_lines < block_lines { return Ok(None); }
+        let mut rdr = BufReader::new(File::open(&self.tail_path)?);
+        let (count, zst_start, end, end_id) = {
+            let blocks = self.blocks.read();
+            let last = blocks.last().map_or((0, 0, 0), |b| (b.zst_offset + b.zst_len, b.raw_end(), b.end_id()));
+            (blocks.len(), last.0, last.1, last.2)
+        };
+        // Drops whatever an earlier seal that failed part way wrote past the known blocks.
+        truncate(&self.idx_path, (count * ENTRY_LEN) as u64)?;
+        truncate(&self.zst_path, zst_start)?;
+        let mut new = Vec::new();
+        let mut zst = OpenOptions::new().append(true).open(&self.zst_path)?;
+        let (mut raw, mut lines, mut line, mut first_line_crc) = (Vec::new(), 0u32, Vec::new(), 0);
+        let leftover = loop {
+            line.clear();
+            let n = rdr.read_until(b'\n', &mut line)?;
+            if n == 0 || line.last() != Some(&b'\n') {
+                let mut rest = std::mem::take(&mut raw);
+                rest.extend_from_slice(&line);
+                break (rest, lines as usize);
+            }
+            if lines == 0 { first_line_crc = crc32fast::hash(&line[..n - 1]); }
+            raw.extend_from_slice(&line);
+            lines += 1;
+            if lines as usize == block_lines {
+                let packed = zstd::bulk::compress(&raw, level)?;
+                let prev = new.last().map_or((zst_start, end, end_id), |b: &Block| (b.zst_offset + b.zst_len, b.raw_end(), b.end_id()));
+                zst.write_all(&packed)?;
+                new.push(Block {
+                    zst_offset: prev.0,
+                    zst_len: packed.len() as u64,
+                    raw_offset: prev.1,
+                    raw_len: raw.len() as u64,
+                    first_id: prev.2 as u64,
+                    lines,
+                    raw_crc: crc32fast::hash(&raw),
+                    first_line_crc,
+                });
+                raw.clear();
+                lines = 0;
+            }
+        };
+        if new.is_empty() { return Ok(None); }
+        zst.sync_all()?;
+        let mut idx = OpenOptions::new().append(true).open(&self.idx_path)?;
+        for b in &new { idx.write_all(&b.encode())?; }
+        idx.sync_all()?;
+        // The index entries are the commit point; from here a crash is undone by `drop_sealed_head`.
+        let mut blocks = self.blocks.write();
+        self.replace_tail(&leftover.0)?;
+        let (sealed, bytes) = (new.iter().map(|b| b.lines as usize).sum::<usize>(), new.iter().map(|b| b.zst_len).sum::<u64>());
+        blocks.extend(new);
+        tracing::debug!("metadata blocks: sealed {sealed} lines into {bytes} bytes");
+        Ok(Some(leftover.1))
+    }
+
+    /// Moves every block holding a line at or after `id` back to the head of reviews.jsonl,
+    /// for recovery to cut metadata whose vectors were lost, or for a purge to rewrite lines.
+    pub fn unseal_from(&self, id: usize) -> Result<()> {
+        let mut blocks = self.blocks.write();
+        let from = blocks.partition_point(|b| b.end_id() <= id);
+        if from == blocks.len() { return Ok(()); }
+        let mut raw = Vec::new();
+        let mut zst = File::open(&self.zst_path)?;
+        for b in &blocks[from..] { raw.extend_from_slice(&read_block(&mut zst, b)?); }
+        File::open(&self.tail_path)?.read_to_end(&mut raw)?;
+        // Crash after this and the next start drops the copies again, as after a seal.
+        self.replace_tail(&raw)?;
+        truncate(&self.idx_path, (from * ENTRY_LEN) as u64)?;
+        truncate(&self.zst_path, blocks[from].zst_offset)?;
+        tracing::warn!("metadata blocks: unsealed {} blocks from line {}", blocks.len() - from, blocks[from].first_id);
+        blocks.truncate(from);
+        Ok(())
+    }
+
+    /// Rewrites reviews.jsonl with every complete line (without its '\n') for which
+    /// `f(id, line)` returns a replacement swapped for it. Replacements must be as long as
+    /// the lines they replace, so handed-out offsets still point at the same lines. Callers
+    /// hold the write gate. Returns the lines in reviews.jsonl.
+    pub fn rewrite_tail(&self, mut f: impl FnMut(usize, &[u8]) -> Option<Vec<u8>>) -> Result<usize> {
+        let blocks = self.blocks.write();
+        let first = blocks.last().map_or(0, Block::end_id);
+        let raw = std::fs::read(&self.tail_path)?;
+        let mut out = Vec::with_capacity(raw.len());
+        let mut lines = 0;
+        for line in raw.split_inclusive(|&b| b == b'\n') {
+            let Some(body) = line.strip_suffix(b"\n") else {
+                out.extend_from_slice(line);
+                break;
+            };
+            match f(first + lines, body) {
+                Some(new) => {
+                    anyhow::ensure!(new.len() == body.len(), "metadata line {} rewritten to a different length", first + lines);
+                    out.extend_from_slice(&new);
+                    out.push(b'\n');
+                }
+                None => out.extend_from_slice(line),
+            }
+            lines += 1;
+        }
+        self.replace_tail(&out)?;
+        drop(blocks);
+        Ok(lines)
+    }
+
+    /// Metadata lines in all: sealed plus those in reviews.jsonl.
+    pub fn count_lines(&self) -> Result<usize> {
+        let blocks = self.blocks.read();
+        let tail = File::open(&self.tail_path)?;
+        let sealed = blocks.last().map_or(0, Block::end_id);
+        drop(blocks);
+        Ok(sealed + BufReader::new(tail).split(b'\n').count())
+    }
+
+    /// Uncompressed metadata from byte `offset` on.
+    pub fn reader_at(&self, offset: u64) -> Result<BufReader<RawReader>> {
+        let blocks = self.blocks.read();
+        let from = blocks.partition_point(|b| b.raw_end() <= offset);
+        let mut tail = File::open(&self.tail_path)?;
+        let sealed_end = blocks.last().map_or(0, Block::raw_end);
+        let (pending, skip) = match blocks.get(from) {
+            Some(b) => (blocks[from..].to_vec(), offset - b.raw_offset),
+            None => {
+                tail.seek(SeekFrom::Start(offset.saturating_sub(sealed_end)))?;
+                (Vec::new(), 0)
+            }
+        };
+        drop(blocks);
+        let mut rdr = BufReader::new(RawReader::new(&self.zst_path, pending, tail)?);
+        std::io::copy(&mut (&mut rdr).take(skip), &mut std::io::sink())?;
+        Ok(rdr)
+    }
+
+    /// Id of the line `reader_at_id(id)` would start at: the first of the block holding line
+    /// `id`, or of the tail.
+    pub fn start_of(&self, id: usize) -> usize {
+        let blocks = self.blocks.read();
+        match blocks.get(blocks.partition_point(|b| b.end_id() <= id)) {
+            Some(b) => b.first_id as usize,
+            None => blocks.last().map_or(0, Block::end_id),
+        }
+    }
+
+    /// Uncompressed metadata from the start of a line at or before line `id`, and that
+    /// line's id.
+    pub fn reader_at_id(&self, id: usize) -> Result<(BufReader<RawReader>, usize)> {
+        let blocks = self.blocks.read();
+        let from = blocks.partition_point(|b| b.end_id() <= id);
+        let tail = File::open(&self.tail_path)?;
+        let (pending, first) = match blocks.get(from) {
+            Some(b) => (blocks[from..].to_vec(), b.first_id as usize),
+            None => (Vec::new(), blocks.last().map_or(0, Block::end_id)),
+        };
+        drop(blocks);
+        Ok((BufReader::new(RawReader::new(&self.zst_path, pending, tail)?), first))
+    }
+
+    /// Swaps reviews.jsonl for `contents` atomically.
+    fn replace_tail(&self, contents: &[u8]) -> Result<()> {
+        let tmp = self.tail_path.with_extension("jsonl.tmp");
+        let mut f = File::create(&tmp)?;
+        f.write_all(contents)?;
+        f.sync_all()?;
+        std::fs::rename(&tmp, &self.tail_path)?;
+        Ok(())
+    }
+}
+
+fn truncate(path: &Path, len: u64) -> Result<()> {
+    let f = OpenOptions::new().write(true).create(true).truncate(false).open(path)?;
+    f.set_len(len)?;
+    f.sync_all()?;
+    Ok(())
+}
+
+fn read_block(zst: &mut File, b: &Block) -> Result<Vec<u8>> {
+    let mut packed = vec![0u8; b.zst_len as usize];
+    zst.seek(SeekFrom::Start(b.zst_offset))?;
+    zst.read_exact(&mut packed)?;
+    let raw = zstd::bulk::decompress(&packed, b.raw_len as usize)?;
+    anyhow::ensure!(raw.len() as u64 == b.raw_len && crc32fast::hash(&raw) == b.raw_crc, "metadata block at line {} is corrupt", b.first_id);
+    Ok(raw)
+}
+
+/// Sealed blocks, each decompressed when reached, then reviews.jsonl.
+pub struct RawReader {
+    zst: Option<File>,
+    pending: std::vec::IntoIter<Block>,
+    current: std::io::Cursor<Vec<u8>>,
+    tail: File,
+}
+
+impl RawReader {
+    fn new(zst_path: &Path, pending: Vec<Block>, tail: File) -> Result<Self> {
+        let zst = if pending.is_empty() { None } else { Some(File::open(zst_path)?) };
+        Ok(Self { zst, pending: pending.into_iter(), current: Default::default(), tail })
+    }
+}
+
+impl Read for RawReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            let n = self.current.read(buf)?;
+            if n > 0 || buf.is_empty() { return Ok(n); }
+            match (self.pending.next(), self.zst.as_mut()) {
+                (Some(b), Some(zst)) => {
+                    let raw = read_block(zst, &b).map_err(std::io::Error::other)?;
+                    self.current = std::io::Cursor::new(raw);
+                }
+                _ => return self.tail.read(buf),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dir(name: &str) -> PathBuf {
+        let d = std::env::temp_dir().join(format!("meta-blocks-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&d);
+        d
+    }
+
+    fn zstd(block_lines: usize) -> MetadataConfig {
+        MetadataConfig { compression: Compression::Zstd, block_lines, level: 3 }
+    }
+
+    fn lines(n: usize) -> Vec<u8> {
+        (0..n).flat_map(|i| format!("{{\"line\":{i}}}\n").into_bytes()).collect()
+    }
+
+    /// Byte offset of line `id` in `raw`.
+    fn offset(raw: &[u8], id: usize) -> usize {
+        raw.split_inclusive(|&c| c == b'\n').take(id).map(<[u8]>::len).sum()
+    }
+
+    fn read_all(b: &Blocks, offset: u64) -> Vec<u8> {
+        let mut out = Vec::new();
+        b.reader_at(offset).unwrap().read_to_end(&mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn seal_then_read() {
+        let d = dir("seal");
+        let b = Blocks::open(&d, &zstd(3)).unwrap();
+        let raw = lines(8);
+        std::fs::write(b.tail_path(), &raw).unwrap();
+        assert_eq!(b.seal(2).unwrap(), None, "no full block");
+        assert_eq!(b.seal(8).unwrap(), Some(2));
+        assert_eq!(b.sealed_lines(), 6);
+        assert_eq!(b.count_lines().unwrap(), 8);
+        assert_eq!(std::fs::read(b.tail_path()).unwrap(), raw[offset(&raw, 6)..]);
+        assert_eq!(read_all(&b, 0), raw);
+        let mid = offset(&raw, 4) + 3;
+        assert_eq!(read_all(&b, mid as u64), raw[mid..]);
+        assert_eq!(read_all(&b, raw.len() as u64 - 3), raw[raw.len() - 3..]);
+        assert_eq!((b.start_of(4), b.start_of(6), b.start_of(7)), (3, 6, 6));
+        let (mut rdr, first) = b.reader_at_id(4).unwrap();
+        let mut line = String::new();
+        rdr.read_line(&mut line).unwrap();
+        assert_eq!((first, line.as_str()), (3, "{\"line\":3}\n"));
+
+        let b = Blocks::open(&d, &zstd(3)).unwrap();
+        assert_eq!(b.sealed_lines(), 6, "blocks survive a reopen");
+        assert_eq!(read_all(&b, 0), raw);
+        b.unseal_from(4).unwrap();
+        assert_eq!(b.sealed_lines(), 3);
+        assert_eq!(read_all(&b, 0), raw);
+        std::fs::remove_dir_all(&d).unwrap();
+    }
+
+    #[test]
+    fn open_drops_an_unfinished_seal() {
+        let d = dir("torn");
+        let b = Blocks::open(&d, &zstd(2)).unwrap();
+        let raw = lines(5);
+        std::fs::write(b.tail_path(), &raw).unwrap();
+        assert_eq!(b.seal(5).unwrap(), Some(1));
+        let idx_len = std::fs::metadata(&b.idx_path).unwrap().len();
+        let mut idx = OpenOptions::new().append(true).open(&b.idx_path).unwrap();
+        idx.write_all(&[7u8; ENTRY_LEN / 2]).unwrap();
+        OpenOptions::new().append(true).open(&b.zst_path).unwrap().write_all(b"junk").unwrap();
+        drop(b);
+
+        let b = Blocks::open(&d, &zstd(2)).unwrap();
+        assert_eq!(std::fs::metadata(&b.idx_path).unwrap().len(), idx_len);
+        assert_eq!(b.sealed_lines(), 4);
+        assert_eq!(read_all(&b, 0), raw);
+        std::fs::remove_dir_all(&d).unwrap();
+    }
+
+    #[test]
+    fn open_drops_sealed_lines_left_in_the_tail() {
+        let d = dir("head");
+        let b = Blocks::open(&d, &zstd(2)).unwrap();
+        let raw = lines(3);
+        std::fs::write(b.tail_path(), &raw).unwrap();
+        assert_eq!(b.seal(3).unwrap(), Some(1));
+        // As if the crash came after the index entry but before reviews.jsonl was rewritten.
+        std::fs::write(b.tail_path(), &raw).unwrap();
+        drop(b);
+
+        let b = Blocks::open(&d, &zstd(2)).unwrap();
+        assert_eq!(b.count_lines().unwrap(), 3);
+        assert_eq!(read_all(&b, 0), raw);
+        std::fs::remove_dir_all(&d).unwrap();
+    }
+
+    #[test]
+    fn index_entry_round_trip() {
+        let b = Block { zst_offset: 1, zst_len: 2, raw_offset: 3, raw_len: 4, first_id: 5, lines: 6, raw_crc: 7, first_line_crc: 8 };
+        let mut bytes = b.encode();
+        let d = Block::decode(&bytes).unwrap();
+        assert_eq!((d.zst_offset, d.raw_end(), d.end_id(), d.raw_crc, d.first_line_crc), (1, 7, 11, 7, 8));
+        bytes[10] ^= 1;
+        assert!(Block::decode(&bytes).is_none());
+        assert!(Block::decode(&bytes[..ENTRY_LEN - 1]).is_none());
+    }
+}
@@ -0,0 +1,126 @@
+use crate::{drift, AppState};
+use axum::extract::State;
+use axum::http::header;
+use axum::response::IntoResponse;
+use reviews_types::{DriftResp, SideReport};
+use std::{
+    fmt::Write as _,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Instant,
+};
+
+/// Buckets per doubling of latency; quantiles are accurate to within ~19%.
+const SUB_BUCKETS: f64 = 4.0;
+/// Bucket `i` holds latencies up to 2^(i/4) µs; the last one (~2^26 µs, 67 s) catches the rest.
+const BUCKETS: usize = 105;
+
+/// Pipeline stages with a latency histogram.
+#[derive(Clone, Copy)]
+pub enum Stage {
+    Embed,
+    IndexAppend,
+    MirrorWrite,
+    MetaRead,
+    Scoring,
+}
+
+const STAGES: [(Stage, &str); 5] = [
+    (Stage::Embed, "embed"),
+    (Stage::IndexAppend, "index_append"),
+    (Stage::MirrorWrite, "mirror_write"),
+    (Stage::MetaRead, "meta_read"),
+    (Stage::Scoring, "scoring"),
+];
+
+/// Lock-free log-scale histogram of microsecond latencies.
+struct Histogram {
+    buckets: [AtomicU64; BUCKETS],
+    count: AtomicU64,
+    sum_us: AtomicU64,
+}
+
+impl Histogram {
+    const fn new() -> Self {
+        Self { buckets: [const { AtomicU64::new(0) }; BUCKETS], count: AtomicU64::new(0), sum_us: AtomicU64::new(0) }
+    }
+
+    fn record(&self, us: u64) {
+        let idx = if us <= 1 { 0 } else { ((us as f64).log2() * SUB_BUCKETS).ceil() as usize };
+        self.buckets[idx.min(BUCKETS - 1)].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_us.fetch_add(us, Ordering::Relaxed);
+    }
+
+    /// Upper bound, in seconds, of the bucket holding the `q` quantile.
+    fn quantile(&self, q: f64) -> f64 {
+        let counts: Vec<u64> = self.buckets.iter().map(|b| b.load(Ordering::Relaxed)).collect();
+        let total: u64 = counts.iter().sum();
+        if total == 0 { return 0.0; }
+        let rank = (q * total as f64).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (i, c) in counts.iter().enumerate() {
+            seen += c;
+            if seen >= rank { return 2f64.powf(i as f64 / SUB_BUCKETS) / 1e6; }
+        }
+        2f64.powf((BUCKETS - 1) as f64 / SUB_BUCKETS) / 1e6
+    }
+}
+
+static HISTOGRAMS: [Histogram; 5] = [const { Histogram::new() }; 5];
+
+/// Records the time until it is dropped against `stage`.
+pub struct Timer { stage: Stage, start: Instant }
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        HISTOGRAMS[self.stage as usize].record(self.start.elapsed().as_micros() as u64);
+    }
+}
+
+pub fn timer(stage: Stage) -> Timer { Timer { stage, start: Instant::now() } }
+
+/// GET /metrics — Prometheus text format, one summary per stage, and the embedding drift
+/// gauges of the active collection.
+pub async fn render(State(st): State<AppState>) -> impl IntoResponse {
+    let mut out = String::new();
+    out.push_str("# HELP spfresh_stage_latency_seconds Latency per pipeline stage.\n");
+    out.push_str("# TYPE spfresh_stage_latency_seconds summary\n");
+    for (stage, name) in STAGES {
+        let h = &HISTOGRAMS[stage as usize];
+        for q in [0.5, 0.95, 0.99] {
+            let _ = writeln!(out, "spfresh_stage_latency_seconds{{stage=\"{name}\",quantile=\"{q}\"}} {}", h.quantile(q));
+        }
+        let _ = writeln!(out, "spfresh_stage_latency_seconds_sum{{stage=\"{name}\"}} {}", h.sum_us.load(Ordering::Relaxed) as f64 / 1e6);
+        let _ = writeln!(out, "spfresh_stage_latency_seconds_count{{stage=\"{name}\"}} {}", h.count.load(Ordering::Relaxed));
+    }
+    if let Some(d) = drift::report(&st) { render_drift(&mut out, &d); }
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], out)
+}
+
+/// Reads one drift gauge off a side's report.
+type DriftGauge = fn(&SideReport) -> Option<f64>;
+
+/// Gauges of the last full window per kind, left out until there is one.
+fn render_drift(out: &mut String, d: &DriftResp) {
+    let gauges: [(&str, &str, DriftGauge); 4] = [
+        ("centroid_shift", "1 - cosine of the baseline and last window centroids.", |s| s.centroid_shift),
+        ("mean_norm", "Mean vector norm of the last window.", |s| s.recent.as_ref().map(|w| w.mean_norm)),
+        ("zero_vector_rate", "Share of all-zero vectors in the last window.", |s| s.recent.as_ref().map(|w| w.zero_rate)),
+        ("oov_rate", "Share of tokens in the last window unseen by the index.", |s| s.recent.as_ref().and_then(|w| w.oov_rate)),
+    ];
+    for (name, help, get) in gauges {
+        let _ = writeln!(out, "# HELP spfresh_drift_{name} {help}");
+        let _ = writeln!(out, "# TYPE spfresh_drift_{name} gauge");
+        for (kind, side) in [("query", &d.query), ("document", &d.document)] {
+            if let Some(v) = get(side) { let _ = writeln!(out, "spfresh_drift_{name}{{kind=\"{kind}\"}} {v}"); }
+        }
+    }
+    if let Some(u) = d.dims_used {
+        out.push_str("# HELP spfresh_drift_dims_used Share of the embedder's dimensions some document has used.\n");
+        out.push_str("# TYPE spfresh_drift_dims_used gauge\n");
+        let _ = writeln!(out, "spfresh_drift_dims_used {u}");
+    }
+    out.push_str("# HELP spfresh_drift_alert 1 while any [drift] threshold is crossed.\n");
+    out.push_str("# TYPE spfresh_drift_alert gauge\n");
+    let _ = writeln!(out, "spfresh_drift_alert {}", u8::from(!d.alerts.is_empty()));
+}
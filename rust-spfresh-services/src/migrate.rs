@@ -0,0 +1,228 @@
+use crate::{aspects, codec, config::Config, dir_lock, lang, open_state};
+use anyhow::{Context, Result};
+use reviews_types::{Review, ReviewStatus};
+use serde_json::{Map, Value};
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufRead, BufWriter, Write},
+    path::Path,
+};
+
+/// Schema version of the metadata lines this build writes.
+pub const SCHEMA_VERSION: u32 = 5;
+/// Holds the schema version of the metadata in the data dir; absent means 1, the first.
+const VERSION_FILE: &str = "SCHEMA_VERSION";
+/// Where the migrated reviews.jsonl is built.
+const STAGING_DIR: &str = "migrate";
+/// Written into the staging dir once the migrated metadata is synced; from then on the
+/// migration is finished, on the next start if need be, instead of discarded.
+const DONE_FILE: &str = "DONE";
+/// Replaced by the migrated reviews.jsonl, which is sealed into blocks again on open.
+const OBSOLETE_FILES: [&str; 2] = ["reviews.zst", "reviews.zst.idx"];
+
+/// One schema change, taking a line of version `from` to `from + 1`: fields renamed
+/// (`(old, new)`, skipped when `new` is already there), then fields filled in where absent.
+/// Both have to be harmless on a line already at the new version, since a data dir that
+/// was never migrated may hold lines of every version.
+struct Step {
+    from: u32,
+    renames: &'static [(&'static str, &'static str)],
+    fill: fn(&mut Map<String, Value>) -> bool,
+}
+
+const STEPS: &[Step] = &[
+    // created_at, external_id and version; reads took a missing version as 1.
+    Step { from: 1, renames: &[], fill: |r| fill(r, "version", || Value::from(1)) },
+    // lang, detected on write.
+    Step {
+        from: 2,
+        renames: &[],
+        fill: |r| {
+            let text = ["review_title", "review_body"].map(|f| r.get(f).and_then(Value::as_str).unwrap_or("").to_string()).join(" ");
+            fill(r, "lang", || Value::from(lang::detect(&text)))
+        },
+    },
+    // status; reads took a missing one as approved.
+    Step { from: 3, renames: &[], fill: |r| fill(r, "status", || serde_json::to_value(ReviewStatus::Approved).unwrap()) },
+    // aspects, extracted on write.
+    Step {
+        from: 4,
+        renames: &[],
+        fill: |r| {
+            let [title, body] = ["review_title", "review_body"].map(|f| r.get(f).and_then(Value::as_str).unwrap_or("").to_string());
+            let rating = r.get("review_rating").and_then(Value::as_i64).unwrap_or(0) as i32;
+            fill(r, "aspects", || serde_json::to_value(aspects::extract(&title, &body, rating)).unwrap())
+        },
+    },
+];
+
+fn fill(review: &mut Map<String, Value>, field: &str, value: impl FnOnce() -> Value) -> bool {
+    if review.contains_key(field) { return false; }
+    review.insert(field.to_string(), value());
+    true
+}
+
+/// What a migration did (or, dry, would do) to the metadata.
+#[derive(Default)]
+struct Report {
+    lines: usize,
+    changed: usize,
+    /// Written before lines were framed.
+    unframed: usize,
+    renamed: BTreeMap<&'static str, usize>,
+    filled: BTreeMap<u32, usize>,
+    /// Fields the current schema does not know, dropped.
+    dropped: BTreeMap<String, usize>,
+    /// Lines that fail their checksum or do not parse as a review even after the steps;
+    /// they are kept as they are, so ids stay aligned.
+    unreadable: Vec<usize>,
+}
+
+/// `migrate [--dry-run]`: rewrites the metadata to the current schema version, line for
+/// line so ids are kept: each line goes through the `STEPS` from the data dir's version
+/// on and is written back framed, as the service writes it. Runs offline, like `compact`.
+pub fn run(config: Config, args: &[String]) -> Result<()> {
+    let dry_run = match args {
+        [] => false,
+        [flag] if flag == "--dry-run" => true,
+        _ => anyhow::bail!("usage: migrate [--dry-run]"),
+    };
+    anyhow::ensure!(config.replica.is_none(), "a replica copies its leader's lines; migrate the leader and re-seed the replica");
+    let data_dir = std::env::current_dir()?.join(&config.data_dir);
+    anyhow::ensure!(data_dir.is_dir(), "no data dir at {}", data_dir.display());
+    let _dir_lock = dir_lock::acquire(&data_dir)?;
+    let from = read_version(&data_dir)?;
+    let st = open_state(config, &data_dir, &Default::default())?;
+    println!("metadata schema version {from}, current {SCHEMA_VERSION}");
+
+    let staging = data_dir.join(STAGING_DIR);
+    let mut out = match dry_run {
+        true => None,
+        false => {
+            std::fs::create_dir_all(&staging)?;
+            Some(BufWriter::new(File::create(staging.join("reviews.jsonl"))?))
+        }
+    };
+    let mut report = Report::default();
+    for (id, line) in st.meta.files.reader_at(0)?.split(b'\n').enumerate() {
+        let line = line?;
+        let new = migrate_line(id, &line, from, &mut report);
+        report.lines += 1;
+        if new.is_some() { report.changed += 1; }
+        if let Some(out) = &mut out {
+            match new {
+                Some(new) => out.write_all(&new)?,
+                None => { out.write_all(&line)?; out.write_all(b"\n")?; }
+            }
+        }
+    }
+    drop(st);
+    print_report(&report, dry_run);
+    let Some(out) = out else { return Ok(()) };
+    out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+    if report.changed == 0 {
+        std::fs::remove_dir_all(&staging)?;
+        return write_version(&data_dir);
+    }
+    File::create(staging.join(DONE_FILE))?.sync_all()?;
+    finish(&data_dir)?;
+    println!("migrated to schema version {SCHEMA_VERSION}; byte offsets moved, so re-seed replicas and restart listings");
+    Ok(())
+}
+
+/// Line `id` (without its '\n') at the current schema, framed and with its '\n'; None if
+/// it is already there byte for byte, or unreadable.
+fn migrate_line(id: usize, line: &[u8], from: u32, report: &mut Report) -> Option<Vec<u8>> {
+    let parsed = codec::decode_line(line).ok().and_then(|json| serde_json::from_slice::<Map<String, Value>>(json).ok());
+    let Some(mut review) = parsed else {
+        report.unreadable.push(id);
+        return None;
+    };
+    for step in STEPS.iter().filter(|s| s.from >= from) {
+        for &(old, new) in step.renames {
+            if review.contains_key(new) { continue; }
+            if let Some(v) = review.remove(old) {
+                review.insert(new.to_string(), v);
+                *report.renamed.entry(old).or_default() += 1;
+            }
+        }
+        if (step.fill)(&mut review) { *report.filled.entry(step.from + 1).or_default() += 1; }
+    }
+    let Ok(parsed) = serde_json::from_value::<Review>(Value::Object(review.clone())) else {
+        report.unreadable.push(id);
+        return None;
+    };
+    let json = serde_json::to_vec(&parsed).ok()?;
+    let kept: Map<String, Value> = serde_json::from_slice(&json).ok()?;
+    for (k, _) in review.into_iter().filter(|(k, v)| !v.is_null() && !kept.contains_key(k)) {
+        *report.dropped.entry(k).or_default() += 1;
+    }
+    if line.first() == Some(&b'{') { report.unframed += 1; }
+    let new = codec::encode_line(&json);
+    (new[..new.len() - 1] != *line).then_some(new)
+}
+
+fn print_report(r: &Report, dry_run: bool) {
+    let verb = if dry_run { "would change" } else { "changed" };
+    println!("{} of {} lines {verb} ({} unframed)", r.changed, r.lines, r.unframed);
+    for (old, n) in &r.renamed { println!("  renamed {old}: {n} lines"); }
+    for (version, n) in &r.filled { println!("  filled in for version {version}: {n} lines"); }
+    for (field, n) in &r.dropped { println!("  dropped unknown field {field}: {n} lines"); }
+    if !r.unreadable.is_empty() {
+        println!("  {} unreadable lines kept as they are, e.g. {:?}", r.unreadable.len(), &r.unreadable[..r.unreadable.len().min(10)]);
+    }
+}
+
+/// Completes a migration that got as far as `DONE`, or discards one that did not. Runs
+/// before the metadata is opened.
+pub fn finish(data_dir: &Path) -> Result<()> {
+    let staging = data_dir.join(STAGING_DIR);
+    if !staging.is_dir() { return Ok(()); }
+    if !staging.join(DONE_FILE).is_file() {
+        tracing::warn!("discarding unfinished migration in {}", staging.display());
+        return Ok(std::fs::remove_dir_all(&staging)?);
+    }
+    for name in OBSOLETE_FILES {
+        match std::fs::remove_file(data_dir.join(name)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+    }
+    // Gone if a crash came after the rename.
+    let staged = staging.join("reviews.jsonl");
+    if staged.is_file() { std::fs::rename(staged, data_dir.join("reviews.jsonl"))?; }
+    write_version(data_dir)?;
+    std::fs::remove_dir_all(&staging)?;
+    tracing::info!("migrated metadata moved into {}", data_dir.display());
+    Ok(())
+}
+
+/// Warns on startup about metadata older than this build writes, and refuses metadata
+/// newer. A data dir without any metadata yet is at the current version.
+pub fn check(data_dir: &Path, lines: usize) -> Result<()> {
+    let version = read_version(data_dir)?;
+    anyhow::ensure!(version <= SCHEMA_VERSION, "metadata schema version {version} is newer than supported {SCHEMA_VERSION}");
+    if version == SCHEMA_VERSION { return Ok(()); }
+    if lines == 0 { return write_version(data_dir); }
+    tracing::warn!("metadata schema version {version} is older than {SCHEMA_VERSION}; stop the service and run `migrate`");
+    Ok(())
+}
+
+fn read_version(data_dir: &Path) -> Result<u32> {
+    let path = data_dir.join(VERSION_FILE);
+    match std::fs::read_to_string(&path) {
+        Ok(s) => s.trim().parse().with_context(|| format!("{}: not a version", path.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(1),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn write_version(data_dir: &Path) -> Result<()> {
+    let tmp = data_dir.join(format!("{VERSION_FILE}.tmp"));
+    let mut f = File::create(&tmp)?;
+    f.write_all(SCHEMA_VERSION.to_string().as_bytes())?;
+    f.sync_all()?;
+    std::fs::rename(&tmp, data_dir.join(VERSION_FILE))?;
+    Ok(())
+}
@@ -0,0 +1,312 @@
+use crate::{
+    audit::{Action, Actor},
+    blocking, codec,
+    config::{ModerationAction, ModerationConfig},
+    lang, ApiError, AppState, Review,
+};
+use anyhow::{Context, Result};
+use axum::{
+    extract::{Path as UrlPath, Query, State},
+    http::StatusCode,
+    Json,
+};
+use parking_lot::{Mutex, RwLock};
+use regex::{Regex, RegexBuilder};
+use reviews_types::{Decision, DecisionReq, QueueItem, QueueParams, QueueResp, QueueStatus, ReviewStatus, Verdict};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// In the data dir; rewritten with the new ids by `compact`.
+pub const LOG_FILE: &str = "moderation.log";
+const MAX_LIMIT: usize = 1000;
+
+/// One line of the moderation log; also what the replication log carries.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Entry {
+    id: usize,
+    #[serde(rename = "status")]
+    decision: Decision,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
+    ts_ms: u64,
+}
+
+/// The configured checks, run on every inserted review before its metadata is written.
+pub struct Moderator {
+    patterns: Vec<Regex>,
+    /// Lowercased. Single words match whole words; phrases, and Thai words (written
+    /// without spaces around them), match anywhere in the text.
+    words: HashSet<String>,
+    decision: Decision,
+    default_status: ReviewStatus,
+    #[cfg(feature = "moderation-classifier")]
+    classifier: Option<Classifier>,
+}
+
+impl Moderator {
+    pub fn new(cfg: &ModerationConfig) -> Result<Self> {
+        let patterns = cfg.patterns.iter()
+            .map(|p| RegexBuilder::new(p).case_insensitive(true).build().with_context(|| format!("[moderation] pattern {p:?}")))
+            .collect::<Result<_>>()?;
+        let mut words = HashSet::new();
+        for path in &cfg.word_lists {
+            let text = std::fs::read_to_string(path).with_context(|| format!("[moderation] word list {}", path.display()))?;
+            words.extend(text.lines()
+                .map(|l| l.split('#').next().unwrap_or_default().trim().to_lowercase())
+                .filter(|w| !w.is_empty()));
+        }
+        #[cfg(not(feature = "moderation-classifier"))]
+        anyhow::ensure!(cfg.classifier.is_none(), "[moderation.classifier] configured, but built without the `moderation-classifier` feature");
+        anyhow::ensure!(cfg.default_status != ReviewStatus::Rejected, "[moderation] default_status must be approved or pending");
+        let decision = match cfg.action {
+            ModerationAction::Flag => Decision::Flagged,
+            ModerationAction::Quarantine => Decision::Pending,
+        };
+        Ok(Self {
+            patterns,
+            words,
+            decision,
+            default_status: cfg.default_status,
+            #[cfg(feature = "moderation-classifier")]
+            classifier: cfg.classifier.as_ref().map(Classifier::new),
+        })
+    }
+
+    /// None when the review passes every check. A classifier that cannot be reached holds
+    /// the review as pending whatever the configured action, so nothing unchecked is served.
+    pub fn check(&self, review: &Review) -> Option<Verdict> {
+        let text = review.embed_text();
+        let verdict = |reason: String| Some(Verdict { decision: self.decision, reason });
+        if let Some(p) = self.patterns.iter().find(|p| p.is_match(&text)) {
+            return verdict(format!("pattern {}", p.as_str()));
+        }
+        if !self.words.is_empty() {
+            let lower = text.to_lowercase();
+            let tokens: HashSet<&str> = lower.split(|c: char| !c.is_alphanumeric()).filter(|t| !t.is_empty()).collect();
+            let hit = self.words.iter().find(|w| {
+                if w.contains(' ') || lang::detect(w) == lang::THAI {
+                    lower.contains(w.as_str())
+                } else {
+                    tokens.contains(w.as_str())
+                }
+            });
+            if let Some(w) = hit { return verdict(format!("word list: {w}")); }
+        }
+        #[cfg(feature = "moderation-classifier")]
+        if let Some(c) = &self.classifier {
+            return match c.classify(&text) {
+                Ok(None) => None,
+                Ok(Some(reason)) => verdict(reason),
+                Err(e) => {
+                    tracing::warn!("moderation classifier failed, quarantining: {e}");
+                    Some(Verdict { decision: Decision::Pending, reason: format!("classifier error: {e}") })
+                }
+            };
+        }
+        None
+    }
+
+    /// Status a review is written with, given what `check` made of it.
+    pub fn initial_status(&self, verdict: Option<&Verdict>) -> ReviewStatus {
+        match verdict {
+            Some(v) if v.decision == Decision::Pending => ReviewStatus::Pending,
+            _ => self.default_status,
+        }
+    }
+}
+
+#[cfg(feature = "moderation-classifier")]
+struct Classifier {
+    agent: ureq::Agent,
+    url: String,
+}
+
+#[cfg(feature = "moderation-classifier")]
+#[derive(Deserialize)]
+struct ClassifierResp {
+    flagged: bool,
+    #[serde(default)]
+    reason: Option<String>,
+}
+
+#[cfg(feature = "moderation-classifier")]
+impl Classifier {
+    fn new(cfg: &crate::config::ClassifierConfig) -> Self {
+        let agent = ureq::AgentBuilder::new().timeout(std::time::Duration::from_millis(cfg.timeout_ms)).build();
+        Self { agent, url: cfg.url.clone() }
+    }
+
+    /// The reason, if the classifier flags `text`.
+    fn classify(&self, text: &str) -> Result<Option<String>> {
+        let resp: ClassifierResp = self.agent.post(&self.url)
+            .send_json(serde_json::json!({ "text": text }))?
+            .into_json()?;
+        Ok(resp.flagged.then(|| resp.reason.unwrap_or_else(|| "classifier".into())))
+    }
+}
+
+/// Moderation decisions, by id: what the checks held or flagged and what curators
+/// decided since. Every one is appended to `data/moderation.log`, framed like
+/// reviews.jsonl and synced, and replayed on open; statuses themselves live in the
+/// review attributes, which `MetaStore` brings up to date from here.
+pub struct Queue {
+    path: PathBuf,
+    /// The latest entry of each id.
+    latest: RwLock<BTreeMap<usize, Entry>>,
+    file: Mutex<File>,
+}
+
+impl Queue {
+    pub fn open(data_dir: &Path) -> Result<Self> {
+        let path = data_dir.join(LOG_FILE);
+        let mut latest = BTreeMap::new();
+        if let Ok(f) = File::open(&path) {
+            for (n, line) in BufReader::new(f).split(b'\n').enumerate() {
+                match codec::decode_line(&line?).and_then(|json| Ok(serde_json::from_slice::<Entry>(json)?)) {
+                    Ok(e) => { latest.insert(e.id, e); }
+                    Err(e) => tracing::warn!("{}: line {} skipped: {e}", path.display(), n + 1),
+                }
+            }
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(Self { path, latest: RwLock::new(latest), file: Mutex::new(file) })
+    }
+
+    fn append(&self, entry: Entry) -> Result<()> {
+        let line = codec::encode_line(&serde_json::to_vec(&entry)?);
+        let mut f = self.file.lock();
+        f.write_all(&line)?;
+        f.sync_data()?;
+        self.latest.write().insert(entry.id, entry);
+        Ok(())
+    }
+
+    pub fn record(&self, id: usize, v: Verdict) -> Result<()> {
+        tracing::info!("review {id} {}: {}", if v.decision == Decision::Flagged { "flagged" } else { "held" }, v.reason);
+        self.append(Entry { id, decision: v.decision, reason: Some(v.reason), ts_ms: now_ms() })
+    }
+
+    fn decide(&self, id: usize, decision: Decision, reason: Option<String>) -> Result<()> {
+        self.append(Entry { id, decision, reason, ts_ms: now_ms() })
+    }
+
+    /// Logs a curator's setting review `id` to `status` outside the moderation queue's own
+    /// endpoints (PATCH /reviews/:id).
+    pub fn set_status(&self, id: usize, status: ReviewStatus, reason: Option<String>) -> Result<()> {
+        let decision = match status {
+            ReviewStatus::Pending => Decision::Pending,
+            ReviewStatus::Approved => Decision::Approved,
+            ReviewStatus::Rejected => Decision::Rejected,
+        };
+        self.decide(id, decision, reason)
+    }
+
+    /// Entries logged after byte `offset`, stopping before the first about an id at or past
+    /// `below` (not yet replicated). Returns them and the offset to resume from.
+    pub fn read_from(&self, offset: u64, below: usize) -> Result<(Vec<Entry>, u64)> {
+        codec::read_log_from(&self.path, offset, below, |e: &Entry| e.id)
+    }
+
+    /// Logs an entry of the leader's log as it stands (a replica following it). Returns the
+    /// id and the status it sets. Callers hold the write gate.
+    #[cfg_attr(not(feature = "replica"), allow(dead_code))]
+    pub fn replay(&self, entry: Entry) -> Result<(usize, Option<ReviewStatus>)> {
+        let (id, status) = (entry.id, entry.decision.status());
+        self.append(entry)?;
+        Ok((id, status))
+    }
+
+    /// The status each logged id was last given.
+    pub fn statuses(&self) -> Vec<(usize, ReviewStatus)> {
+        self.latest.read().values().filter_map(|e| Some((e.id, e.decision.status()?))).collect()
+    }
+
+    fn latest(&self, id: usize) -> Option<Entry> {
+        self.latest.read().get(&id).cloned()
+    }
+
+    /// Writes the latest entries of kept reviews to `path` under their new ids.
+    pub fn write_remapped(&self, path: &Path, map: &[Option<usize>]) -> Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        for e in self.latest.read().values() {
+            let Some(Some(id)) = map.get(e.id) else { continue };
+            out.write_all(&codec::encode_line(&serde_json::to_vec(&Entry { id: *id, ..e.clone() })?))?;
+        }
+        out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        Ok(())
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0)
+}
+
+/// GET /admin/moderation?status=pending|flagged|rejected&after=&limit= — live reviews in
+/// that state, in id order, with their latest moderation entry.
+pub async fn list(State(st): State<AppState>, Query(p): Query<QueueParams>) -> Result<Json<QueueResp>, ApiError> {
+    let limit = p.limit.clamp(1, MAX_LIMIT);
+    let start = p.after.map_or(0, |a| a + 1);
+    let mut ids = Vec::new();
+    match p.status {
+        QueueStatus::Flagged => {
+            let latest = st.meta.moderation.latest.read();
+            ids.extend(latest.range(start..)
+                .filter(|(id, e)| e.decision == Decision::Flagged && st.meta.is_live(**id))
+                .map(|(&id, _)| id)
+                .take(limit));
+        }
+        QueueStatus::Pending | QueueStatus::Rejected => {
+            let status = if p.status == QueueStatus::Pending { ReviewStatus::Pending } else { ReviewStatus::Rejected };
+            st.meta.attrs.each_in_status(start, status, |id| {
+                if st.meta.is_live(id) { ids.push(id); }
+                ids.len() < limit
+            });
+        }
+    }
+    let items: Vec<QueueItem> = ids.into_iter().map(|id| {
+        let e = st.meta.moderation.latest(id);
+        QueueItem {
+            id,
+            status: st.meta.attrs.get(id).status,
+            decision: e.as_ref().map(|e| e.decision),
+            ts_ms: e.as_ref().map(|e| e.ts_ms),
+            reason: e.and_then(|e| e.reason),
+        }
+    }).collect();
+    let next_after = (items.len() == limit).then(|| items[limit - 1].id);
+    Ok(Json(QueueResp { items, next_after }))
+}
+
+/// POST /admin/moderation/:id/approve — makes a review searchable (and clears a flag).
+pub async fn approve(st: State<AppState>, actor: Actor, id: UrlPath<usize>, req: Option<Json<DecisionReq>>) -> Result<StatusCode, ApiError> {
+    decide(st, actor, id, req, Decision::Approved).await
+}
+
+/// POST /admin/moderation/:id/reject — keeps a review out of search without deleting it.
+pub async fn reject(st: State<AppState>, actor: Actor, id: UrlPath<usize>, req: Option<Json<DecisionReq>>) -> Result<StatusCode, ApiError> {
+    decide(st, actor, id, req, Decision::Rejected).await
+}
+
+/// Any live review can be decided on again, whatever its status.
+async fn decide(State(st): State<AppState>, actor: Actor, UrlPath(id): UrlPath<usize>, req: Option<Json<DecisionReq>>, decision: Decision) -> Result<StatusCode, ApiError> {
+    let reason = req.and_then(|Json(r)| r.reason);
+    blocking(move || {
+        {
+            let _w = st.write_gate.lock();
+            if id >= st.meta.count()? || !st.meta.is_live(id) {
+                return Err(ApiError::not_found(format!("review {id} not found")));
+            }
+            st.meta.moderation.decide(id, decision, reason.clone())?;
+            if let Some(s) = decision.status() { st.meta.set_status(id, s); }
+        }
+        let action = if decision == Decision::Approved { Action::Approve } else { Action::Reject };
+        st.audit.record(&actor, action, [id].into_iter().collect(), reason)?;
+        Ok(StatusCode::NO_CONTENT)
+    }).await
+}
@@ -0,0 +1,87 @@
+use crate::ApiError;
+use axum::{
+    async_trait,
+    body::Bytes,
+    extract::{FromRequest, Request},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Wire format for request/response bodies, picked from Content-Type / Accept.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Format { Json, MsgPack }
+
+impl Format {
+    fn from_mime(v: &str) -> Option<Self> {
+        if v.contains("application/msgpack") || v.contains("application/x-msgpack") {
+            Some(Format::MsgPack)
+        } else if v.contains("application/json") || v.contains("+json") {
+            Some(Format::Json)
+        } else {
+            None
+        }
+    }
+    fn content_type(self) -> &'static str {
+        match self {
+            Format::Json => "application/json",
+            Format::MsgPack => "application/msgpack",
+        }
+    }
+}
+
+fn header_format(headers: &HeaderMap, name: header::HeaderName) -> Option<Format> {
+    headers.get(name).and_then(|v| v.to_str().ok()).and_then(Format::from_mime)
+}
+
+/// Body extractor accepting JSON or MessagePack. The second field is the format the
+/// response should use: `Accept` if it names one, otherwise the request's own format.
+pub struct Negotiated<T>(pub T, pub Format);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for Negotiated<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let headers = req.headers();
+        let input = match headers.get(header::CONTENT_TYPE) {
+            None => Format::Json,
+            Some(_) => header_format(headers, header::CONTENT_TYPE).ok_or_else(|| {
+                ApiError::new(
+                    StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                    "expected Content-Type application/json or application/msgpack",
+                ).into_response()
+            })?,
+        };
+        let output = header_format(headers, header::ACCEPT).unwrap_or(input);
+        let bytes = Bytes::from_request(req, state).await.map_err(IntoResponse::into_response)?;
+        let value = match input {
+            Format::Json => serde_json::from_slice(&bytes).map_err(|e| e.to_string()),
+            Format::MsgPack => rmp_serde::from_slice(&bytes).map_err(|e| e.to_string()),
+        };
+        value
+            .map(|v| Negotiated(v, output))
+            .map_err(|e| ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, e).into_response())
+    }
+}
+
+/// Response body serialized in the negotiated format.
+pub struct Reply<T>(pub Format, pub T);
+
+impl<T: Serialize> IntoResponse for Reply<T> {
+    fn into_response(self) -> Response {
+        let body = match self.0 {
+            Format::Json => serde_json::to_vec(&self.1).map_err(anyhow::Error::from),
+            // Named (map) encoding keeps field names, so msgpack mirrors the JSON shape.
+            Format::MsgPack => rmp_serde::to_vec_named(&self.1).map_err(anyhow::Error::from),
+        };
+        match body {
+            Ok(b) => ([(header::CONTENT_TYPE, HeaderValue::from_static(self.0.content_type()))], b).into_response(),
+            Err(e) => ApiError::from(e).into_response(),
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use crate::{codec, Review};
+use anyhow::Result;
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+pub const LOG_FILE: &str = "patches.log";
+
+/// One line of the patch log; also what the replication log carries.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Entry {
+    pub id: usize,
+    /// The review's version after the edit.
+    pub version: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub review_rating: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub product_id: Option<String>,
+    ts_ms: u64,
+}
+
+/// Edits of reviews' metadata that leave their vectors alone (PATCH /reviews/:id).
+/// Metadata lines are append-only and keep their length, since listing cursors are byte
+/// offsets, so an edit is recorded here instead, in `data/patches.log`, framed like
+/// reviews.jsonl and synced before it is acknowledged. Each entry carries all of a review's
+/// edits so far; reads apply the latest. Compaction writes the edited reviews out and drops
+/// the log.
+pub struct Patches {
+    path: PathBuf,
+    latest: RwLock<HashMap<usize, Entry>>,
+    file: Mutex<File>,
+}
+
+impl Patches {
+    pub fn open(data_dir: &Path) -> Result<Self> {
+        let path = data_dir.join(LOG_FILE);
+        let mut latest = HashMap::new();
+        if let Ok(f) = File::open(&path) {
+            for (n, line) in BufReader::new(f).split(b'\n').enumerate() {
+                match codec::decode_line(&line?).and_then(|json| Ok(serde_json::from_slice::<Entry>(json)?)) {
+                    Ok(e) => { latest.insert(e.id, e); }
+                    Err(e) => tracing::warn!("{}: line {} skipped: {e}", path.display(), n + 1),
+                }
+            }
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(Self { path, latest: RwLock::new(latest), file: Mutex::new(file) })
+    }
+
+    /// Records that review `id` is now at `version`, with the rating and product given on
+    /// top of its earlier edits. Callers hold the write gate.
+    pub fn add(&self, id: usize, version: u64, review_rating: Option<i32>, product_id: Option<String>) -> Result<()> {
+        let prev = self.latest.read().get(&id).cloned();
+        let entry = Entry {
+            id,
+            version,
+            review_rating: review_rating.or_else(|| prev.as_ref().and_then(|p| p.review_rating)),
+            product_id: product_id.or_else(|| prev.and_then(|p| p.product_id)),
+            ts_ms: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0),
+        };
+        let line = codec::encode_line(&serde_json::to_vec(&entry)?);
+        let mut f = self.file.lock();
+        f.write_all(&line)?;
+        f.sync_data()?;
+        self.latest.write().insert(id, entry);
+        Ok(())
+    }
+
+    /// Entries logged after byte `offset`, stopping before the first about an id at or past
+    /// `below` (not yet replicated). Returns them and the offset to resume from.
+    pub fn read_from(&self, offset: u64, below: usize) -> Result<(Vec<Entry>, u64)> {
+        codec::read_log_from(&self.path, offset, below, |e: &Entry| e.id)
+    }
+
+    /// `review` (line `id`) with its edits, if it has any.
+    pub fn apply(&self, id: usize, mut review: Review) -> Review {
+        if let Some(e) = self.latest.read().get(&id) {
+            review.version = Some(e.version);
+            if let Some(rating) = e.review_rating { review.review_rating = rating; }
+            if let Some(product_id) = &e.product_id { review.product_id.clone_from(product_id); }
+        }
+        review
+    }
+}
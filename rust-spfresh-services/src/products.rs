@@ -0,0 +1,53 @@
+use crate::{ApiError, AppState};
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use reviews_types::{ProductInfo, ProductsResp, ReviewStatus};
+use serde::Deserialize;
+
+const DEFAULT_LIMIT: usize = 1_000;
+const MAX_LIMIT: usize = 10_000;
+
+#[derive(Deserialize)]
+pub struct ProductsParams {
+    /// Only product ids starting with this.
+    #[serde(default)]
+    prefix: String,
+    limit: Option<usize>,
+}
+
+/// GET /products?prefix=&limit= — the product ids reviews have been written for, in id
+/// order, with their live, approved review count and average rating, e.g. to fill a
+/// product filter. From the in-memory attributes; no metadata is read.
+pub async fn list_products(State(st): State<AppState>, Query(p): Query<ProductsParams>) -> Result<Json<ProductsResp>, ApiError> {
+    let limit = p.limit.unwrap_or(DEFAULT_LIMIT);
+    if limit == 0 || limit > MAX_LIMIT {
+        return Err(ApiError::bad_request(format!("limit must be between 1 and {MAX_LIMIT}")));
+    }
+    let attrs = &st.meta.attrs;
+    let all = attrs.product_names();
+    // (reviews, stars, rated) by interned number; one pass over every review.
+    let mut tally = vec![(0usize, 0i64, 0usize); all.len()];
+    let mut names: Vec<(String, usize)> = all.into_iter().enumerate()
+        .filter(|(_, name)| name.starts_with(&p.prefix))
+        .map(|(n, name)| (name, n))
+        .collect();
+    names.sort_unstable();
+    let total = names.len();
+    names.truncate(limit);
+    attrs.each(|id, a| {
+        let Some(t) = a.product.and_then(|n| tally.get_mut(n as usize)) else { return };
+        if a.status != ReviewStatus::Approved || !st.meta.is_live(id) { return; }
+        t.0 += 1;
+        if (1..=5).contains(&a.rating) {
+            t.1 += a.rating as i64;
+            t.2 += 1;
+        }
+    });
+    let products = names.into_iter().map(|(product_id, n)| {
+        let (reviews, stars, rated) = tally[n];
+        ProductInfo { product_id, reviews, avg_rating: (rated > 0).then(|| stars as f64 / rated as f64) }
+    }).collect();
+    Ok(Json(ProductsResp { total, products }))
+}
@@ -0,0 +1,152 @@
+use crate::{attrs::Attr, blocking, synth::Rng, ApiError, AppState};
+use axum::{extract::State, Json};
+use reviews_types::{PointLabel, ProjectedPoint, ProjectionReq, ProjectionResp, ReviewStatus};
+
+const DEFAULT_SAMPLE: usize = 1_000;
+const MAX_SAMPLE: usize = 10_000;
+/// Power iterations per component at most; most converge well before.
+const MAX_ITERATIONS: usize = 100;
+const TOLERANCE: f64 = 1e-6;
+
+/// POST /analytics/projection — 2D coordinates of a sample of review vectors, for plotting
+/// the embedding space without exporting the vectors themselves. PCA: the points are the
+/// vectors, centred, projected onto their first two principal components, found by power
+/// iteration. Vectors are kept sparse and centred on the fly, so sparse TF-IDF vectors
+/// stay cheap whatever the `dim`. Live reviews only; `titles` costs a metadata read per
+/// point.
+pub async fn projection(State(st): State<AppState>, Json(req): Json<ProjectionReq>) -> Result<Json<ProjectionResp>, ApiError> {
+    let sample = req.sample.unwrap_or(DEFAULT_SAMPLE);
+    if sample == 0 || sample > MAX_SAMPLE {
+        return Err(ApiError::bad_request(format!("sample must be between 1 and {MAX_SAMPLE}")));
+    }
+    if req.ids.len() > MAX_SAMPLE {
+        return Err(ApiError::bad_request(format!("at most {MAX_SAMPLE} ids")));
+    }
+    let resp = blocking(move || {
+        let active = st.target(req.collection.as_deref())?;
+        let attrs = &st.meta.attrs;
+        let products: Vec<u32> = req.product_ids.iter().filter_map(|p| attrs.product(p)).collect();
+        let langs: Vec<u32> = req.lang.iter().filter_map(|l| attrs.lang(&l.trim().to_lowercase())).collect();
+        let wanted = |id: usize, a: &Attr| {
+            (req.include_unapproved || a.status == ReviewStatus::Approved)
+                && (req.product_ids.is_empty() || a.product.is_some_and(|p| products.contains(&p)))
+                && (req.lang.is_empty() || a.lang.is_some_and(|l| langs.contains(&l)))
+                && req.min_rating.is_none_or(|r| a.rating >= r)
+                && req.max_rating.is_none_or(|r| a.rating <= r)
+                && st.meta.is_live(id)
+        };
+        let mut matched: Vec<(usize, Attr)> = Vec::new();
+        if req.ids.is_empty() {
+            attrs.each(|id, a| if wanted(id, a) { matched.push((id, *a)) });
+        } else {
+            let mut ids = req.ids.clone();
+            ids.sort_unstable();
+            ids.dedup();
+            matched = ids.into_iter().filter(|&id| id < attrs.len()).map(|id| (id, attrs.get(id))).filter(|(id, a)| wanted(*id, a)).collect();
+        }
+        let total = matched.len();
+        let mut rng = Rng::new(req.seed);
+        if matched.len() > sample {
+            // Partial Fisher-Yates: the first `sample` entries become a uniform sample.
+            for i in 0..sample {
+                let j = i + rng.below((matched.len() - i) as u64) as usize;
+                matched.swap(i, j);
+            }
+            matched.truncate(sample);
+            matched.sort_unstable_by_key(|&(id, _)| id);
+        }
+        let dim = active.vindex.dim();
+        let ids: Vec<usize> = matched.iter().map(|&(id, _)| id).collect();
+        let mut rows: Vec<(usize, Vec<(u32, f32)>)> = Vec::with_capacity(ids.len());
+        st.vcache.scan_ids(active.vindex.mirror_path(), dim, active.vindex.len()?, &ids, |id, v| {
+            rows.push((id, v.iter().enumerate().filter(|&(_, &x)| x != 0.0).map(|(i, &x)| (i as u32, x)).collect()));
+        })?;
+        let vectors: Vec<&[(u32, f32)]> = rows.iter().map(|(_, v)| &v[..]).collect();
+        let (coords, explained_variance) = pca(&vectors, dim, &mut rng);
+        let (products, langs) = match req.label {
+            PointLabel::ProductId => (attrs.product_names(), Vec::new()),
+            PointLabel::Lang => (Vec::new(), attrs.lang_names()),
+            _ => Default::default(),
+        };
+        let label = |a: &Attr| match req.label {
+            PointLabel::Rating => a.rating.to_string(),
+            PointLabel::ProductId => a.product.and_then(|p| products.get(p as usize).cloned()).unwrap_or_default(),
+            PointLabel::Lang => a.lang.and_then(|l| langs.get(l as usize).cloned()).unwrap_or_default(),
+            PointLabel::Status => serde_json::to_value(a.status).ok().and_then(|v| v.as_str().map(str::to_string)).unwrap_or_default(),
+        };
+        // Rows come back in id order, like `matched`; ids without a readable vector are skipped.
+        let mut attrs_of = matched.iter().peekable();
+        let mut points: Vec<ProjectedPoint> = rows.iter().zip(coords).filter_map(|((id, _), (x, y))| {
+            while attrs_of.next_if(|(m, _)| m < id).is_some() {}
+            let (_, a) = attrs_of.next_if(|(m, _)| m == id)?;
+            Some(ProjectedPoint { id: *id, x, y, label: label(a), title: None })
+        }).collect();
+        if req.titles {
+            for p in &mut points { p.title = Some(st.meta.read_review_by_line(p.id)?.review_title); }
+        }
+        Ok(ProjectionResp { matched: total, explained_variance, points })
+    }).await?;
+    Ok(Json(resp))
+}
+
+/// Each row's coordinates on the first two principal components, and the share of the
+/// total variance each accounts for.
+fn pca(rows: &[&[(u32, f32)]], dim: usize, rng: &mut Rng) -> (Vec<(f64, f64)>, [f64; 2]) {
+    let n = rows.len();
+    if n == 0 { return (Vec::new(), [0.0; 2]); }
+    let mut mean = vec![0.0f64; dim];
+    for row in rows { for &(i, x) in *row { mean[i as usize] += x as f64; } }
+    for m in &mut mean { *m /= n as f64; }
+    let sq_norms: f64 = rows.iter().map(|r| r.iter().map(|&(_, x)| x as f64 * x as f64).sum::<f64>()).sum();
+    let total = sq_norms / n as f64 - dot(&mean, &mean);
+    let first = component(rows, &mean, None, rng);
+    let second = component(rows, &mean, Some(&first), rng);
+    let (xs, ys) = (project(rows, &mean, &first), project(rows, &mean, &second));
+    let share = |p: &[f64]| if total > 0.0 { p.iter().map(|x| x * x).sum::<f64>() / n as f64 / total } else { 0.0 };
+    let explained = [share(&xs), share(&ys)];
+    (xs.into_iter().zip(ys).collect(), explained)
+}
+
+/// The unit direction of greatest variance of the centred rows, orthogonal to `prev`:
+/// power iteration on the covariance, applied as `Xᵀ(X v)` without forming it. Its sign
+/// is fixed so that its largest entry is positive.
+fn component(rows: &[&[(u32, f32)]], mean: &[f64], prev: Option<&[f64]>, rng: &mut Rng) -> Vec<f64> {
+    let mut v: Vec<f64> = (0..mean.len()).map(|_| rng.unit() - 0.5).collect();
+    orthonormalize(&mut v, prev);
+    for _ in 0..MAX_ITERATIONS {
+        let u = project(rows, mean, &v);
+        let mut w = vec![0.0f64; mean.len()];
+        for (row, &ui) in rows.iter().zip(&u) { for &(i, x) in *row { w[i as usize] += ui * x as f64; } }
+        let sum_u: f64 = u.iter().sum();
+        for (wj, m) in w.iter_mut().zip(mean) { *wj -= m * sum_u; }
+        if !orthonormalize(&mut w, prev) { break; }
+        let moved = w.iter().zip(&v).map(|(a, b)| (a - b).abs()).fold(0.0, f64::max);
+        v = w;
+        if moved < TOLERANCE { break; }
+    }
+    if v.iter().copied().max_by(|a, b| a.abs().total_cmp(&b.abs())).is_some_and(|b| b < 0.0) {
+        for x in &mut v { *x = -*x; }
+    }
+    v
+}
+
+/// Each row, centred, dotted with `v`.
+fn project(rows: &[&[(u32, f32)]], mean: &[f64], v: &[f64]) -> Vec<f64> {
+    let offset = dot(mean, v);
+    rows.iter().map(|row| row.iter().map(|&(i, x)| x as f64 * v[i as usize]).sum::<f64>() - offset).collect()
+}
+
+/// Removes `prev`'s direction from `v` and scales it to unit length; false when nothing
+/// is left of it.
+fn orthonormalize(v: &mut [f64], prev: Option<&[f64]>) -> bool {
+    if let Some(p) = prev {
+        let d = dot(v, p);
+        for (x, q) in v.iter_mut().zip(p) { *x -= d * q; }
+    }
+    let norm = dot(v, v).sqrt();
+    if norm < 1e-12 { return false; }
+    for x in v.iter_mut() { *x /= norm; }
+    true
+}
+
+fn dot(a: &[f64], b: &[f64]) -> f64 { a.iter().zip(b).map(|(x, y)| x * y).sum() }
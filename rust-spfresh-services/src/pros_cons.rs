@@ -0,0 +1,129 @@
+use crate::{blocking, cosine, keyword, lang, sentiment, trending::STOPWORDS, ApiError, AppState};
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use reviews_types::{ProsConsParams, ProsConsResp, ReviewStatus, SentenceCluster};
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+/// Reviews read per request, the most recent ones.
+const MAX_REVIEWS: usize = 500;
+const MAX_SENTENCES: usize = 2_000;
+const MAX_LIMIT: usize = 50;
+/// Review ids listed per aspect.
+const MAX_IDS: usize = 20;
+/// Terms naming an aspect.
+const TERMS: usize = 3;
+
+struct Sentence {
+    review: usize,
+    text: String,
+    sentiment: f64,
+}
+
+/// Sentences close enough to one another to be about the same thing.
+struct Cluster {
+    /// Sum of the members' vectors, and its length.
+    sum: Vec<f32>,
+    norm: f32,
+    members: Vec<usize>,
+}
+
+/// GET /products/:id/pros-cons?limit=&min_reviews=&similarity= — what a product's reviews
+/// praise and complain about. The sentences of its latest live, approved reviews are
+/// embedded (as queries, so document frequencies are untouched) and clustered greedily
+/// by cosine; a cluster mentioned in at least `min_reviews` reviews whose sentences lean
+/// positive is a pro, negative a con. Sentence sentiment comes from a word list (see
+/// `sentiment`), or the review's stars for sentences without sentiment words.
+pub async fn pros_cons(State(st): State<AppState>, Path(product_id): Path<String>, Query(p): Query<ProsConsParams>) -> Result<Json<ProsConsResp>, ApiError> {
+    let Some(product) = st.meta.attrs.product(&product_id) else {
+        return Err(ApiError::not_found(format!("product {product_id} not found")));
+    };
+    if !(p.similarity > 0.0 && p.similarity <= 1.0) {
+        return Err(ApiError::bad_request("similarity must be in (0, 1]"));
+    }
+    let resp = blocking(move || {
+        let mut ids = Vec::new();
+        st.meta.attrs.each_of_product(product, ReviewStatus::Approved, |id, _| {
+            if st.meta.is_live(id) { ids.push(id); }
+        });
+        let ids = &ids[ids.len().saturating_sub(MAX_REVIEWS)..];
+        let mut sentences = Vec::new();
+        for &id in ids.iter().rev() {
+            if sentences.len() >= MAX_SENTENCES { break; }
+            let r = st.meta.read_review_by_line(id)?;
+            for text in lang::sentences(&r.review_title).chain(lang::sentences(&r.review_body)).take(MAX_SENTENCES - sentences.len()) {
+                let sentiment = sentiment::polarity(text).unwrap_or_else(|| sentiment::from_rating(r.review_rating));
+                sentences.push(Sentence { review: id, text: text.to_string(), sentiment });
+            }
+        }
+        let texts: Vec<String> = sentences.iter().map(|s| s.text.clone()).collect();
+        let vectors = st.embedder().embed_query_batch(&texts)?;
+        let (mut pros, mut cons) = (Vec::new(), Vec::new());
+        for c in cluster(&vectors, p.similarity) {
+            let aspect = aspect(&c, &sentences, &vectors);
+            if aspect.reviews < p.min_reviews.max(1) { continue; }
+            if aspect.sentiment >= sentiment::MIN_LEAN { pros.push(aspect) } else if aspect.sentiment <= -sentiment::MIN_LEAN { cons.push(aspect) }
+        }
+        pros.sort_by(|a, b| b.reviews.cmp(&a.reviews).then(b.sentiment.total_cmp(&a.sentiment)));
+        cons.sort_by(|a, b| b.reviews.cmp(&a.reviews).then(a.sentiment.total_cmp(&b.sentiment)));
+        let limit = p.limit.min(MAX_LIMIT);
+        pros.truncate(limit);
+        cons.truncate(limit);
+        Ok(ProsConsResp { product_id, reviews: ids.len(), sentences: sentences.len(), pros, cons })
+    }).await?;
+    Ok(Json(resp))
+}
+
+/// Each vector joins the cluster whose centre it is closest to, if at least `similarity`
+/// away, or starts one. Vectors are compared by their non-zero entries only, which keeps
+/// sparse TF-IDF vectors cheap.
+fn cluster(vectors: &[Vec<f32>], similarity: f32) -> Vec<Cluster> {
+    let mut clusters: Vec<Cluster> = Vec::new();
+    for (i, v) in vectors.iter().enumerate() {
+        let nonzero: Vec<(usize, f32)> = v.iter().copied().enumerate().filter(|&(_, x)| x != 0.0).collect();
+        if nonzero.is_empty() { continue; }
+        let best = clusters.iter().enumerate()
+            .map(|(c, cl)| (c, nonzero.iter().map(|&(d, x)| cl.sum[d] * x).sum::<f32>() / cl.norm))
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        match best {
+            Some((c, sim)) if sim >= similarity => {
+                let cl = &mut clusters[c];
+                for &(d, x) in &nonzero { cl.sum[d] += x; }
+                cl.norm = cl.sum.iter().map(|x| x * x).sum::<f32>().sqrt();
+                cl.members.push(i);
+            }
+            _ => {
+                let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+                clusters.push(Cluster { sum: v.clone(), norm, members: vec![i] });
+            }
+        }
+    }
+    clusters
+}
+
+fn aspect(c: &Cluster, sentences: &[Sentence], vectors: &[Vec<f32>]) -> SentenceCluster {
+    let members = || c.members.iter().map(|&i| &sentences[i]);
+    let reviews: BTreeSet<usize> = members().map(|s| s.review).collect();
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for s in members() {
+        let words: HashSet<String> = keyword::terms(&s.text)
+            .filter(|w| w.chars().count() > 1 && !STOPWORDS.contains(&w.as_str()) && !sentiment::is_sentiment_word(w))
+            .filter(|w| !w.chars().all(char::is_numeric))
+            .collect();
+        for w in words { *counts.entry(w).or_default() += 1; }
+    }
+    let mut terms: Vec<(String, usize)> = counts.into_iter().collect();
+    terms.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    let example = c.members.iter().copied()
+        .max_by(|&a, &b| cosine(&c.sum, &vectors[a]).total_cmp(&cosine(&c.sum, &vectors[b])))
+        .map_or_else(String::new, |i| sentences[i].text.clone());
+    SentenceCluster {
+        terms: terms.into_iter().take(TERMS).map(|(t, _)| t).collect(),
+        example,
+        sentiment: members().map(|s| s.sentiment).sum::<f64>() / c.members.len() as f64,
+        sentences: c.members.len(),
+        reviews: reviews.len(),
+        review_ids: reviews.into_iter().rev().take(MAX_IDS).collect(),
+    }
+}
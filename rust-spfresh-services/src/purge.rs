@@ -0,0 +1,165 @@
+use crate::{
+    audit::{Action, Actor},
+    codec, compact, jobs::JobHandle, ApiError, AppState, VecIndex,
+};
+use anyhow::{Context, Result};
+use axum::{extract::State, http::StatusCode, Json};
+use reviews_types::PurgeResp;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{
+    collections::{BTreeSet, HashSet},
+    fs::OpenOptions,
+    io::{Read, Seek, SeekFrom, Write},
+    path::Path,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const JOB_KIND: &str = "purge";
+/// Ids erased, for replicas to erase too; in the data dir, dropped by `compact`.
+pub const LOG_FILE: &str = "redactions.log";
+/// Blanked in the metadata of a purged review; everything else (ids, product, rating,
+/// version, status) is kept, for the review to stay dead when the metadata is read again.
+const TEXT_FIELDS: [&str; 2] = ["review_title", "review_body"];
+
+/// One line of the redaction log.
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    id: usize,
+    ts_ms: u64,
+}
+
+/// POST /admin/purge — erases deleted reviews for good, as a job, to honor data-erasure
+/// requests; a delete only tombstones a review. Every review no longer live (deleted, or
+/// an earlier version a replacement superseded) has its title and body blanked in the
+/// metadata, and its vector zeroed and payload cleared in every mirror and spfresh index,
+/// in place so ids and offsets hold; then a compaction is requested, dropping the reviews
+/// and their index entries on the next start. The ids go to the redaction log for replicas
+/// to erase them as well. Writes wait while the job runs.
+pub async fn start_purge(State(st): State<AppState>, actor: Actor) -> Result<(StatusCode, Json<PurgeResp>), ApiError> {
+    let job = st.jobs.try_start(JOB_KIND, 0).ok_or_else(|| ApiError::conflict("a purge is already running"))?;
+    let job_id = job.id;
+    tokio::task::spawn_blocking(move || {
+        let res = run(&st, &actor, &job);
+        if let Err(e) = &res { tracing::error!("purge {} failed: {e}", job.id); }
+        job.finish(&res);
+    });
+    Ok((StatusCode::ACCEPTED, Json(PurgeResp { job_id })))
+}
+
+fn run(st: &AppState, actor: &Actor, job: &JobHandle) -> Result<()> {
+    let _w = st.write_gate.lock();
+    let ids: BTreeSet<usize> = (0..st.meta.attrs.len()).filter(|&id| !st.meta.is_live(id)).collect();
+    job.set_total(ids.len());
+    // The job does nothing until it is audited; a failed audit write fails the job.
+    st.audit.record(actor, Action::Purge, ids.iter().copied().collect(), Some(format!("job {}", job.id)))?;
+    let (lines, vectors) = erase(st, &ids)?;
+    log(&st.data_dir, &ids)?;
+    if !ids.is_empty() { compact::request(&st.data_dir)?; }
+    job.set_processed(ids.len());
+    tracing::info!("purge {}: {} reviews, {lines} metadata lines blanked, {vectors} vectors zeroed", job.id, ids.len());
+    Ok(())
+}
+
+/// Blanks the text of `ids` in the metadata, zeroes their vectors in every index
+/// directory's mirror, and zeroes their vectors and clears their payloads in the spfresh
+/// index of every index open and every collection, flushed. Callers hold the write gate.
+/// Returns the lines and mirror records overwritten.
+pub fn erase(st: &AppState, ids: &BTreeSet<usize>) -> Result<(usize, usize)> {
+    let lines = st.meta.redact(ids)?;
+    let mut vectors = 0;
+    for rel in compact::index_dirs(&st.data_dir, &st.config)? {
+        vectors += zero_vectors(&st.data_dir.join(rel).join("reviews.index"), ids)?;
+    }
+    let mut open: Vec<Arc<dyn VecIndex>> = vec![st.vindex()];
+    if let Some(sh) = &st.shadow { open.push(sh.vindex.clone()); }
+    if let Some(fields) = &st.fields { open.extend(fields.indexes()); }
+    if let Some(late) = &st.late_interaction { open.push(late.index()); }
+    let mut erased = HashSet::new();
+    for index in open {
+        if !erased.insert(index.mirror_path().to_path_buf()) { continue; }
+        erase_index(st, index.as_ref(), ids)?;
+    }
+    st.collections.for_each_index(st, &erased, |index| erase_index(st, index, ids))?;
+    st.vcache.clear();
+    Ok((lines, vectors))
+}
+
+/// Zeroes `ids` in `index`'s spfresh index, and in its sketches so they match the mirror.
+fn erase_index(st: &AppState, index: &dyn VecIndex, ids: &BTreeSet<usize>) -> Result<()> {
+    index.erase(ids)?;
+    if let Some(sketches) = &st.sketches {
+        let zeros = vec![0.0; index.dim()];
+        for &id in ids { sketches.resketch(index.mirror_path(), id, &zeros); }
+    }
+    Ok(())
+}
+
+/// Appends `ids` to the redaction log, synced.
+fn log(data_dir: &Path, ids: &BTreeSet<usize>) -> Result<()> {
+    if ids.is_empty() { return Ok(()); }
+    let ts_ms = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0);
+    let mut out = Vec::new();
+    for &id in ids { out.extend(codec::encode_line(&serde_json::to_vec(&Entry { id, ts_ms })?)); }
+    let mut f = OpenOptions::new().create(true).append(true).open(data_dir.join(LOG_FILE))?;
+    f.write_all(&out)?;
+    f.sync_data()?;
+    Ok(())
+}
+
+/// Ids erased after byte `offset` of the redaction log, stopping before the first at or past
+/// `below` (not yet replicated). Returns them and the offset to resume from.
+pub fn read_from(data_dir: &Path, offset: u64, below: usize) -> Result<(Vec<usize>, u64)> {
+    let (entries, next) = codec::read_log_from(&data_dir.join(LOG_FILE), offset, below, |e: &Entry| e.id)?;
+    Ok((entries.into_iter().map(|e| e.id).collect(), next))
+}
+
+/// Metadata line `id` (without its '\n') with the review's text blanked and spaces after
+/// the JSON making up the length; None if there is nothing to blank or the line does not
+/// parse, which compaction drops anyway.
+pub fn redact(id: usize, line: &[u8]) -> Option<Vec<u8>> {
+    let json = codec::decode_line(line).ok()?;
+    let mut review: Map<String, Value> = serde_json::from_slice(json).ok()?;
+    let mut blanked = false;
+    for field in TEXT_FIELDS {
+        if let Some(Value::String(s)) = review.get_mut(field) && !s.is_empty() {
+            s.clear();
+            blanked = true;
+        }
+    }
+    if !blanked { return None; }
+    let mut out = serde_json::to_vec(&review).ok()?;
+    if out.len() > json.len() {
+        tracing::warn!("metadata line {id} grew when blanked; left as is");
+        return None;
+    }
+    out.resize(json.len(), b' ');
+    // Lines from before framing stay unframed.
+    if line.first() == Some(&b'{') { return Some(out); }
+    let mut framed = codec::encode_line(&out);
+    framed.pop();
+    Some(framed)
+}
+
+/// Overwrites the records of `ids` in `mirror` with zero vectors; ids past its end are
+/// skipped. Returns the records overwritten.
+fn zero_vectors(mirror: &Path, ids: &BTreeSet<usize>) -> Result<usize> {
+    let mut f = OpenOptions::new().read(true).write(true).open(mirror)?;
+    let mut head = [0u8; codec::HEADER_LEN as usize];
+    f.read_exact(&mut head)?;
+    let dim = codec::FileHeader::decode(codec::FileKind::Mirror, &head)
+        .with_context(|| format!("{}: not a vector mirror", mirror.display()))?
+        .dim as usize;
+    let rec_len = codec::record_len(dim) as u64;
+    let count = (f.metadata()?.len().saturating_sub(codec::HEADER_LEN) / rec_len) as usize;
+    let zeros = codec::encode_record(&vec![0.0; dim]);
+    let mut n = 0;
+    for &id in ids.range(..count) {
+        f.seek(SeekFrom::Start(codec::HEADER_LEN + id as u64 * rec_len))?;
+        f.write_all(&zeros)?;
+        n += 1;
+    }
+    f.sync_all()?;
+    Ok(n)
+}
@@ -0,0 +1,54 @@
+use crate::{
+    blocking,
+    negotiate::{Negotiated, Reply},
+    ApiError, AppState, Review,
+};
+use axum::extract::State;
+use reviews_types::{RangeHit, RangeSearchReq, RangeSearchResp, ReviewStatus};
+use std::collections::HashMap;
+
+/// POST /search/range — every review within `max_distance` of the query, nearest first,
+/// from the ANN index's range search: for duplicate detection and clustering, where no
+/// top-k fits. The index stops at `limit` before deleted and (unless asked for) unapproved
+/// reviews are dropped, so `truncated` says whether it did.
+pub async fn range_search(State(st): State<AppState>, Negotiated(req, fmt): Negotiated<RangeSearchReq>) -> Result<Reply<RangeSearchResp>, ApiError> {
+    if req.query.trim().is_empty() {
+        return Err(ApiError::bad_request("query must not be empty"));
+    }
+    if !req.max_distance.is_finite() || req.max_distance < 0.0 {
+        return Err(ApiError::bad_request("max_distance must be a finite, non-negative number"));
+    }
+    let limit = st.config.search.stream_top_k(req.limit);
+    let resp = blocking(move || search(&st, &req, limit)).await?;
+    Ok(Reply(fmt, resp))
+}
+
+/// With `ids_only`, each hit's external_id comes from the payload the index keeps next to
+/// its vector, so no metadata line is read.
+pub(crate) fn search(st: &AppState, req: &RangeSearchReq, limit: usize) -> Result<RangeSearchResp, ApiError> {
+    let active = st.target(req.collection.as_deref())?;
+    let q = active.embedder.embed_query(&req.query)?;
+    let found = active.vindex.range_search(&q, req.max_distance, limit)?;
+    let truncated = found.len() >= limit;
+    let found: Vec<(usize, f32)> = found.into_iter()
+        .filter(|&(id, _)| {
+            st.meta.is_live(id) && (req.include_unapproved || st.meta.attrs.get(id).status == ReviewStatus::Approved)
+        })
+        .collect();
+    if req.ids_only {
+        let hits = found.into_iter()
+            .map(|(id, distance)| {
+                let external_id = active.vindex.payload(id)?.map(|p| String::from_utf8_lossy(&p).into_owned());
+                Ok(RangeHit { id, distance, review: None, external_id })
+            })
+            .collect::<anyhow::Result<_>>()?;
+        return Ok(RangeSearchResp { hits, truncated });
+    }
+    let mut ids: Vec<usize> = found.iter().map(|&(id, _)| id).collect();
+    ids.sort_unstable();
+    let mut reviews: HashMap<usize, Review> = st.meta.read_reviews(&ids)?.into_iter().collect();
+    let hits = found.into_iter()
+        .filter_map(|(id, distance)| Some(RangeHit { id, distance, review: Some(reviews.remove(&id)?), external_id: None }))
+        .collect();
+    Ok(RangeSearchResp { hits, truncated })
+}
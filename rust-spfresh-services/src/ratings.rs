@@ -0,0 +1,116 @@
+use crate::{attrs::Attr, ApiError, AppState};
+use reviews_types::{Granularity, ReviewStatus, TimelineBucket, TimelineParams, TimelineResp};
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use parking_lot::RwLock;
+use std::collections::{BTreeMap, HashMap};
+
+const DAY_SECS: u64 = 86_400;
+/// 1970-01-01 was a Thursday; weeks start on Monday.
+const EPOCH_WEEKDAY: u64 = 3;
+
+/// Daily rating sums and counts of every product's live, approved reviews, kept in step with
+/// the metadata by `MetaStore` (added on append, taken away again on delete or replacement,
+/// moved on a status change), so a timeline never costs a metadata read. Reviews without
+/// `created_at` are left out.
+#[derive(Default)]
+pub struct Timeline {
+    /// Interned product -> day since the epoch -> bucket.
+    days: RwLock<HashMap<u32, BTreeMap<u64, Bucket>>>,
+}
+
+#[derive(Clone, Copy, Default)]
+struct Bucket { sum: i64, count: u32 }
+
+impl Timeline {
+    pub fn add(&self, attr: Attr) { self.apply(attr, 1); }
+
+    pub fn remove(&self, attr: Attr) { self.apply(attr, -1); }
+
+    /// Pending and rejected reviews are not counted, so adding or removing one is a no-op.
+    fn apply(&self, attr: Attr, sign: i64) {
+        if attr.status != ReviewStatus::Approved { return; }
+        let (Some(product), Some(t)) = (attr.product, attr.created_at) else { return };
+        let mut days = self.days.write();
+        let buckets = days.entry(product).or_default();
+        let day = t / DAY_SECS;
+        let b = buckets.entry(day).or_default();
+        b.sum += sign * attr.rating as i64;
+        b.count = b.count.saturating_add_signed(sign as i32);
+        if b.count == 0 { buckets.remove(&day); }
+    }
+
+    /// The days of `product` in `from..to` merged into buckets; `start_of` maps a day to
+    /// the first day of its bucket.
+    fn buckets(&self, product: u32, from: u64, to: u64, start_of: impl Fn(u64) -> u64) -> Vec<(u64, Bucket)> {
+        let days = self.days.read();
+        let mut out: Vec<(u64, Bucket)> = Vec::new();
+        for (&day, b) in days.get(&product).into_iter().flat_map(|m| m.range(from..to)) {
+            let start = start_of(day);
+            match out.last_mut() {
+                Some((s, acc)) if *s == start => { acc.sum += b.sum; acc.count += b.count; }
+                _ => out.push((start, *b)),
+            }
+        }
+        out
+    }
+}
+
+/// GET /products/:id/ratings-timeline?bucket=day|week&from=&to= — average rating and
+/// review count of a product per UTC day or week, from the in-memory `Timeline`, for
+/// charting how ratings moved after a product change. Live, approved reviews with a
+/// `created_at` only; `from` and `to` are rounded down to whole days.
+pub async fn ratings_timeline(State(st): State<AppState>, Path(product_id): Path<String>, Query(p): Query<TimelineParams>) -> Result<Json<TimelineResp>, ApiError> {
+    let Some(product) = st.meta.attrs.product(&product_id) else {
+        return Err(ApiError::not_found(format!("product {product_id} not found")));
+    };
+    let from = p.from.map_or(0, |t| t / DAY_SECS);
+    let to = p.to.map_or(u64::MAX, |t| t / DAY_SECS);
+    if from > to {
+        return Err(ApiError::bad_request("from must not be after to"));
+    }
+    let buckets = match p.bucket {
+        Granularity::Day => st.meta.ratings.buckets(product, from, to, |d| d),
+        Granularity::Week => st.meta.ratings.buckets(product, from, to, |d| d.saturating_sub((d + EPOCH_WEEKDAY) % 7)),
+    };
+    let (sum, count) = buckets.iter().fold((0i64, 0u32), |(s, c), (_, b)| (s + b.sum, c + b.count));
+    Ok(Json(TimelineResp {
+        product_id,
+        bucket: p.bucket,
+        count,
+        avg_rating: (count > 0).then(|| sum as f64 / count as f64),
+        buckets: buckets.into_iter()
+            .map(|(day, b)| TimelineBucket { start: day * DAY_SECS, count: b.count, avg_rating: b.sum as f64 / b.count as f64 })
+            .collect(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attr(rating: i32, status: ReviewStatus) -> Attr {
+        Attr { created_at: Some(10 * DAY_SECS + 5), rating, product: Some(0), status, ..Default::default() }
+    }
+
+    fn day(t: &Timeline) -> (i64, u32) {
+        t.buckets(0, 0, u64::MAX, |d| d).first().map_or((0, 0), |(_, b)| (b.sum, b.count))
+    }
+
+    #[test]
+    fn counts_approved_reviews_only() {
+        let t = Timeline::default();
+        t.add(attr(5, ReviewStatus::Approved));
+        t.add(attr(1, ReviewStatus::Pending));
+        assert_eq!(day(&t), (5, 1));
+        // What `MetaStore::set_status` does on a decision.
+        t.remove(attr(1, ReviewStatus::Pending));
+        t.add(attr(1, ReviewStatus::Approved));
+        assert_eq!(day(&t), (6, 2));
+        t.remove(attr(5, ReviewStatus::Approved));
+        t.add(attr(5, ReviewStatus::Rejected));
+        assert_eq!(day(&t), (1, 1));
+    }
+}
@@ -0,0 +1,142 @@
+use crate::{codec, meta_blocks::Blocks, MetaStore};
+use anyhow::Result;
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+/// Backwards scan step when looking for the last line break of reviews.jsonl.
+const TAIL_CHUNK: u64 = 64 * 1024;
+
+/// Repairs what a crash can leave behind before anything is served: a torn last line in
+/// reviews.jsonl (the one the compressed blocks in `meta` left over from an interrupted
+/// seal were already dealt with by `Blocks::open`), a partial or corrupt last record in a mirror, and stores that disagree
+/// on the record count. A write is only acknowledged once both its metadata line and its
+/// mirror record are written, so after a process crash the unmatched tails being dropped
+/// belong to writes that were never acknowledged.
+///
+/// `primary` must match the metadata one-to-one; the `secondary` mirrors (shadow, fields) may
+/// lag (they are back-filled on startup) but never lead. Mirrors not yet in the current format are left to their
+/// migration on open. The spfresh index next to each mirror is cut or filled in to match it
+/// when it is opened (`spfresh_index::reconcile`). Returns the metadata lines left.
+pub fn recover(meta: &Blocks, primary: (&Path, usize), secondary: &[(PathBuf, usize)]) -> Result<usize> {
+    repair_meta_tail(meta.tail_path())?;
+    let mut meta_count = meta.count_lines()?;
+    if let Some(vectors) = repair_mirror_tail(primary.0, primary.1)? {
+        if vectors > meta_count {
+            truncate_mirror(primary.0, primary.1, meta_count, "vectors without metadata")?;
+        } else if vectors < meta_count {
+            // Sealed lines whose vectors are gone go back to reviews.jsonl to be cut there.
+            meta.unseal_from(vectors)?;
+            let sealed = meta.sealed_lines();
+            truncate_meta(meta.tail_path(), vectors - sealed, meta_count - sealed)?;
+            meta_count = vectors;
+        }
+    }
+    for (mirror, dim) in secondary {
+        if repair_mirror_tail(mirror, *dim)?.is_some_and(|v| v > meta_count) {
+            truncate_mirror(mirror, *dim, meta_count, "secondary vectors without metadata")?;
+        }
+    }
+    Ok(meta_count)
+}
+
+/// Drops an unterminated or unparseable last line.
+fn repair_meta_tail(path: &Path) -> Result<()> {
+    let Ok(mut f) = OpenOptions::new().read(true).write(true).open(path) else { return Ok(()) };
+    let len = f.metadata()?.len();
+    if len == 0 { return Ok(()); }
+    let mut keep = len;
+    let mut last = [0u8; 1];
+    f.seek(SeekFrom::Start(len - 1))?;
+    f.read_exact(&mut last)?;
+    if last[0] != b'\n' {
+        keep = line_start_before(&mut f, len)?;
+    }
+    if keep > 0 {
+        let start = line_start_before(&mut f, keep - 1)?;
+        let mut line = vec![0u8; (keep - 1 - start) as usize];
+        f.seek(SeekFrom::Start(start))?;
+        f.read_exact(&mut line)?;
+        if let Err(e) = MetaStore::parse_line(&line) {
+            tracing::warn!("recovery: last metadata line is damaged ({e})");
+            keep = start;
+        }
+    }
+    if keep == len { return Ok(()); }
+    f.set_len(keep)?;
+    f.sync_all()?;
+    tracing::warn!("recovery: {}: truncated torn tail, {} -> {} bytes", path.display(), len, keep);
+    Ok(())
+}
+
+/// Offset just past the last '\n' before `end` (0 if there is none).
+fn line_start_before(f: &mut File, end: u64) -> Result<u64> {
+    let mut pos = end;
+    let mut buf = vec![0u8; TAIL_CHUNK as usize];
+    while pos > 0 {
+        let from = pos.saturating_sub(TAIL_CHUNK);
+        let chunk = &mut buf[..(pos - from) as usize];
+        f.seek(SeekFrom::Start(from))?;
+        f.read_exact(chunk)?;
+        if let Some(i) = chunk.iter().rposition(|&b| b == b'\n') {
+            return Ok(from + i as u64 + 1);
+        }
+        pos = from;
+    }
+    Ok(0)
+}
+
+/// Cuts `path` back to its first `keep` lines.
+fn truncate_meta(path: &Path, keep: usize, had: usize) -> Result<()> {
+    let mut rdr = BufReader::new(File::open(path)?);
+    let (mut offset, mut line) = (0u64, Vec::new());
+    for _ in 0..keep {
+        line.clear();
+        offset += rdr.read_until(b'\n', &mut line)? as u64;
+    }
+    let f = OpenOptions::new().write(true).open(path)?;
+    f.set_len(offset)?;
+    f.sync_all()?;
+    tracing::warn!("recovery: {}: dropped {} metadata lines without vectors", path.display(), had - keep);
+    Ok(())
+}
+
+/// Trims a partial or corrupt last record from a current-format mirror and returns its
+/// vector count; None if there is no mirror or it still awaits migration.
+fn repair_mirror_tail(path: &Path, dim: usize) -> Result<Option<usize>> {
+    let Ok(mut f) = OpenOptions::new().read(true).write(true).open(path) else { return Ok(None) };
+    let len = f.metadata()?.len();
+    let mut head = [0u8; codec::HEADER_LEN as usize];
+    if len < codec::HEADER_LEN || f.read_exact(&mut head).is_err() { return Ok(None); }
+    match codec::FileHeader::decode(codec::FileKind::Mirror, &head) {
+        Some(h) if h.version == codec::FORMAT_VERSION => {}
+        _ => return Ok(None),
+    }
+    let rec_len = codec::record_len(dim) as u64;
+    let mut vectors = (len - codec::HEADER_LEN) / rec_len;
+    if !(len - codec::HEADER_LEN).is_multiple_of(rec_len) {
+        truncate_mirror(path, dim, vectors as usize, "partial record")?;
+    }
+    if vectors > 0 {
+        let mut rec = vec![0u8; rec_len as usize];
+        f.seek(SeekFrom::Start(codec::HEADER_LEN + (vectors - 1) * rec_len))?;
+        f.read_exact(&mut rec)?;
+        if !codec::decode_record_into(&rec, dim, &mut Vec::new()) {
+            vectors -= 1;
+            truncate_mirror(path, dim, vectors as usize, "corrupt last record")?;
+        }
+    }
+    Ok(Some(vectors as usize))
+}
+
+fn truncate_mirror(path: &Path, dim: usize, keep: usize, why: &str) -> Result<()> {
+    let f = OpenOptions::new().write(true).open(path)?;
+    let before = f.metadata()?.len();
+    let after = codec::HEADER_LEN + keep as u64 * codec::record_len(dim) as u64;
+    f.set_len(after)?;
+    f.sync_all()?;
+    tracing::warn!("recovery: {}: {why}, truncated {} -> {} bytes ({} vectors)", path.display(), before, after, keep);
+    Ok(())
+}
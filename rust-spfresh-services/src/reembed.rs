@@ -0,0 +1,108 @@
+use crate::{
+    audit::{Action, Actor},
+    blocking, codec, ApiError, AppState, Review, VecIndex,
+};
+use anyhow::Result;
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use reviews_types::ReembedResp;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::OpenOptions,
+    io::Write,
+    path::Path as FsPath,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Ids re-embedded, for replicas to overwrite them too; in the data dir, dropped by `compact`.
+pub const LOG_FILE: &str = "reembeds.log";
+
+/// An index and a review's new vector for it.
+pub type Target = (Arc<dyn VecIndex>, Vec<f32>);
+
+/// One line of the re-embed log.
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    id: usize,
+    ts_ms: u64,
+}
+
+/// POST /reviews/:id/reembed — embeds a review's text again and overwrites its vectors in
+/// place, under the same id, e.g. after a tokenizer fix, without a full reindex: the active
+/// index's (ANN index and mirror), the shadow's, and the title/body and late-interaction
+/// ones where configured. Searches see the new vectors at once. The id goes to the re-embed
+/// log for replicas to overwrite theirs as well. A TF-IDF embedder counts the text in its
+/// document frequencies again, until the next IDF refresh.
+pub async fn reembed(State(st): State<AppState>, actor: Actor, Path(id): Path<usize>) -> Result<Json<ReembedResp>, ApiError> {
+    blocking(move || {
+        let similarity = {
+            let _w = st.write_gate.lock();
+            if id >= st.meta.count()? || !st.meta.is_live(id) {
+                return Err(ApiError::not_found(format!("review {id} not found")));
+            }
+            let review = st.meta.read_review_by_line(id)?;
+            if id >= st.vindex().len()? {
+                return Err(ApiError::conflict(format!("review {id} has no vector in the active index yet")));
+            }
+            let (old, vec) = overwrite(&st, id, &review, None)?;
+            log(&st.data_dir, id)?;
+            old.map(|old| similarity(&old, &vec))
+        };
+        st.audit.record(&actor, Action::Reembed, [id].into_iter().collect(), None)?;
+        Ok(Json(ReembedResp { id, similarity }))
+    }).await
+}
+
+/// Overwrites review `id`'s vector in every index holding it: with `primary` in the active
+/// index, or one embedded from `review` when None, and with fresh embeddings in the others.
+/// Indexes that have not caught up to `id` yet are skipped. Callers hold the write gate.
+/// Returns the active index's old and new vectors.
+pub fn overwrite(st: &AppState, id: usize, review: &Review, primary: Option<Vec<f32>>) -> Result<(Option<Vec<f32>>, Vec<f32>)> {
+    let active = st.active.read().clone();
+    let text = review.embed_text();
+    let vec = match primary {
+        Some(v) => v,
+        None => active.embedder.embed_index(&text)?,
+    };
+    // Everything is embedded before anything is written, so a failed embedding leaves all
+    // of the review's vectors as they were.
+    let mut targets: Vec<Target> = vec![(active.vindex.clone(), vec.clone())];
+    if let Some(sh) = &st.shadow { targets.push((sh.vindex.clone(), sh.embedder.embed_index(&text)?)); }
+    if let Some(fields) = &st.fields { targets.extend(fields.embed(review)?); }
+    if let Some(late) = &st.late_interaction { targets.push(late.embed(review)?); }
+    let old = active.vindex.read_mirror(id)?;
+    for (index, v) in targets {
+        if id >= index.len()? { continue; }
+        index.overwrite(id, &v)?;
+        let mirror = index.mirror_path();
+        st.vcache.forget(mirror, id);
+        if let Some(sketches) = &st.sketches { sketches.resketch(mirror, id, &v); }
+    }
+    Ok((old, vec))
+}
+
+/// Appends `id` to the re-embed log, synced.
+fn log(data_dir: &FsPath, id: usize) -> Result<()> {
+    let ts_ms = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0);
+    let mut f = OpenOptions::new().create(true).append(true).open(data_dir.join(LOG_FILE))?;
+    f.write_all(&codec::encode_line(&serde_json::to_vec(&Entry { id, ts_ms })?))?;
+    f.sync_data()?;
+    Ok(())
+}
+
+/// Ids re-embedded after byte `offset` of the re-embed log, stopping before the first at or
+/// past `below` (not yet replicated). Returns them and the offset to resume from.
+pub fn read_from(data_dir: &FsPath, offset: u64, below: usize) -> Result<(Vec<usize>, u64)> {
+    let (entries, next) = codec::read_log_from(&data_dir.join(LOG_FILE), offset, below, |e: &Entry| e.id)?;
+    Ok((entries.into_iter().map(|e| e.id).collect(), next))
+}
+
+fn similarity(a: &[f32], b: &[f32]) -> f32 {
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let (na, nb) = (norm(a), norm(b));
+    if na == 0.0 || nb == 0.0 { return 0.0; }
+    a.iter().zip(b).map(|(x, y)| x * y).sum::<f32>() / (na * nb)
+}
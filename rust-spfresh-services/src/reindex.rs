@@ -0,0 +1,112 @@
+use crate::{
+    audit::{Action, Actor, IdRanges},
+    blocking, build_embedder, collections, config::{Config, EmbedderConfig}, jobs::JobHandle, payload_of, spfresh_index, Active,
+    ApiError, AppState, VecIndex,
+};
+use anyhow::Result;
+use axum::{extract::State, http::StatusCode, Json};
+use reviews_types::{ReindexReq, ReindexResp};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// Names the index sub-directory currently serving traffic. Absent means the data dir itself.
+const CURRENT_FILE: &str = "CURRENT";
+
+pub fn current_index_dir(data_dir: &Path) -> Result<PathBuf> {
+    match std::fs::read_to_string(data_dir.join(CURRENT_FILE)) {
+        Ok(s) if !s.trim().is_empty() => Ok(data_dir.join(s.trim())),
+        Ok(_) => Ok(data_dir.to_path_buf()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(data_dir.to_path_buf()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn set_current_index_dir(data_dir: &Path, name: &str) -> Result<()> {
+    let tmp = data_dir.join(format!("{CURRENT_FILE}.tmp"));
+    std::fs::write(&tmp, name)?;
+    std::fs::rename(&tmp, data_dir.join(CURRENT_FILE))?;
+    Ok(())
+}
+
+pub async fn start_reindex(State(st): State<AppState>, actor: Actor, req: Option<Json<ReindexReq>>) -> Result<(StatusCode, Json<ReindexResp>), ApiError> {
+    let (swap, model) = req.map_or((true, None), |Json(r)| (r.swap, r.embedder));
+    let emb_cfg = match model {
+        Some(name) => Some(st.embedders.get(&name).ok_or_else(|| ApiError::not_found(format!("unknown embedder '{name}'")))?),
+        None => None,
+    };
+    let gate_st = st.clone();
+    let total = blocking(move || { let _w = gate_st.write_gate.lock(); Ok(gate_st.meta.count()?) }).await?;
+    let job = st.jobs.try_start("reindex", total).ok_or_else(|| ApiError::conflict("a reindex job is already running"))?;
+    let job_id = job.id;
+    let collection = collection_name(job_id);
+    let subject = format!("job {job_id} -> {collection}, swap={swap}");
+    tokio::task::spawn_blocking(move || {
+        // The job does nothing until it is audited; a failed audit write fails the job.
+        let res = st.audit.record(&actor, Action::Reindex, IdRanges::default(), Some(subject))
+            .and_then(|()| run(&st, &job, total, swap, emb_cfg));
+        if let Err(e) = &res { tracing::error!("reindex {} failed: {e}", job.id); }
+        job.finish(&res);
+    });
+    Ok((StatusCode::ACCEPTED, Json(ReindexResp { job_id, collection })))
+}
+
+fn collection_name(job_id: uuid::Uuid) -> String { format!("index-{}", job_id.simple()) }
+
+/// Replays reviews.jsonl through a fresh embedder into `data/index-<job id>`, then swaps it in.
+/// Without a registered embedder named in the request, the embedder section of the config
+/// file is re-read, so a model change takes effect here.
+fn run(st: &AppState, job: &JobHandle, total: usize, swap: bool, emb_cfg: Option<EmbedderConfig>) -> Result<()> {
+    let emb_cfg = match emb_cfg {
+        Some(cfg) => cfg,
+        None => Config::load()?.embedder,
+    };
+    if emb_cfg != st.config.embedder {
+        tracing::info!("reindex {}: embedder {:?} -> {:?}", job.id, st.config.embedder, emb_cfg);
+    }
+    let name = collection_name(job.id);
+    let dir = st.data_dir.join(&name);
+    let vindex = spfresh_index::DefaultIndex::open(&dir, emb_cfg.dim(), &st.config.durability)?;
+    collections::record_embedder(&dir, &emb_cfg)?;
+    let model = st.embedders.bind(&name, &emb_cfg)?;
+    tracing::info!("reindex {}: building {} with embedder {}", job.id, name, model);
+    let embedder = build_embedder(&emb_cfg, &dir)?;
+    // Mirror writes are group-committed; waiting on the last one covers the whole range.
+    let replay = |range| -> Result<usize> {
+        let mut last = None;
+        let n = st.meta.for_each_in(range, |id, r| {
+            let v = match &r {
+                Some(r) => embedder.embed_index(&r.embed_text())?,
+                None => vec![0.0; vindex.dim()], // placeholder keeps ids aligned with the metadata
+            };
+            last = Some(vindex.append_pending(&v, r.as_ref().and_then(payload_of))?.1);
+            job.set_processed(id + 1);
+            Ok(())
+        })?;
+        if let Some(c) = last { c.wait()?; }
+        Ok(n)
+    };
+
+    let done = replay(0..total)?;
+    if !swap {
+        job.set_total(done);
+        tracing::info!("reindex {}: {} vectors -> {} (not swapped in)", job.id, done, dir.display());
+        return Ok(());
+    }
+    // Catch up on inserts that landed during the replay; holding the gate until the
+    // swap means no write can slip in between.
+    let _w = st.write_gate.lock();
+    let tail = replay(done..usize::MAX)?;
+    job.set_total(done + tail);
+    set_current_index_dir(&st.data_dir, &name)?;
+    let old = std::mem::replace(
+        &mut *st.active.write(),
+        Active { vindex: Arc::new(vindex), embedder },
+    );
+    tracing::info!(
+        "reindex {}: {} vectors -> {}, previous mirror {} left on disk",
+        job.id, done + tail, dir.display(), old.vindex.mirror_path().display()
+    );
+    Ok(())
+}
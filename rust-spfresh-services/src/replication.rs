@@ -0,0 +1,355 @@
+use crate::{blocking, codec, moderation, patches, purge, reembed, ApiError, AppState, Review};
+use axum::{
+    extract::{Query, Request, State},
+    http::Method,
+    middleware::Next,
+    response::Response,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{BufReader, Read, Seek, SeekFrom},
+};
+
+const DEFAULT_LIMIT: usize = 500;
+const MAX_LIMIT: usize = 5_000;
+
+#[derive(Deserialize)]
+pub struct LogParams {
+    /// Id of the first review wanted.
+    #[serde(default)]
+    from: usize,
+    /// Byte offset of review `from` in reviews.jsonl, as returned in `next_offset`; found
+    /// by scanning when absent.
+    offset: Option<u64>,
+    /// Byte offset into tombstones.log, as returned in `tombstones_next`.
+    #[serde(default)]
+    tombstones_from: u64,
+    /// Byte offset into moderation.log, as returned in `statuses_next`.
+    #[serde(default)]
+    statuses_from: u64,
+    /// Byte offset into redactions.log, as returned in `redactions_next`.
+    #[serde(default)]
+    redactions_from: u64,
+    /// Byte offset into patches.log, as returned in `patches_next`.
+    #[serde(default)]
+    patches_from: u64,
+    /// Byte offset into reembeds.log, as returned in `reembeds_next`.
+    #[serde(default)]
+    reembeds_from: u64,
+    limit: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct LogRecord {
+    pub id: usize,
+    pub review: Review,
+    /// None when the mirror record fails its checksum; the replica re-embeds.
+    pub vector: Option<Vec<f32>>,
+}
+
+/// A review re-embedded (POST /reviews/:id/reembed), with its active-index vector as it is
+/// now; None when the mirror record fails its checksum, and the replica re-embeds.
+#[derive(Serialize, Deserialize)]
+pub struct Reembed {
+    pub id: usize,
+    pub vector: Option<Vec<f32>>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct LogResp {
+    pub dim: usize,
+    /// Consecutive ids from `from`; an unreadable metadata line ends the page early and
+    /// shows up as a gap in the next one.
+    pub records: Vec<LogRecord>,
+    pub next_offset: u64,
+    /// Ids deleted, only up to the last id in `records` so a replica never sees a delete
+    /// of a review it does not have yet.
+    pub tombstones: Vec<usize>,
+    pub tombstones_next: u64,
+    /// Moderation entries (checks, approvals, rejections, status edits), bounded like
+    /// `tombstones`.
+    #[serde(default)]
+    pub statuses: Vec<moderation::Entry>,
+    #[serde(default)]
+    pub statuses_next: u64,
+    /// Ids a purge erased, bounded like `tombstones`.
+    #[serde(default)]
+    pub redactions: Vec<usize>,
+    #[serde(default)]
+    pub redactions_next: u64,
+    /// Edits of ratings and products (PATCH /reviews/:id), bounded like `tombstones`.
+    #[serde(default)]
+    pub patches: Vec<patches::Entry>,
+    #[serde(default)]
+    pub patches_next: u64,
+    /// Reviews re-embedded, bounded like `tombstones`.
+    #[serde(default)]
+    pub reembeds: Vec<Reembed>,
+    #[serde(default)]
+    pub reembeds_next: u64,
+}
+
+/// GET /replication/log?from=&offset=&limit=, plus a `<log>_from` offset per side log —
+/// the write log of this instance for a read replica to apply: metadata lines with their
+/// active-index vectors in id order, then deletes, moderation decisions, purges, edits and
+/// re-embeds.
+/// Metadata and mirror are append-only apart from purges, so they are the log.
+pub async fn log(State(st): State<AppState>, Query(p): Query<LogParams>) -> Result<Json<LogResp>, ApiError> {
+    let limit = p.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    blocking(move || Ok(Json(read_log(&st, &p, limit)?))).await
+}
+
+fn read_log(st: &AppState, p: &LogParams, limit: usize) -> anyhow::Result<LogResp> {
+    let vindex = st.vindex();
+    let dim = vindex.dim();
+    let n = st.meta.count()?.min(vindex.len()?);
+    let want = limit.min(n.saturating_sub(p.from));
+    let offset = match p.offset {
+        Some(o) => o,
+        None => offset_of(st, 0, 0, p.from)?,
+    };
+    let (rows, next_offset, read) = st.meta.read_page(offset, p.from, want)?;
+    let mut vectors = BufReader::new(File::open(vindex.mirror_path())?);
+    let rec_len = codec::record_len(dim);
+    vectors.seek(SeekFrom::Start(codec::HEADER_LEN + (p.from * rec_len) as u64))?;
+    let (mut rec, mut records) = (vec![0u8; rec_len], Vec::with_capacity(rows.len()));
+    let mut expect = p.from;
+    for (id, review) in rows {
+        if id != expect { break; }
+        vectors.read_exact(&mut rec)?;
+        let mut v = Vec::with_capacity(dim);
+        let vector = codec::decode_record_into(&rec, dim, &mut v).then_some(v);
+        records.push(LogRecord { id, review, vector });
+        expect += 1;
+    }
+    anyhow::ensure!(!records.is_empty() || read == 0, "metadata line {} is unreadable; replicas cannot pass it", p.from);
+    // Stop at a gap: the replica must not skip past an id it could not copy.
+    let next_offset = if records.len() == read { next_offset } else { offset_of(st, offset, p.from, records.len())? };
+    let (tombstones, tombstones_next) = st.meta.tombstones.read_from(p.tombstones_from, expect)?;
+    let (statuses, statuses_next) = st.meta.moderation.read_from(p.statuses_from, expect)?;
+    let (redactions, redactions_next) = purge::read_from(&st.data_dir, p.redactions_from, expect)?;
+    let (patches, patches_next) = st.meta.patches.read_from(p.patches_from, expect)?;
+    let (ids, reembeds_next) = reembed::read_from(&st.data_dir, p.reembeds_from, expect)?;
+    let reembeds = ids.into_iter()
+        .map(|id| Ok(Reembed { id, vector: vindex.read_mirror(id)? }))
+        .collect::<anyhow::Result<_>>()?;
+    Ok(LogResp {
+        dim, records, next_offset, tombstones, tombstones_next, statuses, statuses_next, redactions, redactions_next, patches,
+        patches_next, reembeds, reembeds_next,
+    })
+}
+
+/// POSTs a read replica still answers; they read the index and write nothing.
+const READ_POSTS: &[&str] = &["/v1/search", "/v1/search/stream", "/v1/search/range", "/v1/eval", "/v1/graphql", "/v1/reviews/dry-run", "/v1/reviews/batch"];
+
+/// Replica mode: refuses every write with 403 naming the leader, since anything written
+/// here would be missing from the leader and shift the ids of what it sends next.
+pub async fn read_only(State(st): State<AppState>, req: Request, next: Next) -> Result<Response, ApiError> {
+    if !is_read(&req) {
+        let leader = st.config.replica.as_ref().map(|r| r.leader.as_str()).unwrap_or_default();
+        return Err(ApiError::forbidden(format!("read replica; send writes to the leader {leader}")));
+    }
+    Ok(next.run(req).await)
+}
+
+/// Whether `req` only reads: a GET, or a POST that searches.
+pub fn is_read(req: &Request) -> bool {
+    matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS)
+        || (req.method() == Method::POST && READ_POSTS.contains(&req.uri().path()))
+}
+
+/// Byte offset just past `count` lines starting at `offset`.
+fn offset_of(st: &AppState, offset: u64, first_id: usize, count: usize) -> anyhow::Result<u64> {
+    Ok(st.meta.read_page(offset, first_id, count)?.1)
+}
+
+#[cfg(feature = "replica")]
+pub use follower::follow;
+
+#[cfg(feature = "replica")]
+mod follower {
+    use super::LogResp;
+    use crate::{blocking, config::ReplicaConfig, payload_of, purge, reembed, AppState};
+    use anyhow::{Context, Result};
+    use parking_lot::Mutex;
+    use serde::{Deserialize, Serialize};
+    use std::{path::Path, sync::Arc, time::Duration};
+
+    /// Where the replica is in its leader's log; `data/replica.json`.
+    #[derive(Serialize, Deserialize, Default)]
+    struct Cursor {
+        leader: String,
+        next_id: usize,
+        /// Leader byte offset of `next_id`; unknown after a crash between applying a page
+        /// and saving the cursor, in which case the leader looks it up.
+        offset: Option<u64>,
+        tombstones_offset: u64,
+        #[serde(default)]
+        statuses_offset: u64,
+        #[serde(default)]
+        redactions_offset: u64,
+        #[serde(default)]
+        patches_offset: u64,
+        #[serde(default)]
+        reembeds_offset: u64,
+    }
+
+    impl Cursor {
+        fn load(data_dir: &Path, leader: &str, have: usize) -> Result<Self> {
+            let path = data_dir.join("replica.json");
+            let mut c = match std::fs::read(&path) {
+                Ok(bytes) => serde_json::from_slice::<Cursor>(&bytes).with_context(|| format!("parse {}", path.display()))?,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    anyhow::ensure!(have == 0, "{} is missing but the data dir holds {have} reviews; a replica starts empty", path.display());
+                    Cursor { leader: leader.to_string(), offset: Some(0), ..Default::default() }
+                }
+                Err(e) => return Err(e.into()),
+            };
+            anyhow::ensure!(c.leader == leader, "{} follows {}, not {leader}", path.display(), c.leader);
+            if c.next_id != have {
+                tracing::warn!("replica: cursor at id {} but {have} reviews are stored; resuming from {have}", c.next_id);
+                (c.next_id, c.offset) = (have, None);
+            }
+            Ok(c)
+        }
+
+        fn save(&self, data_dir: &Path) -> Result<()> {
+            let tmp = data_dir.join("replica.json.tmp");
+            std::fs::write(&tmp, serde_json::to_vec_pretty(self)?)?;
+            std::fs::rename(&tmp, data_dir.join("replica.json"))?;
+            Ok(())
+        }
+    }
+
+    /// Applies the leader's log forever: polls, appends what is new under the write gate
+    /// (vectors copied, not re-embedded), applies deletes, moderation decisions, edits,
+    /// re-embeds and purges, then saves the cursor. Polls back-to-back while there is a
+    /// backlog, every `poll_ms` once caught up. Errors are logged and retried on the next
+    /// poll.
+    pub async fn follow(st: AppState, cfg: ReplicaConfig) {
+        let leader = cfg.leader.trim_end_matches('/').to_string();
+        let agent = ureq::AgentBuilder::new().timeout(Duration::from_millis(cfg.timeout_ms)).build();
+        let (st2, leader2) = (st.clone(), leader.clone());
+        let cursor = match blocking(move || Ok(Cursor::load(&st2.data_dir, &leader2, st2.meta.count()?)?)).await {
+            Ok(c) => Arc::new(Mutex::new(c)),
+            Err(e) => { tracing::error!("replica: {}; not following {leader}", e.msg); return; }
+        };
+        tracing::info!("replica: following {leader} from id {}", cursor.lock().next_id);
+        loop {
+            let (st, agent, url, cursor, limit) = (st.clone(), agent.clone(), leader.clone(), cursor.clone(), cfg.batch);
+            let res = tokio::task::spawn_blocking(move || pull(&st, &agent, &url, limit, &mut cursor.lock())).await;
+            match res {
+                Ok(Ok(n)) if n >= limit => continue,
+                Ok(Ok(_)) => {}
+                Ok(Err(e)) => tracing::warn!("replica: {e:#}"),
+                Err(e) => tracing::error!("replica task failed: {e}"),
+            }
+            tokio::time::sleep(Duration::from_millis(cfg.poll_ms)).await;
+        }
+    }
+
+    /// One page of the leader's log; returns how many reviews were added.
+    fn pull(st: &AppState, agent: &ureq::Agent, leader: &str, limit: usize, c: &mut Cursor) -> Result<usize> {
+        let mut req = agent.get(&format!("{leader}/v1/replication/log"))
+            .query("from", &c.next_id.to_string())
+            .query("tombstones_from", &c.tombstones_offset.to_string())
+            .query("statuses_from", &c.statuses_offset.to_string())
+            .query("redactions_from", &c.redactions_offset.to_string())
+            .query("patches_from", &c.patches_offset.to_string())
+            .query("reembeds_from", &c.reembeds_offset.to_string())
+            .query("limit", &limit.to_string());
+        if let Some(offset) = c.offset { req = req.query("offset", &offset.to_string()); }
+        apply(st, req.call()?.into_json()?, c)
+    }
+
+    /// Applies one page of the leader's log and saves the cursor past it; returns how many
+    /// reviews were added.
+    fn apply(st: &AppState, resp: LogResp, c: &mut Cursor) -> Result<usize> {
+        let (embedder, vindex) = (st.embedder(), st.vindex());
+        anyhow::ensure!(resp.dim == vindex.dim(), "leader vectors have dim {}, the local index {}", resp.dim, vindex.dim());
+        let added = resp.records.len();
+        {
+            let _w = st.write_gate.lock();
+            let mut last = None;
+            for rec in resp.records {
+                anyhow::ensure!(rec.id == c.next_id, "leader sent id {} where {} was expected", rec.id, c.next_id);
+                let vector = match rec.vector {
+                    Some(v) => v,
+                    None => embedder.embed_index(&rec.review.embed_text())?,
+                };
+                let (_, commit) = vindex.append_pending(&vector, payload_of(&rec.review))?;
+                st.meta.append_verbatim(&rec.review)?;
+                st.append_secondary(&rec.review);
+                last = Some(commit);
+                c.next_id += 1;
+            }
+            if let Some(commit) = last { commit.wait()?; }
+            for id in resp.tombstones {
+                if !st.meta.is_live(id) { continue; }
+                let review = st.meta.read_review_by_line(id)?;
+                st.meta.delete(id, &review)?;
+            }
+            for entry in resp.statuses {
+                let (id, status) = st.meta.moderation.replay(entry)?;
+                if let Some(s) = status { st.meta.set_status(id, s); }
+            }
+            for e in resp.patches {
+                st.meta.patch(e.id, e.version, e.review_rating, e.product_id)?;
+            }
+            // The active-index vector is the leader's; shadow, field and late interaction
+            // vectors, where this replica keeps them, are embedded here.
+            for r in resp.reembeds {
+                let review = st.meta.read_review_by_line(r.id)?;
+                reembed::overwrite(st, r.id, &review, r.vector)?;
+            }
+            if !resp.redactions.is_empty() {
+                let (lines, vectors) = purge::erase(st, &resp.redactions.into_iter().collect())?;
+                tracing::info!("replica: purge applied, {lines} metadata lines blanked, {vectors} vectors zeroed");
+            }
+        }
+        c.offset = Some(resp.next_offset);
+        c.tombstones_offset = resp.tombstones_next;
+        c.statuses_offset = resp.statuses_next;
+        c.redactions_offset = resp.redactions_next;
+        c.patches_offset = resp.patches_next;
+        c.reembeds_offset = resp.reembeds_next;
+        c.save(&st.data_dir)?;
+        Ok(added)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::{config::{Config, FieldsConfig}, open_state, replication::LogRecord, startup};
+
+        #[test]
+        fn pulled_records_reach_the_field_indexes() {
+            let dir = std::env::temp_dir().join(format!("service-replica-fields-{}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            let fields = FieldsConfig { dir: "fields".into(), scoring: Default::default() };
+            let config = Config { data_dir: dir.clone(), fields: Some(fields), ..Config::default() };
+            let st = open_state(config, &dir, &startup::Progress::default()).unwrap();
+            let review: crate::Review = serde_json::from_value(serde_json::json!({
+                "review_title": "charger", "review_body": "stopped working", "product_id": "p1", "review_rating": 1,
+            })).unwrap();
+            let vector = st.embedder().embed_index(&review.embed_text()).unwrap();
+            let resp: LogResp = serde_json::from_value(serde_json::json!({
+                "dim": vector.len(), "records": [LogRecord { id: 0, review, vector: Some(vector) }],
+                "next_offset": 0, "tombstones": [], "tombstones_next": 0,
+            })).unwrap();
+            let mut c = Cursor::default();
+            assert_eq!(apply(&st, resp, &mut c).unwrap(), 1);
+            // Field appends are queued, not waited on.
+            let fields = st.fields.as_ref().unwrap();
+            let grown = || fields.indexes().iter().all(|index| index.len().unwrap() == 1);
+            for _ in 0..100 {
+                if grown() { break; }
+                std::thread::sleep(Duration::from_millis(20));
+            }
+            assert!(grown());
+            let _ = std::fs::remove_dir_all(&dir);
+        }
+    }
+}
@@ -0,0 +1,220 @@
+/// Ranking formula supplied with a search, e.g. `cosine * 0.8 + rating / 5 * 0.2`.
+/// Grammar: numbers, the variables in `Var`, `+ - * /`, unary minus, parentheses and
+/// `min(a, b)` / `max(a, b)`. An optional leading `score =` is accepted. Parsed once per
+/// request and evaluated for every scored candidate.
+#[derive(Debug, Clone)]
+pub enum Expr {
+    Num(f64),
+    Var(Var),
+    Neg(Box<Expr>),
+    Bin(Op, Box<Expr>, Box<Expr>),
+    Call(Func, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Var {
+    /// Vector similarity of the candidate.
+    Cosine,
+    /// `review_rating`.
+    Rating,
+    /// Days since `created_at`; 0 for reviews without one.
+    AgeDays,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Op { Add, Sub, Mul, Div }
+
+#[derive(Debug, Clone, Copy)]
+pub enum Func { Min, Max }
+
+/// Values of the variables for one candidate.
+pub struct Vars { pub cosine: f64, pub rating: f64, pub age_days: f64 }
+
+/// Longest expression accepted, to keep evaluation per candidate cheap.
+const MAX_LEN: usize = 256;
+
+impl Expr {
+    pub fn parse(src: &str) -> Result<Self, String> {
+        if src.len() > MAX_LEN { return Err(format!("score expression longer than {MAX_LEN} characters")); }
+        let src = src.trim();
+        let src = src.strip_prefix("score").map(str::trim_start).and_then(|s| s.strip_prefix('=')).unwrap_or(src);
+        let mut p = Parser { toks: tokenize(src)?, pos: 0 };
+        let e = p.sum()?;
+        match p.toks.get(p.pos) {
+            None => Ok(e),
+            Some(t) => Err(format!("unexpected {t:?} in score expression")),
+        }
+    }
+
+    /// The value ranked on: `eval` as f32, with anything that is not a finite number
+    /// (division by zero, overflow, `inf - inf`) ranking last instead of serializing as null.
+    pub fn score(&self, v: &Vars) -> f32 {
+        let x = self.eval(v) as f32;
+        if x.is_finite() { x } else { f32::MIN }
+    }
+
+    pub fn eval(&self, v: &Vars) -> f64 {
+        match self {
+            Expr::Num(n) => *n,
+            Expr::Var(Var::Cosine) => v.cosine,
+            Expr::Var(Var::Rating) => v.rating,
+            Expr::Var(Var::AgeDays) => v.age_days,
+            Expr::Neg(e) => -e.eval(v),
+            Expr::Bin(op, a, b) => {
+                let (a, b) = (a.eval(v), b.eval(v));
+                match op {
+                    Op::Add => a + b,
+                    Op::Sub => a - b,
+                    Op::Mul => a * b,
+                    // Division by zero ranks the candidate last (see `score`) rather than producing NaN.
+                    Op::Div => if b == 0.0 { f64::NEG_INFINITY } else { a / b },
+                }
+            }
+            Expr::Call(Func::Min, a, b) => a.eval(v).min(b.eval(v)),
+            Expr::Call(Func::Max, a, b) => a.eval(v).max(b.eval(v)),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Tok { Num(f64), Ident(String), Sym(char) }
+
+fn tokenize(src: &str) -> Result<Vec<Tok>, String> {
+    let mut out = Vec::new();
+    let mut chars = src.char_indices().peekable();
+    while let Some(&(i, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() || c == '.' {
+            let mut end = i;
+            while let Some(&(j, d)) = chars.peek() {
+                if !(d.is_ascii_digit() || d == '.') { break; }
+                end = j + d.len_utf8();
+                chars.next();
+            }
+            out.push(Tok::Num(src[i..end].parse().map_err(|_| format!("bad number '{}'", &src[i..end]))?));
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let mut end = i;
+            while let Some(&(j, d)) = chars.peek() {
+                if !(d.is_ascii_alphanumeric() || d == '_') { break; }
+                end = j + d.len_utf8();
+                chars.next();
+            }
+            out.push(Tok::Ident(src[i..end].to_string()));
+        } else if "+-*/(),".contains(c) {
+            out.push(Tok::Sym(c));
+            chars.next();
+        } else {
+            return Err(format!("unexpected '{c}' in score expression"));
+        }
+    }
+    Ok(out)
+}
+
+struct Parser { toks: Vec<Tok>, pos: usize }
+
+impl Parser {
+    fn eat(&mut self, c: char) -> bool {
+        let hit = self.toks.get(self.pos) == Some(&Tok::Sym(c));
+        if hit { self.pos += 1; }
+        hit
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        if self.eat(c) { Ok(()) } else { Err(format!("expected '{c}' in score expression")) }
+    }
+
+    fn sum(&mut self) -> Result<Expr, String> {
+        let mut e = self.product()?;
+        loop {
+            let op = if self.eat('+') { Op::Add } else if self.eat('-') { Op::Sub } else { return Ok(e) };
+            e = Expr::Bin(op, Box::new(e), Box::new(self.product()?));
+        }
+    }
+
+    fn product(&mut self) -> Result<Expr, String> {
+        let mut e = self.unary()?;
+        loop {
+            let op = if self.eat('*') { Op::Mul } else if self.eat('/') { Op::Div } else { return Ok(e) };
+            e = Expr::Bin(op, Box::new(e), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.eat('-') { return Ok(Expr::Neg(Box::new(self.unary()?))); }
+        self.atom()
+    }
+
+    fn atom(&mut self) -> Result<Expr, String> {
+        let tok = self.toks.get(self.pos).ok_or("score expression ends early")?;
+        self.pos += 1;
+        match tok {
+            Tok::Num(n) => Ok(Expr::Num(*n)),
+            Tok::Sym('(') => {
+                let e = self.sum()?;
+                self.expect(')')?;
+                Ok(e)
+            }
+            Tok::Ident(name) => match name.as_str() {
+                "cosine" => Ok(Expr::Var(Var::Cosine)),
+                "rating" => Ok(Expr::Var(Var::Rating)),
+                "age_days" => Ok(Expr::Var(Var::AgeDays)),
+                "min" | "max" => {
+                    let f = if name == "min" { Func::Min } else { Func::Max };
+                    self.expect('(')?;
+                    let a = self.sum()?;
+                    self.expect(',')?;
+                    let b = self.sum()?;
+                    self.expect(')')?;
+                    Ok(Expr::Call(f, Box::new(a), Box::new(b)))
+                }
+                other => Err(format!("unknown name '{other}' in score expression (allowed: cosine, rating, age_days, min, max)")),
+            },
+            Tok::Sym(c) => Err(format!("unexpected '{c}' in score expression")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const V: Vars = Vars { cosine: 0.5, rating: 4.0, age_days: 10.0 };
+
+    fn eval(src: &str) -> f64 { Expr::parse(src).unwrap().eval(&V) }
+
+    #[test]
+    fn precedence_and_associativity() {
+        assert_eq!(eval("1 + 2 * 3"), 7.0);
+        assert_eq!(eval("(1 + 2) * 3"), 9.0);
+        assert_eq!(eval("8 - 2 - 1"), 5.0);
+        assert_eq!(eval("8 / 4 / 2"), 1.0);
+        assert_eq!(eval("-2 * -3"), 6.0);
+        assert_eq!(eval("--1"), 1.0);
+    }
+
+    #[test]
+    fn variables_and_calls() {
+        assert_eq!(eval("cosine * 0.8 + rating / 5 * 0.2"), 0.5 * 0.8 + 4.0 / 5.0 * 0.2);
+        assert_eq!(eval("score = min(age_days, 3) + max(rating, cosine)"), 7.0);
+        assert_eq!(eval("  score=cosine"), 0.5);
+    }
+
+    #[test]
+    fn parse_errors() {
+        for bad in ["", "1 +", "(1", "1)", "min(1)", "min(1, 2", "foo", "cosine ^ 2", "1..2", "score", "1 2"] {
+            assert!(Expr::parse(bad).is_err(), "{bad:?} should not parse");
+        }
+        assert!(Expr::parse(&"1+".repeat(200)).is_err(), "too long");
+    }
+
+    #[test]
+    fn non_finite_ranks_last() {
+        for src in ["cosine / 0", "-(1 / 0)", "0 / 0", "1 / 0 + -(1 / 0)"] {
+            assert_eq!(Expr::parse(src).unwrap().score(&V), f32::MIN, "{src}");
+        }
+        assert_eq!(Expr::parse("cosine / 0").unwrap().eval(&V), f64::NEG_INFINITY);
+        assert_eq!(Expr::parse("rating / 2").unwrap().score(&V), 2.0);
+        assert_eq!(Expr::parse("1000000 * 1000000 * 1000000 * 1000000 * 1000000 * 1000000 * 1000000").unwrap().score(&V), f32::MIN);
+    }
+}
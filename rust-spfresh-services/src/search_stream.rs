@@ -0,0 +1,79 @@
+use crate::{blocking, rank_in, Ranked, slow_log::SlowQuery, Active, ApiError, AppState, RankOpts, SearchHit, SearchReq, SearchStats};
+use axum::{
+    body::Body,
+    extract::State,
+    http::{header, HeaderMap},
+    response::{sse::Event, IntoResponse, Response, Sse},
+    Json,
+};
+use futures_util::{stream::{self, Stream}, StreamExt};
+use std::{convert::Infallible, time::Instant};
+
+/// POST /search/stream — same request as /search, but hits are written one per line
+/// (NDJSON) as they are read from the MetaStore instead of being collected first.
+/// Clients sending `Accept: text/event-stream` get one SSE `hit` event per result instead.
+pub async fn search_stream(State(st): State<AppState>, headers: HeaderMap, Json(req): Json<SearchReq>) -> Result<Response, ApiError> {
+    let k = st.config.search.stream_top_k(req.top_k);
+    let mut opts = RankOpts::from_req(&req, k)?;
+    opts.attach(&st, &req)?;
+    let started = Instant::now();
+    let (target_st, collection, embedder) = (st.clone(), req.collection.clone(), req.embedder.clone());
+    let (active, opts) = blocking(move || {
+        let active = target_st.target_for(collection.as_deref(), embedder.as_deref())?;
+        opts.check_examples(&target_st.meta, &active)?;
+        Ok((active, opts))
+    }).await?;
+    let hits = materialize(st, active, req, opts, started);
+    let wants_sse = headers
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.contains("text/event-stream"));
+
+    if wants_sse {
+        let events = hits.map(|hit| {
+            Ok::<_, Infallible>(Event::default().event("hit").json_data(hit).unwrap_or_default())
+        });
+        Ok(Sse::new(events).into_response())
+    } else {
+        let lines = hits.map(|hit| {
+            let mut line = serde_json::to_vec(&hit).unwrap_or_default();
+            line.push(b'\n');
+            Ok::<_, Infallible>(line)
+        });
+        Ok(([(header::CONTENT_TYPE, "application/x-ndjson")], Body::from_stream(lines)).into_response())
+    }
+}
+
+/// Ranks on a blocking thread, then feeds hits through a small channel as each review
+/// is read, so at most a handful of materialized hits are held in memory at once.
+/// The slow-query log entry is written once the last hit has been handed to the client.
+fn materialize(st: AppState, active: Active, req: SearchReq, opts: RankOpts, started: Instant) -> impl Stream<Item = SearchHit> {
+    let (tx, rx) = tokio::sync::mpsc::channel::<SearchHit>(16);
+    tokio::task::spawn_blocking(move || {
+        let mut stats = SearchStats::default();
+        let ranked = rank_in(&st.meta, &st.vcache, &active, &opts, &mut stats);
+        let fetch = Instant::now();
+        let mut sent = 0;
+        for Ranked { id, score, collapsed } in ranked {
+            match st.meta.read_review_by_line(id) {
+                Ok(review) => {
+                    if tx.blocking_send(SearchHit { id, score, review, explain: None, score_parts: None, collapsed, shard: None }).is_err() { break; }
+                    sent += 1;
+                }
+                Err(e) => tracing::warn!("meta read id={} failed: {e}", id),
+            }
+        }
+        stats.fetch_ms = fetch.elapsed().as_secs_f64() * 1e3;
+        let elapsed = started.elapsed();
+        stats.total_ms = elapsed.as_secs_f64() * 1e3;
+        st.slow_log.record(elapsed, SlowQuery {
+            endpoint: "/search/stream",
+            query: &opts.text(),
+            collection: req.collection.as_deref(),
+            top_k: opts.k,
+            hits: sent,
+            stages: &stats,
+        });
+    });
+    stream::unfold(rx, |mut rx| async move { rx.recv().await.map(|hit| (hit, rx)) })
+}
@@ -0,0 +1,80 @@
+use crate::lang;
+
+/// Words that make a sentence about a product sound good or bad. Kept small and about
+/// products; a sentence with none of them is judged by its review's stars instead.
+const POSITIVE: &[&str] = &[
+    "accurate", "affordable", "amazing", "awesome", "beautiful", "best", "bright", "clean", "clear", "comfortable",
+    "delicious", "durable", "easy", "excellent", "fantastic", "fast", "favorite", "fresh", "friendly", "good",
+    "great", "happy", "helpful", "impressed", "impressive", "lightweight", "love", "loved", "loves", "nice",
+    "perfect", "perfectly", "pleasant", "quick", "quiet", "recommend", "recommended", "reliable", "responsive",
+    "satisfied", "smooth", "solid", "sturdy", "superb", "tasty", "wonderful", "works", "worth",
+];
+const NEGATIVE: &[&str] = &[
+    "annoying", "awful", "bad", "bland", "break", "breaks", "broke", "broken", "cracked", "damaged", "dead",
+    "defective", "died", "dim", "dirty", "disappointed", "disappointing", "disappointment", "expensive", "fail", "failed",
+    "fails", "failure", "faulty", "flimsy", "garbage", "hate", "hated", "horrible", "issue", "issues", "junk",
+    "late", "leak", "leaking", "leaks", "mediocre", "missing", "noisy", "overheating", "overheats", "overpriced",
+    "poor", "problem", "problems", "refund", "returned", "rude", "scratched", "slow", "smells", "stale", "stopped",
+    "terrible", "uncomfortable", "unreliable", "useless", "waste", "weak", "worst", "wrong",
+];
+/// Flip the sentiment words in the `NEGATION_SPAN` words after them ("not good", "don't love").
+const NEGATORS: &[&str] = &[
+    "aren", "cannot", "didn", "doesn", "don", "hardly", "isn", "never", "no", "not", "wasn", "weren", "without", "won",
+];
+const NEGATION_SPAN: usize = 3;
+/// How far a sentiment must lean either way to count as positive or negative.
+pub const MIN_LEAN: f64 = 0.2;
+/// Thai is written without spaces, so these are found as substrings; none contains
+/// another, so nothing is counted twice.
+const THAI_POSITIVE: &[&str] = &["ดี", "ชอบ", "ประทับใจ", "คุ้ม", "สวย", "เร็ว", "อร่อย", "แนะนำ", "ถูกใจ", "พอใจ", "ทนทาน", "สะดวก", "สบาย"];
+const THAI_NEGATIVE: &[&str] = &["แย่", "เสีย", "พัง", "ช้า", "ผิดหวัง", "ห่วย", "แพง", "ชำรุด", "ปัญหา", "คืนเงิน", "แตก"];
+/// Directly before a Thai sentiment word, flips it ("ไม่ดี").
+const THAI_NEGATOR: &str = "ไม่";
+
+/// The sentiment of `text`, from -1 (all negative) to 1 (all positive), by its sentiment
+/// words; None when it has none.
+pub fn polarity(text: &str) -> Option<f64> {
+    let (pos, neg) = if lang::detect(text) == lang::THAI { thai_counts(text) } else { counts(text) };
+    (pos + neg > 0).then(|| (pos as f64 - neg as f64) / (pos + neg) as f64)
+}
+
+/// What a review's stars say about its sentences without sentiment words, more softly
+/// than the words would: 5 stars is 0.5, 3 is 0, 1 is -0.5. 0 for ratings outside 1-5.
+pub fn from_rating(rating: i32) -> f64 {
+    if !(1..=5).contains(&rating) { return 0.0; }
+    (rating - 3) as f64 / 4.0
+}
+
+/// Whether `term` (a token of `lang::terms`) is one of the sentiment words or negators.
+pub fn is_sentiment_word(term: &str) -> bool {
+    POSITIVE.contains(&term) || NEGATIVE.contains(&term) || NEGATORS.contains(&term)
+}
+
+fn counts(text: &str) -> (usize, usize) {
+    let (mut pos, mut neg, mut since_negator) = (0, 0, usize::MAX);
+    for term in lang::terms(text) {
+        let negated = since_negator < NEGATION_SPAN;
+        since_negator = since_negator.saturating_add(1);
+        let polarity = match term.as_str() {
+            t if NEGATORS.contains(&t) => { since_negator = 0; continue; }
+            t if POSITIVE.contains(&t) => 1,
+            t if NEGATIVE.contains(&t) => -1,
+            _ => continue,
+        };
+        if (polarity > 0) != negated { pos += 1 } else { neg += 1 }
+    }
+    (pos, neg)
+}
+
+fn thai_counts(text: &str) -> (usize, usize) {
+    let (mut pos, mut neg) = (0, 0);
+    for (words, positive) in [(THAI_POSITIVE, true), (THAI_NEGATIVE, false)] {
+        for word in words {
+            for (i, _) in text.match_indices(word) {
+                let negated = text[..i].trim_end().ends_with(THAI_NEGATOR);
+                if positive != negated { pos += 1 } else { neg += 1 }
+            }
+        }
+    }
+    (pos, neg)
+}
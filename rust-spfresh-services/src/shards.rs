@@ -0,0 +1,79 @@
+use crate::{config::ShardsConfig, ApiError, SearchReq, SearchResp, SearchStats};
+use axum::http::StatusCode;
+use std::time::{Duration, Instant};
+
+/// Router mode: /search is answered by fanning the request out to every shard instance
+/// in parallel and merging their hits by score. Shards are ordinary instances of this
+/// service, each holding part of the corpus; ids are only unique per shard, so every hit
+/// is tagged with the shard it came from. Runs on the blocking pool with a blocking
+/// client, like the remote embedder.
+pub struct ShardRouter {
+    agent: ureq::Agent,
+    urls: Vec<String>,
+    allow_partial: bool,
+}
+
+impl ShardRouter {
+    pub fn new(cfg: &ShardsConfig) -> anyhow::Result<Self> {
+        anyhow::ensure!(!cfg.urls.is_empty(), "[shards] needs at least one url");
+        let agent = ureq::AgentBuilder::new().timeout(Duration::from_millis(cfg.timeout_ms)).build();
+        let urls = cfg.urls.iter().map(|u| u.trim_end_matches('/').to_string()).collect();
+        Ok(Self { agent, urls, allow_partial: cfg.allow_partial })
+    }
+
+    /// Top `k` hits over all shards. A shard that fails or times out is listed in
+    /// `failed_shards` when partial results are allowed, and fails the search otherwise.
+    /// Per-shard options (group_by, candidates, score, ...) are applied by each shard; the
+    /// merge only orders and truncates.
+    pub fn search(&self, mut req: SearchReq, k: usize) -> Result<SearchResp, ApiError> {
+        if !req.positive_ids.is_empty() {
+            return Err(ApiError::bad_request("positive_ids cannot be used in router mode: ids are per shard"));
+        }
+        let started = Instant::now();
+        req.top_k = Some(k);
+        // `compare` would return shadow hits that are not merged.
+        req.compare = false;
+        let results: Vec<anyhow::Result<SearchResp>> = std::thread::scope(|s| {
+            let calls: Vec<_> = self.urls.iter().map(|url| s.spawn(|| self.call(url, &req))).collect();
+            calls.into_iter().map(|c| c.join().unwrap_or_else(|_| Err(anyhow::anyhow!("shard call panicked")))).collect()
+        });
+        let (mut hits, mut failed_shards, mut stats) = (Vec::new(), Vec::new(), SearchStats::default());
+        for (url, res) in self.urls.iter().zip(results) {
+            match res {
+                Ok(resp) => {
+                    if let Some(s) = resp.stats { merge_stats(&mut stats, &s); }
+                    hits.extend(resp.hits.into_iter().map(|mut h| { h.shard = Some(url.clone()); h }));
+                }
+                Err(e) => {
+                    tracing::warn!("shard {url}: {e}");
+                    if !self.allow_partial {
+                        return Err(ApiError::new(StatusCode::BAD_GATEWAY, format!("shard {url}: {e}")));
+                    }
+                    failed_shards.push(url.clone());
+                }
+            }
+        }
+        if failed_shards.len() == self.urls.len() {
+            return Err(ApiError::new(StatusCode::BAD_GATEWAY, "no shard answered"));
+        }
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+        hits.truncate(k);
+        stats.total_ms = started.elapsed().as_secs_f64() * 1e3;
+        Ok(SearchResp { hits, shadow_hits: None, failed_shards, stats: Some(stats) })
+    }
+
+    fn call(&self, url: &str, req: &SearchReq) -> anyhow::Result<SearchResp> {
+        let resp = self.agent.post(&format!("{url}/v1/search")).send_json(req)?;
+        Ok(resp.into_json()?)
+    }
+}
+
+/// Shards run in parallel: their counts add up, the slowest one sets each stage's time.
+fn merge_stats(into: &mut SearchStats, s: &SearchStats) {
+    into.candidates += s.candidates;
+    into.matched += s.matched;
+    into.embed_ms = into.embed_ms.max(s.embed_ms);
+    into.retrieve_ms = into.retrieve_ms.max(s.retrieve_ms);
+    into.score_ms = into.score_ms.max(s.score_ms);
+    into.fetch_ms = into.fetch_ms.max(s.fetch_ms);
+}
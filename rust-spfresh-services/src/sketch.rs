@@ -0,0 +1,151 @@
+use crate::{codec, config::SketchConfig, startup, synth::Rng, vcache::VectorCache};
+use anyhow::Result;
+use parking_lot::{Mutex, RwLock};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// Fixed so a mirror's sketches come out the same on every start.
+const SEED: u64 = 0x5EED_5EED;
+/// Records sketched between startup progress reports.
+const WARM_CHUNK: usize = 10_000;
+
+/// Binary sketches of the vectors in each mirror searched: the signs of `bits` random
+/// projections (SimHash), so the Hamming distance of two sketches tracks the angle between
+/// their vectors. A search ranks every vector by sketch first, a few popcounts each, and
+/// scores only the closest `candidates` exactly. Held in memory and derived from the
+/// mirrors, which are only appended to, so a mirror's sketches just catch up with its
+/// new records before each search; a re-embedded record is sketched again. Nothing is
+/// written to disk.
+pub struct Sketches {
+    bits: usize,
+    candidates: usize,
+    mirrors: Mutex<HashMap<PathBuf, Arc<RwLock<MirrorSketches>>>>,
+}
+
+struct MirrorSketches {
+    dim: usize,
+    /// For each dimension, one bit per projection: set when the projection weighs it -1
+    /// rather than +1.
+    planes: Vec<u64>,
+    /// `bits / 64` words per record; all zero for a record failing its checksum.
+    words: Vec<u64>,
+    count: usize,
+}
+
+impl MirrorSketches {
+    fn new(dim: usize, words_per: usize) -> Self {
+        let mut rng = Rng::new(SEED ^ dim as u64);
+        Self { dim, planes: (0..dim * words_per).map(|_| rng.next()).collect(), words: Vec::new(), count: 0 }
+    }
+
+    fn sketch_into(&self, v: &[f32], out: &mut [u64]) {
+        let mut acc = vec![0f32; out.len() * 64];
+        // Sparse vectors (TF-IDF) only pay for their non-zero dimensions.
+        for (i, &x) in v.iter().enumerate().filter(|(_, x)| **x != 0.0) {
+            for (k, &signs) in self.planes[i * out.len()..(i + 1) * out.len()].iter().enumerate() {
+                for (b, a) in acc[k * 64..(k + 1) * 64].iter_mut().enumerate() {
+                    *a += if signs >> b & 1 == 1 { -x } else { x };
+                }
+            }
+        }
+        for (k, w) in out.iter_mut().enumerate() {
+            *w = acc[k * 64..(k + 1) * 64].iter().enumerate().fold(0, |w, (b, a)| if *a > 0.0 { w | 1 << b } else { w });
+        }
+    }
+}
+
+impl Sketches {
+    pub fn new(cfg: &SketchConfig) -> Result<Self> {
+        anyhow::ensure!(cfg.bits > 0 && cfg.bits.is_multiple_of(64), "[search.sketch] bits must be a positive multiple of 64");
+        anyhow::ensure!(cfg.candidates > 0, "[search.sketch] candidates must be at least 1");
+        Ok(Self { bits: cfg.bits, candidates: cfg.candidates, mirrors: Mutex::default() })
+    }
+
+    fn words_per(&self) -> usize { self.bits / 64 }
+
+    /// Sketches of the first `n` records of `mirror` (fewer if it is shorter), catching up
+    /// on records written since the last call.
+    fn of_mirror(&self, cache: &VectorCache, mirror: &Path, dim: usize, n: usize) -> Result<Arc<RwLock<MirrorSketches>>> {
+        let entry = self.mirrors.lock()
+            .entry(mirror.to_path_buf())
+            .or_insert_with(|| Arc::new(RwLock::new(MirrorSketches::new(dim, self.words_per()))))
+            .clone();
+        let body = std::fs::metadata(mirror)?.len().saturating_sub(codec::HEADER_LEN);
+        let n = n.min((body / codec::record_len(dim) as u64) as usize);
+        if entry.read().count >= n { return Ok(entry); }
+        let mut ms = entry.write();
+        // Another index of another dimension took over the path (a collection rebuilt).
+        if ms.dim != dim { *ms = MirrorSketches::new(dim, self.words_per()); }
+        if ms.count < n {
+            let (from, w) = (ms.count, self.words_per());
+            let mut words = std::mem::take(&mut ms.words);
+            words.resize(n * w, 0);
+            let ids: Vec<usize> = (from..n).collect();
+            cache.scan_ids(mirror, dim, n, &ids, |id, v| ms.sketch_into(v, &mut words[id * w..(id + 1) * w]))?;
+            ms.words = words;
+            ms.count = n;
+        }
+        drop(ms);
+        Ok(entry)
+    }
+
+    /// Sketches record `id` of `mirror` again, now holding `v`, if it was sketched already.
+    pub fn resketch(&self, mirror: &Path, id: usize, v: &[f32]) {
+        let Some(entry) = self.mirrors.lock().get(mirror).cloned() else { return };
+        let mut ms = entry.write();
+        if id >= ms.count || ms.dim != v.len() { return; }
+        let w = self.words_per();
+        let mut words = std::mem::take(&mut ms.words);
+        ms.sketch_into(v, &mut words[id * w..(id + 1) * w]);
+        ms.words = words;
+    }
+
+    /// Sketches the whole of `mirror` ahead of the first search, reporting to `progress`.
+    pub fn warm(&self, cache: &VectorCache, mirror: &Path, dim: usize, n: usize, progress: &startup::Progress) -> Result<()> {
+        progress.set_total(n);
+        let mut done = 0;
+        while done < n {
+            done = (done + WARM_CHUNK).min(n);
+            self.of_mirror(cache, mirror, dim, done)?;
+            progress.set_done(done);
+        }
+        Ok(())
+    }
+
+    /// Ids (ascending) of the at most `max(candidates, k)` records of `pool` whose sketches
+    /// are closest to one of `qvs`'; ids at or past `n` are ignored.
+    pub fn survivors(
+        &self,
+        cache: &VectorCache,
+        mirror: &Path,
+        n: usize,
+        qvs: &[Vec<f32>],
+        pool: impl Iterator<Item = usize>,
+        k: usize,
+    ) -> Result<Vec<usize>> {
+        let entry = self.of_mirror(cache, mirror, qvs[0].len(), n)?;
+        let ms = entry.read();
+        let (w, n) = (self.words_per(), n.min(ms.count));
+        let mut qs = vec![0u64; qvs.len() * w];
+        for (q, out) in qvs.iter().zip(qs.chunks_exact_mut(w)) { ms.sketch_into(q, out); }
+        let distance = |id: usize| {
+            let s = &ms.words[id * w..(id + 1) * w];
+            qs.chunks_exact(w)
+                .map(|q| q.iter().zip(s).map(|(a, b)| (a ^ b).count_ones()).sum::<u32>())
+                .min()
+                .unwrap_or(0)
+        };
+        let mut near: Vec<(u32, usize)> = pool.filter(|&id| id < n).map(|id| (distance(id), id)).collect();
+        let want = self.candidates.max(k);
+        if near.len() > want {
+            near.select_nth_unstable(want - 1);
+            near.truncate(want);
+        }
+        let mut out: Vec<usize> = near.into_iter().map(|(_, id)| id).collect();
+        out.sort_unstable();
+        Ok(out)
+    }
+}
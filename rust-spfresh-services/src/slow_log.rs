@@ -0,0 +1,79 @@
+use crate::{config::SlowQueryConfig, SearchStats};
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Searches slower than the configured threshold, one JSON object per line, in a file of
+/// its own so tuning doesn't require turning on debug logging. The file is rotated by
+/// size: `slow_queries.log` -> `.1` -> ... -> `.<keep>`, oldest dropped.
+pub struct SlowLog {
+    threshold: Option<Duration>,
+    path: PathBuf,
+    max_bytes: u64,
+    keep: usize,
+    file: Mutex<Option<File>>,
+}
+
+#[derive(Serialize)]
+pub struct SlowQuery<'a> {
+    pub endpoint: &'a str,
+    pub query: &'a str,
+    pub collection: Option<&'a str>,
+    pub top_k: usize,
+    pub hits: usize,
+    pub stages: &'a SearchStats,
+}
+
+impl SlowLog {
+    pub fn new(cfg: &SlowQueryConfig, data_dir: &Path) -> Self {
+        Self {
+            threshold: (cfg.threshold_ms > 0).then(|| Duration::from_millis(cfg.threshold_ms)),
+            path: data_dir.join(&cfg.path),
+            max_bytes: cfg.max_bytes,
+            keep: cfg.keep,
+            file: Mutex::new(None),
+        }
+    }
+
+    /// Appends `q` if `elapsed` is over the threshold. Failures are logged, never surfaced.
+    pub fn record(&self, elapsed: Duration, q: SlowQuery<'_>) {
+        if self.threshold.is_none_or(|t| elapsed < t) { return; }
+        let ts = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis()).unwrap_or(0);
+        let mut line = serde_json::json!({ "ts_ms": ts, "query": q }).to_string();
+        line.push('\n');
+        if let Err(e) = self.write(line.as_bytes()) {
+            tracing::warn!("slow query log {}: {e}", self.path.display());
+        }
+    }
+
+    fn write(&self, line: &[u8]) -> std::io::Result<()> {
+        let mut guard = self.file.lock();
+        let size = match guard.as_ref() {
+            Some(f) => f.metadata()?.len(),
+            None => std::fs::metadata(&self.path).map(|m| m.len()).unwrap_or(0),
+        };
+        if size > 0 && size + line.len() as u64 > self.max_bytes {
+            *guard = None;
+            self.rotate()?;
+        }
+        if guard.is_none() {
+            *guard = Some(OpenOptions::new().create(true).append(true).open(&self.path)?);
+        }
+        guard.as_mut().expect("opened above").write_all(line)
+    }
+
+    fn rotate(&self) -> std::io::Result<()> {
+        let nth = |i: usize| PathBuf::from(format!("{}.{i}", self.path.display()));
+        if self.keep == 0 { return std::fs::remove_file(&self.path); }
+        let _ = std::fs::remove_file(nth(self.keep));
+        for i in (1..self.keep).rev() {
+            if nth(i).exists() { std::fs::rename(nth(i), nth(i + 1))?; }
+        }
+        std::fs::rename(&self.path, nth(1))
+    }
+}
@@ -0,0 +1,135 @@
+use axum::{
+    extract::{Request, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use serde::Serialize;
+use std::sync::{
+    atomic::{AtomicU8, AtomicUsize, Ordering},
+    Arc, OnceLock,
+};
+use tower::ServiceExt;
+
+/// What startup is doing, in the order it does it.
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+#[repr(u8)]
+pub enum Stage {
+    Starting,
+    /// The compaction a purge or /admin/compact requested.
+    Compaction,
+    /// Finishing an interrupted compaction or migration, and repairing torn tails.
+    Recovery,
+    /// Reading every metadata line into the in-memory indexes.
+    Metadata,
+    /// Opening the vector index and loading the embedder.
+    Index,
+    /// Embedding the reviews the shadow index is missing.
+    Shadow,
+    /// Embedding the titles and bodies the field indexes are missing.
+    Fields,
+    /// Embedding the reviews the late interaction index is missing.
+    LateInteraction,
+    /// Sketching the active index's vectors for the Hamming-distance first pass.
+    Sketches,
+    Ready,
+}
+
+const STAGES: [Stage; 10] = [
+    Stage::Starting, Stage::Compaction, Stage::Recovery, Stage::Metadata, Stage::Index, Stage::Shadow, Stage::Fields,
+    Stage::LateInteraction, Stage::Sketches, Stage::Ready,
+];
+/// Where each stage starts in the overall percent, by its rough share of a typical start;
+/// the next stage's start is where it ends.
+const STAGE_START: [f64; 10] = [0.0, 0.0, 10.0, 20.0, 65.0, 75.0, 82.0, 89.0, 95.0, 100.0];
+
+/// How far startup has got. The stage's `done` of `total` (0 when it is not measured) moves
+/// the overall percent within the stage's share.
+#[derive(Default)]
+pub struct Progress {
+    stage: AtomicU8,
+    done: AtomicUsize,
+    total: AtomicUsize,
+}
+
+#[derive(Serialize)]
+pub struct Report {
+    ready: bool,
+    stage: Stage,
+    /// Rough overall progress, 0 to 100.
+    percent: u8,
+    /// The stage's units (metadata lines, reviews back-filled) done and in all, when counted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    done: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    total: Option<usize>,
+}
+
+impl Progress {
+    pub fn stage(&self, stage: Stage) {
+        self.total.store(0, Ordering::Relaxed);
+        self.done.store(0, Ordering::Relaxed);
+        self.stage.store(stage as u8, Ordering::Relaxed);
+    }
+    pub fn set_total(&self, n: usize) { self.total.store(n, Ordering::Relaxed); }
+    pub fn set_done(&self, n: usize) { self.done.store(n, Ordering::Relaxed); }
+
+    fn report(&self) -> Report {
+        let i = self.stage.load(Ordering::Relaxed) as usize;
+        let (done, total) = (self.done.load(Ordering::Relaxed), self.total.load(Ordering::Relaxed));
+        let share = STAGE_START.get(i + 1).map_or(0.0, |next| next - STAGE_START[i]);
+        let frac = if total > 0 { done.min(total) as f64 / total as f64 } else { 0.0 };
+        Report {
+            ready: STAGES[i] == Stage::Ready,
+            stage: STAGES[i],
+            percent: (STAGE_START[i] + share * frac) as u8,
+            done: (total > 0).then_some(done),
+            total: (total > 0).then_some(total),
+        }
+    }
+}
+
+/// The service while it starts: it listens from the first moment so /readyz can answer
+/// load balancers, and hands every other request to the app once `ready` has set it.
+#[derive(Default)]
+pub struct Startup {
+    pub progress: Progress,
+    app: OnceLock<Router>,
+}
+
+impl Startup {
+    /// Starts serving `app`; /readyz answers 200 from here on.
+    pub fn ready(&self, app: Router) {
+        let _ = self.app.set(app);
+        self.progress.stage(Stage::Ready);
+    }
+}
+
+/// /readyz, then everything else: 503 with the startup progress until the app is ready.
+pub fn router(startup: Arc<Startup>) -> Router {
+    Router::new()
+        .route("/readyz", get(readyz))
+        .fallback(forward)
+        .with_state(startup)
+}
+
+/// GET /readyz — 200 once startup is done, else 503; both with the progress report.
+async fn readyz(State(startup): State<Arc<Startup>>) -> Response {
+    let report = startup.progress.report();
+    let status = if report.ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (status, Json(report)).into_response()
+}
+
+async fn forward(State(startup): State<Arc<Startup>>, req: Request) -> Response {
+    match startup.app.get() {
+        Some(app) => app.clone().oneshot(req).await.unwrap_or_else(|e| match e {}),
+        // Not an ApiError: a load balancer polling a starting instance is no server error.
+        None => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            [(header::RETRY_AFTER, "1")],
+            Json(serde_json::json!({ "error": "starting up", "progress": startup.progress.report() })),
+        ).into_response(),
+    }
+}
@@ -0,0 +1,108 @@
+use crate::{blocking, ApiError, AppState};
+use anyhow::Result;
+use axum::{extract::State, Json};
+use parking_lot::Mutex;
+use reviews_types::{FileUsage, StorageReport};
+use std::{
+    collections::VecDeque,
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+const SAMPLE_EVERY: Duration = Duration::from_secs(60);
+/// Growth is estimated over at most the last 24h of samples.
+const MAX_SAMPLES: usize = 24 * 60;
+
+/// Periodic samples of total data-dir size, used to project when the volume fills up.
+#[derive(Default)]
+pub struct GrowthTracker {
+    samples: Mutex<VecDeque<(u64, u64)>>,
+}
+
+impl GrowthTracker {
+    fn record(&self, bytes: u64) {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        let mut s = self.samples.lock();
+        s.push_back((now, bytes));
+        while s.len() > MAX_SAMPLES { s.pop_front(); }
+    }
+
+    fn bytes_per_day(&self) -> Option<f64> {
+        let s = self.samples.lock();
+        let (&(t0, b0), &(t1, b1)) = (s.front()?, s.back()?);
+        // Below ~5 minutes of history the slope is mostly noise.
+        if t1 < t0 + 300 { return None; }
+        Some((b1 as f64 - b0 as f64) / (t1 - t0) as f64 * 86_400.0)
+    }
+}
+
+fn classify(rel: &Path) -> &'static str {
+    let name = rel.file_name().and_then(|n| n.to_str()).unwrap_or("");
+    if rel.components().any(|c| c.as_os_str() == "snapshots") { return "snapshot"; }
+    match name {
+        "reviews.spfresh" | "reviews.spfresh.hdr" => "spfresh",
+        "reviews.index" => "mirror",
+        "reviews.jsonl" | "reviews.zst" | "reviews.zst.idx" => "metadata",
+        _ if name.ends_with(".wal") => "wal",
+        _ => "other",
+    }
+}
+
+fn walk(root: &Path, dir: &Path, out: &mut Vec<FileUsage>) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let meta = entry.metadata()?;
+        let path = entry.path();
+        if meta.is_dir() {
+            walk(root, &path, out)?;
+        } else {
+            let rel = path.strip_prefix(root).unwrap_or(&path);
+            out.push(FileUsage { path: rel.display().to_string(), kind: classify(rel).to_string(), bytes: meta.len() });
+        }
+    }
+    Ok(())
+}
+
+fn usage(dir: &Path) -> Result<(Vec<FileUsage>, u64)> {
+    let mut files = Vec::new();
+    walk(dir, dir, &mut files)?;
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    let total = files.iter().map(|f| f.bytes).sum();
+    Ok((files, total))
+}
+
+/// Background task sampling the data dir size every minute.
+pub async fn sample_growth(st: AppState) {
+    let mut tick = tokio::time::interval(SAMPLE_EVERY);
+    loop {
+        tick.tick().await;
+        let dir = st.data_dir.clone();
+        match tokio::task::spawn_blocking(move || usage(&dir)).await {
+            Ok(Ok((_, total))) => st.growth.record(total),
+            Ok(Err(e)) => tracing::warn!("storage sample failed: {e}"),
+            Err(e) => tracing::warn!("storage sample task failed: {e}"),
+        }
+    }
+}
+
+/// GET /admin/storage
+pub async fn storage_report(State(st): State<AppState>) -> Result<Json<StorageReport>, ApiError> {
+    let dir = st.data_dir.clone();
+    let (files, total_bytes, free_bytes, volume_bytes) = blocking(move || {
+        let (files, total) = usage(&dir)?;
+        let free = fs2::available_space(&dir).map_err(anyhow::Error::from)?;
+        let volume = fs2::total_space(&dir).map_err(anyhow::Error::from)?;
+        Ok((files, total, free, volume))
+    }).await?;
+    let growth = st.growth.bytes_per_day();
+    let days_until_full = growth.filter(|g| *g > 0.0).map(|g| free_bytes as f64 / g);
+    Ok(Json(StorageReport {
+        data_dir: st.data_dir.display().to_string(),
+        files,
+        total_bytes,
+        free_bytes,
+        volume_bytes,
+        growth_bytes_per_day: growth,
+        days_until_full,
+    }))
+}
@@ -0,0 +1,158 @@
+use crate::{audit::Actor, blocking, insert_batch, now_secs, ApiError, AppState, Review};
+use anyhow::{Context, Result};
+use axum::{extract::State, Json};
+use reviews_types::{GenerateReq, GenerateResp};
+use std::io::Write;
+
+const DEFAULT_PRODUCTS: u64 = 200;
+/// Most reviews one POST /admin/generate may insert.
+const MAX_COUNT: usize = 1_000_000;
+/// Reviews inserted per write batch by the endpoint.
+const CHUNK: usize = 1_000;
+/// Generated `created_at` values fall within this many days before now.
+const SPREAD_DAYS: u64 = 365;
+
+struct Category {
+    code: &'static str,
+    nouns: &'static [&'static str],
+    aspects: &'static [&'static str],
+}
+
+const CATEGORIES: &[Category] = &[
+    Category { code: "PHN", nouns: &["phone", "handset", "smartphone"], aspects: &["battery", "screen", "camera", "charger", "speaker"] },
+    Category { code: "HDP", nouns: &["headphones", "earbuds", "headset"], aspects: &["sound", "bass", "fit", "noise cancelling", "microphone"] },
+    Category { code: "KTC", nouns: &["kettle", "blender", "toaster"], aspects: &["lid", "cord", "handle", "heating", "cleaning"] },
+    Category { code: "SHO", nouns: &["shoes", "sneakers", "boots"], aspects: &["sole", "fit", "laces", "grip", "padding"] },
+    Category { code: "BAG", nouns: &["backpack", "bag", "suitcase"], aspects: &["zipper", "straps", "pockets", "wheels", "fabric"] },
+];
+
+const POSITIVE: &[&str] = &["great", "excellent", "solid", "comfortable", "reliable", "impressive", "well made"];
+const MIXED: &[&str] = &["okay", "decent", "average", "fine", "acceptable"];
+const NEGATIVE: &[&str] = &["poor", "disappointing", "flimsy", "terrible", "unreliable", "cheap feeling"];
+const DELIVERY: &[&str] = &["Delivery was fast.", "Shipping took longer than promised.", "Arrived well packaged.", "The box was dented on arrival.", "Came a day early."];
+
+/// Plausible fake reviews for load tests and demos. Review `i` depends only on the seed
+/// and `i`, so a given (seed, i) always yields the same review. Ratings lean positive like
+/// real stores, the wording follows the rating, and a few products get most of the reviews.
+pub struct Generator {
+    seed: u64,
+    products: u64,
+    now: u64,
+}
+
+/// xorshift64*, seeded per review.
+pub struct Rng(u64);
+
+impl Rng {
+    /// Any seed, 0 included.
+    pub fn new(seed: u64) -> Self { Self(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1) }
+    pub fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+    pub fn below(&mut self, n: u64) -> u64 { self.next() % n.max(1) }
+    pub fn unit(&mut self) -> f64 { (self.next() >> 11) as f64 / (1u64 << 53) as f64 }
+    fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str { items[self.below(items.len() as u64) as usize] }
+}
+
+impl Generator {
+    pub fn new(seed: u64, products: u64) -> Self {
+        Self { seed, products: products.max(1), now: now_secs() }
+    }
+
+    pub fn review(&self, i: u64) -> Review {
+        let mut rng = Rng((self.seed ^ i.wrapping_mul(0x9E37_79B9_7F4A_7C15)) | 1);
+        // Squaring skews toward low product numbers: a long tail of rarely reviewed products.
+        let product = (rng.unit().powi(2) * self.products as f64) as u64;
+        let cat = &CATEGORIES[(product % CATEGORIES.len() as u64) as usize];
+        let rating = match rng.below(100) {
+            0..8 => 1,
+            8..15 => 2,
+            15..30 => 3,
+            30..60 => 4,
+            _ => 5,
+        };
+        let words = match rating {
+            1 | 2 => NEGATIVE,
+            3 => MIXED,
+            _ => POSITIVE,
+        };
+        let (noun, aspect, other) = (rng.pick(cat.nouns), rng.pick(cat.aspects), rng.pick(cat.aspects));
+        let title = match rng.below(3) {
+            0 => format!("{} {noun}", capitalize(rng.pick(words))),
+            1 => format!("{} {aspect}", capitalize(rng.pick(words))),
+            _ => format!("{rating} stars for this {noun}"),
+        };
+        let verdict = match rating {
+            1 | 2 => "Would not buy again.",
+            3 => "Does the job, nothing more.",
+            _ => "Would recommend.",
+        };
+        let body = format!(
+            "The {aspect} is {} and the {other} is {}. {} {verdict}",
+            rng.pick(words), rng.pick(words), rng.pick(DELIVERY),
+        );
+        Review {
+            review_title: title,
+            review_body: body,
+            product_id: format!("{}-{product:04}", cat.code),
+            review_rating: rating,
+            created_at: Some(self.now.saturating_sub(rng.below(SPREAD_DAYS * 86_400))),
+            external_id: None,
+            version: None,
+            lang: None,
+            status: None,
+            aspects: None,
+        }
+    }
+}
+
+fn capitalize(s: &str) -> String {
+    let mut c = s.chars();
+    c.next().map(|f| f.to_uppercase().chain(c).collect()).unwrap_or_default()
+}
+
+/// POST /admin/generate — inserts `count` generated reviews through the normal write
+/// path (embedding, quotas, audit), in batches.
+pub async fn generate(State(st): State<AppState>, actor: Actor, Json(req): Json<GenerateReq>) -> Result<Json<GenerateResp>, ApiError> {
+    if req.count == 0 || req.count > MAX_COUNT {
+        return Err(ApiError::bad_request(format!("count must be between 1 and {MAX_COUNT}")));
+    }
+    let seed = req.seed.unwrap_or_else(now_secs);
+    let generator = Generator::new(seed, req.products.unwrap_or(DEFAULT_PRODUCTS));
+    let mut inserted = 0;
+    while inserted < req.count {
+        let n = CHUNK.min(req.count - inserted);
+        let reviews: Vec<Review> = (inserted..inserted + n).map(|i| generator.review(i as u64)).collect();
+        let (st, actor) = (st.clone(), actor.clone());
+        inserted += blocking(move || insert_batch(&st, &actor, reviews, Some("generated".into()))).await?;
+    }
+    Ok(Json(GenerateResp { inserted, seed }))
+}
+
+/// `generate --count N [--seed S] [--products P]` — writes generated reviews to stdout as
+/// NDJSON, ready for POST /reviews/import or `bench --corpus`.
+pub fn run(args: &[String]) -> Result<()> {
+    let (mut count, mut seed, mut products) = (None, 0u64, DEFAULT_PRODUCTS);
+    let mut it = args.iter();
+    while let Some(flag) = it.next() {
+        let value = it.next().with_context(|| format!("{flag} needs a value"))?;
+        match flag.as_str() {
+            "--count" => count = Some(value.parse::<u64>()?),
+            "--seed" => seed = value.parse()?,
+            "--products" => products = value.parse()?,
+            _ => anyhow::bail!("unknown generate option {flag} (--count, --seed, --products)"),
+        }
+    }
+    let count = count.context("generate needs --count")?;
+    let generator = Generator::new(seed, products);
+    let mut out = std::io::BufWriter::new(std::io::stdout().lock());
+    for i in 0..count {
+        serde_json::to_writer(&mut out, &generator.review(i))?;
+        out.write_all(b"\n")?;
+    }
+    out.flush()?;
+    Ok(())
+}
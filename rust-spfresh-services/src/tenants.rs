@@ -0,0 +1,120 @@
+use crate::{audit::Actor, config::{Quota, QuotasConfig}, ApiError, AppState};
+use anyhow::Result;
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+    Json,
+};
+use parking_lot::Mutex;
+use reviews_types::{TenantQuota, TenantUsage, Usage};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const USAGE_FILE: &str = "usage.json";
+
+/// Per-tenant usage: documents and bytes written (persisted in `data/usage.json`) and
+/// requests in the current minute (in memory). A tenant is the `x-principal` of a request.
+/// Reviews written before usage was tracked are not attributed to anyone.
+pub struct Tenants {
+    path: PathBuf,
+    usage: Mutex<BTreeMap<String, Usage>>,
+    rates: Mutex<HashMap<String, Window>>,
+}
+
+/// Requests counted in one wall-clock minute.
+struct Window { minute: u64, count: u64 }
+
+fn now_minute() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() / 60).unwrap_or(0)
+}
+
+impl Tenants {
+    pub fn open(data_dir: &Path) -> Result<Self> {
+        let path = data_dir.join(USAGE_FILE);
+        let usage = match std::fs::read(&path) {
+            Ok(b) => serde_json::from_slice(&b)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self { path, usage: Mutex::new(usage), rates: Mutex::new(HashMap::new()) })
+    }
+
+    /// Rejects a write of `docs` reviews that would take `tenant` past its document quota,
+    /// or any write once it is at its byte quota (the size of a write is only known after
+    /// embedding). Concurrent writes are checked independently, so they can overshoot by
+    /// at most one request each.
+    pub fn admit_write(&self, quotas: &QuotasConfig, tenant: &str, docs: u64) -> Result<(), ApiError> {
+        let q = quotas.for_tenant(tenant);
+        let usage = self.usage.lock().get(tenant).cloned().unwrap_or_default();
+        if let Some(max) = q.max_documents.filter(|&max| usage.documents + docs > max) {
+            return Err(ApiError::forbidden(format!(
+                "tenant '{tenant}' document quota exceeded: {} + {docs} > {max}", usage.documents
+            )));
+        }
+        if let Some(max) = q.max_bytes.filter(|&max| usage.bytes >= max) {
+            return Err(ApiError::forbidden(format!("tenant '{tenant}' storage quota exceeded: {} >= {max} bytes", usage.bytes)));
+        }
+        Ok(())
+    }
+
+    /// Adds a committed write to `tenant`'s usage and persists the totals.
+    pub fn charge(&self, tenant: &str, docs: u64, bytes: u64) -> Result<()> {
+        let mut usage = self.usage.lock();
+        let u = usage.entry(tenant.to_string()).or_default();
+        u.documents += docs;
+        u.bytes += bytes;
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(&*usage)?)?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+
+    /// Counts one request against `tenant`'s per-minute rate.
+    fn hit(&self, quota: &Quota, tenant: &str) -> Result<(), ApiError> {
+        let minute = now_minute();
+        let mut rates = self.rates.lock();
+        let w = rates.entry(tenant.to_string()).or_insert(Window { minute, count: 0 });
+        if w.minute != minute { *w = Window { minute, count: 0 }; }
+        if let Some(max) = quota.requests_per_minute.filter(|&max| w.count >= max) {
+            return Err(ApiError::too_many_requests(format!("tenant '{tenant}' is over its {max} requests/minute")));
+        }
+        w.count += 1;
+        Ok(())
+    }
+
+    fn report(&self, quotas: &QuotasConfig) -> Vec<TenantUsage> {
+        let usage = self.usage.lock().clone();
+        let minute = now_minute();
+        let rates: HashMap<String, u64> = self.rates.lock().iter()
+            .filter(|(_, w)| w.minute == minute)
+            .map(|(t, w)| (t.clone(), w.count))
+            .collect();
+        let mut tenants: Vec<&String> = usage.keys().chain(rates.keys()).chain(quotas.tenants.keys()).collect();
+        tenants.sort();
+        tenants.dedup();
+        tenants.into_iter().map(|t| {
+            let q = quotas.for_tenant(t);
+            TenantUsage {
+                tenant: t.clone(),
+                usage: usage.get(t).copied().unwrap_or_default(),
+                requests_this_minute: rates.get(t).copied().unwrap_or(0),
+                quota: TenantQuota { max_documents: q.max_documents, max_bytes: q.max_bytes, requests_per_minute: q.requests_per_minute },
+            }
+        }).collect()
+    }
+}
+
+/// Middleware enforcing `requests_per_minute` for the calling tenant; over the limit: 429.
+pub async fn meter(State(st): State<AppState>, actor: Actor, req: Request, next: Next) -> Result<Response, ApiError> {
+    st.tenants.hit(&st.config.quotas.for_tenant(&actor.principal), &actor.principal)?;
+    Ok(next.run(req).await)
+}
+
+/// GET /admin/usage — usage and effective quota of every tenant seen or configured.
+pub async fn usage_report(State(st): State<AppState>) -> Json<Vec<TenantUsage>> {
+    Json(st.tenants.report(&st.config.quotas))
+}
@@ -0,0 +1,139 @@
+use crate::config::TlsConfig;
+use anyhow::{Context, Result};
+use axum::{extract::Request, response::Response};
+use parking_lot::RwLock;
+use std::{
+    convert::Infallible,
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use tokio::net::TcpListener;
+use tokio_rustls::{
+    rustls::{
+        crypto::ring,
+        server::{ClientHello, ResolvesServerCert},
+        sign::CertifiedKey,
+        ServerConfig,
+    },
+    TlsAcceptor,
+};
+use tower::Service;
+
+/// A client that has not finished its handshake by then is dropped.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Serves `app` over HTTPS (HTTP/1.1 and HTTP/2 through ALPN) on `listener`. The
+/// certificate is re-read whenever the cert or key file changes, checked every
+/// `reload_interval_secs`; new handshakes pick it up, open connections keep theirs.
+pub async fn serve<S>(listener: TcpListener, app: S, cfg: &TlsConfig) -> Result<()>
+where
+    S: Service<Request, Response = Response, Error = Infallible> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    let resolver = Arc::new(Reloading::load(&cfg.cert_path, &cfg.key_path)?);
+    if cfg.reload_interval_secs > 0 {
+        tokio::spawn(watch(resolver.clone(), Duration::from_secs(cfg.reload_interval_secs)));
+    }
+    let mut server = ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+        .with_safe_default_protocol_versions()?
+        .with_no_client_auth()
+        .with_cert_resolver(resolver);
+    server.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    let acceptor = TlsAcceptor::from(Arc::new(server));
+
+    loop {
+        let (tcp, peer) = match listener.accept().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                // Out of file descriptors and the like; back off instead of spinning.
+                tracing::warn!("accept failed: {e}");
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                continue;
+            }
+        };
+        let (acceptor, app) = (acceptor.clone(), app.clone());
+        tokio::spawn(async move {
+            let stream = match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(tcp)).await {
+                Ok(Ok(s)) => s,
+                Ok(Err(e)) => { tracing::debug!("TLS handshake with {peer} failed: {e}"); return; }
+                Err(_) => { tracing::debug!("TLS handshake with {peer} timed out"); return; }
+            };
+            crate::listeners::serve_connection(stream, app, peer.to_string()).await;
+        });
+    }
+}
+
+/// Certificate resolver whose key pair can be swapped while the server runs.
+#[derive(Debug)]
+struct Reloading {
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    current: RwLock<Loaded>,
+}
+
+#[derive(Debug)]
+struct Loaded {
+    key: Arc<CertifiedKey>,
+    /// Modification times of (cert, key) when they were read.
+    mtimes: (Option<SystemTime>, Option<SystemTime>),
+}
+
+impl Reloading {
+    fn load(cert_path: &Path, key_path: &Path) -> Result<Self> {
+        let loaded = read_pair(cert_path, key_path)?;
+        tracing::info!("TLS certificate loaded from {}", cert_path.display());
+        Ok(Self { cert_path: cert_path.into(), key_path: key_path.into(), current: RwLock::new(loaded) })
+    }
+
+    fn reload_if_changed(&self) {
+        let mtimes = (mtime(&self.cert_path), mtime(&self.key_path));
+        if mtimes == self.current.read().mtimes { return; }
+        // A renewal writes two files; a half-written pair fails here and is retried next tick.
+        match read_pair(&self.cert_path, &self.key_path) {
+            Ok(loaded) => {
+                *self.current.write() = loaded;
+                tracing::info!("TLS certificate reloaded from {}", self.cert_path.display());
+            }
+            Err(e) => tracing::warn!("TLS certificate not reloaded, keeping the current one: {e:#}"),
+        }
+    }
+}
+
+impl ResolvesServerCert for Reloading {
+    fn resolve(&self, _hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.current.read().key.clone())
+    }
+}
+
+async fn watch(resolver: Arc<Reloading>, every: Duration) {
+    let mut tick = tokio::time::interval(every);
+    tick.tick().await;
+    loop {
+        tick.tick().await;
+        let r = resolver.clone();
+        // File reads; off the async workers like other disk work.
+        let _ = tokio::task::spawn_blocking(move || r.reload_if_changed()).await;
+    }
+}
+
+fn mtime(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Reads a PEM certificate chain and private key and checks that they belong together.
+fn read_pair(cert_path: &Path, key_path: &Path) -> Result<Loaded> {
+    let mtimes = (mtime(cert_path), mtime(key_path));
+    let certs = rustls_pemfile::certs(&mut BufReader::new(File::open(cert_path).with_context(|| format!("open {}", cert_path.display()))?))
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("parse {}", cert_path.display()))?;
+    anyhow::ensure!(!certs.is_empty(), "{} holds no certificate", cert_path.display());
+    let key = rustls_pemfile::private_key(&mut BufReader::new(File::open(key_path).with_context(|| format!("open {}", key_path.display()))?))
+        .with_context(|| format!("parse {}", key_path.display()))?
+        .with_context(|| format!("{} holds no private key", key_path.display()))?;
+    let key = CertifiedKey::from_der(certs, key, &ring::default_provider())
+        .with_context(|| format!("{} does not match {}", key_path.display(), cert_path.display()))?;
+    Ok(Loaded { key: Arc::new(key), mtimes })
+}
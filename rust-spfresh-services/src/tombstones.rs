@@ -0,0 +1,65 @@
+use crate::codec;
+use anyhow::Result;
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    id: usize,
+    ts_ms: u64,
+}
+
+/// Ids of deleted reviews. Metadata lines and vectors are append-only and keep their
+/// place, so a delete is recorded here instead, `data/tombstones.log`, framed like
+/// reviews.jsonl and synced before the delete is acknowledged.
+pub struct Tombstones {
+    path: PathBuf,
+    ids: RwLock<HashSet<usize>>,
+    file: Mutex<File>,
+}
+
+impl Tombstones {
+    pub fn open(data_dir: &Path) -> Result<Self> {
+        let path = data_dir.join("tombstones.log");
+        let mut ids = HashSet::new();
+        if let Ok(f) = File::open(&path) {
+            for (n, line) in BufReader::new(f).split(b'\n').enumerate() {
+                let entry = codec::decode_line(&line?).and_then(|json| Ok(serde_json::from_slice::<Entry>(json)?));
+                match entry {
+                    Ok(e) => { ids.insert(e.id); }
+                    Err(e) => tracing::warn!("{}: line {} skipped: {e}", path.display(), n + 1),
+                }
+            }
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(Self { path, ids: RwLock::new(ids), file: Mutex::new(file) })
+    }
+
+    pub fn add(&self, id: usize) -> Result<()> {
+        let ts_ms = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0);
+        let line = codec::encode_line(&serde_json::to_vec(&Entry { id, ts_ms })?);
+        let mut f = self.file.lock();
+        f.write_all(&line)?;
+        f.sync_data()?;
+        self.ids.write().insert(id);
+        Ok(())
+    }
+
+    pub fn contains(&self, id: usize) -> bool {
+        self.ids.read().contains(&id)
+    }
+
+    /// Ids deleted after byte `offset` of the log, in order, stopping before the first id
+    /// at or past `below` (not yet replicated). Returns them and the offset to resume from.
+    pub fn read_from(&self, offset: u64, below: usize) -> Result<(Vec<usize>, u64)> {
+        let (entries, next) = codec::read_log_from(&self.path, offset, below, |e: &Entry| e.id)?;
+        Ok((entries.into_iter().map(|e| e.id).collect(), next))
+    }
+}
@@ -0,0 +1,87 @@
+use crate::{blocking, keyword, now_secs, ApiError, AppState};
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use reviews_types::{TrendingParams, TrendingResp, TrendingTerm};
+use std::collections::{HashMap, HashSet};
+
+const DAY_SECS: u64 = 86_400;
+const MAX_LIMIT: usize = 100;
+/// Example review ids per term, the most recent ones.
+const EXAMPLES: usize = 3;
+/// Too common to say anything about a product; never reported, and phrases containing one
+/// are not formed.
+pub const STOPWORDS: &[&str] = &[
+    "a", "about", "after", "all", "also", "am", "an", "and", "any", "are", "as", "at", "be", "been", "but", "by",
+    "can", "could", "did", "do", "does", "for", "from", "had", "has", "have", "he", "her", "him", "his", "how", "i",
+    "if", "in", "into", "is", "it", "its", "just", "me", "more", "my", "no", "not", "of", "on", "one", "or", "our",
+    "out", "she", "so", "than", "that", "the", "their", "them", "then", "there", "they", "this", "to", "too", "up",
+    "us", "very", "was", "we", "were", "what", "when", "which", "who", "will", "with", "would", "you", "your",
+];
+
+/// Document counts of one term in both windows.
+#[derive(Default)]
+struct Counts { recent: u32, baseline: u32, examples: Vec<usize> }
+
+/// GET /analytics/trending?window_days=&baseline_days=&limit=&min_count=&product_id= — words
+/// and two-word phrases whose share of reviews rose the most in the last `window_days`
+/// against the `baseline_days` before, for spotting emerging issues. Live reviews with a
+/// `created_at` only; the metadata is read in full on every call.
+pub async fn trending(State(st): State<AppState>, Query(p): Query<TrendingParams>) -> Result<Json<TrendingResp>, ApiError> {
+    if p.window_days == 0 || p.baseline_days == 0 {
+        return Err(ApiError::bad_request("window_days and baseline_days must be at least 1"));
+    }
+    let now = now_secs();
+    let recent_from = now.saturating_sub(p.window_days.saturating_mul(DAY_SECS));
+    let baseline_from = recent_from.saturating_sub(p.baseline_days.saturating_mul(DAY_SECS));
+    let resp = blocking(move || {
+        let (mut counts, mut recent_reviews, mut baseline_reviews) = (HashMap::<String, Counts>::new(), 0, 0);
+        st.meta.for_each_in(0..usize::MAX, |id, r| {
+            let Some(r) = r.filter(|_| st.meta.is_live(id)) else { return Ok(()) };
+            let Some(t) = r.created_at.filter(|&t| t >= baseline_from && t <= now) else { return Ok(()) };
+            if p.product_id.as_ref().is_some_and(|pid| *pid != r.product_id) { return Ok(()); }
+            let recent = t >= recent_from;
+            if recent { recent_reviews += 1 } else { baseline_reviews += 1 }
+            for term in review_terms(&r.embed_text()) {
+                let c = counts.entry(term).or_default();
+                if !recent {
+                    c.baseline += 1;
+                    continue;
+                }
+                c.recent += 1;
+                if c.examples.len() == EXAMPLES { c.examples.remove(0); }
+                c.examples.push(id);
+            }
+            Ok(())
+        })?;
+        let share = |n: u32, total: u32| (n as f64 + 1.0) / (total as f64 + 1.0);
+        let mut terms: Vec<TrendingTerm> = counts.into_iter()
+            .filter(|(_, c)| c.recent >= p.min_count.max(1))
+            .map(|(term, c)| TrendingTerm {
+                lift: share(c.recent, recent_reviews) / share(c.baseline, baseline_reviews),
+                term,
+                recent: c.recent,
+                baseline: c.baseline,
+                example_ids: c.examples.into_iter().rev().collect(),
+            })
+            .filter(|t| t.lift > 1.0)
+            .collect();
+        terms.sort_by(|a, b| b.lift.total_cmp(&a.lift).then(b.recent.cmp(&a.recent)).then(a.term.cmp(&b.term)));
+        terms.truncate(p.limit.min(MAX_LIMIT));
+        Ok(TrendingResp { recent_reviews, baseline_reviews, terms })
+    }).await?;
+    Ok(Json(resp))
+}
+
+/// Distinct words and adjacent word pairs of a review, stopwords and numbers left out.
+fn review_terms(text: &str) -> HashSet<String> {
+    let words: Vec<Option<String>> = keyword::terms(text)
+        .map(|w| (w.chars().count() > 1 && !STOPWORDS.contains(&w.as_str()) && !w.chars().all(|c| c.is_numeric())).then_some(w))
+        .collect();
+    let mut out: HashSet<String> = words.iter().flatten().cloned().collect();
+    for pair in words.windows(2) {
+        if let [Some(a), Some(b)] = pair { out.insert(format!("{a} {b}")); }
+    }
+    out
+}
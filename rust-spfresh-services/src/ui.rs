@@ -0,0 +1,92 @@
+use axum::{
+    body::{Body, Bytes},
+    extract::Request,
+    http::{header, HeaderValue, Method, StatusCode, Uri},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use rust_embed::RustEmbed;
+use std::borrow::Cow;
+
+/// The admin UI as built by `trunk build --release` in rust-spfresh-ui, compiled into the
+/// binary (read from disk instead in debug builds).
+#[derive(RustEmbed)]
+#[folder = "../rust-spfresh-ui/dist"]
+struct Assets;
+
+/// Prefix the UI sends API calls under, as it did through trunk's dev proxy.
+const API_PREFIX: &str = "/api";
+
+/// Runs in front of `versioning::negotiate`. `/api/...` is the API with the prefix
+/// removed; `/` and paths naming a file of the UI build are served from it; anything
+/// else goes on to the API unchanged, so existing clients see no difference.
+pub async fn serve(mut req: Request, next: Next) -> Response {
+    let path = req.uri().path();
+    if let Some(rest) = path.strip_prefix(API_PREFIX).filter(|r| r.is_empty() || r.starts_with('/')) {
+        match strip(req.uri(), rest.len()) {
+            Some(uri) => *req.uri_mut() = uri,
+            None => return StatusCode::BAD_REQUEST.into_response(),
+        }
+        return next.run(req).await;
+    }
+    if matches!(*req.method(), Method::GET | Method::HEAD) {
+        let file = match path { "/" => "index.html", p => &p[1..] };
+        if let Some(asset) = Assets::get(file) {
+            return asset_response(&req, file, asset);
+        }
+    }
+    next.run(req).await
+}
+
+/// `uri` with everything before its last `keep` path bytes removed.
+fn strip(uri: &Uri, keep: usize) -> Option<Uri> {
+    let path = uri.path();
+    let rest = match &path[path.len() - keep..] { "" => "/", r => r };
+    let pq = match uri.query() {
+        Some(q) => format!("{rest}?{q}"),
+        None => rest.to_string(),
+    };
+    let mut parts = uri.clone().into_parts();
+    parts.path_and_query = Some(pq.parse().ok()?);
+    Uri::from_parts(parts).ok()
+}
+
+fn asset_response(req: &Request, file: &str, asset: rust_embed::EmbeddedFile) -> Response {
+    let etag = format!("\"{}\"", hex(&asset.metadata.sha256_hash()[..16]));
+    // trunk puts a content hash in every file name but index.html, so those never change.
+    let cache = match file {
+        "index.html" => "no-cache",
+        _ => "public, max-age=31536000, immutable",
+    };
+    let headers = [
+        (header::CONTENT_TYPE, HeaderValue::from_static(content_type(file))),
+        (header::CACHE_CONTROL, HeaderValue::from_static(cache)),
+        (header::ETAG, HeaderValue::from_str(&etag).expect("hex is a valid header value")),
+    ];
+    if req.headers().get(header::IF_NONE_MATCH).is_some_and(|v| v.as_bytes() == etag.as_bytes()) {
+        return (StatusCode::NOT_MODIFIED, headers).into_response();
+    }
+    let body = match asset.data {
+        Cow::Borrowed(b) => Bytes::from_static(b),
+        Cow::Owned(v) => Bytes::from(v),
+    };
+    (headers, Body::from(body)).into_response()
+}
+
+fn content_type(file: &str) -> &'static str {
+    match file.rsplit_once('.').map(|(_, ext)| ext) {
+        Some("html") => "text/html; charset=utf-8",
+        Some("js") => "text/javascript",
+        Some("wasm") => "application/wasm",
+        Some("css") => "text/css",
+        Some("json") => "application/json",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("ico") => "image/x-icon",
+        _ => "application/octet-stream",
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
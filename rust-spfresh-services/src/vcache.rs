@@ -0,0 +1,200 @@
+use crate::codec::{self, F32_BYTES};
+use anyhow::Result;
+use memmap2::Mmap;
+use parking_lot::Mutex;
+use reviews_types::{CacheStats, MirrorResidency};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// Mirror vectors kept in RAM as fixed-size segments, up to a byte budget. A segment that
+/// does not fit (or the growing tail segment) is read straight from a read-only mmap of the
+/// mirror instead, so memory use stays bounded whatever the index size. Mirrors are
+/// append-only, so a full segment never changes once cached; a purge and a re-embed, the
+/// writers overwriting records, clear the cache or the segment afterwards.
+pub struct VectorCache {
+    budget_bytes: usize,
+    segment_vectors: usize,
+    inner: Mutex<CacheState>,
+}
+
+#[derive(Default)]
+struct CacheState {
+    segments: HashMap<(PathBuf, usize), Segment>,
+    /// Segment count per mirror as of its last scan, for residency reporting.
+    seen: HashMap<PathBuf, usize>,
+    resident_bytes: usize,
+    clock: u64,
+    hits: u64,
+    misses: u64,
+    evictions: u64,
+}
+
+struct Segment {
+    data: Arc<SegmentData>,
+    last_used: u64,
+}
+
+/// Decoded vectors of one segment. Records failing their checksum are zero-filled in
+/// `vectors`, to keep offsets fixed, and listed in `bad` so scans skip them.
+struct SegmentData {
+    vectors: Vec<f32>,
+    bad: Vec<usize>,
+}
+
+impl SegmentData {
+    fn bytes(&self) -> usize { self.vectors.len() * F32_BYTES }
+}
+
+impl VectorCache {
+    pub fn new(budget_bytes: usize, segment_vectors: usize) -> Self {
+        Self { budget_bytes, segment_vectors: segment_vectors.max(1), inner: Mutex::new(CacheState::default()) }
+    }
+
+    /// Calls `f(id, vector)` for the first `n` vectors of `mirror` (fewer if the file is shorter).
+    pub fn scan(&self, mirror: &Path, dim: usize, n: usize, mut f: impl FnMut(usize, &[f32])) -> Result<()> {
+        let file = std::fs::File::open(mirror)?;
+        let rec_len = codec::record_len(dim);
+        let body = file.metadata()?.len().saturating_sub(codec::HEADER_LEN);
+        let n = n.min((body / rec_len as u64) as usize);
+        if n == 0 { return Ok(()); }
+        // SAFETY: the mirror is only ever appended to (or overwritten record for record by a
+        // purge or re-embed, which a checksum catches mid-write), so the mapped prefix stays
+        // valid.
+        let map = unsafe { Mmap::map(&file)? };
+        let seg_len = self.segment_vectors;
+        let segments = n.div_ceil(seg_len);
+        self.inner.lock().seen.insert(mirror.to_path_buf(), segments);
+
+        let records = &map[codec::HEADER_LEN as usize..];
+        for seg in 0..segments {
+            let start = seg * seg_len;
+            let data = self.segment(mirror, records, dim, n, seg);
+            for (i, v) in data.vectors.chunks_exact(dim).enumerate() {
+                if !data.bad.contains(&i) { f(start + i, v); }
+            }
+        }
+        Ok(())
+    }
+
+    /// Like `scan`, but only for `ids` (ascending); only the segments holding them are
+    /// loaded. Ids at or past `n` are ignored.
+    pub fn scan_ids(&self, mirror: &Path, dim: usize, n: usize, ids: &[usize], mut f: impl FnMut(usize, &[f32])) -> Result<()> {
+        let file = std::fs::File::open(mirror)?;
+        let rec_len = codec::record_len(dim);
+        let body = file.metadata()?.len().saturating_sub(codec::HEADER_LEN);
+        let n = n.min((body / rec_len as u64) as usize);
+        if n == 0 || ids.is_empty() { return Ok(()); }
+        // SAFETY: the mirror is only ever appended to (or overwritten record for record by a
+        // purge or re-embed, which a checksum catches mid-write), so the mapped prefix stays
+        // valid.
+        let map = unsafe { Mmap::map(&file)? };
+        let records = &map[codec::HEADER_LEN as usize..];
+        let seg_len = self.segment_vectors;
+        for group in ids.iter().copied().filter(|&id| id < n).collect::<Vec<_>>().chunk_by(|a, b| a / seg_len == b / seg_len) {
+            let seg = group[0] / seg_len;
+            let data = self.segment(mirror, records, dim, n, seg);
+            for &id in group {
+                let i = id - seg * seg_len;
+                if !data.bad.contains(&i) { f(id, &data.vectors[i * dim..(i + 1) * dim]); }
+            }
+        }
+        Ok(())
+    }
+
+    /// Segment `seg` of a mirror holding `n` records: from the cache if it is full and
+    /// resident, otherwise decoded from `records` (and cached if full).
+    fn segment(&self, mirror: &Path, records: &[u8], dim: usize, n: usize, seg: usize) -> Arc<SegmentData> {
+        let (seg_len, rec_len) = (self.segment_vectors, codec::record_len(dim));
+        let start = seg * seg_len;
+        let end = (start + seg_len).min(n);
+        let full = end - start == seg_len;
+        if let Some(d) = full.then(|| self.get(mirror, seg)).flatten() { return d; }
+        let d = Arc::new(decode_segment(&records[start * rec_len..end * rec_len], dim));
+        for &i in &d.bad {
+            tracing::warn!("{}: record {} fails its checksum, skipped", mirror.display(), start + i);
+        }
+        if full { self.admit(mirror, seg, d.clone()); }
+        d
+    }
+
+    fn get(&self, mirror: &Path, seg: usize) -> Option<Arc<SegmentData>> {
+        let mut st = self.inner.lock();
+        st.clock += 1;
+        let now = st.clock;
+        match st.segments.get_mut(&(mirror.to_path_buf(), seg)) {
+            Some(s) => {
+                s.last_used = now;
+                let data = s.data.clone();
+                st.hits += 1;
+                Some(data)
+            }
+            None => { st.misses += 1; None }
+        }
+    }
+
+    /// Caches a full segment, evicting the least recently used ones to stay within budget.
+    fn admit(&self, mirror: &Path, seg: usize, data: Arc<SegmentData>) {
+        let size = data.bytes();
+        if size > self.budget_bytes { return; }
+        let mut st = self.inner.lock();
+        while st.resident_bytes + size > self.budget_bytes {
+            let Some(coldest) = st.segments.iter().min_by_key(|(_, s)| s.last_used).map(|(k, _)| k.clone()) else { break };
+            if let Some(s) = st.segments.remove(&coldest) {
+                st.resident_bytes -= s.data.bytes();
+                st.evictions += 1;
+            }
+        }
+        st.clock += 1;
+        let last_used = st.clock;
+        st.resident_bytes += size;
+        st.segments.insert((mirror.to_path_buf(), seg), Segment { data, last_used });
+    }
+
+    /// Drops the cached segment of `mirror` holding record `id`, which was overwritten.
+    pub fn forget(&self, mirror: &Path, id: usize) {
+        let mut st = self.inner.lock();
+        if let Some(s) = st.segments.remove(&(mirror.to_path_buf(), id / self.segment_vectors)) {
+            st.resident_bytes -= s.data.bytes();
+        }
+    }
+
+    /// Drops every cached segment.
+    pub fn clear(&self) {
+        let mut st = self.inner.lock();
+        st.segments.clear();
+        st.resident_bytes = 0;
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let st = self.inner.lock();
+        let mut mirrors: Vec<_> = st.seen.iter().map(|(mirror, &segments)| MirrorResidency {
+            mirror: mirror.display().to_string(),
+            segments,
+            resident_segments: st.segments.keys().filter(|(p, _)| p == mirror).count(),
+        }).collect();
+        mirrors.sort_by(|a, b| a.mirror.cmp(&b.mirror));
+        CacheStats {
+            budget_bytes: self.budget_bytes,
+            resident_bytes: st.resident_bytes,
+            segment_vectors: self.segment_vectors,
+            hits: st.hits,
+            misses: st.misses,
+            evictions: st.evictions,
+            mirrors,
+        }
+    }
+}
+
+fn decode_segment(records: &[u8], dim: usize) -> SegmentData {
+    let mut seg = SegmentData { vectors: Vec::with_capacity(records.len() / codec::record_len(dim) * dim), bad: Vec::new() };
+    for (i, rec) in records.chunks_exact(codec::record_len(dim)).enumerate() {
+        if !codec::decode_record_into(rec, dim, &mut seg.vectors) {
+            seg.vectors.resize(seg.vectors.len() + dim, 0.0);
+            seg.bad.push(i);
+        }
+    }
+    seg
+}
@@ -0,0 +1,61 @@
+use crate::{blocking, codec, ApiError, AppState};
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use reviews_types::{Int8Vector, VectorResp};
+use serde::Deserialize;
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Quantize {
+    /// One signed byte per dimension, scaled by the largest magnitude.
+    Int8,
+}
+
+#[derive(Deserialize)]
+pub struct VectorParams {
+    /// Alias or collection whose vector to read instead of the active index's.
+    collection: Option<String>,
+    quantize: Option<Quantize>,
+}
+
+/// GET /vectors/:id?collection=&quantize= — review `id`'s vector as the mirror file of the
+/// active index (or of `collection`) holds it, with the record's byte offset so a tool can
+/// check the file itself, and the payload the index file keeps with it (none once the review
+/// is no longer live); `quantize=int8` sends it scalar-quantized instead. 404 past the end
+/// of the mirror, 500 if the record fails its checksum.
+pub async fn get_vector(State(st): State<AppState>, Path(id): Path<usize>, Query(p): Query<VectorParams>) -> Result<Json<VectorResp>, ApiError> {
+    blocking(move || {
+        let active = st.target(p.collection.as_deref())?;
+        let vindex = &active.vindex;
+        if id >= vindex.len()? {
+            return Err(ApiError::not_found(format!("no vector {id}")));
+        }
+        let vector = vindex.read_mirror(id)?
+            .ok_or_else(|| anyhow::anyhow!("{}: record {id} fails its checksum", vindex.mirror_path().display()))?;
+        let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+        let live = st.meta.is_live(id);
+        let payload = if live { vindex.payload(id)?.map(|p| String::from_utf8_lossy(&p).into_owned()) } else { None };
+        let (vector, int8) = match p.quantize {
+            Some(Quantize::Int8) => (None, Some(int8(&vector))),
+            None => (Some(vector), None),
+        };
+        Ok(Json(VectorResp {
+            id,
+            dim: vindex.dim(),
+            live,
+            offset: codec::HEADER_LEN + (id * codec::record_len(vindex.dim())) as u64,
+            norm,
+            vector,
+            int8,
+            payload,
+        }))
+    }).await
+}
+
+fn int8(v: &[f32]) -> Int8Vector {
+    let max = v.iter().fold(0f32, |m, x| m.max(x.abs()));
+    let scale = if max > 0.0 { max / 127.0 } else { 1.0 };
+    Int8Vector { scale, values: v.iter().map(|x| (x / scale).round() as i8).collect() }
+}
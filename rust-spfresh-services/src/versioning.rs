@@ -0,0 +1,60 @@
+use crate::ApiError;
+use axum::{
+    extract::Request,
+    http::{HeaderValue, Uri},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+/// API versions mounted as `/v<N>`, oldest first. A breaking change (new id type, new
+/// search response shape, ...) ships as a new version with its own router, while the
+/// older ones keep their routes and handlers as they are.
+pub const VERSIONS: [&str; 1] = ["v1"];
+/// Served to unprefixed requests that don't ask for a version.
+pub const DEFAULT_VERSION: &str = "v1";
+/// Request header selecting the version of unprefixed paths, e.g. `X-API-Version: 1`;
+/// echoed on every response with the version that served it.
+const VERSION_HEADER: &str = "x-api-version";
+
+/// Runs in front of routing. Paths under `/v<N>/` pass through; any other path is
+/// rewritten under the version named in `X-API-Version`, or the default one, so clients
+/// written before the prefix existed keep working. Unknown versions get 400.
+pub async fn negotiate(mut req: Request, next: Next) -> Response {
+    let version = match version_prefix(req.uri().path()) {
+        Some(v) => v,
+        None => {
+            let asked = req.headers().get(VERSION_HEADER).and_then(|v| v.to_str().ok()).map(str::trim);
+            let version = match asked {
+                None => DEFAULT_VERSION,
+                Some(a) => match VERSIONS.iter().copied().find(|v| v[1..] == *a || *v == a) {
+                    Some(v) => v,
+                    None => {
+                        let msg = format!("unsupported API version '{a}'; supported: {}", VERSIONS.join(", "));
+                        return ApiError::bad_request(msg).into_response();
+                    }
+                },
+            };
+            match rewrite(req.uri(), version) {
+                Some(uri) => *req.uri_mut() = uri,
+                None => return ApiError::bad_request("invalid request path").into_response(),
+            }
+            version
+        }
+    };
+    let mut resp = next.run(req).await;
+    resp.headers_mut().insert(VERSION_HEADER, HeaderValue::from_static(&version[1..]));
+    resp
+}
+
+/// The version a path is mounted under, if it starts with `/v<N>` of a known version.
+fn version_prefix(path: &str) -> Option<&'static str> {
+    let rest = path.strip_prefix('/')?;
+    VERSIONS.iter().copied().find(|v| rest.strip_prefix(v).is_some_and(|r| r.is_empty() || r.starts_with('/')))
+}
+
+fn rewrite(uri: &Uri, version: &str) -> Option<Uri> {
+    let pq = uri.path_and_query().map(|pq| pq.as_str()).unwrap_or("/");
+    let mut parts = uri.clone().into_parts();
+    parts.path_and_query = Some(format!("/{version}{pq}").parse().ok()?);
+    Uri::from_parts(parts).ok()
+}
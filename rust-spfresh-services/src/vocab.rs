@@ -0,0 +1,62 @@
+use anyhow::Result;
+use parking_lot::{Mutex, RwLock};
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::Path,
+};
+
+const VOCAB_FILE: &str = "vocab.txt";
+
+/// Exact term -> dimension mapping for the TF-IDF embedder, so that no two terms share a
+/// dimension. Terms get the next free dimension the first time an indexed document uses
+/// them, up to `cap`; terms seen after that are ignored. Persisted next to the index as
+/// `vocab.txt`, one term per line in dimension order, appended as terms are added.
+pub struct Vocabulary {
+    cap: usize,
+    terms: RwLock<(HashMap<String, usize>, Vec<String>)>,
+    file: Mutex<File>,
+}
+
+impl Vocabulary {
+    pub fn open(dir: &Path, cap: usize) -> Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        let path = dir.join(VOCAB_FILE);
+        let (mut index, mut list) = (HashMap::new(), Vec::new());
+        if let Ok(f) = File::open(&path) {
+            for line in BufReader::new(f).lines() {
+                let term = line?;
+                index.insert(term.clone(), list.len());
+                list.push(term);
+            }
+        }
+        anyhow::ensure!(list.len() <= cap, "{}: {} terms exceed the dim of {}", path.display(), list.len(), cap);
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        tracing::info!("vocabulary {}: {} of {} terms", path.display(), list.len(), cap);
+        Ok(Self { cap, terms: RwLock::new((index, list)), file: Mutex::new(file) })
+    }
+
+    /// Dimension of `term`, if it has one.
+    pub fn get(&self, term: &str) -> Option<usize> {
+        self.terms.read().0.get(term).copied()
+    }
+
+    /// Dimension of `term`, assigning the next free one if it is new and the cap allows.
+    pub fn get_or_insert(&self, term: &str) -> Result<Option<usize>> {
+        if let Some(i) = self.get(term) { return Ok(Some(i)); }
+        let mut terms = self.terms.write();
+        if let Some(&i) = terms.0.get(term) { return Ok(Some(i)); }
+        if terms.1.len() >= self.cap { return Ok(None); }
+        // On disk before it is used, so a vector never refers to an unrecorded dimension.
+        self.file.lock().write_all(format!("{term}\n").as_bytes())?;
+        let i = terms.1.len();
+        terms.0.insert(term.to_string(), i);
+        terms.1.push(term.to_string());
+        Ok(Some(i))
+    }
+
+    pub fn term(&self, i: usize) -> Option<String> {
+        self.terms.read().1.get(i).cloned()
+    }
+}
@@ -0,0 +1,63 @@
+use crate::ApiError;
+use anyhow::Result;
+use parking_lot::Mutex;
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::Arc,
+};
+use tokio::sync::{mpsc, oneshot};
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// The one thread that appends reviews. Inserts, upserts, bulk and raw inserts and imports
+/// queue their write here and wait for its result (the ids assigned), so vectors and
+/// metadata lines are appended strictly in the order the writes were queued, one write at a
+/// time. The queue is bounded: once `capacity` writes wait, further writers wait to queue.
+///
+/// Each write runs holding the write gate, so the rarer writers still taking the gate
+/// themselves (deletes, moderation, purge, reindex, replication) never interleave with one.
+pub struct Writer {
+    tx: mpsc::Sender<Job>,
+}
+
+impl Writer {
+    pub fn spawn(gate: Arc<Mutex<()>>, capacity: usize) -> Result<Self> {
+        anyhow::ensure!(capacity > 0, "[limits] write_queue must be at least 1");
+        let (tx, mut rx) = mpsc::channel::<Job>(capacity);
+        std::thread::Builder::new()
+            .name("writer".into())
+            .spawn(move || {
+                while let Some(job) = rx.blocking_recv() {
+                    let _w = gate.lock();
+                    // A panicking write fails its own request only; the writer carries on.
+                    if panic::catch_unwind(AssertUnwindSafe(job)).is_err() {
+                        tracing::error!("a queued write panicked");
+                    }
+                }
+            })?;
+        Ok(Self { tx })
+    }
+
+    /// Queues `write`, waiting for room in the queue, and returns its result once it ran.
+    pub async fn submit<T: Send + 'static>(&self, write: impl FnOnce() -> Result<T, ApiError> + Send + 'static) -> Result<T, ApiError> {
+        let (job, done) = job(write);
+        self.tx.send(job).await.map_err(|_| stopped())?;
+        done.await.unwrap_or_else(|_| Err(failed()))
+    }
+
+    /// `submit` for callers off the async runtime (the blocking pool, offline commands).
+    pub fn submit_blocking<T: Send + 'static>(&self, write: impl FnOnce() -> Result<T, ApiError> + Send + 'static) -> Result<T, ApiError> {
+        let (job, done) = job(write);
+        self.tx.blocking_send(job).map_err(|_| stopped())?;
+        done.blocking_recv().unwrap_or_else(|_| Err(failed()))
+    }
+}
+
+fn job<T: Send + 'static>(write: impl FnOnce() -> Result<T, ApiError> + Send + 'static) -> (Job, oneshot::Receiver<Result<T, ApiError>>) {
+    let (tx, rx) = oneshot::channel();
+    (Box::new(move || { let _ = tx.send(write()); }), rx)
+}
+
+fn stopped() -> ApiError { ApiError::unavailable("writer stopped") }
+
+fn failed() -> ApiError { ApiError::from(anyhow::anyhow!("write failed")) }
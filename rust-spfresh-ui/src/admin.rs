@@ -0,0 +1,213 @@
+use gloo_net::http::Request;
+use leptos::*;
+use reviews_types::{Job, JobStatus, ReadOnly, VerifyResp};
+use std::time::Duration;
+
+use crate::app::post_json;
+
+/// How often the jobs list is refreshed while the Admin tab is open.
+const POLL: Duration = Duration::from_secs(1);
+/// Finished jobs listed under the running ones.
+const FINISHED_SHOWN: usize = 10;
+
+/// A maintenance operation started with a bare POST: (label, URL, confirmation asked first
+/// for the destructive ones).
+const OPERATIONS: [(&str, &str, Option<&str>); 5] = [
+    ("Reindex", "/api/admin/reindex",
+        Some("Re-embed every review into a new index and swap to it? Searches use the old index until it finishes.")),
+    ("Refresh IDF", "/api/admin/idf", None),
+    ("Snapshot", "/api/admin/snapshot", None),
+    ("Request compaction", "/api/admin/compact",
+        Some("Compact on the next start? Deleted reviews' ids are reclaimed and the surviving reviews are renumbered.")),
+    ("Purge deleted", "/api/admin/purge",
+        Some("Erase every deleted review for good? This cannot be undone.")),
+];
+
+/// The Admin tab: maintenance jobs, with their progress polled from /jobs while the tab is
+/// open, an integrity check and the read-only switch.
+#[component]
+pub fn AdminTab() -> impl IntoView {
+    let (jobs, set_jobs) = create_signal::<Vec<Job>>(vec![]);
+    let (read_only, set_read_only) = create_signal::<Option<bool>>(None);
+    let (busy, set_busy) = create_signal(false);
+    let (message, set_message) = create_signal(String::new());
+    let (err, set_err) = create_signal(String::new());
+    let (verify_resp, set_verify_resp) = create_signal::<Option<VerifyResp>>(None);
+
+    let refresh_jobs = move || spawn_local(async move {
+        // A failed poll keeps the last list; the next one tries again.
+        if let Ok(list) = get_json::<Vec<Job>>("/api/jobs").await { set_jobs.set(list) }
+    });
+    refresh_jobs();
+    spawn_local(async move {
+        match get_json::<ReadOnly>("/api/admin/read-only").await {
+            Ok(r) => set_read_only.set(Some(r.read_only)),
+            Err(e) => set_err.set(e),
+        }
+    });
+    if let Ok(handle) = set_interval_with_handle(refresh_jobs, POLL) {
+        on_cleanup(move || handle.clear());
+    }
+
+    let run = move |label: &'static str, url: &'static str, confirm: Option<&'static str>| {
+        if let Some(question) = confirm {
+            if !window().confirm_with_message(question).unwrap_or(false) { return; }
+        }
+        set_busy.set(true);
+        set_err.set(String::new());
+        set_message.set(String::new());
+        spawn_local(async move {
+            match post_empty(url).await {
+                Ok(text) => {
+                    set_message.set(format!("{}: {}", label, text));
+                    refresh_jobs();
+                }
+                Err(e) => set_err.set(e),
+            }
+            set_busy.set(false);
+        });
+    };
+
+    let verify = move |_| {
+        set_busy.set(true);
+        set_err.set(String::new());
+        set_verify_resp.set(None);
+        spawn_local(async move {
+            match get_json::<VerifyResp>("/api/admin/verify").await {
+                Ok(v) => set_verify_resp.set(Some(v)),
+                Err(e) => set_err.set(e),
+            }
+            set_busy.set(false);
+        });
+    };
+
+    let toggle_read_only = move |_| {
+        let on = !read_only.get_untracked().unwrap_or(false);
+        let question = if on {
+            "Turn on read-only mode? Inserts, updates and deletes are refused until it is turned off or the server restarts."
+        } else {
+            "Turn off read-only mode and accept writes again?"
+        };
+        if !window().confirm_with_message(question).unwrap_or(false) { return; }
+        set_busy.set(true);
+        set_err.set(String::new());
+        spawn_local(async move {
+            match post_json::<_, ReadOnly>("/api/admin/read-only", &serde_json::json!({ "read_only": on })).await {
+                Ok(r) => set_read_only.set(Some(r.read_only)),
+                Err(e) => set_err.set(e),
+            }
+            set_busy.set(false);
+        });
+    };
+
+    view! {
+        <div>
+            <div class="card">
+                <div style="font-weight:600;margin-bottom:8px;">"Maintenance"</div>
+                <div class="row">
+                    {OPERATIONS.iter().map(|&(label, url, confirm)| view!{
+                        <button class="btn" disabled=move || busy.get() on:click=move |_| run(label, url, confirm)>{label}</button>
+                    }).collect::<Vec<_>>()}
+                    <button class="btn" disabled=move || busy.get() on:click=verify>"Verify"</button>
+                </div>
+                <div class="row" style="margin-top:12px;">
+                    <span>{move || match read_only.get() {
+                        Some(true) => "Read-only: writes are refused",
+                        Some(false) => "Read-write",
+                        None => "Read-only mode unknown",
+                    }}</span>
+                    <button class="btn" disabled=move || busy.get() || read_only.get().is_none() on:click=toggle_read_only>
+                        {move || if read_only.get() == Some(true) {"Accept writes"} else {"Make read-only"}}
+                    </button>
+                </div>
+                <Show when=move || !message.get().is_empty()>
+                    <pre style="margin-top:8px;white-space:pre-wrap;">{message}</pre>
+                </Show>
+                <Show when=move || !err.get().is_empty()>
+                    {move || view!{<div class="danger" style="margin-top:8px;">{err.get()}</div>}}
+                </Show>
+            </div>
+
+            {move || verify_resp.get().map(verify_report)}
+
+            <div class="card" style="margin-top:16px;">
+                <div style="font-weight:600;margin-bottom:8px;">"Jobs"</div>
+                {move || {
+                    let (running, finished): (Vec<Job>, Vec<Job>) = jobs.get().into_iter().partition(|j| j.status == JobStatus::Running);
+                    if running.is_empty() && finished.is_empty() {
+                        return view!{<div style="color:var(--muted);">"No jobs since the server started."</div>}.into_view();
+                    }
+                    running.into_iter().chain(finished.into_iter().take(FINISHED_SHOWN)).map(job_row).collect::<Vec<_>>().into_view()
+                }}
+            </div>
+        </div>
+    }
+}
+
+fn job_row(j: Job) -> impl IntoView {
+    let progress = if j.total > 0 { format!("{} / {}", j.processed, j.total) } else { j.processed.to_string() };
+    let status_class = if j.status == JobStatus::Failed {"danger"} else {""};
+    let status = match j.status {
+        JobStatus::Running => "running",
+        JobStatus::Done => "done",
+        JobStatus::Failed => "failed",
+    };
+    let bar = (j.status == JobStatus::Running).then(|| view!{<progress style="width:100%;" max=j.total.max(1).to_string() value=j.processed.to_string()></progress>});
+    view! {
+        <div class="job">
+            <div class="row" style="justify-content:space-between;">
+                <span><b>{j.kind.clone()}</b>" "<span style="color:var(--muted);font-size:12px;">{j.id.to_string()}</span></span>
+                <span class=status_class>{status}" · "{progress}</span>
+            </div>
+            {bar}
+            {j.error.clone().map(|e| view!{<div class="danger">{e}</div>})}
+        </div>
+    }
+}
+
+fn verify_report(v: VerifyResp) -> impl IntoView {
+    view! {
+        <div class="card" style="margin-top:16px;">
+            <div style="font-weight:600;margin-bottom:8px;">{format!("Verify: {} reviews", v.reviews)}</div>
+            {if v.problems.is_empty() {
+                view!{<div>"No problems found."</div>}.into_view()
+            } else {
+                view!{
+                    <ul class="danger">{v.problems.into_iter().map(|p| view!{<li>{p}</li>}).collect::<Vec<_>>()}</ul>
+                    <div style="color:var(--muted);font-size:12px;">"Torn records and count mismatches are repaired when the server restarts."</div>
+                }.into_view()
+            }}
+            <table style="margin-top:8px;">
+                <thead><tr><th>"Index"</th><th>"Vectors"</th><th>"Corrupt"</th><th>"Torn bytes"</th></tr></thead>
+                <tbody>
+                    {v.indexes.into_iter().map(|i| view!{
+                        <tr>
+                            <td>{if i.dir.is_empty() { "(data dir)".to_string() } else { i.dir }}{if i.active {" (active)"} else {""}}</td>
+                            <td>{i.vectors}</td>
+                            <td>{i.corrupt}</td>
+                            <td>{i.partial_bytes}</td>
+                        </tr>
+                    }).collect::<Vec<_>>()}
+                </tbody>
+            </table>
+        </div>
+    }
+}
+
+/// GETs `url` and parses the JSON reply; errors as shown to the user.
+async fn get_json<R: serde::de::DeserializeOwned>(url: &str) -> Result<R, String> {
+    let r = Request::get(url).send().await.map_err(|e| format!("fetch error: {}", e))?;
+    let status = r.status();
+    let text = r.text().await.unwrap_or_default();
+    if status >= 400 { return Err(format!("HTTP {}: {}", status, text)); }
+    serde_json::from_str(&text).map_err(|e| format!("bad response: {}", e))
+}
+
+/// POSTs nothing to `url`, returning the reply's text.
+async fn post_empty(url: &str) -> Result<String, String> {
+    let r = Request::post(url).send().await.map_err(|e| format!("fetch error: {}", e))?;
+    let status = r.status();
+    let text = r.text().await.unwrap_or_default();
+    if status >= 400 { return Err(format!("HTTP {}: {}", status, text)); }
+    Ok(text)
+}
@@ -1,26 +1,48 @@
 use gloo_net::http::Request;
 use leptos::*;
-use serde::{Deserialize, Serialize};
+use reviews_types::{
+    BulkInsertReq, BulkResp, CompareReq, CompareResp, InsertReq, PointLabel, ProductComparison, ProductInfo, ProductsResp,
+    ProjectedPoint, ProjectionReq, ProjectionResp, RatingSummary, Review, SearchHit, SearchReq, SearchResp,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::HashMap;
+use wasm_bindgen::JsCast;
+
+use crate::admin::AdminTab;
+use crate::history::{self, Entry};
+use crate::virtual_list::{self, VirtualList};
 
 #[derive(Clone, Copy, PartialEq)]
-enum Tab { Insert, Bulk, Search }
-
-#[derive(Serialize, Deserialize, Debug, Clone, Default)]
-struct ReviewPayload {
-    review_title: String,
-    review_body: String,
-    product_id: String,
-    review_rating: i32,
-}
+enum Tab { Insert, Bulk, Search, Compare, Visualize, Admin }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Default)]
-struct InsertRequest { review: ReviewPayload }
+/// The tabs in order, with their names; keys 1 to 6 switch to them.
+const TABS: [(Tab, &str); 6] = [
+    (Tab::Insert, "Insert Review"), (Tab::Bulk, "Bulk Insert"), (Tab::Search, "Search"), (Tab::Compare, "Compare"),
+    (Tab::Visualize, "Visualize"), (Tab::Admin, "Admin"),
+];
 
-#[derive(Serialize, Deserialize, Debug, Clone, Default)]
-struct BulkRequest { reviews: Vec<ReviewPayload> }
+/// What the command palette can do.
+#[derive(Clone, Copy, PartialEq)]
+enum Command { Go(Tab), FocusSearch, Submit, ToggleSideBySide, ShowHistory }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Default)]
-struct SearchRequest { query: String, top_k: i32 }
+/// Scatter plot size in SVG units; it scales to the card's width.
+const PLOT_W: f64 = 640.0;
+const PLOT_H: f64 = 440.0;
+const PLOT_PAD: f64 = 12.0;
+/// Colours of labels other than ratings, picked by a hash of the label.
+const PALETTE: [&str; 10] = [
+    "#4f46e5", "#dc2626", "#16a34a", "#d97706", "#0891b2", "#db2777", "#65a30d", "#7c3aed", "#ea580c", "#0d9488",
+];
+/// Legend entries shown, the most frequent labels.
+const LEGEND_MAX: usize = 12;
+/// A search hit's row, cut to a title line, two lines of body and the score line, and the
+/// height of the scrolling list of them.
+const HIT_ROW_H: f64 = 100.0;
+const HITS_VIEW_H: f64 = 600.0;
+/// A bulk table row: its 60px textarea, the cell padding and the border below; the table
+/// scrolls within `BULK_VIEW_H`.
+const BULK_ROW_H: f64 = 77.0;
+const BULK_VIEW_H: f64 = 480.0;
 
 #[component]
 pub fn App() -> impl IntoView {
@@ -36,27 +58,88 @@ pub fn App() -> impl IntoView {
     let (insert_err, set_insert_err) = create_signal(String::new());
 
     // Bulk state
-    let (bulk_items, set_bulk_items) = create_signal::<Vec<ReviewPayload>>(vec![]);
+    let (bulk_items, set_bulk_items) = create_signal::<Vec<Review>>(vec![]);
     let (bulk_loading, set_bulk_loading) = create_signal(false);
     let (bulk_resp, set_bulk_resp) = create_signal(String::new());
     let (bulk_err, set_bulk_err) = create_signal(String::new());
+    // Only the table rows scrolled into view are rendered.
+    let (bulk_scroll, set_bulk_scroll) = create_signal(0.0f64);
+    let bulk_window = create_memo(move |_| {
+        virtual_list::visible_rows(bulk_items.with(Vec::len), BULK_ROW_H, bulk_scroll.get(), BULK_VIEW_H)
+    });
+    // Chunked upload: `upload_rows` (a loaded file, else a copy of the table) go up
+    // `batch_size` at a time from `upload_next`, so a cancelled or failed upload resumes there.
+    let upload_rows = create_rw_signal::<Vec<Review>>(vec![]);
+    let (upload_file, set_upload_file) = create_signal(String::new());
+    let (batch_size, set_batch_size) = create_signal(500usize);
+    let (upload_next, set_upload_next) = create_signal(0usize);
+    let (upload_inserted, set_upload_inserted) = create_signal(0usize);
+    let (upload_cancel, set_upload_cancel) = create_signal(false);
+    let (rows_per_sec, set_rows_per_sec) = create_signal(0.0f64);
 
     // Search state
     let (query, set_query) = create_signal(String::new());
-    let (top_k, set_top_k) = create_signal(3);
+    let (top_k, set_top_k) = create_signal(3usize);
     let (search_loading, set_search_loading) = create_signal(false);
     let (search_resp, set_search_resp) = create_signal(String::new());
+    // The response's hits, listed under it.
+    let (search_hits, set_search_hits) = create_signal::<Vec<SearchHit>>(vec![]);
     let (search_err, set_search_err) = create_signal(String::new());
+    // Side by side: `query` on the left, `query_b` (or `query` on the shadow index) on the right.
+    let (side_by_side, set_side_by_side) = create_signal(false);
+    let (vs_shadow, set_vs_shadow) = create_signal(false);
+    let (query_b, set_query_b) = create_signal(String::new());
+    let (sbs_resp, set_sbs_resp) = create_signal::<Option<(SearchColumn, SearchColumn)>>(None);
+    // Filters, comma-separated language codes and whether to include unapproved reviews.
+    let (langs, set_langs) = create_signal(String::new());
+    let (unapproved, set_unapproved) = create_signal(false);
+    // Structured filters: a product ("" for any), a rating range and `created_at` dates
+    // as `<input type="date">` values ("" for unbounded).
+    let (products, set_products) = create_signal::<Vec<ProductInfo>>(vec![]);
+    let (product, set_product) = create_signal(String::new());
+    let (min_rating, set_min_rating) = create_signal(1);
+    let (max_rating, set_max_rating) = create_signal(5);
+    let (date_from, set_date_from) = create_signal(String::new());
+    let (date_to, set_date_to) = create_signal(String::new());
+    // The product selector's options; without them only "Any product" is offered.
+    spawn_local(async move {
+        if let Ok(r) = Request::get("/api/products").send().await {
+            if let Ok(p) = r.json::<ProductsResp>().await { set_products.set(p.products); }
+        }
+    });
+    // Recent and saved searches, kept in localStorage.
+    let search_history = create_rw_signal(history::load());
+    let (show_history, set_show_history) = create_signal(false);
+    create_effect(move |_| search_history.with(|h| history::store(h)));
+
+    // Compare state
+    let (cmp_a, set_cmp_a) = create_signal(String::new());
+    let (cmp_b, set_cmp_b) = create_signal(String::new());
+    let (cmp_query, set_cmp_query) = create_signal(String::new());
+    let (cmp_k, set_cmp_k) = create_signal(5usize);
+    let (cmp_loading, set_cmp_loading) = create_signal(false);
+    let (cmp_resp, set_cmp_resp) = create_signal::<Option<CompareResp>>(None);
+    let (cmp_err, set_cmp_err) = create_signal(String::new());
+
+    // Visualize state
+    let (viz_sample, set_viz_sample) = create_signal(1000usize);
+    let (viz_label, set_viz_label) = create_signal(PointLabel::Rating);
+    let (viz_loading, set_viz_loading) = create_signal(false);
+    // With the label the points were fetched with, which the select may have moved on from.
+    let (viz_resp, set_viz_resp) = create_signal::<Option<(PointLabel, ProjectionResp)>>(None);
+    let (viz_err, set_viz_err) = create_signal(String::new());
+    let (viz_hover, set_viz_hover) = create_signal::<Option<ProjectedPoint>>(None);
 
     // ---- Actions (ผ่าน proxy => /api/... -> localhost:8000) ----
-    let do_insert = move |_| {
+    let submit_insert = move || {
         let url = "/api/reviews";
-        let payload = InsertRequest { review: ReviewPayload {
+        let payload = InsertReq { review: Review {
             review_title: title.get_untracked(),
             review_body: body.get_untracked(),
             product_id: pid.get_untracked(),
             review_rating: rating.get_untracked(),
-        }};
+            ..Default::default()
+        }, return_vector: false };
         set_insert_loading.set(true);
         set_insert_err.set(String::new());
         set_insert_resp.set(String::new());
@@ -78,39 +161,147 @@ pub fn App() -> impl IntoView {
         });
     };
 
-    let add_bulk_row = move |_| set_bulk_items.update(|v| v.push(ReviewPayload::default()));
+    let add_bulk_row = move |_| set_bulk_items.update(|v| v.push(Review::default()));
     let remove_bulk_row = move |idx: usize| set_bulk_items.update(|v| { if idx < v.len() { v.remove(idx); } });
 
-    let do_bulk = move |_| {
+    // Reads a JSON or NDJSON file of reviews; its rows replace the table's as what gets uploaded.
+    let load_file = move |ev: ev::Event| {
+        let input: web_sys::HtmlInputElement = event_target(&ev);
+        let Some(file) = input.files().and_then(|f| f.get(0)) else { return };
+        let Ok(reader) = web_sys::FileReader::new() else { return };
+        let name = file.name();
+        let (r, shown) = (reader.clone(), name.clone());
+        let onload = wasm_bindgen::closure::Closure::once_into_js(move || {
+            let text = r.result().ok().and_then(|v| v.as_string()).unwrap_or_default();
+            match parse_reviews(&text) {
+                Ok(rows) => {
+                    upload_rows.set(rows);
+                    set_upload_file.set(name);
+                    set_upload_next.set(0);
+                    set_upload_inserted.set(0);
+                    set_bulk_err.set(String::new());
+                    set_bulk_resp.set(String::new());
+                }
+                Err(e) => set_bulk_err.set(format!("{}: {}", name, e)),
+            }
+        });
+        reader.set_onload(Some(onload.unchecked_ref()));
+        if let Err(e) = reader.read_as_text(&file) { set_bulk_err.set(format!("cannot read {}: {:?}", shown, e)); }
+    };
+    let clear_file = move |_| {
+        upload_rows.set(vec![]);
+        set_upload_file.set(String::new());
+        set_upload_next.set(0);
+        set_upload_inserted.set(0);
+    };
+
+    // Uploads from row 0, or with `resume` from where the last run stopped, one batch at a
+    // time until done, cancelled (after the batch in flight) or a batch fails.
+    let upload = move |resume: bool| {
         let url = "/api/reviews/bulk";
-        let payload = BulkRequest { reviews: bulk_items.get_untracked() };
+        if !resume {
+            if upload_file.get_untracked().is_empty() { upload_rows.set(bulk_items.get_untracked()); }
+            set_upload_next.set(0);
+            set_upload_inserted.set(0);
+        }
+        let total = upload_rows.with_untracked(Vec::len);
+        if total == 0 {
+            set_bulk_err.set("nothing to upload".to_string());
+            return;
+        }
+        set_upload_cancel.set(false);
         set_bulk_loading.set(true);
         set_bulk_err.set(String::new());
         set_bulk_resp.set(String::new());
+        set_rows_per_sec.set(0.0);
         spawn_local(async move {
-            let resp = Request::post(url)
-                .header("Content-Type", "application/json")
-                .json(&payload).unwrap()
-                .send().await;
-            match resp {
-                Ok(r) => {
-                    let status = r.status();
-                    let text = r.text().await.unwrap_or_default();
-                    if status >= 400 { set_bulk_err.set(format!("HTTP {}: {}", status, text)); }
-                    else { set_bulk_resp.set(text); }
+            let (started, mut sent) = (now_ms(), 0usize);
+            loop {
+                let next = upload_next.get_untracked();
+                if next >= total || upload_cancel.get_untracked() { break; }
+                let end = (next + batch_size.get_untracked().max(1)).min(total);
+                let payload = BulkInsertReq { reviews: upload_rows.with_untracked(|rows| rows[next..end].to_vec()) };
+                match post_json::<_, BulkResp>(url, &payload).await {
+                    Ok(r) => {
+                        set_upload_inserted.update(|n| *n += r.inserted);
+                        set_upload_next.set(end);
+                        sent += end - next;
+                        let secs = (now_ms() - started) / 1000.0;
+                        if secs > 0.0 { set_rows_per_sec.set(sent as f64 / secs); }
+                    }
+                    Err(e) => {
+                        set_bulk_err.set(format!("rows {}-{}: {}", next + 1, end, e));
+                        break;
+                    }
                 }
-                Err(e) => set_bulk_err.set(format!("fetch error: {}", e)),
             }
+            let (done, inserted) = (upload_next.get_untracked(), upload_inserted.get_untracked());
+            set_bulk_resp.set(if done >= total {
+                format!("{} rows uploaded, {} inserted", total, inserted)
+            } else {
+                format!("stopped after {} of {} rows ({} inserted); resume to continue from row {}", done, total, inserted, done + 1)
+            });
             set_bulk_loading.set(false);
         });
     };
+    let do_bulk = move |_| upload(false);
+    let resume_bulk = move |_| upload(true);
 
-    let do_search = move |_| {
+    let run_search = move || {
         let url = "/api/search";
-        let payload = SearchRequest { query: query.get_untracked(), top_k: top_k.get_untracked() };
+        let payload = SearchReq {
+            lang: langs.get_untracked().split(',').map(|l| l.trim().to_string()).filter(|l| !l.is_empty()).collect(),
+            include_unapproved: unapproved.get_untracked(),
+            product_ids: Some(product.get_untracked()).filter(|p| !p.is_empty()).into_iter().collect(),
+            min_rating: Some(min_rating.get_untracked()).filter(|&r| r > 1),
+            max_rating: Some(max_rating.get_untracked()).filter(|&r| r < 5),
+            created_after: date_to_secs(&date_from.get_untracked()),
+            // The "to" date is included.
+            created_before: date_to_secs(&date_to.get_untracked()).map(|s| s + DAY_SECS),
+            ..SearchReq::new(query.get_untracked()).top_k(top_k.get_untracked())
+        };
+        search_history.update(|h| history::record(h, Entry {
+            name: None,
+            req: payload.clone(),
+            side_by_side: side_by_side.get_untracked(),
+            vs_shadow: vs_shadow.get_untracked(),
+            query_b: query_b.get_untracked(),
+        }));
+        set_show_history.set(false);
         set_search_loading.set(true);
         set_search_err.set(String::new());
         set_search_resp.set(String::new());
+        set_search_hits.set(vec![]);
+        set_sbs_resp.set(None);
+        if side_by_side.get_untracked() {
+            let (query_a, shadow) = (payload.query.clone(), vs_shadow.get_untracked());
+            let payload_b = SearchReq { query: query_b.get_untracked(), ..payload.clone() };
+            spawn_local(async move {
+                let columns = if shadow {
+                    post_json::<_, SearchResp>(url, &SearchReq { compare: true, ..payload }).await.and_then(|r| match r.shadow_hits {
+                        Some(shadow_hits) => Ok((
+                            SearchColumn { heading: format!("\"{}\" on the active index", query_a), hits: r.hits },
+                            SearchColumn { heading: format!("\"{}\" on the shadow index", query_a), hits: shadow_hits },
+                        )),
+                        None => Err("no shadow index is configured".to_string()),
+                    })
+                } else {
+                    match post_json::<_, SearchResp>(url, &payload).await {
+                        Ok(a) => post_json::<_, SearchResp>(url, &payload_b).await.map(|b| (
+                            SearchColumn { heading: format!("\"{}\"", query_a), hits: a.hits },
+                            SearchColumn { heading: format!("\"{}\"", payload_b.query), hits: b.hits },
+                        )),
+                        Err(e) => Err(e),
+                    }
+                };
+                match columns {
+                    Ok(c) => set_sbs_resp.set(Some(c)),
+                    Err(e) => set_search_err.set(e),
+                }
+                set_search_loading.set(false);
+            });
+            return;
+        }
         spawn_local(async move {
             let resp = Request::post(url)
                 .header("Content-Type", "application/json")
@@ -121,7 +312,10 @@ pub fn App() -> impl IntoView {
                     let status = r.status();
                     let text = r.text().await.unwrap_or_default();
                     if status >= 400 { set_search_err.set(format!("HTTP {}: {}", status, text)); }
-                    else { set_search_resp.set(text); }
+                    else {
+                        if let Ok(r) = serde_json::from_str::<SearchResp>(&text) { set_search_hits.set(r.hits); }
+                        set_search_resp.set(text);
+                    }
                 }
                 Err(e) => set_search_err.set(format!("fetch error: {}", e)),
             }
@@ -129,6 +323,188 @@ pub fn App() -> impl IntoView {
         });
     };
 
+    let do_search = move |_| run_search();
+
+    // Puts a history entry's settings back in the form and runs it again.
+    let rerun = move |e: Entry| {
+        set_query.set(e.req.query);
+        set_top_k.set(e.req.top_k.unwrap_or(3));
+        set_langs.set(e.req.lang.join(", "));
+        set_unapproved.set(e.req.include_unapproved);
+        set_product.set(e.req.product_ids.first().cloned().unwrap_or_default());
+        set_min_rating.set(e.req.min_rating.unwrap_or(1));
+        set_max_rating.set(e.req.max_rating.unwrap_or(5));
+        set_date_from.set(e.req.created_after.map(secs_to_date).unwrap_or_default());
+        set_date_to.set(e.req.created_before.map(|s| secs_to_date(s.saturating_sub(DAY_SECS))).unwrap_or_default());
+        set_side_by_side.set(e.side_by_side);
+        set_vs_shadow.set(e.vs_shadow);
+        set_query_b.set(e.query_b);
+        run_search();
+    };
+    let save_entry = move |i: usize, current: String| {
+        let name = window().prompt_with_message_and_default("Name this search", &current).ok().flatten();
+        if let Some(name) = name.map(|n| n.trim().to_string()).filter(|n| !n.is_empty()) {
+            search_history.update(|h| history::save(h, i, name));
+        }
+    };
+
+    let do_insert = move |_| submit_insert();
+
+    let run_compare = move || {
+        let url = "/api/compare";
+        let payload = CompareReq {
+            top_k: Some(cmp_k.get_untracked()),
+            ..CompareReq::new(cmp_a.get_untracked(), cmp_b.get_untracked(), cmp_query.get_untracked())
+        };
+        set_cmp_loading.set(true);
+        set_cmp_err.set(String::new());
+        set_cmp_resp.set(None);
+        spawn_local(async move {
+            let resp = Request::post(url)
+                .header("Content-Type", "application/json")
+                .json(&payload).unwrap()
+                .send().await;
+            match resp {
+                Ok(r) => {
+                    let status = r.status();
+                    let text = r.text().await.unwrap_or_default();
+                    if status >= 400 { set_cmp_err.set(format!("HTTP {}: {}", status, text)); }
+                    else {
+                        match serde_json::from_str::<CompareResp>(&text) {
+                            Ok(c) => set_cmp_resp.set(Some(c)),
+                            Err(e) => set_cmp_err.set(format!("bad response: {}", e)),
+                        }
+                    }
+                }
+                Err(e) => set_cmp_err.set(format!("fetch error: {}", e)),
+            }
+            set_cmp_loading.set(false);
+        });
+    };
+
+    let do_compare = move |_| run_compare();
+
+    let run_visualize = move || {
+        let url = "/api/analytics/projection";
+        let by = viz_label.get_untracked();
+        let payload = ProjectionReq { sample: Some(viz_sample.get_untracked()), label: by, titles: true, ..Default::default() };
+        set_viz_loading.set(true);
+        set_viz_err.set(String::new());
+        set_viz_hover.set(None);
+        spawn_local(async move {
+            let resp = Request::post(url)
+                .header("Content-Type", "application/json")
+                .json(&payload).unwrap()
+                .send().await;
+            match resp {
+                Ok(r) => {
+                    let status = r.status();
+                    let text = r.text().await.unwrap_or_default();
+                    if status >= 400 { set_viz_err.set(format!("HTTP {}: {}", status, text)); }
+                    else {
+                        match serde_json::from_str::<ProjectionResp>(&text) {
+                            Ok(p) => set_viz_resp.set(Some((by, p))),
+                            Err(e) => set_viz_err.set(format!("bad response: {}", e)),
+                        }
+                    }
+                }
+                Err(e) => set_viz_err.set(format!("fetch error: {}", e)),
+            }
+            set_viz_loading.set(false);
+        });
+    };
+
+    let do_visualize = move |_| run_visualize();
+
+    // ---- Keyboard: "/" focuses the search box, Cmd/Ctrl+Enter submits the current tab,
+    // 1-6 switch tabs and Cmd/Ctrl+K opens the command palette ----
+    let query_ref = create_node_ref::<html::Input>();
+    let palette_ref = create_node_ref::<html::Input>();
+    let (palette_open, set_palette_open) = create_signal(false);
+    let (palette_query, set_palette_query) = create_signal(String::new());
+    let (palette_sel, set_palette_sel) = create_signal(0usize);
+
+    let focus_search = move || {
+        set_tab.set(Tab::Search);
+        // The Search tab's input exists once the switch has rendered.
+        request_animation_frame(move || { if let Some(input) = query_ref.get_untracked() { let _ = input.focus(); } });
+    };
+    let submit = move || match tab.get_untracked() {
+        Tab::Insert => if !insert_loading.get_untracked() { submit_insert() },
+        Tab::Bulk => if !bulk_loading.get_untracked() { upload(false) },
+        Tab::Search => if !search_loading.get_untracked() { run_search() },
+        Tab::Compare => if !cmp_loading.get_untracked() { run_compare() },
+        Tab::Visualize => if !viz_loading.get_untracked() { run_visualize() },
+        // Nothing to submit; each operation has its own button.
+        Tab::Admin => {}
+    };
+    let open_palette = move || {
+        set_palette_query.set(String::new());
+        set_palette_sel.set(0);
+        set_palette_open.set(true);
+        request_animation_frame(move || { if let Some(input) = palette_ref.get_untracked() { let _ = input.focus(); } });
+    };
+    let run_command = move |c: Command| {
+        set_palette_open.set(false);
+        match c {
+            Command::Go(t) => set_tab.set(t),
+            Command::FocusSearch => focus_search(),
+            Command::Submit => submit(),
+            Command::ToggleSideBySide => {
+                set_side_by_side.update(|v| *v = !*v);
+                focus_search();
+            }
+            Command::ShowHistory => {
+                set_show_history.set(true);
+                focus_search();
+            }
+        }
+    };
+    let _ = window_event_listener(ev::keydown, move |ev| {
+        let typing = ev.target().and_then(|t| t.dyn_into::<web_sys::Element>().ok())
+            .is_some_and(|e| matches!(e.tag_name().as_str(), "INPUT" | "TEXTAREA" | "SELECT"));
+        let modifier = ev.meta_key() || ev.ctrl_key();
+        match ev.key().as_str() {
+            "k" | "K" if modifier => {
+                ev.prevent_default();
+                if palette_open.get_untracked() { set_palette_open.set(false) } else { open_palette() }
+            }
+            "Enter" if modifier => {
+                ev.prevent_default();
+                submit();
+            }
+            "Escape" if palette_open.get_untracked() => set_palette_open.set(false),
+            _ if typing || modifier || ev.alt_key() || palette_open.get_untracked() => {}
+            "/" => {
+                ev.prevent_default();
+                focus_search();
+            }
+            key => {
+                let n = key.parse::<usize>().ok().and_then(|n| n.checked_sub(1));
+                if let Some(&(t, _)) = n.and_then(|i| TABS.get(i)) { set_tab.set(t) }
+            }
+        }
+    });
+    let palette_key = move |ev: ev::KeyboardEvent| {
+        let n = palette_matches(&palette_query.get_untracked()).len();
+        match ev.key().as_str() {
+            "ArrowDown" if n > 0 => {
+                ev.prevent_default();
+                set_palette_sel.update(|i| *i = (*i + 1) % n);
+            }
+            "ArrowUp" if n > 0 => {
+                ev.prevent_default();
+                set_palette_sel.update(|i| *i = (*i + n - 1) % n);
+            }
+            "Enter" => {
+                ev.prevent_default();
+                let picked = palette_matches(&palette_query.get_untracked()).get(palette_sel.get_untracked()).map(|&(_, c)| c);
+                if let Some(c) = picked { run_command(c) }
+            }
+            _ => {}
+        }
+    };
+
     view! {
         <div class="wrap">
             <header class="row" style="justify-content:space-between;margin-bottom:16px;">
@@ -139,6 +515,9 @@ pub fn App() -> impl IntoView {
                 <button class=move || if tab.get() == Tab::Insert {"active"} else {""} on:click=move |_| set_tab.set(Tab::Insert)>"Insert Review"</button>
                 <button class=move || if tab.get() == Tab::Bulk {"active"} else {""} on:click=move |_| set_tab.set(Tab::Bulk)>"Bulk Insert"</button>
                 <button class=move || if tab.get() == Tab::Search {"active"} else {""} on:click=move |_| set_tab.set(Tab::Search)>"Search"</button>
+                <button class=move || if tab.get() == Tab::Compare {"active"} else {""} on:click=move |_| set_tab.set(Tab::Compare)>"Compare"</button>
+                <button class=move || if tab.get() == Tab::Visualize {"active"} else {""} on:click=move |_| set_tab.set(Tab::Visualize)>"Visualize"</button>
+                <button class=move || if tab.get() == Tab::Admin {"active"} else {""} on:click=move |_| set_tab.set(Tab::Admin)>"Admin"</button>
             </div>
 
             {move || match tab.get() {
@@ -186,31 +565,69 @@ pub fn App() -> impl IntoView {
                         <div class="row" style="justify-content:space-between;margin-bottom:8px;">
                             <div style="font-weight:600;">"Bulk Insert Reviews"</div>
                             <div class="row">
-                                <button on:click=add_bulk_row>"+ Add Row"</button>
+                                <Show when=move || upload_file.get().is_empty()>
+                                    <button on:click=add_bulk_row>"+ Add Row"</button>
+                                </Show>
+                                <label class="row" style="flex-direction:row;">
+                                    <span>"Batch"</span>
+                                    <input type="number" style="width:90px;" prop:value=move || batch_size.get().to_string() on:input=move |ev| if let Ok(v)=event_target_value(&ev).parse(){ set_batch_size.set(v) } />
+                                </label>
                                 <button class="btn" on:click=do_bulk disabled=move || bulk_loading.get()>
                                     {move || if bulk_loading.get() {"Submitting..."} else {"Submit Bulk"}}
                                 </button>
+                                <Show when=move || bulk_loading.get()>
+                                    <button on:click=move |_| set_upload_cancel.set(true) disabled=move || upload_cancel.get()>
+                                        {move || if upload_cancel.get() {"Cancelling..."} else {"Cancel"}}
+                                    </button>
+                                </Show>
+                                <Show when=move || !bulk_loading.get() && upload_next.get() > 0 && upload_next.get() < upload_rows.with(Vec::len)>
+                                    <button on:click=resume_bulk>"Resume"</button>
+                                </Show>
                             </div>
                         </div>
-                        <div style="overflow:auto;">
-                            <table>
-                                <thead><tr><th>Title</th><th>Body</th><th>Product ID</th><th>Rating</th><th>Actions</th></tr></thead>
-                                <tbody>
-                                    {move || {
-                                        let items = bulk_items.get();
-                                        items.into_iter().enumerate().map(|(i, it)| view!{
-                                            <tr>
-                                                <td><input prop:value=it.review_title on:input=move |ev| set_bulk_items.update(|v| v[i].review_title = event_target_value(&ev)) /></td>
-                                                <td><textarea on:input=move |ev| set_bulk_items.update(|v| v[i].review_body = event_target_value(&ev))>{it.review_body}</textarea></td>
-                                                <td><input prop:value=it.product_id on:input=move |ev| set_bulk_items.update(|v| v[i].product_id = event_target_value(&ev)) /></td>
-                                                <td><input type="number" prop:value=it.review_rating.to_string() on:input=move |ev| if let Ok(v)=event_target_value(&ev).parse(){ set_bulk_items.update(|vct| vct[i].review_rating = v); } /></td>
-                                                <td><button on:click=move |_| remove_bulk_row(i)>"Remove"</button></td>
-                                            </tr>
-                                        }).collect::<Vec<_>>()
-                                    }}
-                                </tbody>
-                            </table>
+                        <div class="row" style="margin-bottom:8px;">
+                            <label class="row" style="flex-direction:row;">
+                                <span>"Load file (JSON array or NDJSON)"</span>
+                                <input type="file" accept=".json,.jsonl,.ndjson" on:change=load_file />
+                            </label>
+                            <Show when=move || !upload_file.get().is_empty()>
+                                <span>{move || format!("{} rows from {}", upload_rows.with(Vec::len), upload_file.get())}</span>
+                                <button on:click=clear_file disabled=move || bulk_loading.get()>"Clear file"</button>
+                            </Show>
                         </div>
+                        <Show when=move || bulk_loading.get() || upload_next.get() > 0>
+                            <div style="margin-bottom:8px;">
+                                <progress style="width:100%;" max=move || upload_rows.with(Vec::len).max(1).to_string() value=move || upload_next.get().to_string()></progress>
+                                <div style="color:var(--muted);font-size:12px;">
+                                    {move || format!("{} / {} rows, {:.0} rows/s", upload_next.get(), upload_rows.with(Vec::len), rows_per_sec.get())}
+                                </div>
+                            </div>
+                        </Show>
+                        <Show when=move || upload_file.get().is_empty()>
+                            <div style=format!("overflow:auto;max-height:{}px;", BULK_VIEW_H)
+                                on:scroll=move |ev| set_bulk_scroll.set(event_target::<web_sys::Element>(&ev).scroll_top() as f64)>
+                                <table class="bulk">
+                                    <thead><tr><th>Title</th><th>Body</th><th>Product ID</th><th>Rating</th><th>Actions</th></tr></thead>
+                                    <tbody>
+                                        {move || {
+                                            let w = bulk_window.get();
+                                            let items = bulk_items.with(|v| v[w.rows.clone()].to_vec());
+                                            let spacer = |px: f64| view!{ <tr><td colspan="5" style=format!("height:{}px;padding:0;border:0;", px)></td></tr> };
+                                            let rows = items.into_iter().zip(w.rows.clone()).map(|(it, i)| view!{
+                                                <tr>
+                                                    <td><input prop:value=it.review_title on:input=move |ev| set_bulk_items.update(|v| v[i].review_title = event_target_value(&ev)) /></td>
+                                                    <td><textarea on:input=move |ev| set_bulk_items.update(|v| v[i].review_body = event_target_value(&ev))>{it.review_body}</textarea></td>
+                                                    <td><input prop:value=it.product_id on:input=move |ev| set_bulk_items.update(|v| v[i].product_id = event_target_value(&ev)) /></td>
+                                                    <td><input type="number" prop:value=it.review_rating.to_string() on:input=move |ev| if let Ok(v)=event_target_value(&ev).parse(){ set_bulk_items.update(|vct| vct[i].review_rating = v); } /></td>
+                                                    <td><button on:click=move |_| remove_bulk_row(i)>"Remove"</button></td>
+                                                </tr>
+                                            }).collect::<Vec<_>>();
+                                            view!{ {spacer(w.before)} {rows} {spacer(w.after)} }
+                                        }}
+                                    </tbody>
+                                </table>
+                            </div>
+                        </Show>
                         <Show when=move || !bulk_err.get().is_empty()>
                             {move || view!{<div class="danger" style="margin-top:8px;">{bulk_err.get()}</div>}}
                         </Show>
@@ -221,37 +638,479 @@ pub fn App() -> impl IntoView {
                     </div>
                 }.into_view(),
                 Tab::Search => view! {
-                    <div class="grid cols-2">
+                    <div>
+                        <div class="grid cols-2">
+                            <div class="card">
+                                <div style="font-weight:600;margin-bottom:8px;">"Search Reviews"</div>
+                                <label>
+                                    <span>{move || if side_by_side.get() && !vs_shadow.get() {"Query A"} else {"Query"}}</span>
+                                    <input node_ref=query_ref prop:value=move || query.get() on:input=move |ev| set_query.set(event_target_value(&ev)) />
+                                </label>
+                                <div style="margin-bottom:6px;">
+                                    <button on:click=move |_| set_show_history.update(|v| *v = !*v)>
+                                        {move || format!("History ({})", search_history.with(|h| h.len()))}
+                                    </button>
+                                </div>
+                                <Show when=move || show_history.get()>
+                                    <div class="history">
+                                        {move || search_history.get().into_iter().enumerate().map(|(i, e)| {
+                                            let (label, settings, saved) = (e.label(), e.settings(), e.name.is_some());
+                                            let current = label.clone();
+                                            view!{
+                                                <div class="row history-item">
+                                                    <span class="history-run" title="Run again" on:click=move |_| rerun(e.clone())>
+                                                        {if saved {"★ "} else {""}}{label}
+                                                        <span style="color:var(--muted);font-size:12px;">{format!(" ({})", settings)}</span>
+                                                    </span>
+                                                    <button on:click=move |_| save_entry(i, current.clone())>{if saved {"Rename"} else {"Save"}}</button>
+                                                    <button on:click=move |_| search_history.update(|h| history::remove(h, i))>"Remove"</button>
+                                                </div>
+                                            }
+                                        }).collect::<Vec<_>>()}
+                                        <Show when=move || search_history.with(|h| h.is_empty())>
+                                            <div style="color:var(--muted);">"No searches yet."</div>
+                                        </Show>
+                                    </div>
+                                </Show>
+                                <Show when=move || side_by_side.get() && !vs_shadow.get()>
+                                    <label>
+                                        <span>"Query B"</span>
+                                        <input prop:value=move || query_b.get() on:input=move |ev| set_query_b.set(event_target_value(&ev)) />
+                                    </label>
+                                </Show>
+                                <label style="width:160px">
+                                    <span>"Top K"</span>
+                                    <input type="number" prop:value=move || top_k.get().to_string() on:input=move |ev| if let Ok(v)=event_target_value(&ev).parse(){ set_top_k.set(v) } />
+                                </label>
+                                <div class="row">
+                                    <label style="flex:1;">
+                                        <span>"Languages"</span>
+                                        <input placeholder="en, th" prop:value=move || langs.get() on:input=move |ev| set_langs.set(event_target_value(&ev)) />
+                                    </label>
+                                    <label class="row" style="flex-direction:row;">
+                                        <input type="checkbox" prop:checked=move || unapproved.get() on:change=move |ev| set_unapproved.set(event_target_checked(&ev)) />
+                                        <span>"Include unapproved"</span>
+                                    </label>
+                                </div>
+                                <div class="row">
+                                    <label style="flex:1;">
+                                        <span>"Product"</span>
+                                        <select on:change=move |ev| set_product.set(event_target_value(&ev))>
+                                            <option value="" selected=move || product.get().is_empty()>"Any product"</option>
+                                            {move || products.get().into_iter().map(|p| {
+                                                let id = p.product_id.clone();
+                                                view!{
+                                                    <option value=p.product_id.clone() selected=move || product.get() == id>
+                                                        {format!("{} ({} reviews)", p.product_id, p.reviews)}
+                                                    </option>
+                                                }
+                                            }).collect::<Vec<_>>()}
+                                        </select>
+                                    </label>
+                                    <label style="flex:1;">
+                                        <span>{move || format!("Rating {} to {}", min_rating.get(), max_rating.get())}</span>
+                                        <div class="row">
+                                            <input type="range" min="1" max="5" step="1" prop:value=move || min_rating.get().to_string()
+                                                on:input=move |ev| if let Ok(v) = event_target_value(&ev).parse::<i32>() {
+                                                    set_min_rating.set(v);
+                                                    if max_rating.get_untracked() < v { set_max_rating.set(v) }
+                                                } />
+                                            <input type="range" min="1" max="5" step="1" prop:value=move || max_rating.get().to_string()
+                                                on:input=move |ev| if let Ok(v) = event_target_value(&ev).parse::<i32>() {
+                                                    set_max_rating.set(v);
+                                                    if min_rating.get_untracked() > v { set_min_rating.set(v) }
+                                                } />
+                                        </div>
+                                    </label>
+                                </div>
+                                <div class="row">
+                                    <label style="flex:1;">
+                                        <span>"Created from"</span>
+                                        <input type="date" prop:value=move || date_from.get() on:input=move |ev| set_date_from.set(event_target_value(&ev)) />
+                                    </label>
+                                    <label style="flex:1;">
+                                        <span>"Created to"</span>
+                                        <input type="date" prop:value=move || date_to.get() on:input=move |ev| set_date_to.set(event_target_value(&ev)) />
+                                    </label>
+                                    <button style="align-self:flex-end;margin-bottom:6px;" on:click=move |_| {
+                                        set_langs.set(String::new());
+                                        set_unapproved.set(false);
+                                        set_product.set(String::new());
+                                        set_min_rating.set(1);
+                                        set_max_rating.set(5);
+                                        set_date_from.set(String::new());
+                                        set_date_to.set(String::new());
+                                    }>"Clear filters"</button>
+                                </div>
+                                <div class="row">
+                                    <label class="row" style="flex-direction:row;">
+                                        <input type="checkbox" prop:checked=move || side_by_side.get() on:change=move |ev| set_side_by_side.set(event_target_checked(&ev)) />
+                                        <span>"Side by side"</span>
+                                    </label>
+                                    <Show when=move || side_by_side.get()>
+                                        <label class="row" style="flex-direction:row;">
+                                            <input type="checkbox" prop:checked=move || vs_shadow.get() on:change=move |ev| set_vs_shadow.set(event_target_checked(&ev)) />
+                                            <span>"Against the shadow index"</span>
+                                        </label>
+                                    </Show>
+                                </div>
+                                <div style="margin-top:8px;">
+                                    <button class="btn" on:click=do_search disabled=move || search_loading.get()>
+                                        {move || if search_loading.get() {"Searching..."} else {"Search"}}
+                                    </button>
+                                    <Show when=move || !search_err.get().is_empty()>
+                                        {move || view!{<span class="danger" style="margin-left:8px;">{search_err.get()}</span>}}
+                                    </Show>
+                                </div>
+                            </div>
+                            <Show when=move || !side_by_side.get()>
+                                <div class="card">
+                                    <div style="font-weight:600;margin-bottom:8px;">"Response"</div>
+                                    <pre>{move || search_resp.get()}</pre>
+                                </div>
+                            </Show>
+                        </div>
+                        <Show when=move || !side_by_side.get() && search_hits.with(|h| !h.is_empty())>
+                            {move || {
+                                let hits = search_hits.get();
+                                view! {
+                                    <div style="margin-top:16px;">
+                                        {search_column(SearchColumn { heading: format!("{} hits", hits.len()), hits }, HashMap::new())}
+                                    </div>
+                                }
+                            }}
+                        </Show>
+                        <Show when=move || side_by_side.get()>
+                            {move || sbs_resp.get().map(|(a, b)| {
+                                let (rank_a, rank_b) = (a.ranks(), b.ranks());
+                                let common = rank_a.keys().filter(|id| rank_b.contains_key(id)).count();
+                                view! {
+                                    <div style="color:var(--muted);margin-top:16px;">
+                                        {format!("{} of the hits in both columns", common)}
+                                    </div>
+                                    <div class="grid cols-2" style="margin-top:8px;">
+                                        {search_column(a, rank_b)}
+                                        {search_column(b, rank_a)}
+                                    </div>
+                                }
+                            })}
+                        </Show>
+                    </div>
+                }.into_view(),
+                Tab::Compare => view! {
+                    <div>
                         <div class="card">
-                            <div style="font-weight:600;margin-bottom:8px;">"Search Reviews"</div>
-                            <label>
-                                <span>"Query"</span>
-                                <input prop:value=move || query.get() on:input=move |ev| set_query.set(event_target_value(&ev)) />
-                            </label>
-                            <label style="width:160px">
-                                <span>"Top K"</span>
-                                <input type="number" prop:value=move || top_k.get().to_string() on:input=move |ev| if let Ok(v)=event_target_value(&ev).parse(){ set_top_k.set(v) } />
-                            </label>
+                            <div style="font-weight:600;margin-bottom:8px;">"Compare Products"</div>
+                            <div class="row">
+                                <label style="flex:1">
+                                    <span>"Product A"</span>
+                                    <input prop:value=move || cmp_a.get() on:input=move |ev| set_cmp_a.set(event_target_value(&ev)) />
+                                </label>
+                                <label style="flex:1">
+                                    <span>"Product B"</span>
+                                    <input prop:value=move || cmp_b.get() on:input=move |ev| set_cmp_b.set(event_target_value(&ev)) />
+                                </label>
+                            </div>
+                            <div class="row">
+                                <label style="flex:1">
+                                    <span>"Topic"</span>
+                                    <input prop:value=move || cmp_query.get() on:input=move |ev| set_cmp_query.set(event_target_value(&ev)) />
+                                </label>
+                                <label style="width:160px">
+                                    <span>"Top K"</span>
+                                    <input type="number" prop:value=move || cmp_k.get().to_string() on:input=move |ev| if let Ok(v)=event_target_value(&ev).parse(){ set_cmp_k.set(v) } />
+                                </label>
+                            </div>
                             <div style="margin-top:8px;">
-                                <button class="btn" on:click=do_search disabled=move || search_loading.get()>
-                                    {move || if search_loading.get() {"Searching..."} else {"Search"}}
+                                <button class="btn" on:click=do_compare disabled=move || cmp_loading.get()>
+                                    {move || if cmp_loading.get() {"Comparing..."} else {"Compare"}}
                                 </button>
-                                <Show when=move || !search_err.get().is_empty()>
-                                    {move || view!{<span class="danger" style="margin-left:8px;">{search_err.get()}</span>}}
+                                <Show when=move || !cmp_err.get().is_empty()>
+                                    {move || view!{<span class="danger" style="margin-left:8px;">{cmp_err.get()}</span>}}
                                 </Show>
                             </div>
                         </div>
+                        <div class="grid cols-2" style="margin-top:16px;">
+                            {move || cmp_resp.get().map(|c| c.products.into_iter().map(compare_side).collect::<Vec<_>>())}
+                        </div>
+                    </div>
+                }.into_view(),
+                Tab::Visualize => view! {
+                    <div>
                         <div class="card">
-                            <div style="font-weight:600;margin-bottom:8px;">"Response"</div>
-                            <pre>{move || search_resp.get()}</pre>
+                            <div style="font-weight:600;margin-bottom:8px;">"Embedding Space"</div>
+                            <div class="row">
+                                <label style="width:160px">
+                                    <span>"Sample"</span>
+                                    <input type="number" prop:value=move || viz_sample.get().to_string() on:input=move |ev| if let Ok(v)=event_target_value(&ev).parse(){ set_viz_sample.set(v) } />
+                                </label>
+                                <label style="width:200px">
+                                    <span>"Color by"</span>
+                                    <select on:change=move |ev| set_viz_label.set(match event_target_value(&ev).as_str() {
+                                        "product_id" => PointLabel::ProductId,
+                                        _ => PointLabel::Rating,
+                                    })>
+                                        <option value="rating" selected=move || viz_label.get() == PointLabel::Rating>"Rating"</option>
+                                        <option value="product_id" selected=move || viz_label.get() == PointLabel::ProductId>"Product"</option>
+                                    </select>
+                                </label>
+                            </div>
+                            <div style="margin-top:8px;">
+                                <button class="btn" on:click=do_visualize disabled=move || viz_loading.get()>
+                                    {move || if viz_loading.get() {"Projecting..."} else {"Plot"}}
+                                </button>
+                                <Show when=move || !viz_err.get().is_empty()>
+                                    {move || view!{<span class="danger" style="margin-left:8px;">{viz_err.get()}</span>}}
+                                </Show>
+                            </div>
+                        </div>
+                        <div class="grid" style="grid-template-columns:3fr 1fr;margin-top:16px;">
+                            {move || viz_resp.get().map(|(by, p)| scatter(by, p, set_viz_hover))}
+                            <Show when=move || viz_resp.get().is_some()>
+                                <div class="card">
+                                    {move || match viz_hover.get() {
+                                        Some(p) => view!{
+                                            <div style="font-weight:600;">{p.title.clone().unwrap_or_default()}</div>
+                                            <div>{p.label.clone()}</div>
+                                            <div style="color:var(--muted);font-size:12px;">{format!("#{} at ({:.3}, {:.3})", p.id, p.x, p.y)}</div>
+                                        }.into_view(),
+                                        None => view!{<div style="color:var(--muted);">"Hover over a point to see its review."</div>}.into_view(),
+                                    }}
+                                </div>
+                            </Show>
                         </div>
                     </div>
                 }.into_view(),
+                Tab::Admin => view! { <AdminTab/> }.into_view(),
             }}
 
             <div class="row" style="margin-top:18px;color:var(--muted);font-size:12px;">
-                "Built for POST /reviews, /reviews/bulk, /search"
+                "Built for POST /reviews, /reviews/bulk, /search, /compare, /analytics/projection, /admin, /jobs"
+            </div>
+            <div class="row" style="color:var(--muted);font-size:12px;">
+                "Keys: / search, Cmd/Ctrl+Enter submit, 1-6 tabs, Cmd/Ctrl+K commands"
             </div>
+
+            <Show when=move || palette_open.get()>
+                <div class="palette-backdrop" on:click=move |_| set_palette_open.set(false)>
+                    <div class="card palette" on:click=|ev| ev.stop_propagation()>
+                        <input node_ref=palette_ref placeholder="Type a command" prop:value=move || palette_query.get()
+                            on:input=move |ev| { set_palette_query.set(event_target_value(&ev)); set_palette_sel.set(0); }
+                            on:keydown=palette_key />
+                        {move || palette_matches(&palette_query.get()).into_iter().enumerate().map(|(i, (label, c))| view!{
+                            <div class=move || if palette_sel.get() == i {"palette-item active"} else {"palette-item"}
+                                on:mouseenter=move |_| set_palette_sel.set(i) on:click=move |_| run_command(c)>{label}</div>
+                        }).collect::<Vec<_>>()}
+                    </div>
+                </div>
+            </Show>
         </div>
     }
 }
+
+const DAY_SECS: u64 = 86_400;
+
+/// The palette's commands whose label contains `query`, ignoring case, in order.
+fn palette_matches(query: &str) -> Vec<(String, Command)> {
+    let query = query.trim().to_lowercase();
+    TABS.iter().map(|&(t, name)| (format!("Go to {}", name), Command::Go(t)))
+        .chain([
+            ("Focus search".to_string(), Command::FocusSearch),
+            ("Submit the current tab".to_string(), Command::Submit),
+            ("Toggle side-by-side search".to_string(), Command::ToggleSideBySide),
+            ("Show search history".to_string(), Command::ShowHistory),
+        ])
+        .filter(|(label, _)| label.to_lowercase().contains(&query))
+        .collect()
+}
+
+/// Reviews from a file's text: a JSON array, or one JSON review per line (NDJSON).
+fn parse_reviews(text: &str) -> Result<Vec<Review>, String> {
+    if text.trim_start().starts_with('[') {
+        return serde_json::from_str(text).map_err(|e| format!("bad JSON: {}", e));
+    }
+    text.lines().enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| serde_json::from_str(line).map_err(|e| format!("line {}: {}", i + 1, e)))
+        .collect()
+}
+
+/// Milliseconds on the page's monotonic clock.
+fn now_ms() -> f64 {
+    window().performance().map(|p| p.now()).unwrap_or_default()
+}
+
+/// A "YYYY-MM-DD" date, as `<input type="date">` gives it, as seconds since the epoch at
+/// 00:00 UTC; None for an empty or malformed one.
+fn date_to_secs(date: &str) -> Option<u64> {
+    let mut parts = date.splitn(3, '-').map(|p| p.parse::<i64>().ok());
+    let (y, m, d) = (parts.next()??, parts.next()??, parts.next()??);
+    if !(1..=12).contains(&m) || !(1..=31).contains(&d) { return None; }
+    // Days from the civil date, after Howard Hinnant's algorithm.
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    u64::try_from((era * 146_097 + doe - 719_468) * DAY_SECS as i64).ok()
+}
+
+/// The UTC date of `secs` since the epoch, as "YYYY-MM-DD".
+fn secs_to_date(secs: u64) -> String {
+    let z = (secs / DAY_SECS) as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", y, m, d)
+}
+
+/// One side of a side-by-side search.
+#[derive(Clone)]
+struct SearchColumn { heading: String, hits: Vec<SearchHit> }
+
+impl SearchColumn {
+    /// Hit id -> 1-based rank.
+    fn ranks(&self) -> HashMap<usize, usize> {
+        self.hits.iter().enumerate().map(|(i, h)| (h.id, i + 1)).collect()
+    }
+}
+
+/// POSTs `payload` as JSON and parses the JSON reply; errors as shown to the user.
+pub(crate) async fn post_json<T: Serialize, R: DeserializeOwned>(url: &str, payload: &T) -> Result<R, String> {
+    let r = Request::post(url)
+        .header("Content-Type", "application/json")
+        .json(payload).unwrap()
+        .send().await
+        .map_err(|e| format!("fetch error: {}", e))?;
+    let status = r.status();
+    let text = r.text().await.unwrap_or_default();
+    if status >= 400 { return Err(format!("HTTP {}: {}", status, text)); }
+    serde_json::from_str(&text).map_err(|e| format!("bad response: {}", e))
+}
+
+/// A column of search hits, virtualized so a large `top_k` keeps the DOM small; hits also
+/// in the other column of a side-by-side search (`other`, id -> rank) are highlighted, with
+/// their rank there.
+fn search_column(c: SearchColumn, other: HashMap<usize, usize>) -> impl IntoView {
+    let SearchColumn { heading, hits } = c;
+    let len = hits.len();
+    view! {
+        <div class="card">
+            <div style="font-weight:600;margin-bottom:8px;">{heading}</div>
+            <VirtualList len=len row_height=HIT_ROW_H height=HITS_VIEW_H row=move |i| {
+                let h = &hits[i];
+                let also = other.get(&h.id).copied();
+                view!{
+                    <div class=if also.is_some() {"hit common"} else {"hit"}>
+                        <div class="clamp-1" style="font-weight:600;">{format!("{}. {} ({}/5)", i + 1, h.review.review_title, h.review.review_rating)}</div>
+                        <div class="clamp-2" title=h.review.review_body.clone()>{h.review.review_body.clone()}</div>
+                        <div style="color:var(--muted);font-size:12px;">
+                            {format!("#{} score {:.3}", h.id, h.score)}
+                            {also.map(|r| format!(" · also #{} in the other column", r))}
+                        </div>
+                    </div>
+                }
+            } />
+        </div>
+    }
+}
+
+/// One product's column of the comparison: its summaries, then its hits.
+fn compare_side(p: ProductComparison) -> impl IntoView {
+    view! {
+        <div class="card">
+            <div style="font-weight:600;margin-bottom:8px;">{p.product_id}</div>
+            <div>{summary_line("On topic", &p.topic)}</div>
+            <div style="color:var(--muted);font-size:12px;margin-bottom:8px;">{summary_line("Overall", &p.overall)}</div>
+            {p.hits.into_iter().map(|h| view!{
+                <div style="border-top:1px solid var(--border);padding:8px 0;">
+                    <div style="font-weight:600;">{format!("{} ({}/5)", h.review.review_title, h.review.review_rating)}</div>
+                    <div>{h.review.review_body}</div>
+                    <div style="color:var(--muted);font-size:12px;">{format!("#{} score {:.3}", h.id, h.score)}</div>
+                </div>
+            }).collect::<Vec<_>>()}
+        </div>
+    }
+}
+
+fn summary_line(label: &str, s: &RatingSummary) -> String {
+    match (s.avg_rating, s.sentiment) {
+        (Some(avg), Some(sentiment)) => format!(
+            "{}: {} reviews, avg {:.2}, sentiment {:+.2} ({} positive / {} neutral / {} negative)",
+            label, s.count, avg, sentiment, s.positive, s.neutral, s.negative
+        ),
+        _ => format!("{}: no rated reviews", label),
+    }
+}
+
+/// The projected points as an SVG scatter plot, coloured by label, with a legend of the
+/// most frequent labels. Hovering a point shows it through `set_hover`.
+fn scatter(by: PointLabel, resp: ProjectionResp, set_hover: WriteSignal<Option<ProjectedPoint>>) -> impl IntoView {
+    let (min_x, max_x) = bounds(resp.points.iter().map(|p| p.x));
+    let (min_y, max_y) = bounds(resp.points.iter().map(|p| p.y));
+    let sx = move |x: f64| PLOT_PAD + (x - min_x) / (max_x - min_x).max(1e-12) * (PLOT_W - 2.0 * PLOT_PAD);
+    let sy = move |y: f64| PLOT_H - PLOT_PAD - (y - min_y) / (max_y - min_y).max(1e-12) * (PLOT_H - 2.0 * PLOT_PAD);
+    let caption = format!(
+        "{} of {} reviews; x and y explain {:.1}% and {:.1}% of the variance",
+        resp.points.len(), resp.matched, resp.explained_variance[0] * 100.0, resp.explained_variance[1] * 100.0,
+    );
+    let legend = legend(&resp.points);
+    view! {
+        <div class="card">
+            <div style="color:var(--muted);font-size:12px;margin-bottom:8px;">{caption}</div>
+            <svg viewBox=format!("0 0 {} {}", PLOT_W, PLOT_H) style="width:100%;height:auto;border:1px solid var(--border);border-radius:12px;"
+                on:mouseleave=move |_| set_hover.set(None)>
+                {resp.points.into_iter().map(|p| {
+                    let (cx, cy, fill) = (sx(p.x), sy(p.y), point_color(by, &p.label));
+                    view!{
+                        <circle cx=format!("{:.1}", cx) cy=format!("{:.1}", cy) r="3.5" fill=fill opacity="0.75"
+                            on:mouseenter=move |_| set_hover.set(Some(p.clone())) />
+                    }
+                }).collect::<Vec<_>>()}
+            </svg>
+            <div class="row" style="flex-wrap:wrap;gap:10px;margin-top:8px;font-size:12px;">
+                {legend.into_iter().map(|(label, n)| {
+                    let dot = format!("display:inline-block;width:10px;height:10px;border-radius:50%;background:{}", point_color(by, &label));
+                    view!{<span class="row" style="gap:4px;"><span style=dot></span>{format!("{} ({})", label, n)}</span>}
+                }).collect::<Vec<_>>()}
+            </div>
+        </div>
+    }
+}
+
+fn bounds(values: impl Iterator<Item = f64>) -> (f64, f64) {
+    values.fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), v| (lo.min(v), hi.max(v)))
+}
+
+/// Ratings from red (1) to green (5); other labels from the palette.
+fn point_color(by: PointLabel, label: &str) -> &'static str {
+    match by {
+        PointLabel::Rating => match label {
+            "1" => "#b91c1c",
+            "2" => "#ea580c",
+            "3" => "#ca8a04",
+            "4" => "#65a30d",
+            "5" => "#15803d",
+            _ => "#9ca3af",
+        },
+        _ => {
+            let h = label.bytes().fold(0u32, |h, b| h.wrapping_mul(31).wrapping_add(b as u32));
+            PALETTE[h as usize % PALETTE.len()]
+        }
+    }
+}
+
+/// The most frequent labels with their counts; ties in label order.
+fn legend(points: &[ProjectedPoint]) -> Vec<(String, usize)> {
+    let mut counts = std::collections::BTreeMap::<&str, usize>::new();
+    for p in points { *counts.entry(p.label.as_str()).or_default() += 1; }
+    let mut out: Vec<(String, usize)> = counts.into_iter().map(|(l, n)| (l.to_string(), n)).collect();
+    out.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    out.truncate(LEGEND_MAX);
+    out
+}
@@ -0,0 +1,96 @@
+use reviews_types::SearchReq;
+use serde::{Deserialize, Serialize};
+
+/// localStorage key of the Search tab's history.
+const KEY: &str = "spfresh.search_history";
+/// Recent searches kept; saved ones stay until removed.
+const MAX_RECENT: usize = 20;
+
+/// A search as the Search tab ran it, filters and side-by-side mode included, so it can be
+/// run again as it was.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Entry {
+    /// Set once saved; saved entries are listed first and never dropped for newer ones.
+    #[serde(default)]
+    pub name: Option<String>,
+    pub req: SearchReq,
+    #[serde(default)]
+    pub side_by_side: bool,
+    #[serde(default)]
+    pub vs_shadow: bool,
+    #[serde(default)]
+    pub query_b: String,
+}
+
+impl Entry {
+    /// Its name, else the query (both queries side by side).
+    pub fn label(&self) -> String {
+        match &self.name {
+            Some(name) => name.clone(),
+            None if self.side_by_side && self.vs_shadow => format!("{} (vs shadow)", self.req.query),
+            None if self.side_by_side => format!("{} | {}", self.req.query, self.query_b),
+            None => self.req.query.clone(),
+        }
+    }
+
+    /// The settings other than the query, e.g. "top 5, th, unapproved, 1-2 stars".
+    pub fn settings(&self) -> String {
+        let mut parts = vec![format!("top {}", self.req.top_k.unwrap_or_default())];
+        if !self.req.lang.is_empty() { parts.push(self.req.lang.join(" ")); }
+        if self.req.include_unapproved { parts.push("unapproved".to_string()); }
+        if !self.req.product_ids.is_empty() { parts.push(self.req.product_ids.join(" ")); }
+        match (self.req.min_rating, self.req.max_rating) {
+            (None, None) => {}
+            (min, max) => parts.push(format!("{}-{} stars", min.unwrap_or(1), max.unwrap_or(5))),
+        }
+        if self.req.created_after.is_some() || self.req.created_before.is_some() { parts.push("date range".to_string()); }
+        parts.join(", ")
+    }
+
+    /// The same search, whatever its name.
+    fn same_as(&self, other: &Entry) -> bool {
+        Entry { name: None, ..self.clone() } == Entry { name: None, ..other.clone() }
+    }
+}
+
+fn storage() -> Option<web_sys::Storage> {
+    leptos::window().local_storage().ok().flatten()
+}
+
+/// The stored entries, saved first; none when storage is unavailable or unreadable.
+pub fn load() -> Vec<Entry> {
+    storage()
+        .and_then(|s| s.get_item(KEY).ok().flatten())
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+pub fn store(entries: &[Entry]) {
+    if let (Some(s), Ok(json)) = (storage(), serde_json::to_string(entries)) {
+        // Storage may be full or disabled; the history then lasts for the page only.
+        let _ = s.set_item(KEY, &json);
+    }
+}
+
+/// Puts `entry` first among the recent searches, dropping an earlier run of it and the
+/// oldest beyond `MAX_RECENT`. A search already saved stays where it is.
+pub fn record(entries: &mut Vec<Entry>, entry: Entry) {
+    if entries.iter().any(|e| e.name.is_some() && e.same_as(&entry)) { return; }
+    entries.retain(|e| e.name.is_some() || !e.same_as(&entry));
+    let saved = entries.iter().filter(|e| e.name.is_some()).count();
+    entries.insert(saved, entry);
+    entries.truncate(saved + MAX_RECENT);
+}
+
+/// Names entry `i` and moves it to the end of the saved ones.
+pub fn save(entries: &mut Vec<Entry>, i: usize, name: String) {
+    if i >= entries.len() { return; }
+    let mut entry = entries.remove(i);
+    entry.name = Some(name);
+    let saved = entries.iter().filter(|e| e.name.is_some()).count();
+    entries.insert(saved, entry);
+}
+
+pub fn remove(entries: &mut Vec<Entry>, i: usize) {
+    if i < entries.len() { entries.remove(i); }
+}
@@ -1,7 +1,10 @@
 use leptos::{mount_to_body, view};
 use console_error_panic_hook::set_once;
 
+mod admin;
 mod app;
+mod history;
+mod virtual_list;
 
 fn main() {
     set_once();
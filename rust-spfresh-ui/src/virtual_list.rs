@@ -0,0 +1,59 @@
+use leptos::*;
+use std::ops::Range;
+
+/// Rows rendered past the visible ones on each side, so a quick scroll shows no blank gap.
+const OVERSCAN: usize = 8;
+
+/// The rows of a fixed-row-height list worth rendering, and the space standing in for the
+/// ones above and below them.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Window {
+    pub rows: Range<usize>,
+    /// Pixels above the first rendered row.
+    pub before: f64,
+    /// Pixels below the last rendered row.
+    pub after: f64,
+}
+
+/// The window of `len` rows of `row_height` pixels visible in `viewport` pixels scrolled
+/// down `scroll_top`, with `OVERSCAN` rows either side.
+pub fn visible_rows(len: usize, row_height: f64, scroll_top: f64, viewport: f64) -> Window {
+    let first = (scroll_top.max(0.0) / row_height) as usize;
+    let visible = (viewport / row_height).ceil() as usize + 1;
+    let start = first.saturating_sub(OVERSCAN).min(len);
+    let end = (first + visible + OVERSCAN).min(len);
+    Window { rows: start..end, before: start as f64 * row_height, after: (len - end) as f64 * row_height }
+}
+
+/// A scrolling list `height` pixels tall that only puts the rows in view in the DOM, each
+/// `row_height` pixels tall (taller content is cut off). `row(i)` renders row `i`; the
+/// visible rows are rendered again only when the window moves a row or `len` changes.
+#[component]
+pub fn VirtualList<F, V>(
+    #[prop(into)] len: MaybeSignal<usize>,
+    row_height: f64,
+    height: f64,
+    row: F,
+) -> impl IntoView
+where
+    F: Fn(usize) -> V + 'static,
+    V: IntoView + 'static,
+{
+    let (scroll_top, set_scroll_top) = create_signal(0.0);
+    let win = create_memo(move |_| visible_rows(len.get(), row_height, scroll_top.get(), height));
+    view! {
+        <div style=format!("max-height:{}px;overflow-y:auto;", height)
+            on:scroll=move |ev| set_scroll_top.set(event_target::<web_sys::Element>(&ev).scroll_top() as f64)>
+            {move || {
+                let w = win.get();
+                view! {
+                    <div style=format!("height:{}px;", w.before)></div>
+                    {w.rows.map(|i| view!{
+                        <div style=format!("height:{}px;overflow:hidden;", row_height)>{row(i)}</div>
+                    }).collect::<Vec<_>>()}
+                    <div style=format!("height:{}px;", w.after)></div>
+                }
+            }}
+        </div>
+    }
+}